@@ -0,0 +1,110 @@
+// Benchmarks for the `BigInt` operations. Run them with `cargo bench --features bench`. They give
+// a baseline for exercises like "make addition twice as fast": Run them once before changing the
+// code, and once after, and criterion reports the difference.
+#[macro_use]
+extern crate criterion;
+extern crate solutions;
+
+use criterion::{black_box, BenchmarkId, Criterion};
+use solutions::bigint::BigInt;
+
+// The sizes (in digits) of the numbers we benchmark with.
+const SIZES: [usize; 3] = [1, 100, 10_000];
+
+fn bench_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add");
+    for &n in SIZES.iter() {
+        let (a, b) = (BigInt::random_with_limbs(n, 1), BigInt::random_with_limbs(n, 2));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |bench, _| {
+            bench.iter(|| black_box(&a) + black_box(&b))
+        });
+    }
+    group.finish();
+}
+
+// `+=` reuses the digits of the left operand, while `add` allocates a new number every time. So
+// comparing the two groups shows what the allocations cost.
+fn bench_add_assign(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_assign");
+    for &n in SIZES.iter() {
+        let (a, b) = (BigInt::random_with_limbs(n, 1), BigInt::random_with_limbs(n, 2));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |bench, _| {
+            let mut acc = a.clone();
+            bench.iter(|| acc += black_box(&b))
+        });
+    }
+    group.finish();
+}
+
+fn bench_sub(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sub");
+    for &n in SIZES.iter() {
+        // Make sure the result is not negative.
+        let b = BigInt::random_with_limbs(n, 2);
+        let a = &b + &BigInt::random_with_limbs(n, 1);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |bench, _| {
+            bench.iter(|| black_box(&a) - black_box(&b))
+        });
+    }
+    group.finish();
+}
+
+fn bench_inc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("inc");
+    for &n in SIZES.iter() {
+        let a = BigInt::random_with_limbs(n, 1);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |bench, _| {
+            let mut a = a.clone();
+            bench.iter(|| a.inc(black_box(u64::MAX)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_mul(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mul");
+    // Multiplying numbers with 10,000 digits takes a while, so we take fewer samples.
+    group.sample_size(10);
+    for &n in SIZES.iter() {
+        let (a, b) = (BigInt::random_with_limbs(n, 1), BigInt::random_with_limbs(n, 2));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |bench, _| {
+            bench.iter(|| black_box(&a) * black_box(&b))
+        });
+    }
+    group.finish();
+}
+
+// Parallel addition only pays off for very large numbers, so we use even larger ones than above.
+fn bench_par_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("par_add");
+    group.sample_size(10);
+    let n = 1_000_000;
+    let (a, b) = (BigInt::random_with_limbs(n, 1), BigInt::random_with_limbs(n, 2));
+    group.bench_function("sequential", |bench| bench.iter(|| black_box(&a) + black_box(&b)));
+    for &threads in [1, 2, 4, 8].iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |bench, &threads| {
+            bench.iter(|| black_box(&a).par_add(black_box(&b), threads))
+        });
+    }
+    group.finish();
+}
+
+// Numbers with at most two digits are stored without allocating. These benchmarks measure
+// workloads where that matters: Creating, cloning and combining many small numbers.
+fn bench_small(c: &mut Criterion) {
+    let mut group = c.benchmark_group("small");
+    let numbers: Vec<BigInt> = (0..1000).map(|i| BigInt::new(i * 1_000_003)).collect();
+    group.bench_function("new", |bench| {
+        bench.iter(|| (0..1000).map(|i| BigInt::new(black_box(i))).collect::<Vec<_>>())
+    });
+    group.bench_function("clone", |bench| bench.iter(|| black_box(&numbers).clone()));
+    group.bench_function("sum", |bench| bench.iter(|| black_box(&numbers).iter().sum::<BigInt>()));
+    group.bench_function("mul", |bench| {
+        bench.iter(|| black_box(&numbers).iter().map(|x| x * x).collect::<Vec<_>>())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_add, bench_add_assign, bench_sub, bench_inc, bench_mul,
+                 bench_par_add, bench_small);
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+// Benchmarks for rgrep with several threads. Run them with
+// `cargo bench --features bench --bench rgrep`.
+//
+// * `rgrep_readers` reads many small files, which is where one reader becomes the bottleneck.
+// * `rgrep_pipeline` greps a few megabytes of text, once without any threads, once with the
+//   pipeline of part 13 (one thread per stage), and once with several threads filtering the lines.
+//   Whether the threads pay off depends on the number of cores, and on how much sending the lines
+//   through the channels costs compared to the filtering itself.
+// * `rgrep_regex` searches the same text for a regular expression, which is more work per line
+//   than a plain substring, with 1 to 8 threads filtering the lines (`--threads`). Those threads
+//   come with two more: One numbers the lines, the other puts them back in order. So with few
+//   cores, or a regex that is cheap after all, the single filter wins.
+// * `rgrep_buffer` greps the same text with different sizes of the channel buffers (`--buffer`).
+//   With a buffer of 0, every `send` waits until the next thread takes the line, so the threads
+//   run in lockstep and switch all the time. Larger buffers let a thread work ahead for a while,
+//   so the threads switch less often - but every buffered line takes up memory.
+#[macro_use]
+extern crate criterion;
+extern crate solutions;
+
+use std::{env, fs, process};
+use std::path::PathBuf;
+use criterion::{BenchmarkId, Criterion, Throughput};
+use solutions::rgrep::{count_matches, count_matches_with, count_regex_matches_with, Threads};
+
+const FILES: usize = 100;
+const LINES: usize = 200;
+
+// The corpus for `rgrep_pipeline`: A few large files, with about 4 MiB of text in total.
+const CORPUS_FILES: usize = 4;
+const CORPUS_LINES: usize = 20_000;
+
+fn bench_readers(c: &mut Criterion) {
+    let dir = env::temp_dir().join(format!("rust-101-solutions-rgrep-bench-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..FILES {
+        let text: String = (0..LINES).map(|j| {
+            let word = if j % 7 == 0 { "needle" } else { "hay" };
+            format!("line {} of file {}: {}\n", j, i, word)
+        }).collect();
+        fs::write(dir.join(format!("{}.txt", i)), text).unwrap();
+    }
+    let files = vec![dir.to_string_lossy().into_owned()];
+    let expected = FILES * LINES.div_ceil(7);
+
+    let mut group = c.benchmark_group("rgrep_readers");
+    for &readers in [1, 2, 4, 8].iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(readers), &readers, |bench, &readers| {
+            bench.iter(|| {
+                assert_eq!(count_matches("needle", files.clone(), readers).unwrap(), expected)
+            })
+        });
+    }
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Write the corpus for `rgrep_pipeline` and `rgrep_buffer` into a new directory. Returns the
+// directory, its size in bytes, and the number of lines containing "needle".
+fn write_corpus(name: &str) -> (PathBuf, usize, usize) {
+    let dir = env::temp_dir().join(format!("rust-101-solutions-rgrep-bench-{}-{}", name,
+                                           process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut bytes = 0;
+    for i in 0..CORPUS_FILES {
+        let text: String = (0..CORPUS_LINES).map(|j| {
+            let word = if j % 13 == 0 { "needle" } else { "haystack" };
+            format!("line {} of file {}, with some more text to search through: {}\n", j, i, word)
+        }).collect();
+        bytes += text.len();
+        fs::write(dir.join(format!("{}.txt", i)), text).unwrap();
+    }
+    (dir, bytes, CORPUS_FILES * CORPUS_LINES.div_ceil(13))
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let (dir, bytes, expected) = write_corpus("pipeline");
+    let files = vec![dir.to_string_lossy().into_owned()];
+
+    let mut group = c.benchmark_group("rgrep_pipeline");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    let configurations = [
+        ("single", Threads::Single),
+        ("pipeline", Threads::Pipeline { readers: 1, filters: 1, buffer: 16 }),
+        ("filters-2", Threads::Pipeline { readers: 1, filters: 2, buffer: 16 }),
+        ("filters-4", Threads::Pipeline { readers: 1, filters: 4, buffer: 16 }),
+    ];
+    for &(name, threads) in configurations.iter() {
+        group.bench_function(name, |bench| {
+            bench.iter(|| {
+                assert_eq!(count_matches_with("needle", files.clone(), threads).unwrap(), expected)
+            })
+        });
+    }
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn bench_regex(c: &mut Criterion) {
+    let (dir, bytes, expected) = write_corpus("regex");
+    let files = vec![dir.to_string_lossy().into_owned()];
+    // This matches the same lines as "needle", but the regex engine has to work for it.
+    let pattern = r"^line \d+ of file \d+, (\w+ )+to \w+ \w+: n\w*e$";
+
+    let mut group = c.benchmark_group("rgrep_regex");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    for &filters in [1, 2, 4, 8].iter() {
+        let threads = Threads::Pipeline { readers: 1, filters, buffer: 16 };
+        group.bench_with_input(BenchmarkId::from_parameter(filters), &threads, |bench, &threads| {
+            bench.iter(|| {
+                let count = count_regex_matches_with(pattern, files.clone(), threads).unwrap();
+                assert_eq!(count, expected)
+            })
+        });
+    }
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn bench_buffer(c: &mut Criterion) {
+    let (dir, bytes, expected) = write_corpus("buffer");
+    let files = vec![dir.to_string_lossy().into_owned()];
+
+    let mut group = c.benchmark_group("rgrep_buffer");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    for &buffer in [0, 1, 16, 256, 4096].iter() {
+        let threads = Threads::Pipeline { readers: 1, filters: 1, buffer };
+        group.bench_with_input(BenchmarkId::from_parameter(buffer), &threads, |bench, &threads| {
+            bench.iter(|| {
+                assert_eq!(count_matches_with("needle", files.clone(), threads).unwrap(), expected)
+            })
+        });
+    }
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(benches, bench_readers, bench_pipeline, bench_regex, bench_buffer);
+criterion_main!(benches);
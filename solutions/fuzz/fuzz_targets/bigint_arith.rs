@@ -0,0 +1,60 @@
+// Fuzzing the arithmetic of `BigInt`. The fuzzer feeds us arbitrary bytes, which we turn into two
+// numbers. Then we check that the operations agree with `u128` arithmetic whenever the numbers are
+// small enough, and that they are consistent with each other when they are not. Every result has
+// to satisfy the invariant that there are no trailing zeros.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate solutions;
+
+use std::convert::TryFrom;
+use solutions::bigint::BigInt;
+
+fn check_invariant(b: &BigInt) -> &BigInt {
+    assert_ne!(b.digits().last(), Some(&0), "trailing zero in {:?}", b);
+    b
+}
+
+fn to_u128(b: &BigInt) -> Option<u128> {
+    u128::try_from(b).ok()
+}
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte says where the first number ends and the second begins.
+    let (split, data) = match data.split_first() {
+        Some((&split, data)) => ((split as usize).min(data.len()), data),
+        None => return,
+    };
+    let a = BigInt::from_bytes_le(&data[..split]);
+    let b = BigInt::from_bytes_le(&data[split..]);
+    check_invariant(&a);
+    check_invariant(&b);
+
+    let sum = &a + &b;
+    let product = &a * &b;
+    let difference = a.checked_sub(&b);
+    check_invariant(&sum);
+    check_invariant(&product);
+    if let Some(ref difference) = difference {
+        check_invariant(difference);
+    }
+
+    if let (Some(x), Some(y)) = (to_u128(&a), to_u128(&b)) {
+        // `checked_*` returns `None` exactly when the result does not fit into a `u128`.
+        assert_eq!(to_u128(&sum), x.checked_add(y));
+        assert_eq!(to_u128(&product), x.checked_mul(y));
+        assert_eq!(difference.as_ref().map(|d| to_u128(d).unwrap()), x.checked_sub(y));
+    }
+
+    // Relations that hold for numbers of any size.
+    assert_eq!(check_invariant(&sum).checked_sub(&b).as_ref(), Some(&a));
+    assert_eq!(difference.is_none(), a.sub_signed(&b).1);
+    if let Some(difference) = difference {
+        assert_eq!(check_invariant(&(&difference + &b)), &a);
+    }
+    if !b.is_zero() {
+        let (quotient, remainder) = product.div_rem(&b);
+        assert_eq!(check_invariant(&quotient), &a);
+        assert!(check_invariant(&remainder).is_zero());
+    }
+});
@@ -0,0 +1,67 @@
+/// A natural number of arbitrary size.
+pub struct BigInt {
+    // Least significant digits first. The last digit is never 0. This is visible in the entire
+    // crate (so that `ops` can work on it), but not to users of the crate.
+    pub(crate) data: Vec<u64>,
+}
+
+impl BigInt {
+    /// Construct a `BigInt` from a "small" one.
+    ///
+    /// ```
+    /// use mini_bigint::BigInt;
+    ///
+    /// assert_eq!(BigInt::new(0).num_digits(), 0);
+    /// assert_eq!(BigInt::new(42).num_digits(), 1);
+    /// ```
+    pub fn new(x: u64) -> Self {
+        if x == 0 {
+            BigInt { data: vec![] }
+        } else {
+            BigInt { data: vec![x] }
+        }
+    }
+
+    /// Construct a `BigInt` from a vector of 64-bit digits, with the least significant digit
+    /// coming first. Trailing zeros are removed.
+    ///
+    /// ```
+    /// use mini_bigint::BigInt;
+    ///
+    /// assert_eq!(BigInt::from_vec(vec![1, 0, 0]), BigInt::new(1));
+    /// ```
+    pub fn from_vec(mut v: Vec<u64>) -> Self {
+        while v.last() == Some(&0) {
+            v.pop();
+        }
+        BigInt { data: v }
+    }
+
+    /// Return the number of (64-bit) digits.
+    pub fn num_digits(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn test_invariant(&self) -> bool {
+        self.data.last() != Some(&0)
+    }
+}
+
+impl Clone for BigInt {
+    fn clone(&self) -> Self {
+        BigInt { data: self.data.clone() }
+    }
+}
+
+impl PartialEq for BigInt {
+    fn eq(&self, other: &BigInt) -> bool {
+        debug_assert!(self.test_invariant() && other.test_invariant());
+        self.data == other.data
+    }
+}
+
+impl ::std::fmt::Debug for BigInt {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        self.data.fmt(f)
+    }
+}
@@ -0,0 +1,30 @@
+//! Arbitrary-precision natural numbers.
+//!
+//! This is the `BigInt` from Rust-101, turned into a crate of its own.
+//!
+//! ```
+//! use mini_bigint::BigInt;
+//!
+//! let b = BigInt::from_vec(vec![0, 1]);
+//! assert_eq!(b.num_digits(), 2);
+//! ```
+//!
+//! With the (default) feature `ops`, numbers can be added and subtracted:
+//!
+//! ```
+//! # #[cfg(feature = "ops")] {
+//! use mini_bigint::BigInt;
+//!
+//! let b = BigInt::new(1 << 63) + BigInt::new(1 << 63);
+//! assert_eq!(b, BigInt::from_vec(vec![0, 1]));
+//! # }
+//! ```
+
+// The modules themselves are private; users see only what we re-export below.
+mod bigint;
+mod minimum;
+#[cfg(feature = "ops")]
+mod ops;
+
+pub use bigint::BigInt;
+pub use minimum::{Minimum, vec_min};
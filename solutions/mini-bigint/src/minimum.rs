@@ -0,0 +1,42 @@
+use bigint::BigInt;
+
+/// Types that have a minimum of two elements.
+pub trait Minimum {
+    /// Return the smaller of the two.
+    fn min<'a>(&'a self, other: &'a Self) -> &'a Self;
+}
+
+/// Return a reference to the minimal element of `v`.
+///
+/// ```
+/// use mini_bigint::{BigInt, vec_min};
+///
+/// let v = vec![BigInt::new(3), BigInt::new(1)];
+/// assert_eq!(vec_min(&v), Some(&BigInt::new(1)));
+/// ```
+pub fn vec_min<T: Minimum>(v: &[T]) -> Option<&T> {
+    let mut min = None;
+    for e in v {
+        min = Some(match min {
+            None => e,
+            Some(n) => e.min(n)
+        });
+    }
+    min
+}
+
+impl Minimum for BigInt {
+    fn min<'a>(&'a self, other: &'a Self) -> &'a Self {
+        debug_assert!(self.test_invariant() && other.test_invariant());
+        if self.data.len() != other.data.len() {
+            return if self.data.len() < other.data.len() { self } else { other };
+        }
+        // Compare back-to-front, i.e., most significant digit first.
+        for (a, b) in self.data.iter().rev().zip(other.data.iter().rev()) {
+            if a != b {
+                return if a < b { self } else { other };
+            }
+        }
+        self
+    }
+}
@@ -0,0 +1,111 @@
+use std::{cmp, ops};
+use bigint::BigInt;
+
+// Add with carry, returning the sum and the carry.
+fn overflowing_add(a: u64, b: u64, carry: bool) -> (u64, bool) {
+    let (sum, overflow1) = a.overflowing_add(b);
+    let (sum, overflow2) = sum.overflowing_add(carry as u64);
+    (sum, overflow1 || overflow2)
+}
+
+// Subtract with borrow, returning the difference and the borrow.
+fn overflowing_sub(a: u64, b: u64, borrow: bool) -> (u64, bool) {
+    let (diff, overflow1) = a.overflowing_sub(b);
+    let (diff, overflow2) = diff.overflowing_sub(borrow as u64);
+    (diff, overflow1 || overflow2)
+}
+
+impl<'a> ops::Add<&'a BigInt> for &BigInt {
+    type Output = BigInt;
+    fn add(self, rhs: &'a BigInt) -> BigInt {
+        let max_len = cmp::max(self.data.len(), rhs.data.len());
+        let mut result_vec = Vec::with_capacity(max_len + 1);
+        let mut carry = false;
+        for i in 0..max_len {
+            let lhs_val = self.data.get(i).cloned().unwrap_or(0);
+            let rhs_val = rhs.data.get(i).cloned().unwrap_or(0);
+            let (sum, new_carry) = overflowing_add(lhs_val, rhs_val, carry);
+            result_vec.push(sum);
+            carry = new_carry;
+        }
+        if carry {
+            result_vec.push(1);
+        }
+        BigInt { data: result_vec }
+    }
+}
+
+/// Subtraction panics if the result would be negative.
+///
+/// ```should_panic
+/// use mini_bigint::BigInt;
+///
+/// let _ = &BigInt::new(1) - &BigInt::new(2);
+/// ```
+impl<'a> ops::Sub<&'a BigInt> for &BigInt {
+    type Output = BigInt;
+    fn sub(self, rhs: &'a BigInt) -> BigInt {
+        let max_len = cmp::max(self.data.len(), rhs.data.len());
+        let mut result_vec = Vec::with_capacity(max_len);
+        let mut borrow = false;
+        for i in 0..max_len {
+            let lhs_val = self.data.get(i).cloned().unwrap_or(0);
+            let rhs_val = rhs.data.get(i).cloned().unwrap_or(0);
+            let (diff, new_borrow) = overflowing_sub(lhs_val, rhs_val, borrow);
+            result_vec.push(diff);
+            borrow = new_borrow;
+        }
+        if borrow {
+            panic!("Wrapping subtraction of BigInt");
+        }
+        BigInt::from_vec(result_vec)
+    }
+}
+
+// The remaining combinations of owned and borrowed operands all forward to the ones above.
+macro_rules! forward_binop {
+    ($trait:ident, $method:ident) => {
+        impl<'a> ops::$trait<BigInt> for &'a BigInt {
+            type Output = BigInt;
+            fn $method(self, rhs: BigInt) -> BigInt {
+                ops::$trait::$method(self, &rhs)
+            }
+        }
+        impl<'a> ops::$trait<&'a BigInt> for BigInt {
+            type Output = BigInt;
+            fn $method(self, rhs: &'a BigInt) -> BigInt {
+                ops::$trait::$method(&self, rhs)
+            }
+        }
+        impl ops::$trait<BigInt> for BigInt {
+            type Output = BigInt;
+            fn $method(self, rhs: BigInt) -> BigInt {
+                ops::$trait::$method(&self, &rhs)
+            }
+        }
+    }
+}
+forward_binop!(Add, add);
+forward_binop!(Sub, sub);
+
+#[cfg(test)]
+mod tests {
+    use super::{overflowing_add, overflowing_sub};
+
+    #[test]
+    fn test_overflowing_add() {
+        assert_eq!(overflowing_add(10, 100, false), (110, false));
+        assert_eq!(overflowing_add(10, 100, true), (111, false));
+        assert_eq!(overflowing_add(1 << 63, 1 << 63, false), (0, true));
+        assert_eq!(overflowing_add(1 << 63, 1 << 63, true), (1, true));
+        assert_eq!(overflowing_add(1 << 63, (1 << 63) - 1, true), (0, true));
+    }
+
+    #[test]
+    fn test_overflowing_sub() {
+        assert_eq!(overflowing_sub(100, 10, false), (90, false));
+        assert_eq!(overflowing_sub(100, 10, true), (89, false));
+        assert_eq!(overflowing_sub(10, 1 << 63, false), ((1 << 63) + 10, true));
+        assert_eq!(overflowing_sub(42, 42, true), (u64::MAX, true));
+    }
+}
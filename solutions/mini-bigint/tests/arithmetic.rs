@@ -0,0 +1,43 @@
+extern crate mini_bigint;
+
+mod common;
+
+use mini_bigint::BigInt;
+use common::{big, Rng};
+
+#[test]
+fn test_add_carry() {
+    let b = BigInt::new(1 << 63) + BigInt::new(1 << 63);
+    assert_eq!(b, BigInt::from_vec(vec![0, 1]));
+}
+
+#[test]
+#[should_panic(expected = "Wrapping subtraction of BigInt")]
+fn test_sub_negative() {
+    let _ = BigInt::from_vec(vec![0, 1]) - BigInt::from_vec(vec![1, 1]);
+}
+
+// Compare with arithmetic on `u128`. We use at most 127 bits, so that the sum does not overflow.
+#[test]
+fn test_add_sub_u128() {
+    let mut rng = Rng::new(42);
+    for i in 0..1000 {
+        // Vary the number of bits, so that we also test numbers with fewer digits.
+        let bits = 1 + i % 127;
+        let (a, b) = (rng.next_u128(bits), rng.next_u128(bits));
+        assert_eq!(big(a) + big(b), big(a + b), "{} + {}", a, b);
+        let (small, large) = if a < b { (a, b) } else { (b, a) };
+        assert_eq!(big(large) - big(small), big(large - small), "{} - {}", large, small);
+    }
+}
+
+#[test]
+fn test_properties() {
+    let mut rng = Rng::new(1337);
+    for _ in 0..1000 {
+        let (a, b) = (big(rng.next_u128(128)), big(rng.next_u128(128)));
+        assert_eq!(&a + &b, &b + &a);
+        assert_eq!(&a + &b - &b, a);
+        assert_eq!(&a + &BigInt::new(0), a);
+    }
+}
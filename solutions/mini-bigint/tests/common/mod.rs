@@ -0,0 +1,32 @@
+// Helpers shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use mini_bigint::BigInt;
+
+/// A xorshift pseudo-random number generator, as in part 23.
+pub struct Rng(u64);
+
+impl Rng {
+    /// The seed must not be 0.
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A random number of at most `bits` bits.
+    pub fn next_u128(&mut self, bits: u32) -> u128 {
+        let x = (self.next_u64() as u128) << 64 | self.next_u64() as u128;
+        if bits >= 128 { x } else { x & ((1 << bits) - 1) }
+    }
+}
+
+/// Convert a `u128` to a `BigInt`.
+pub fn big(x: u128) -> BigInt {
+    BigInt::from_vec(vec![x as u64, (x >> 64) as u64])
+}
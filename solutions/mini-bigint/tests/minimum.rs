@@ -0,0 +1,25 @@
+extern crate mini_bigint;
+
+mod common;
+
+use mini_bigint::{vec_min, Minimum};
+use common::{big, Rng};
+
+#[test]
+fn test_min_u128() {
+    let mut rng = Rng::new(7);
+    for i in 0..1000 {
+        let bits = 1 + i % 128;
+        let (a, b) = (rng.next_u128(bits), rng.next_u128(bits));
+        assert_eq!(big(a).min(&big(b)), &big(a.min(b)), "min({}, {})", a, b);
+    }
+}
+
+#[test]
+fn test_vec_min() {
+    let mut rng = Rng::new(99);
+    let v: Vec<u128> = (0..20).map(|_| rng.next_u128(100)).collect();
+    let bigs: Vec<_> = v.iter().map(|&x| big(x)).collect();
+    assert_eq!(vec_min(&bigs), Some(&big(*v.iter().min().unwrap())));
+    assert_eq!(vec_min::<mini_bigint::BigInt>(&[]), None);
+}
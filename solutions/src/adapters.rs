@@ -0,0 +1,269 @@
+//! Iterator adapters from part 20, including the exercises: `size_hint` for all adapters,
+//! `Windows`, and `Interleave`.
+
+pub struct Zip<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A: Iterator, B: Iterator> Iterator for Zip<A, B> {
+    type Item = (A::Item, B::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.a.next(), self.b.next()) {
+            (Some(a), Some(b)) => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a_lo, a_hi) = self.a.size_hint();
+        let (b_lo, b_hi) = self.b.size_hint();
+        let hi = match (a_hi, b_hi) {
+            (Some(a), Some(b)) => Some(::std::cmp::min(a, b)),
+            (Some(a), None) => Some(a),
+            (None, b) => b,
+        };
+        (::std::cmp::min(a_lo, b_lo), hi)
+    }
+}
+
+pub fn zip<A: IntoIterator, B: IntoIterator>(a: A, b: B) -> Zip<A::IntoIter, B::IntoIter> {
+    Zip { a: a.into_iter(), b: b.into_iter() }
+}
+
+pub struct Chunks<I> {
+    iter: I,
+    size: usize,
+}
+
+impl<I: Iterator> Chunks<I> {
+    pub fn new(iter: I, size: usize) -> Self {
+        assert!(size > 0, "Chunk size must be non-zero");
+        Chunks { iter, size }
+    }
+}
+
+impl<I: Iterator> Iterator for Chunks<I> {
+    type Item = Vec<I::Item>;
+
+    fn next(&mut self) -> Option<Vec<I::Item>> {
+        let mut chunk = Vec::with_capacity(self.size);
+        while chunk.len() < self.size {
+            match self.iter.next() {
+                Some(e) => chunk.push(e),
+                None => break,
+            }
+        }
+        if chunk.is_empty() { None } else { Some(chunk) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lo, hi) = self.iter.size_hint();
+        (lo.div_ceil(self.size), hi.map(|hi| hi.div_ceil(self.size)))
+    }
+}
+
+pub struct StepBy<I> {
+    iter: I,
+    step: usize,
+    first: bool,
+}
+
+impl<I: Iterator> StepBy<I> {
+    pub fn new(iter: I, step: usize) -> Self {
+        assert!(step > 0, "Step must be non-zero");
+        StepBy { iter, step, first: true }
+    }
+}
+
+impl<I: Iterator> Iterator for StepBy<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.first {
+            self.first = false;
+            self.iter.next()
+        } else {
+            self.iter.nth(self.step - 1)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Before the first element, we yield one element for every started block of `step`
+        // elements. After it, only for every complete block.
+        let count = |n: usize| if self.first { n.div_ceil(self.step) } else { n / self.step };
+        let (lo, hi) = self.iter.size_hint();
+        (count(lo), hi.map(count))
+    }
+}
+
+/// Overlapping windows of `size` elements.
+pub struct Windows<I: Iterator> {
+    iter: I,
+    size: usize,
+    window: Vec<I::Item>,
+}
+
+impl<I: Iterator> Windows<I> {
+    pub fn new(iter: I, size: usize) -> Self {
+        assert!(size > 0, "Window size must be non-zero");
+        Windows { iter, size, window: Vec::with_capacity(size) }
+    }
+}
+
+impl<I: Iterator> Iterator for Windows<I> where I::Item: Clone {
+    type Item = Vec<I::Item>;
+
+    fn next(&mut self) -> Option<Vec<I::Item>> {
+        if self.window.len() == self.size {
+            self.window.remove(0);
+        }
+        while self.window.len() < self.size {
+            self.window.push(self.iter.next()?);
+        }
+        Some(self.window.clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Every element we did not see yet completes one more window. Before the first window,
+        // `size - 1` elements are needed to get started.
+        let missing = if self.window.len() == self.size {
+            0
+        } else {
+            self.size - 1 - self.window.len()
+        };
+        let (lo, hi) = self.iter.size_hint();
+        (lo.saturating_sub(missing), hi.map(|hi| hi.saturating_sub(missing)))
+    }
+}
+
+/// Alternate between the elements of two iterators, then continue with the longer one.
+pub struct Interleave<A, B> {
+    a: A,
+    b: B,
+    take_a: bool,
+}
+
+impl<A: Iterator, B: Iterator<Item=A::Item>> Iterator for Interleave<A, B> {
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<A::Item> {
+        self.take_a = !self.take_a;
+        if self.take_a {
+            self.a.next().or_else(|| self.b.next())
+        } else {
+            self.b.next().or_else(|| self.a.next())
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a_lo, a_hi) = self.a.size_hint();
+        let (b_lo, b_hi) = self.b.size_hint();
+        let hi = match (a_hi, b_hi) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        (a_lo.saturating_add(b_lo), hi)
+    }
+}
+
+pub trait IterExt: Iterator + Sized {
+    fn my_zip<B: IntoIterator>(self, other: B) -> Zip<Self, B::IntoIter> {
+        zip(self, other)
+    }
+    fn my_chunks(self, size: usize) -> Chunks<Self> {
+        Chunks::new(self, size)
+    }
+    fn my_step_by(self, step: usize) -> StepBy<Self> {
+        StepBy::new(self, step)
+    }
+    fn my_windows(self, size: usize) -> Windows<Self> {
+        Windows::new(self, size)
+    }
+    fn my_interleave<B: IntoIterator<Item=Self::Item>>(self, other: B)
+        -> Interleave<Self, B::IntoIter>
+    {
+        Interleave { a: self, b: other.into_iter(), take_a: false }
+    }
+}
+impl<I: Iterator> IterExt for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::{zip, IterExt};
+
+    // Check that `size_hint` is exact at every step of the iterator created by `make`.
+    fn check_size_hint<I: Iterator, F: Fn() -> I>(make: F) {
+        let len = make().count();
+        for k in 0..len + 1 {
+            let mut iter = make();
+            for _ in 0..k {
+                iter.next();
+            }
+            assert_eq!(iter.size_hint(), (len - k, Some(len - k)));
+        }
+    }
+
+    #[test]
+    fn test_zip() {
+        let v = vec![1, 2, 3];
+        let w = vec!["a", "b"];
+        let ours: Vec<_> = zip(&v, &w).collect();
+        let std: Vec<_> = v.iter().zip(w.iter()).collect();
+        assert_eq!(ours, std);
+        assert_eq!(zip(&v, &w).size_hint(), v.iter().zip(w.iter()).size_hint());
+        check_size_hint(|| zip(&v, &w));
+    }
+
+    #[test]
+    fn test_chunks() {
+        let v: Vec<i32> = (0..7).collect();
+        for size in 1..9 {
+            let ours: Vec<Vec<i32>> = v.iter().cloned().my_chunks(size).collect();
+            let std: Vec<Vec<i32>> = v.chunks(size).map(|c| c.to_vec()).collect();
+            assert_eq!(ours, std);
+            assert_eq!(v.iter().my_chunks(size).size_hint(), (std.len(), Some(std.len())));
+            check_size_hint(|| v.iter().my_chunks(size));
+        }
+    }
+
+    #[test]
+    fn test_step_by() {
+        for step in 1..5 {
+            let ours: Vec<i32> = (0..10).my_step_by(step).collect();
+            let std: Vec<i32> = (0..10).step_by(step).collect();
+            assert_eq!(ours, std);
+            assert_eq!((0..10).my_step_by(step).size_hint(), (0..10).step_by(step).size_hint());
+            check_size_hint(|| (0..10).my_step_by(step));
+        }
+    }
+
+    #[test]
+    fn test_windows() {
+        let v: Vec<i32> = (0..5).collect();
+        for size in 1..7 {
+            let ours: Vec<Vec<i32>> = v.iter().cloned().my_windows(size).collect();
+            let std: Vec<Vec<i32>> = v.windows(size).map(|w| w.to_vec()).collect();
+            assert_eq!(ours, std);
+            assert_eq!(v.iter().my_windows(size).size_hint(), (std.len(), Some(std.len())));
+            check_size_hint(|| v.iter().my_windows(size));
+        }
+    }
+
+    #[test]
+    fn test_interleave() {
+        let ours: Vec<i32> = vec![1, 3].into_iter().my_interleave(vec![2, 4, 5, 6]).collect();
+        assert_eq!(ours, vec![1, 2, 3, 4, 5, 6]);
+        let ours: Vec<i32> = vec![1, 3, 5, 6].into_iter().my_interleave(vec![2, 4]).collect();
+        assert_eq!(ours, vec![1, 2, 3, 4, 5, 6]);
+        check_size_hint(|| vec![1, 3].into_iter().my_interleave(vec![2, 4, 5, 6]));
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let ours: Vec<Vec<(i32, char)>> = (1..).my_step_by(2).my_zip("abcde".chars()).my_chunks(2)
+            .collect();
+        assert_eq!(ours, vec![vec![(1, 'a'), (3, 'b')], vec![(5, 'c'), (7, 'd')], vec![(9, 'e')]]);
+    }
+}
@@ -1,6 +1,18 @@
 use std::ops;
 use std::cmp;
 use std::fmt;
+use std::error;
+use std::convert::TryFrom;
+use std::iter;
+use std::str::FromStr;
+
+pub mod examples;
+pub mod modular;
+mod digits;
+mod parallel;
+
+use self::digits::Digits;
+use rng::Rng;
 
 pub trait Minimum {
     /// Return the smaller of the two
@@ -19,8 +31,37 @@ pub fn vec_min<T: Minimum>(v: &Vec<T>) -> Option<&T> {
     min
 }
 
+pub trait MinMax: Minimum {
+    /// Return the larger of the two
+    fn max<'a>(&'a self, other: &'a Self) -> &'a Self;
+}
+
+/// Return pointers to the minimal and the maximal value of `v`, going over the vector only once.
+pub fn vec_min_max<T: MinMax>(v: &Vec<T>) -> Option<(&T, &T)> {
+    let mut min_max = None;
+    for e in v {
+        min_max = Some(match min_max {
+            None => (e, e),
+            Some((min, max)) => (e.min(min), e.max(max)),
+        });
+    }
+    min_max
+}
+
+impl Minimum for i32 {
+    fn min<'a>(&'a self, other: &'a Self) -> &'a Self {
+        if *self < *other { self } else { other }
+    }
+}
+
+impl MinMax for i32 {
+    fn max<'a>(&'a self, other: &'a Self) -> &'a Self {
+        if *self > *other { self } else { other }
+    }
+}
+
 pub struct BigInt {
-    data: Vec<u64>, // least significant digits first. The last block will *not* be 0.
+    data: Digits, // least significant digits first. The last block will *not* be 0.
 }
 
 // Add with carry, returning the sum and the carry
@@ -51,16 +92,40 @@ fn overflowing_sub(a: u64, b: u64, carry: bool) -> (u64, bool) {
     }
 }
 
+// Multiply with carry, returning the low and the high digit of `a * b + carry`. Like for `mul_add`
+// below, the result always fits into two digits.
+fn overflowing_mul(a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let result = (a as u128) * (b as u128) + (carry as u128);
+    (result as u64, (result >> 64) as u64)
+}
+
+// Multiply and add, returning the low and the high digit of `a * b + c + d`. This cannot overflow:
+// (2^64 - 1)^2 + 2 * (2^64 - 1) = 2^128 - 1.
+fn mul_add(a: u64, b: u64, c: u64, d: u64) -> (u64, u64) {
+    let result = (a as u128) * (b as u128) + (c as u128) + (d as u128);
+    (result as u64, (result >> 64) as u64)
+}
+
 impl BigInt {
     /// Construct a BigInt from a "small" one.
     pub fn new(x: u64) -> Self {
         if x == 0 { // take care of our invariant!
-            BigInt { data: vec![] }
+            BigInt { data: Digits::new() }
         } else {
-            BigInt { data: vec![x] }
+            BigInt { data: Digits::from_slice(&[x]) }
         }
     }
 
+    /// The number 0.
+    pub fn zero() -> Self {
+        BigInt { data: Digits::new() }
+    }
+
+    /// The number 1.
+    pub fn one() -> Self {
+        BigInt { data: Digits::from_slice(&[1]) }
+    }
+
     fn test_invariant(&self) -> bool {
         if self.data.len() == 0 {
             true
@@ -70,14 +135,33 @@ impl BigInt {
     }
 
     /// Construct a BigInt from a vector of 64-bit "digits", with the last significant digit being first. Solution to 05.1.
-    pub fn from_vec(mut v: Vec<u64>) -> Self {
+    pub fn from_vec(v: Vec<u64>) -> Self {
+        BigInt::from_digits(Digits::from(v))
+    }
+
+    // Like `from_vec`, for digits that are already in our storage format.
+    fn from_digits(mut v: Digits) -> Self {
         // remove trailing zeros
         while v.len() > 0 && v[v.len()-1] == 0 {
             v.pop();
         }
+        // If the number became small, we free the heap storage.
+        if v.len() <= digits::INLINE && !v.is_inline() {
+            v = Digits::from_slice(&v);
+        }
         BigInt { data: v }
     }
 
+    /// The digits of the number, least significant digit first. There are no trailing zeros.
+    pub fn digits(&self) -> &[u64] {
+        &self.data
+    }
+
+    /// The number of digits. 0 has no digits at all.
+    pub fn num_digits(&self) -> usize {
+        self.data.len()
+    }
+
     /// Increments the number by 1.
     pub fn inc1(&mut self) {
         let mut idx = 0;
@@ -119,6 +203,308 @@ impl BigInt {
         self.data.push(by);
     }
 
+    /// Multiply the number by a single digit.
+    pub fn mul_digit(&self, d: u64) -> BigInt {
+        let mut result_vec = Digits::with_capacity(self.data.len() + 1);
+        let mut carry = 0;
+        for &digit in self.data.iter() {
+            let (low, high) = overflowing_mul(digit, d, carry);
+            result_vec.push(low);
+            carry = high;
+        }
+        result_vec.push(carry);
+        // If `d` is 0, or there was no final carry, we have trailing zeros.
+        BigInt::from_digits(result_vec)
+    }
+
+    /// Decrements the number by 1. Panics if the number is 0.
+    pub fn dec1(&mut self) {
+        self.dec(1);
+    }
+
+    /// Decrements the number by "by". Panics if the number is smaller than "by".
+    pub fn dec(&mut self, mut by: u64) {
+        if *self < by {
+            panic!("Decrement of BigInt below zero");
+        }
+        let mut idx = 0;
+        // This loop subtracts "by * (1 << idx)". Since the number is large enough, we only run out
+        // of digits if there is nothing to subtract to begin with (i.e., we compute 0 - 0).
+        while idx < self.data.len() {
+            let cur = self.data[idx];
+            let diff = u64::wrapping_sub(cur, by);
+            self.data[idx] = diff;
+            if diff <= cur {
+                // No overflow, we are done.
+                break;
+            } else {
+                // We need to borrow from the next digit.
+                by = 1;
+                idx += 1;
+            }
+        }
+        // Only the most significant digit can have become 0.
+        if self.data.last() == Some(&0) {
+            self.data.pop();
+        }
+        debug_assert!(self.test_invariant());
+    }
+
+    /// Add `rhs` to this number in place. This reuses the storage of `self`, and only allocates if
+    /// the result needs more digits than fit into its capacity.
+    pub fn add_assign_ref(&mut self, rhs: &BigInt) {
+        add_shifted(&mut self.data, &rhs.data, 0);
+        debug_assert!(self.test_invariant());
+    }
+
+    /// Subtract `rhs`, returning `None` if the result would be negative.
+    pub fn checked_sub(&self, rhs: &BigInt) -> Option<BigInt> {
+        let max_len = cmp::max(self.data.len(), rhs.data.len());
+        let mut result_vec = Digits::with_capacity(max_len);
+        let mut carry:bool = false; // the carry bit
+        for i in 0..max_len {
+            // compute next digit and carry
+            let lhs_val = if i < self.data.len() { self.data[i] } else { 0 };
+            let rhs_val = if i < rhs.data.len() { rhs.data[i] } else { 0 };
+            let (sum, new_carry) = overflowing_sub(lhs_val, rhs_val, carry);
+            // store them
+            result_vec.push(sum);
+            carry = new_carry;
+        }
+        if carry {
+            return None;
+        }
+        // We may have trailing zeroes, so get rid of them
+        Some(BigInt::from_digits(result_vec))
+    }
+
+    /// Subtract `rhs`, returning the absolute value of the difference, and whether the difference
+    /// is negative.
+    pub fn sub_signed(&self, rhs: &BigInt) -> (BigInt, bool) {
+        match self.compare(rhs) {
+            cmp::Ordering::Less => (rhs - self, true),
+            _ => (self - rhs, false),
+        }
+    }
+
+    /// The absolute value of the difference of the two numbers. Unlike `-`, this never panics.
+    pub fn abs_diff(&self, other: &BigInt) -> BigInt {
+        match self.compare(other) {
+            cmp::Ordering::Less => other - self,
+            _ => self - other,
+        }
+    }
+
+    // Compare the values of two numbers.
+    fn compare(&self, other: &BigInt) -> cmp::Ordering {
+        // Thanks to the invariant, the longer number is the larger one. If they have the same
+        // length, the most significant digit that differs decides.
+        self.data.len().cmp(&other.data.len())
+            .then_with(|| self.data.iter().rev().cmp(other.data.iter().rev()))
+    }
+
+    // Multiply the number by 2, and add `bit` (which must be 0 or 1).
+    fn shift_left_one(&mut self, bit: u64) {
+        let mut carry = bit;
+        for digit in self.data.iter_mut() {
+            let new_carry = *digit >> 63;
+            *digit = (*digit << 1) | carry;
+            carry = new_carry;
+        }
+        if carry != 0 {
+            self.data.push(carry);
+        }
+    }
+
+    /// Divide by a single digit, returning the quotient and the remainder. Panics if `divisor`
+    /// is 0.
+    // This is much faster than the bitwise long division of `div_rem`, since we can divide one
+    // digit at a time: The remainder so far, together with the next digit, forms a 128-bit number,
+    // and dividing that by `divisor` gives the next digit of the quotient.
+    pub fn div_rem_u64(&self, divisor: u64) -> (BigInt, u64) {
+        if divisor == 0 {
+            panic!("Division of BigInt by zero");
+        }
+        let mut quotient = Digits::zeros(self.data.len());
+        let mut remainder: u64 = 0;
+        for idx in (0..self.data.len()).rev() {
+            let current = ((remainder as u128) << 64) | self.data[idx] as u128;
+            quotient[idx] = (current / divisor as u128) as u64;
+            remainder = (current % divisor as u128) as u64;
+        }
+        (BigInt::from_digits(quotient), remainder)
+    }
+
+    /// Return the bytes of the number, least significant first. There are no trailing zero bytes,
+    /// so 0 becomes the empty vector.
+    pub fn to_bytes_le(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.data.len() * 8);
+        for digit in self.data.iter() {
+            bytes.extend_from_slice(&digit.to_le_bytes());
+        }
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        bytes
+    }
+
+    /// Construct a number from its bytes, least significant first. Trailing zero bytes are fine.
+    pub fn from_bytes_le(bytes: &[u8]) -> Self {
+        let v = bytes.chunks(8).map(|chunk| {
+            // The last chunk may be shorter than 8 bytes.
+            let mut digit = [0; 8];
+            digit[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(digit)
+        }).collect();
+        BigInt::from_vec(v)
+    }
+
+    /// Convert the number to a string in the given radix, using the digits `0`-`9` and `a`-`z`.
+    /// Panics if `radix` is not between 2 and 36.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        assert!((2..=36).contains(&radix), "to_str_radix: radix {} is not in 2..=36", radix);
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        // Like `Display`, we divide by the largest power of the radix that fits into a digit, and
+        // convert the remainders one by one. `chunk_len` is the number of characters per chunk.
+        let (mut chunk, mut chunk_len) = (radix as u64, 1);
+        while let Some(next) = chunk.checked_mul(radix as u64) {
+            chunk = next;
+            chunk_len += 1;
+        }
+        let mut result: Vec<u8> = Vec::new(); // least significant character first
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            let (quotient, mut remainder) = rest.div_rem_u64(chunk);
+            rest = quotient;
+            for _ in 0..chunk_len {
+                let c = ::std::char::from_digit((remainder % radix as u64) as u32, radix).unwrap();
+                result.push(c as u8);
+                remainder /= radix as u64;
+            }
+        }
+        // The last chunk was padded with zeros.
+        while result.last() == Some(&b'0') {
+            result.pop();
+        }
+        result.reverse();
+        String::from_utf8(result).unwrap()
+    }
+
+    /// Parse a number in the given radix, the inverse of `to_str_radix`. Upper-case letters are
+    /// accepted, too, as well as a `0x` prefix in radix 16 and a `0b` prefix in radix 2. Panics
+    /// if `radix` is not between 2 and 36.
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<BigInt, ParseError> {
+        assert!((2..=36).contains(&radix), "from_str_radix: radix {} is not in 2..=36", radix);
+        let prefix = match radix {
+            16 => Some(["0x", "0X"]),
+            2 => Some(["0b", "0B"]),
+            _ => None,
+        };
+        let s = prefix.and_then(|prefix| prefix.iter().filter_map(|p| s.strip_prefix(p)).next())
+            .unwrap_or(s);
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        // We go the other way than `to_str_radix`: We convert a chunk of characters that fits into
+        // a digit, and then shift the result by that chunk.
+        let mut result = BigInt::zero();
+        let (mut chunk_val, mut chunk) = (0u64, 1u64);
+        for c in s.chars() {
+            let digit = match c.to_digit(radix) {
+                Some(digit) => digit as u64,
+                None => return Err(ParseError::InvalidDigit(c)),
+            };
+            if chunk.checked_mul(radix as u64).is_none() {
+                result = &result.mul_digit(chunk) + &BigInt::new(chunk_val);
+                chunk_val = 0;
+                chunk = 1;
+            }
+            chunk_val = chunk_val * radix as u64 + digit;
+            chunk *= radix as u64;
+        }
+        Ok(&result.mul_digit(chunk) + &BigInt::new(chunk_val))
+    }
+
+    /// Divide by `divisor`, returning the quotient and the remainder. Panics if `divisor` is 0.
+    pub fn div_rem(&self, divisor: &BigInt) -> (BigInt, BigInt) {
+        if divisor.is_zero() {
+            panic!("Division of BigInt by zero");
+        }
+        if divisor.data.len() == 1 {
+            let (quotient, remainder) = self.div_rem_u64(divisor.data[0]);
+            return (quotient, BigInt::new(remainder));
+        }
+        // Long division in base 2: We go through the bits of `self`, starting with the most
+        // significant one, and bring them down into the remainder one by one. Whenever the
+        // remainder is at least `divisor`, we subtract it and set that bit of the quotient.
+        let mut quotient = Digits::zeros(self.data.len());
+        let mut remainder = BigInt::zero();
+        for idx in (0..self.data.len()).rev() {
+            for bit in (0..64).rev() {
+                remainder.shift_left_one((self.data[idx] >> bit) & 1);
+                if remainder.compare(divisor) != cmp::Ordering::Less {
+                    remainder = &remainder - divisor;
+                    quotient[idx] |= 1 << bit;
+                }
+            }
+        }
+        (BigInt::from_digits(quotient), remainder)
+    }
+
+    /// Construct a pseudo-random BigInt with exactly `n` digits. The same `seed` always gives the
+    /// same number, so tests and benchmarks can use this to generate large operands reproducibly.
+    pub fn random_with_limbs(n: usize, seed: u64) -> BigInt {
+        let mut rng = Rng::new(seed ^ 0x9E37_79B9_7F4A_7C15);
+        let mut v: Digits = (0..n).map(|_| rng.next_u64()).collect();
+        // Make sure the most significant digit is not 0, so that we really get `n` digits.
+        if let Some(last) = v.last_mut() {
+            if *last == 0 {
+                *last = 1;
+            }
+        }
+        BigInt { data: v }
+    }
+
+    /// Test whether the number is 0. Thanks to the invariant, that's the case iff there are no
+    /// digits.
+    pub fn is_zero(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn is_even(&self) -> bool {
+        !self.bit(0)
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.bit(0)
+    }
+
+    /// Return bit `i` of the number, where bit 0 is the least significant one.
+    pub fn bit(&self, i: u64) -> bool {
+        let idx = (i / 64) as usize;
+        idx < self.data.len() && (self.data[idx] >> (i % 64)) & 1 == 1
+    }
+
+    /// The number of bits needed to write down the number, i.e., the position of the most
+    /// significant 1 bit plus one. 0 needs no bits at all.
+    pub fn bit_length(&self) -> u64 {
+        match self.data.last() {
+            // Thanks to the invariant, the last digit is not 0.
+            Some(&last) => (self.data.len() as u64) * 64 - last.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// The number of 0 bits below the least significant 1 bit, i.e., how often the number can be
+    /// divided by 2. Returns `None` if the number is 0, which can be divided by 2 any number of
+    /// times.
+    pub fn trailing_zeros(&self) -> Option<u64> {
+        let idx = self.data.iter().position(|&digit| digit != 0)?;
+        Some((idx as u64) * 64 + self.data[idx].trailing_zeros() as u64)
+    }
+
     /// Return the nth power-of-2 as BigInt
     pub fn power_of_2(mut power: u64) -> BigInt {
         let mut v = Vec::new();
@@ -131,6 +517,161 @@ impl BigInt {
     }
 }
 
+/// An iterator over the digits of a `BigInt`, least-significant digit first. Solution to 09.2.
+pub struct IterLdf<'a> {
+    num: &'a BigInt,
+    idx: usize, // the index of the next digit to return
+}
+
+impl<'a> Iterator for IterLdf<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.idx < self.num.data.len() {
+            self.idx += 1;
+            Some(self.num.data[self.idx - 1])
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.num.data.len() - self.idx;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for IterLdf<'a> {}
+
+impl BigInt {
+    /// Iterate over the digits, least-significant digit first.
+    pub fn iter_ldf(&self) -> IterLdf<'_> {
+        IterLdf { num: self, idx: 0 }
+    }
+}
+
+impl From<u8> for BigInt {
+    fn from(x: u8) -> Self {
+        BigInt::new(x as u64)
+    }
+}
+
+impl From<u16> for BigInt {
+    fn from(x: u16) -> Self {
+        BigInt::new(x as u64)
+    }
+}
+
+impl From<u32> for BigInt {
+    fn from(x: u32) -> Self {
+        BigInt::new(x as u64)
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(x: u64) -> Self {
+        BigInt::new(x)
+    }
+}
+
+impl From<u128> for BigInt {
+    fn from(x: u128) -> Self {
+        BigInt::from_vec(vec![x as u64, (x >> 64) as u64])
+    }
+}
+
+/// The error returned when a `BigInt` does not fit into a primitive integer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TryFromBigIntError(());
+
+impl fmt::Display for TryFromBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BigInt too large for the target type")
+    }
+}
+
+impl error::Error for TryFromBigIntError {}
+
+/// The error returned when parsing a `BigInt` fails.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// There were no digits.
+    Empty,
+    /// The character is not a digit in the radix.
+    InvalidDigit(char),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Empty => write!(f, "cannot parse BigInt from empty string"),
+            ParseError::InvalidDigit(c) => write!(f, "invalid digit `{}` in BigInt", c),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+// Without a prefix, numbers are decimal.
+impl FromStr for BigInt {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("0x") || s.starts_with("0X") {
+            BigInt::from_str_radix(s, 16)
+        } else if s.starts_with("0b") || s.starts_with("0B") {
+            BigInt::from_str_radix(s, 2)
+        } else {
+            BigInt::from_str_radix(s, 10)
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a BigInt> for u64 {
+    type Error = TryFromBigIntError;
+    fn try_from(b: &'a BigInt) -> Result<Self, Self::Error> {
+        match b.data.len() {
+            0 => Ok(0),
+            1 => Ok(b.data[0]),
+            _ => Err(TryFromBigIntError(())),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a BigInt> for u128 {
+    type Error = TryFromBigIntError;
+    fn try_from(b: &'a BigInt) -> Result<Self, Self::Error> {
+        match b.data.len() {
+            0 => Ok(0),
+            1 => Ok(b.data[0] as u128),
+            2 => Ok(((b.data[1] as u128) << 64) | b.data[0] as u128),
+            _ => Err(TryFromBigIntError(())),
+        }
+    }
+}
+
+// With the `serde` feature, a `BigInt` is serialized as its list of digits, least significant
+// first. Deserialization goes through `from_vec`, so trailing zeros are accepted and removed.
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for BigInt {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.data[..].serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for BigInt {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u64>::deserialize(deserializer).map(BigInt::from_vec)
+    }
+}
+
+// The default value is 0, like for the primitive integers.
+impl Default for BigInt {
+    fn default() -> Self {
+        BigInt::zero()
+    }
+}
+
 impl Clone for BigInt {
     fn clone(&self) -> Self {
         BigInt { data: self.data.clone() }
@@ -144,6 +685,25 @@ impl PartialEq for BigInt {
     }
 }
 
+// Comparing with a `u64` directly saves us from converting it to a `BigInt` first.
+impl PartialEq<u64> for BigInt {
+    fn eq(&self, other: &u64) -> bool {
+        self.partial_cmp(other) == Some(cmp::Ordering::Equal)
+    }
+}
+
+impl PartialOrd<u64> for BigInt {
+    fn partial_cmp(&self, other: &u64) -> Option<cmp::Ordering> {
+        debug_assert!(self.test_invariant());
+        // Thanks to the invariant, a number with more than one digit is larger than any `u64`.
+        Some(match self.data.len() {
+            0 => 0.cmp(other),
+            1 => self.data[0].cmp(other),
+            _ => cmp::Ordering::Greater,
+        })
+    }
+}
+
 impl Minimum for BigInt {
     // This is essentially the solution to 06.1.
     fn min<'a>(&'a self, other: &'a Self) -> &'a Self {
@@ -169,17 +729,101 @@ impl Minimum for BigInt {
     }
 }
 
+impl MinMax for BigInt {
+    fn max<'a>(&'a self, other: &'a Self) -> &'a Self {
+        if self.compare(other) == cmp::Ordering::Less { other } else { self }
+    }
+}
+
 impl fmt::Debug for BigInt {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.data.fmt(f)
     }
 }
 
+// In hexadecimal and binary, every digit of the `BigInt` corresponds to a fixed number of
+// characters, so we can just print the digits, most significant first.
+impl fmt::LowerHex for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.data.split_last() {
+            None => write!(f, "0"),
+            Some((last, rest)) => {
+                write!(f, "{:x}", last)?;
+                for digit in rest.iter().rev() {
+                    write!(f, "{:016x}", digit)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Binary for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.data.split_last() {
+            None => write!(f, "0"),
+            Some((last, rest)) => {
+                write!(f, "{:b}", last)?;
+                for digit in rest.iter().rev() {
+                    write!(f, "{:064b}", digit)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// The largest power of 10 that fits into a digit.
+const DECIMAL_CHUNK: u64 = 10_000_000_000_000_000_000;
+
+// Width, fill, alignment and the `+` and `0` flags work like for the primitive integers. In
+// addition, the alternate form `{:#}` separates groups of three digits with commas.
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // We split the number into chunks of 19 decimal digits, least significant first, by
+        // repeatedly dividing by 10^19.
+        let mut chunks = Vec::new();
+        let mut rest = self.clone();
+        while !rest.is_zero() {
+            let (quotient, chunk) = rest.div_rem_u64(DECIMAL_CHUNK);
+            chunks.push(chunk);
+            rest = quotient;
+        }
+        // The most significant chunk is printed as it is, all the others need their leading zeros.
+        let mut digits = match chunks.pop() {
+            None => "0".to_string(),
+            Some(first) => {
+                let mut digits = first.to_string();
+                for chunk in chunks.iter().rev() {
+                    digits.push_str(&format!("{:019}", chunk));
+                }
+                digits
+            }
+        };
+        if f.alternate() {
+            digits = group_thousands(&digits);
+        }
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+// Insert a comma between every group of three digits, counting from the right.
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
 impl<'a, 'b> ops::Add<&'a BigInt> for &'b BigInt {
     type Output = BigInt;
     fn add(self, rhs: &'a BigInt) -> Self::Output {
         let max_len = cmp::max(self.data.len(), rhs.data.len());
-        let mut result_vec:Vec<u64> = Vec::with_capacity(max_len);
+        let mut result_vec = Digits::with_capacity(max_len);
         let mut carry:bool = false; // the carry bit
         for i in 0..max_len {
             // compute next digit and carry
@@ -211,8 +855,10 @@ impl<'a> ops::Add<BigInt> for &'a BigInt {
 impl<'a> ops::Add<&'a BigInt> for BigInt {
     type Output = BigInt;
     #[inline]
-    fn add(self, rhs: &'a BigInt) -> Self::Output {
-        &self + rhs
+    fn add(mut self, rhs: &'a BigInt) -> Self::Output {
+        // We own `self`, so we can reuse its storage for the result.
+        self.add_assign_ref(rhs);
+        self
     }
 }
 
@@ -220,30 +866,31 @@ impl ops::Add<BigInt> for BigInt {
     type Output = BigInt;
     #[inline]
     fn add(self, rhs: BigInt) -> Self::Output {
-        &self + &rhs
+        self + &rhs
+    }
+}
+
+impl<'a> ops::AddAssign<&'a BigInt> for BigInt {
+    #[inline]
+    fn add_assign(&mut self, rhs: &'a BigInt) {
+        self.add_assign_ref(rhs);
+    }
+}
+
+impl ops::AddAssign<BigInt> for BigInt {
+    #[inline]
+    fn add_assign(&mut self, rhs: BigInt) {
+        self.add_assign_ref(&rhs);
     }
 }
 
 impl<'a, 'b> ops::Sub<&'a BigInt> for &'b BigInt {
     type Output = BigInt;
     fn sub(self, rhs: &'a BigInt) -> Self::Output {
-        let max_len = cmp::max(self.data.len(), rhs.data.len());
-        let mut result_vec:Vec<u64> = Vec::with_capacity(max_len);
-        let mut carry:bool = false; // the carry bit
-        for i in 0..max_len {
-            // compute next digit and carry
-            let lhs_val = if i < self.data.len() { self.data[i] } else { 0 };
-            let rhs_val = if i < rhs.data.len() { rhs.data[i] } else { 0 };
-            let (sum, new_carry) = overflowing_sub(lhs_val, rhs_val, carry);
-            // store them
-            result_vec.push(sum);
-            carry = new_carry;
+        match self.checked_sub(rhs) {
+            Some(result) => result,
+            None => panic!("Wrapping subtraction of BigInt"),
         }
-        if carry {
-            panic!("Wrapping subtraction of BigInt");
-        }
-        // We may have trailing zeroes, so get rid of them
-        BigInt::from_vec(result_vec)
     }
 }
 
@@ -271,14 +918,244 @@ impl ops::Sub<BigInt> for BigInt {
     }
 }
 
+// Schoolbook multiplication of two digit slices: Multiply `lhs` with every digit of `rhs`, and add
+// the result, shifted by the position of that digit. The result may have trailing zeros.
+fn mul_schoolbook(lhs: &[u64], rhs: &[u64]) -> Digits {
+    let mut result_vec = Digits::zeros(lhs.len() + rhs.len());
+    for (j, &rhs_val) in rhs.iter().enumerate() {
+        let mut carry = 0;
+        for (i, &lhs_val) in lhs.iter().enumerate() {
+            let (digit, new_carry) = mul_add(lhs_val, rhs_val, result_vec[i + j], carry);
+            result_vec[i + j] = digit;
+            carry = new_carry;
+        }
+        // Nothing has been stored at this position yet, so there is no sum to overflow.
+        result_vec[j + lhs.len()] = carry;
+    }
+    result_vec
+}
+
+// Add `rhs`, shifted by `shift` digits, to `acc`, growing `acc` as needed.
+fn add_shifted(acc: &mut Digits, rhs: &[u64], shift: usize) {
+    if acc.len() < shift + rhs.len() {
+        acc.resize(shift + rhs.len(), 0);
+    }
+    let carry = {
+        // We work on a slice, so that we do not have to check where the digits are stored for
+        // every single access.
+        let digits = &mut acc[shift..];
+        let mut carry = false;
+        for (digit, &rhs_val) in digits.iter_mut().zip(rhs) {
+            let (sum, new_carry) = overflowing_add(*digit, rhs_val, carry);
+            *digit = sum;
+            carry = new_carry;
+        }
+        for digit in digits[rhs.len()..].iter_mut() {
+            if !carry {
+                break;
+            }
+            let (sum, new_carry) = overflowing_add(*digit, 0, true);
+            *digit = sum;
+            carry = new_carry;
+        }
+        carry
+    };
+    if carry {
+        acc.push(1);
+    }
+}
+
+// Subtract `rhs` from `acc`. The caller makes sure the result is not negative.
+fn sub_in_place(acc: &mut [u64], rhs: &[u64]) {
+    let mut carry = false;
+    for idx in 0..acc.len() {
+        if idx >= rhs.len() && !carry {
+            return;
+        }
+        let rhs_val = if idx < rhs.len() { rhs[idx] } else { 0 };
+        let (diff, new_carry) = overflowing_sub(acc[idx], rhs_val, carry);
+        acc[idx] = diff;
+        carry = new_carry;
+    }
+    debug_assert!(!carry && rhs.iter().skip(acc.len()).all(|&d| d == 0));
+}
+
+/// From this many digits (in the shorter operand) on, multiplication uses Karatsuba's algorithm.
+pub const KARATSUBA_THRESHOLD: usize = 32;
+
+// Multiply two digit slices, using Karatsuba's algorithm if both have at least `threshold` digits.
+// Split both numbers at `m` digits, `lhs = l1 * B^m + l0` and `rhs = r1 * B^m + r0`, where B = 2^64.
+// Then `lhs * rhs = z2 * B^2m + z1 * B^m + z0` with `z2 = l1 * r1`, `z0 = l0 * r0` and
+// `z1 = (l0 + l1) * (r0 + r1) - z2 - z0`. That's three multiplications of half the size instead of
+// four, which makes the algorithm O(n^1.585) instead of O(n^2). The additions and subtractions
+// have a cost, too, which is why we only do this for large numbers.
+fn mul_digits(lhs: &[u64], rhs: &[u64], threshold: usize) -> Digits {
+    // With fewer than 2 digits, there is nothing to split.
+    if cmp::min(lhs.len(), rhs.len()) < cmp::max(threshold, 2) {
+        return mul_schoolbook(lhs, rhs);
+    }
+    let m = cmp::max(lhs.len(), rhs.len()) / 2;
+    let (l0, l1) = lhs.split_at(cmp::min(m, lhs.len()));
+    let (r0, r1) = rhs.split_at(cmp::min(m, rhs.len()));
+    let z0 = mul_digits(l0, r0, threshold);
+    let z2 = mul_digits(l1, r1, threshold);
+    let mut l_sum = Digits::from_slice(l0);
+    add_shifted(&mut l_sum, l1, 0);
+    let mut r_sum = Digits::from_slice(r0);
+    add_shifted(&mut r_sum, r1, 0);
+    let mut z1 = mul_digits(&l_sum, &r_sum, threshold);
+    sub_in_place(&mut z1, &z0);
+    sub_in_place(&mut z1, &z2);
+
+    let mut result_vec = z0;
+    result_vec.reserve(lhs.len() + rhs.len());
+    add_shifted(&mut result_vec, &z1, m);
+    add_shifted(&mut result_vec, &z2, 2 * m);
+    result_vec
+}
+
+impl<'a, 'b> ops::Mul<&'a BigInt> for &'b BigInt {
+    type Output = BigInt;
+    fn mul(self, rhs: &'a BigInt) -> Self::Output {
+        // The most significant digit may be 0, e.g., for 2 * 3.
+        BigInt::from_digits(mul_digits(&self.data, &rhs.data, KARATSUBA_THRESHOLD))
+    }
+}
+
+impl<'a> ops::Mul<BigInt> for &'a BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn mul(self, rhs: BigInt) -> Self::Output {
+        self * &rhs
+    }
+}
+
+impl<'a> ops::Mul<&'a BigInt> for BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn mul(self, rhs: &'a BigInt) -> Self::Output {
+        &self * rhs
+    }
+}
+
+impl ops::Mul<BigInt> for BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn mul(self, rhs: BigInt) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl<'a, 'b> ops::Div<&'a BigInt> for &'b BigInt {
+    type Output = BigInt;
+    /// Panics if `rhs` is 0.
+    fn div(self, rhs: &'a BigInt) -> Self::Output {
+        self.div_rem(rhs).0
+    }
+}
+
+impl<'a> ops::Div<BigInt> for &'a BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn div(self, rhs: BigInt) -> Self::Output {
+        self / &rhs
+    }
+}
+
+impl<'a> ops::Div<&'a BigInt> for BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn div(self, rhs: &'a BigInt) -> Self::Output {
+        &self / rhs
+    }
+}
+
+impl ops::Div<BigInt> for BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn div(self, rhs: BigInt) -> Self::Output {
+        &self / &rhs
+    }
+}
+
+impl<'a, 'b> ops::Rem<&'a BigInt> for &'b BigInt {
+    type Output = BigInt;
+    /// Panics if `rhs` is 0.
+    fn rem(self, rhs: &'a BigInt) -> Self::Output {
+        self.div_rem(rhs).1
+    }
+}
+
+impl<'a> ops::Rem<BigInt> for &'a BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn rem(self, rhs: BigInt) -> Self::Output {
+        self % &rhs
+    }
+}
+
+impl<'a> ops::Rem<&'a BigInt> for BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn rem(self, rhs: &'a BigInt) -> Self::Output {
+        &self % rhs
+    }
+}
+
+impl ops::Rem<BigInt> for BigInt {
+    type Output = BigInt;
+    #[inline]
+    fn rem(self, rhs: BigInt) -> Self::Output {
+        &self % &rhs
+    }
+}
+
+// With these, `v.iter().sum::<BigInt>()` and `v.into_iter().product::<BigInt>()` work.
+impl iter::Sum<BigInt> for BigInt {
+    fn sum<I: Iterator<Item = BigInt>>(iter: I) -> Self {
+        iter.fold(BigInt::zero(), |acc, x| acc + x)
+    }
+}
+
+impl<'a> iter::Sum<&'a BigInt> for BigInt {
+    fn sum<I: Iterator<Item = &'a BigInt>>(iter: I) -> Self {
+        iter.fold(BigInt::zero(), |acc, x| acc + x)
+    }
+}
+
+impl iter::Product<BigInt> for BigInt {
+    fn product<I: Iterator<Item = BigInt>>(iter: I) -> Self {
+        iter.fold(BigInt::one(), |acc, x| acc * x)
+    }
+}
+
+impl<'a> iter::Product<&'a BigInt> for BigInt {
+    fn product<I: Iterator<Item = &'a BigInt>>(iter: I) -> Self {
+        iter.fold(BigInt::one(), |acc, x| acc * x)
+    }
+}
+
+pub fn main() {
+    let a = BigInt::power_of_2(100);
+    let b = BigInt::new(1 << 63);
+    println!("2^100 = {:?}", a);
+    println!("2^100 + 2^63 = {:?}", &a + &b);
+    println!("2^100 - 2^63 = {:?}", &a - &b);
+    println!("2^100 * 2^63 = {:?}", &a * &b);
+    println!("2^100 / 3 = {:?}, remainder {:?}", &a / BigInt::new(3), &a % BigInt::new(3));
+    println!("min = {:?}", vec_min(&vec![a, b]));
+}
+
 #[cfg(test)]
 mod tests {
     use std::u64;
-    use super::{overflowing_add,overflowing_sub,BigInt,Minimum,vec_min};
+    use std::convert::TryFrom;
+    use super::{examples,overflowing_add,overflowing_sub,overflowing_mul,mul_add,mul_digits,mul_schoolbook,BigInt,Minimum,vec_min,vec_min_max,
+                TryFromBigIntError,ParseError,KARATSUBA_THRESHOLD};
 
     #[test]
     fn test_min() {
-        let b1 = BigInt::new(1);
+        let b1 = BigInt::one();
         let b2 = BigInt::new(42);
         let b3 = BigInt::from_vec(vec![0, 1]);
 
@@ -290,7 +1167,7 @@ mod tests {
 
     #[test]
     fn test_vec_min() {
-    let b1 = BigInt::new(1);
+    let b1 = BigInt::one();
         let b2 = BigInt::new(42);
         let b3 = BigInt::from_vec(vec![0, 1]);
 
@@ -300,6 +1177,23 @@ mod tests {
         assert_eq!(vec_min::<BigInt>(&vec![]), None);
     }
 
+    #[test]
+    fn test_vec_min_max() {
+        let b1 = BigInt::one();
+        let b2 = BigInt::new(42);
+        let b3 = BigInt::from_vec(vec![0, 1]);
+
+        assert_eq!(vec_min_max(&vec![b2.clone(), b1.clone(), b3.clone()]), Some((&b1, &b3)));
+        assert_eq!(vec_min_max(&vec![b3.clone(), b2.clone()]), Some((&b2, &b3)));
+        assert_eq!(vec_min_max(&vec![b3.clone()]), Some((&b3, &b3)));
+        assert_eq!(vec_min_max::<BigInt>(&vec![]), None);
+
+        assert_eq!(vec_min_max(&vec![18, 5, -7, 3, 27, 9]), Some((&-7, &27)));
+        assert_eq!(vec_min_max(&vec![1, 1]), Some((&1, &1)));
+        assert_eq!(vec_min_max::<i32>(&vec![]), None);
+        assert_eq!(vec_min(&vec![18, 5, -7]), Some(&-7));
+    }
+
     #[test]
     fn test_overflowing_add() {
         assert_eq!(overflowing_add(10, 100, false), (110, false));
@@ -342,7 +1236,7 @@ mod tests {
 
         assert_eq!(&b2 - &b1, BigInt::from_vec(vec![u64::max_value() - (1 << 32) + 1]));
         assert_eq!(&b3 - &b2, BigInt::from_vec(vec![0, u64::max_value(), 0]));
-        assert_eq!(&b2 - &b4 - &b4, BigInt::new(0));
+        assert_eq!(&b2 - &b4 - &b4, BigInt::zero());
         assert_eq!(&b3 - &b2 - &b4 - &b4, BigInt::from_vec(vec![0, u64::max_value() - 1]));
         assert_eq!(&b3 - &b4 - &b2 - &b4, BigInt::from_vec(vec![0, u64::max_value() - 1]));
         assert_eq!(&b3 - &b4 - &b4 - &b2, BigInt::from_vec(vec![0, u64::max_value() - 1]));
@@ -351,7 +1245,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "Wrapping subtraction of BigInt")]
     fn test_sub_panic1() {
-        let _ = BigInt::new(1) - BigInt::new(5);
+        let _ = BigInt::one() - BigInt::new(5);
     }
 
     #[test]
@@ -360,11 +1254,579 @@ mod tests {
         let _ = BigInt::from_vec(vec![5,8,3,33,1<<13,46,1<<49, 1, 583,1<<60,2533]) - BigInt::from_vec(vec![5,8,3,33,1<<13,46,1<<49, 5, 583,1<<60,2533]);
     }
 
+    #[test]
+    fn test_checked_sub() {
+        let b1 = BigInt::from_vec(vec![0, 1]);
+        let b2 = BigInt::new(1 << 63);
+        assert_eq!(b1.checked_sub(&b2), Some(b2.clone()));
+        assert_eq!(b1.checked_sub(&b1), Some(BigInt::zero()));
+        assert_eq!(b2.checked_sub(&b1), None);
+        assert_eq!(BigInt::one().checked_sub(&BigInt::new(5)), None);
+        assert_eq!(BigInt::from_vec(vec![5,8,3,33,1<<13,46,1<<49, 1, 583,1<<60,2533]).checked_sub(&BigInt::from_vec(vec![5,8,3,33,1<<13,46,1<<49, 5, 583,1<<60,2533])), None);
+    }
+
+    #[test]
+    fn test_sub_signed() {
+        let b1 = BigInt::from_vec(vec![0, 1]);
+        let b2 = BigInt::new(1 << 63);
+        assert_eq!(b1.sub_signed(&b2), (b2.clone(), false));
+        assert_eq!(b2.sub_signed(&b1), (b2.clone(), true));
+        assert_eq!(b1.sub_signed(&b1), (BigInt::zero(), false));
+        assert_eq!(BigInt::new(3).sub_signed(&BigInt::new(10)), (BigInt::new(7), true));
+        assert_eq!(BigInt::new(10).sub_signed(&BigInt::new(3)), (BigInt::new(7), false));
+    }
+
+    #[test]
+    fn test_abs_diff() {
+        let b1 = BigInt::from_vec(vec![0, 1]);
+        let b2 = BigInt::new(1 << 63);
+        assert_eq!(b1.abs_diff(&b2), b2);
+        assert_eq!(b1.abs_diff(&b1), BigInt::zero());
+        assert_eq!(BigInt::new(3).abs_diff(&BigInt::new(10)), BigInt::new(7));
+        assert_eq!(BigInt::zero().abs_diff(&b1), b1);
+        let numbers = [BigInt::zero(), BigInt::new(5), b1.clone(), BigInt::random_with_limbs(4, 1),
+                       BigInt::random_with_limbs(4, 2)];
+        for a in numbers.iter() {
+            for b in numbers.iter() {
+                assert_eq!(a.abs_diff(b), b.abs_diff(a));
+                assert_eq!(a.abs_diff(b), a.sub_signed(b).0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare_u64() {
+        assert!(BigInt::zero() == 0);
+        assert!(BigInt::new(42) == 42);
+        assert!(BigInt::new(42) != 43);
+        assert!(BigInt::new(42) < 100);
+        assert!(BigInt::zero() < 1);
+        assert!(BigInt::new(u64::MAX) >= u64::MAX);
+        assert!(BigInt::from_vec(vec![0, 1]) > u64::MAX);
+        assert!(BigInt::from_vec(vec![0, 1]) != 0);
+    }
+
+    #[test]
+    fn test_overflowing_mul() {
+        assert_eq!(overflowing_mul(6, 7, 0), (42, 0));
+        assert_eq!(overflowing_mul(6, 7, 3), (45, 0));
+        assert_eq!(overflowing_mul(1 << 32, 1 << 32, 0), (0, 1));
+        assert_eq!(overflowing_mul(1 << 63, 4, 5), (5, 2));
+        assert_eq!(overflowing_mul(u64::MAX, u64::MAX, u64::MAX), (0, u64::MAX));
+    }
+
+    #[test]
+    fn test_mul_digit() {
+        let b = BigInt::from_vec(vec![u64::MAX, 1 << 63, 42]);
+        assert_eq!(b.mul_digit(0), BigInt::zero());
+        assert_eq!(b.mul_digit(1), b);
+        assert_eq!(b.mul_digit(2), BigInt::from_vec(vec![u64::MAX - 1, 1, 85]));
+        assert_eq!(BigInt::zero().mul_digit(5), BigInt::zero());
+        assert_eq!(BigInt::new(u64::MAX).mul_digit(u64::MAX), BigInt::from_vec(vec![1, u64::MAX - 1]));
+        for d in [3, 1 << 40, u64::MAX].iter() {
+            assert_eq!(b.mul_digit(*d), &b * &BigInt::new(*d));
+        }
+    }
+
+    #[test]
+    fn test_mul_add() {
+        assert_eq!(mul_add(6, 7, 0, 0), (42, 0));
+        assert_eq!(mul_add(1 << 32, 1 << 32, 1, 2), (3, 1));
+        assert_eq!(mul_add(u64::MAX, u64::MAX, u64::MAX, u64::MAX), (u64::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn test_mul() {
+        let zero = BigInt::zero();
+        let b1 = BigInt::new(6);
+        let b2 = BigInt::new(7);
+        let b3 = BigInt::from_vec(vec![0, 1]);
+        let max = BigInt::new(u64::MAX);
+
+        assert_eq!(&b1 * &b2, BigInt::new(42));
+        assert_eq!(&b1 * &zero, zero);
+        assert_eq!(&zero * &b3, zero);
+        assert_eq!(&b3 * &b3, BigInt::from_vec(vec![0, 0, 1]));
+        // (2^64 - 1)^2 = 2^128 - 2^65 + 1
+        assert_eq!(&max * &max, BigInt::from_vec(vec![1, u64::MAX - 1]));
+        // The carry runs across several digits: (2^192 - 1) * (2^64 + 1) = 2^256 + 2^192 - 2^64 - 1
+        let b4 = BigInt::from_vec(vec![u64::MAX, u64::MAX, u64::MAX]);
+        let b5 = BigInt::from_vec(vec![1, 1]);
+        assert_eq!(&b4 * &b5, BigInt::from_vec(vec![u64::MAX, u64::MAX - 1, u64::MAX, 0, 1]));
+        assert_eq!(&b4 * &b5, &b5 * &b4);
+        // Multiplication distributes over addition.
+        assert_eq!((&b4 + &b5) * &b3, &b4 * &b3 + &b5 * &b3);
+        assert_eq!(b1 * b2 * b3, BigInt::from_vec(vec![0, 42]));
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let zero = BigInt::zero();
+        let b1 = BigInt::new(42);
+        let b2 = BigInt::new(5);
+        let b3 = BigInt::from_vec(vec![0, 1]);
+        let max = BigInt::new(u64::MAX);
+
+        assert_eq!(b1.div_rem(&b2), (BigInt::new(8), BigInt::new(2)));
+        assert_eq!(b2.div_rem(&b1), (zero.clone(), b2.clone()));
+        assert_eq!(zero.div_rem(&b1), (zero.clone(), zero.clone()));
+        assert_eq!(b1.div_rem(&b1), (BigInt::one(), zero.clone()));
+        // 2^64 = 1 * (2^64 - 1) + 1
+        assert_eq!(&b3 / &max, BigInt::one());
+        assert_eq!(&b3 % &max, BigInt::one());
+        assert_eq!(&b3 / BigInt::new(2), BigInt::new(1 << 63));
+        // Multi-digit divisors: (a * b + r) / b = a, with remainder r < b.
+        let a = BigInt::from_vec(vec![7, u64::MAX, 3, 1 << 40]);
+        let b = BigInt::from_vec(vec![u64::MAX, 12345, 6]);
+        let r = BigInt::from_vec(vec![99, 5, 6]);
+        let n = &a * &b + &r;
+        assert_eq!(n.div_rem(&b), (a.clone(), r.clone()));
+        assert_eq!(&n / &a, b);
+        assert_eq!(n % a, r);
+    }
+
+    #[test]
+    fn test_div_rem_u64() {
+        assert_eq!(BigInt::zero().div_rem_u64(7), (BigInt::zero(), 0));
+        assert_eq!(BigInt::new(42).div_rem_u64(5), (BigInt::new(8), 2));
+        assert_eq!(BigInt::new(5).div_rem_u64(42), (BigInt::zero(), 5));
+        assert_eq!(BigInt::new(42).div_rem_u64(1), (BigInt::new(42), 0));
+        // 2^64 = 1 * (2^64 - 1) + 1
+        assert_eq!(BigInt::from_vec(vec![0, 1]).div_rem_u64(u64::MAX), (BigInt::one(), 1));
+        assert_eq!(BigInt::from_vec(vec![0, 1]).div_rem_u64(2), (BigInt::new(1 << 63), 0));
+        // (a * d + r) / d = a, with remainder r < d.
+        let a = BigInt::random_with_limbs(5, 11);
+        for &(d, r) in [(3, 2), (1 << 40, 12345), (u64::MAX, u64::MAX - 1)].iter() {
+            let n = &a.mul_digit(d) + &BigInt::new(r);
+            assert_eq!(n.div_rem_u64(d), (a.clone(), r));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Division of BigInt by zero")]
+    fn test_div_rem_u64_by_zero() {
+        let _ = BigInt::one().div_rem_u64(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Division of BigInt by zero")]
+    fn test_div_by_zero() {
+        let _ = BigInt::one() / BigInt::zero();
+    }
+
+    #[test]
+    #[should_panic(expected = "Division of BigInt by zero")]
+    fn test_rem_by_zero() {
+        let _ = BigInt::one() % BigInt::zero();
+    }
+
+    #[test]
+    fn test_iter_ldf() {
+        let b = BigInt::from_vec(vec![1, 2, 3]);
+        assert_eq!(b.iter_ldf().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(BigInt::zero().iter_ldf().next(), None);
+        // The iterator borrows `b`, and can be restarted any time.
+        let mut iter = b.iter_ldf();
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(b.iter_ldf().max(), Some(3));
+        assert_eq!(iter.count(), 2);
+    }
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(BigInt::zero().digits(), &[] as &[u64]);
+        assert_eq!(BigInt::zero().num_digits(), 0);
+        let b = BigInt::from_vec(vec![1, 2, 3, 0, 0]);
+        assert_eq!(b.digits(), &[1, 2, 3]);
+        assert_eq!(b.num_digits(), 3);
+        assert_eq!(BigInt::from_vec(b.digits().to_vec()), b);
+    }
+
+    #[test]
+    fn test_iter_ldf_len() {
+        for b in [BigInt::zero(), BigInt::new(5), BigInt::from_vec(vec![1, 2, 3]),
+                  BigInt::random_with_limbs(10, 3)].iter() {
+            let mut iter = b.iter_ldf();
+            for left in (0..b.iter_ldf().count() + 1).rev() {
+                assert_eq!(iter.len(), left);
+                assert_eq!(iter.size_hint(), (left, Some(left)));
+                iter.next();
+            }
+            assert_eq!(iter.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_parity_and_bits() {
+        let zero = BigInt::zero();
+        assert!(zero.is_zero() && zero.is_even() && !zero.is_odd());
+        assert!(!zero.bit(0) && !zero.bit(1000));
+        assert!(BigInt::from_vec(vec![0, 0]).is_zero());
+
+        let b = BigInt::from_vec(vec![u64::MAX, 0, 1]);
+        assert!(!b.is_zero() && b.is_odd() && !b.is_even());
+        assert!(b.bit(63));
+        assert!(!b.bit(64) && !b.bit(127));
+        assert!(b.bit(128));
+        assert!(!b.bit(129) && !b.bit(192));
+
+        // 2^64 is even, 2^64 + 1 is odd.
+        let mut c = BigInt::power_of_2(64);
+        assert!(c.is_even() && c.bit(64) && !c.bit(63));
+        c.inc1();
+        assert!(c.is_odd());
+    }
+
+    #[test]
+    fn test_bit_length_trailing_zeros() {
+        assert_eq!(BigInt::zero().bit_length(), 0);
+        assert_eq!(BigInt::zero().trailing_zeros(), None);
+        assert_eq!(BigInt::one().bit_length(), 1);
+        assert_eq!(BigInt::one().trailing_zeros(), Some(0));
+        assert_eq!(BigInt::new(12).bit_length(), 4);
+        assert_eq!(BigInt::new(12).trailing_zeros(), Some(2));
+        // At the limb boundaries
+        assert_eq!(BigInt::new(u64::MAX).bit_length(), 64);
+        assert_eq!(BigInt::new(1 << 63).bit_length(), 64);
+        assert_eq!(BigInt::new(1 << 63).trailing_zeros(), Some(63));
+        assert_eq!(BigInt::from_vec(vec![0, 1]).bit_length(), 65);
+        assert_eq!(BigInt::from_vec(vec![0, 1]).trailing_zeros(), Some(64));
+        assert_eq!(BigInt::from_vec(vec![u64::MAX, u64::MAX]).bit_length(), 128);
+        assert_eq!(BigInt::from_vec(vec![u64::MAX, u64::MAX]).trailing_zeros(), Some(0));
+        for &power in [0, 1, 63, 64, 65, 127, 128, 1000].iter() {
+            let b = BigInt::power_of_2(power);
+            assert_eq!(b.bit_length(), power + 1);
+            assert_eq!(b.trailing_zeros(), Some(power));
+            assert!(b.bit(b.bit_length() - 1) && !b.bit(b.bit_length()));
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(BigInt::zero().to_string(), "0");
+        assert_eq!(BigInt::new(42).to_string(), "42");
+        assert_eq!(BigInt::new(u64::MAX).to_string(), "18446744073709551615");
+        assert_eq!(BigInt::from_vec(vec![0, 1]).to_string(), "18446744073709551616");
+        // 10^19 and 10^38 need the padding of the lower chunks.
+        assert_eq!(BigInt::new(10_000_000_000_000_000_000).to_string(),
+                   "10000000000000000000");
+        let big = BigInt::from(100_000_000_000_000_000_000_000_000_000_000_000_000u128);
+        assert_eq!(big.to_string(), format!("1{}", "0".repeat(38)));
+        assert_eq!(BigInt::from(u128::MAX).to_string(), u128::MAX.to_string());
+    }
+
+    #[test]
+    fn test_display_format() {
+        let b = BigInt::new(1234567);
+        assert_eq!(format!("{:>12}", b), "     1234567");
+        assert_eq!(format!("{:<12}|", b), "1234567     |");
+        assert_eq!(format!("{:^11}", b), "  1234567  ");
+        assert_eq!(format!("{:*>10}", b), "***1234567");
+        assert_eq!(format!("{:010}", b), "0001234567");
+        assert_eq!(format!("{:+}", b), "+1234567");
+        assert_eq!(format!("{:3}", b), "1234567");
+        // The same as for primitive integers
+        for &x in [0, 7, 1234567, u64::MAX].iter() {
+            let b = BigInt::new(x);
+            assert_eq!(format!("{:>20}", b), format!("{:>20}", x));
+            assert_eq!(format!("{:_<25}", b), format!("{:_<25}", x));
+            assert_eq!(format!("{:+024}", b), format!("{:+024}", x));
+        }
+        // Thousands separators
+        assert_eq!(format!("{:#}", BigInt::zero()), "0");
+        assert_eq!(format!("{:#}", BigInt::new(999)), "999");
+        assert_eq!(format!("{:#}", BigInt::new(1000)), "1,000");
+        assert_eq!(format!("{:#}", b), "1,234,567");
+        assert_eq!(format!("{:#}", BigInt::from_vec(vec![0, 1])), "18,446,744,073,709,551,616");
+        assert_eq!(format!("{:>#12}", b), "   1,234,567");
+    }
+
+    #[test]
+    fn test_to_str_radix() {
+        for &x in [0, 1, 35, 36, 255, 1 << 32, u64::MAX].iter() {
+            let b = BigInt::new(x);
+            assert_eq!(b.to_str_radix(10), x.to_string());
+            assert_eq!(b.to_str_radix(16), format!("{:x}", x));
+            assert_eq!(b.to_str_radix(2), format!("{:b}", x));
+            assert_eq!(b.to_str_radix(8), format!("{:o}", x));
+        }
+        assert_eq!(BigInt::new(35).to_str_radix(36), "z");
+        assert_eq!(BigInt::new(36).to_str_radix(36), "10");
+        assert_eq!(BigInt::power_of_2(64).to_str_radix(16), "10000000000000000");
+        for seed in 0..10 {
+            let b = BigInt::random_with_limbs(seed % 4 + 1, seed as u64);
+            assert_eq!(b.to_str_radix(16), format!("{:x}", b));
+            assert_eq!(b.to_str_radix(2), format!("{:b}", b));
+            assert_eq!(b.to_str_radix(10), b.to_string());
+            // Radix 4 has a digit for every 2 bits.
+            assert_eq!(b.to_str_radix(4).len(), format!("{:b}", b).len().div_ceil(2));
+        }
+        // Zeros in the middle of the number.
+        let b = BigInt::from_vec(vec![5, 0, 1]);
+        assert_eq!(format!("{:x}", b), format!("1{:016x}{:016x}", 0, 5));
+        assert_eq!(b.to_str_radix(16), format!("{:x}", b));
+    }
+
+    #[test]
+    #[should_panic(expected = "radix 37 is not in 2..=36")]
+    fn test_to_str_radix_panic() {
+        BigInt::one().to_str_radix(37);
+    }
+
+    #[test]
+    fn test_from_str_radix() {
+        let b = BigInt::from_vec(vec![0x0123_4567_89ab_cdef, 1 << 63, 42]);
+        for radix in 2..37 {
+            assert_eq!(BigInt::from_str_radix(&b.to_str_radix(radix), radix), Ok(b.clone()));
+        }
+        assert_eq!(BigInt::from_str_radix("0", 10), Ok(BigInt::zero()));
+        assert_eq!(BigInt::from_str_radix("000123", 10), Ok(BigInt::new(123)));
+        assert_eq!(BigInt::from_str_radix("FF", 16), Ok(BigInt::new(255)));
+        assert_eq!(BigInt::from_str_radix("0xff", 16), Ok(BigInt::new(255)));
+        assert_eq!(BigInt::from_str_radix("0B101", 2), Ok(BigInt::new(5)));
+        assert_eq!(BigInt::from_str_radix("z", 36), Ok(BigInt::new(35)));
+        assert_eq!(BigInt::from_str_radix("18446744073709551616", 10),
+                   Ok(BigInt::from_vec(vec![0, 1])));
+        // Errors
+        assert_eq!(BigInt::from_str_radix("", 10), Err(ParseError::Empty));
+        assert_eq!(BigInt::from_str_radix("0x", 16), Err(ParseError::Empty));
+        assert_eq!(BigInt::from_str_radix("12a", 10), Err(ParseError::InvalidDigit('a')));
+        assert_eq!(BigInt::from_str_radix("102", 2), Err(ParseError::InvalidDigit('2')));
+        assert_eq!(BigInt::from_str_radix("0x1", 10), Err(ParseError::InvalidDigit('x')));
+        assert_eq!(BigInt::from_str_radix("-1", 10), Err(ParseError::InvalidDigit('-')));
+        assert_eq!(BigInt::from_str_radix(" 1", 10), Err(ParseError::InvalidDigit(' ')));
+    }
+
+    #[test]
+    #[should_panic(expected = "radix 1 is not in 2..=36")]
+    fn test_from_str_radix_panic() {
+        let _ = BigInt::from_str_radix("0", 1);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("12345".parse(), Ok(BigInt::new(12345)));
+        assert_eq!("0x1_0".parse::<BigInt>(), Err(ParseError::InvalidDigit('_')));
+        assert_eq!("0x10000000000000000".parse(), Ok(BigInt::from_vec(vec![0, 1])));
+        assert_eq!("0b11".parse(), Ok(BigInt::new(3)));
+        assert_eq!("ff".parse::<BigInt>(), Err(ParseError::InvalidDigit('f')));
+        assert_eq!(ParseError::InvalidDigit('f').to_string(), "invalid digit `f` in BigInt");
+        let b = BigInt::random_with_limbs(5, 7);
+        assert_eq!(b.to_string().parse(), Ok(b));
+    }
+
+    #[test]
+    fn test_bytes_le() {
+        assert_eq!(BigInt::zero().to_bytes_le(), Vec::<u8>::new());
+        assert_eq!(BigInt::from_bytes_le(&[]), BigInt::zero());
+        assert_eq!(BigInt::new(0x0102).to_bytes_le(), vec![2, 1]);
+        assert_eq!(BigInt::from_bytes_le(&[2, 1]), BigInt::new(0x0102));
+        // Trailing zero bytes (leading zeros of the number) are ignored, and not produced.
+        assert_eq!(BigInt::from_bytes_le(&[2, 1, 0, 0, 0, 0, 0, 0, 0, 0]), BigInt::new(0x0102));
+        assert_eq!(BigInt::from_bytes_le(&[0; 20]), BigInt::zero());
+        // Crossing a digit boundary.
+        let mut bytes = vec![0xff; 8];
+        bytes.push(1);
+        assert_eq!(BigInt::from_bytes_le(&bytes), BigInt::from_vec(vec![u64::MAX, 1]));
+        assert_eq!(BigInt::from_vec(vec![u64::MAX, 1]).to_bytes_le(), bytes);
+        for seed in 0..10 {
+            let b = BigInt::random_with_limbs(seed % 4, seed as u64);
+            assert_eq!(BigInt::from_bytes_le(&b.to_bytes_le()), b);
+        }
+    }
+
+    #[test]
+    fn test_sum_product() {
+        let v = vec![BigInt::new(u64::MAX), BigInt::one(), BigInt::from_vec(vec![0, 1])];
+        assert_eq!(v.iter().sum::<BigInt>(), BigInt::from_vec(vec![0, 2]));
+        assert_eq!(v.iter().product::<BigInt>(), BigInt::from_vec(vec![0, u64::MAX]));
+        assert_eq!(v.clone().into_iter().sum::<BigInt>(), v.iter().sum::<BigInt>());
+        assert_eq!(v.into_iter().product::<BigInt>(), BigInt::from_vec(vec![0, u64::MAX]));
+        // The empty sum is 0, the empty product is 1.
+        assert_eq!(Vec::<BigInt>::new().iter().sum::<BigInt>(), BigInt::zero());
+        assert_eq!(Vec::<BigInt>::new().into_iter().product::<BigInt>(), BigInt::one());
+        assert_eq!((1..=25).map(BigInt::new).product::<BigInt>(), examples::factorial(25));
+    }
+
+    #[test]
+    fn test_inline_storage() {
+        // Numbers with up to two digits do not allocate.
+        let a = BigInt::new(u64::MAX);
+        let b = BigInt::from_vec(vec![1, 2, 0, 0]);
+        assert!(a.data.is_inline() && b.data.is_inline());
+        assert!((&a + &a).data.is_inline());
+        assert!((&b - &a).data.is_inline());
+        assert!((&a * &a).data.is_inline());
+        assert!(BigInt::from(u128::MAX).data.is_inline());
+        // Growing beyond that moves the digits to the heap, shrinking moves them back.
+        let c = &b * &b;
+        assert!(!c.data.is_inline());
+        assert_eq!(c, BigInt::from_vec(vec![1, 4, 4]));
+        assert!((&c / &b).data.is_inline());
+    }
+
+    #[test]
+    fn test_zero_one() {
+        assert_eq!(BigInt::zero(), BigInt::new(0));
+        assert_eq!(BigInt::one(), BigInt::new(1));
+        assert_eq!(BigInt::default(), BigInt::zero());
+        assert!(BigInt::zero().is_zero() && BigInt::zero().digits().is_empty());
+        assert_eq!(&BigInt::one() + &BigInt::zero(), BigInt::one());
+        assert_eq!(&BigInt::one() * &BigInt::new(42), BigInt::new(42));
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(BigInt::from(0u8), BigInt::zero());
+        assert_eq!(BigInt::from(42u16), BigInt::new(42));
+        assert_eq!(BigInt::from(u32::MAX), BigInt::new(u32::MAX as u64));
+        assert_eq!(BigInt::from(u64::MAX), BigInt::new(u64::MAX));
+        assert_eq!(BigInt::from(5u128), BigInt::new(5));
+        assert_eq!(BigInt::from(1u128 << 64), BigInt::from_vec(vec![0, 1]));
+        assert_eq!(BigInt::from(u128::MAX), BigInt::from_vec(vec![u64::MAX, u64::MAX]));
+    }
+
+    #[test]
+    fn test_try_from() {
+        let too_large = BigInt::from_vec(vec![1, 2, 3]);
+        assert_eq!(u64::try_from(&BigInt::zero()), Ok(0));
+        assert_eq!(u64::try_from(&BigInt::new(u64::MAX)), Ok(u64::MAX));
+        assert_eq!(u64::try_from(&BigInt::from_vec(vec![0, 1])), Err(TryFromBigIntError(())));
+        assert_eq!(u128::try_from(&BigInt::new(7)), Ok(7));
+        assert_eq!(u128::try_from(&BigInt::from(u128::MAX)), Ok(u128::MAX));
+        assert!(u128::try_from(&too_large).is_err());
+        // Round trip through `BigInt`.
+        let x = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210u128;
+        assert_eq!(u128::try_from(&BigInt::from(x)), Ok(x));
+    }
+
+    #[test]
+    fn test_random_with_limbs() {
+        assert_eq!(BigInt::random_with_limbs(0, 1), BigInt::zero());
+        assert_eq!(BigInt::random_with_limbs(5, 42), BigInt::random_with_limbs(5, 42));
+        assert!(BigInt::random_with_limbs(5, 42) != BigInt::random_with_limbs(5, 43));
+        for n in 1..10 {
+            let b = BigInt::random_with_limbs(n, n as u64);
+            assert_eq!(b.data.len(), n);
+            assert!(b.test_invariant());
+        }
+    }
+
+    // Check the arithmetic operations against each other on random operands.
+    #[test]
+    fn test_random_arithmetic() {
+        for seed in 0..50 {
+            let a = BigInt::random_with_limbs((seed % 7) as usize + 1, seed);
+            let b = BigInt::random_with_limbs((seed % 4) as usize + 1, seed + 1000);
+            let c = BigInt::random_with_limbs(3, seed + 2000);
+            assert_eq!(&(&a + &b) - &b, a);
+            assert_eq!(&a * &b, &b * &a);
+            assert_eq!(&a * &(&b + &c), &(&a * &b) + &(&a * &c));
+            let (q, r) = a.div_rem(&b);
+            assert_eq!(&(&q * &b) + &r, a);
+            assert!(r.compare(&b) == ::std::cmp::Ordering::Less);
+        }
+    }
+
+    #[test]
+    fn test_add_assign() {
+        let mut b = BigInt::zero();
+        b += BigInt::new(5);
+        assert_eq!(b, BigInt::new(5));
+        b += &BigInt::from_vec(vec![u64::MAX, u64::MAX]);
+        assert_eq!(b, BigInt::from_vec(vec![4, 0, 1]));
+        b.add_assign_ref(&BigInt::zero());
+        assert_eq!(b, BigInt::from_vec(vec![4, 0, 1]));
+        for seed in 0..20 {
+            let x = BigInt::random_with_limbs(seed % 5 + 1, seed as u64);
+            let y = BigInt::random_with_limbs(seed % 3 + 1, seed as u64 + 100);
+            let mut z = x.clone();
+            z += &y;
+            assert_eq!(z, &x + &y);
+            assert_eq!(x.clone() + &y, &x + &y);
+        }
+    }
+
+    #[test]
+    fn test_add_assign_reuses_storage() {
+        let mut b = BigInt::one();
+        b.data.reserve(10);
+        let ptr = b.data.as_ptr();
+        for _ in 0..1000 {
+            b += &BigInt::new(u64::MAX);
+        }
+        assert_eq!(b, BigInt::from_vec(vec![u64::MAX - 998, 999]));
+        assert_eq!(b.data.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_karatsuba() {
+        // Small thresholds force many levels of recursion, and odd or unbalanced lengths.
+        for &(l, r) in [(1, 1), (2, 2), (3, 5), (7, 7), (8, 30), (33, 64), (100, 3)].iter() {
+            for threshold in 1..5 {
+                let a = BigInt::random_with_limbs(l, (l * r + threshold) as u64);
+                let b = BigInt::random_with_limbs(r, (l + r + threshold) as u64);
+                assert_eq!(BigInt::from_digits(mul_digits(&a.data, &b.data, threshold)),
+                           BigInt::from_digits(mul_schoolbook(&a.data, &b.data)));
+            }
+        }
+        // All digits at their maximum, to exercise the carries.
+        let max = BigInt::from_vec(vec![u64::MAX; 80]);
+        assert_eq!(BigInt::from_digits(mul_digits(&max.data, &max.data, 2)),
+                   BigInt::from_digits(mul_schoolbook(&max.data, &max.data)));
+        // And through the operator, with the default threshold.
+        let a = BigInt::random_with_limbs(200, 1);
+        let b = BigInt::random_with_limbs(150, 2);
+        assert_eq!(&a * &b, BigInt::from_digits(mul_schoolbook(&a.data, &b.data)));
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture bench_karatsuba` to see where
+    // Karatsuba starts to pay off. That is where `KARATSUBA_THRESHOLD` should be.
+    #[test]
+    #[ignore]
+    fn bench_karatsuba() {
+        use std::time::Instant;
+        for &n in [8, 16, 32, 64, 128, 256, 512].iter() {
+            let a = BigInt::random_with_limbs(n, 1);
+            let b = BigInt::random_with_limbs(n, 2);
+            let rounds = 20_000 / n;
+            let start = Instant::now();
+            for _ in 0..rounds {
+                mul_schoolbook(&a.data, &b.data);
+            }
+            let schoolbook = start.elapsed() / rounds as u32;
+            let start = Instant::now();
+            for _ in 0..rounds {
+                mul_digits(&a.data, &b.data, KARATSUBA_THRESHOLD);
+            }
+            let karatsuba = start.elapsed() / rounds as u32;
+            println!("{:4} digits: schoolbook {:?}, karatsuba {:?}", n, schoolbook, karatsuba);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde_json;
+        let b = BigInt::from_vec(vec![1, u64::MAX, 3]);
+        let json = serde_json::to_string(&b).unwrap();
+        assert_eq!(json, "[1,18446744073709551615,3]");
+        assert_eq!(serde_json::from_str::<BigInt>(&json).unwrap(), b);
+        assert_eq!(serde_json::to_string(&BigInt::zero()).unwrap(), "[]");
+        assert_eq!(serde_json::from_str::<BigInt>("[5, 0, 0]").unwrap(), BigInt::new(5));
+        for seed in 0..10 {
+            let b = BigInt::random_with_limbs(seed, seed as u64);
+            assert_eq!(serde_json::from_str::<BigInt>(&serde_json::to_string(&b).unwrap()).unwrap(), b);
+        }
+        assert!(serde_json::from_str::<BigInt>("[-1]").is_err());
+        assert!(serde_json::from_str::<BigInt>("\"42\"").is_err());
+    }
+
     #[test]
     fn test_inc1() {
-        let mut b = BigInt::new(0);
+        let mut b = BigInt::zero();
         b.inc1();
-        assert_eq!(b, BigInt::new(1));
+        assert_eq!(b, BigInt::one());
         b.inc1();
         assert_eq!(b, BigInt::new(2));
 
@@ -375,9 +1837,53 @@ mod tests {
         assert_eq!(b, BigInt::from_vec(vec![1, 1]));
     }
 
+    #[test]
+    fn test_dec() {
+        let mut b = BigInt::new(2);
+        b.dec1();
+        assert_eq!(b, BigInt::one());
+        b.dec1();
+        assert_eq!(b, BigInt::zero());
+
+        b = BigInt::from_vec(vec![0, 1]);
+        b.dec1();
+        assert_eq!(b, BigInt::new(u64::MAX));
+        b = BigInt::from_vec(vec![1, 1]);
+        b.dec(2);
+        assert_eq!(b, BigInt::new(u64::MAX));
+        b = BigInt::from_vec(vec![0, 0, 1]);
+        b.dec(1);
+        assert_eq!(b, BigInt::from_vec(vec![u64::MAX, u64::MAX]));
+        b.dec(u64::MAX);
+        assert_eq!(b, BigInt::from_vec(vec![0, u64::MAX]));
+        b.dec(0);
+        assert_eq!(b, BigInt::from_vec(vec![0, u64::MAX]));
+        b = BigInt::zero();
+        b.dec(0);
+        assert_eq!(b, BigInt::zero());
+        // `dec` undoes `inc`.
+        let orig = BigInt::random_with_limbs(3, 5);
+        let mut b = orig.clone();
+        b.inc(u64::MAX);
+        b.dec(u64::MAX);
+        assert_eq!(b, orig);
+    }
+
+    #[test]
+    #[should_panic(expected = "Decrement of BigInt below zero")]
+    fn test_dec_panic1() {
+        BigInt::zero().dec1();
+    }
+
+    #[test]
+    #[should_panic(expected = "Decrement of BigInt below zero")]
+    fn test_dec_panic2() {
+        BigInt::new(5).dec(6);
+    }
+
     #[test]
     fn test_power_of_2() {
-        assert_eq!(BigInt::power_of_2(0), BigInt::new(1));
+        assert_eq!(BigInt::power_of_2(0), BigInt::one());
         assert_eq!(BigInt::power_of_2(13), BigInt::new(1 << 13));
         assert_eq!(BigInt::power_of_2(64), BigInt::from_vec(vec![0, 1]));
         assert_eq!(BigInt::power_of_2(96), BigInt::from_vec(vec![0, 1 << 32]));
@@ -0,0 +1,207 @@
+// The storage for the digits of a `BigInt`. Most numbers are small, so up to `INLINE` digits are
+// stored right inside the value. Only larger numbers allocate a `Vec` on the heap. Through `Deref`,
+// `Digits` can be used like a slice, and it has the `Vec` methods that `BigInt` needs.
+use std::{cmp, fmt, ops};
+use std::iter::FromIterator;
+
+pub const INLINE: usize = 2;
+
+#[derive(Clone)]
+pub enum Digits {
+    // The first `len` elements of the array are the digits.
+    Inline([u64; INLINE], usize),
+    Heap(Vec<u64>),
+}
+use self::Digits::*;
+
+impl Digits {
+    pub fn new() -> Self {
+        Inline([0; INLINE], 0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity <= INLINE { Digits::new() } else { Heap(Vec::with_capacity(capacity)) }
+    }
+
+    // `n` digits, all of them 0.
+    pub fn zeros(n: usize) -> Self {
+        if n <= INLINE { Inline([0; INLINE], n) } else { Heap(vec![0; n]) }
+    }
+
+    pub fn from_slice(digits: &[u64]) -> Self {
+        if digits.len() <= INLINE {
+            let mut inline = [0; INLINE];
+            inline[..digits.len()].copy_from_slice(digits);
+            Inline(inline, digits.len())
+        } else {
+            Heap(digits.to_vec())
+        }
+    }
+
+    pub fn is_inline(&self) -> bool {
+        match *self {
+            Inline(..) => true,
+            Heap(_) => false,
+        }
+    }
+
+    // Move the digits to the heap (if they are not there yet), with room for `capacity` digits.
+    fn spill(&mut self, capacity: usize) {
+        if let Inline(inline, len) = *self {
+            let mut v = Vec::with_capacity(cmp::max(capacity, 2 * INLINE));
+            v.extend_from_slice(&inline[..len]);
+            *self = Heap(v);
+        }
+    }
+
+    pub fn push(&mut self, digit: u64) {
+        if let Inline(_, INLINE) = *self {
+            self.spill(2 * INLINE);
+        }
+        match *self {
+            Inline(ref mut inline, ref mut len) => {
+                inline[*len] = digit;
+                *len += 1;
+            }
+            Heap(ref mut v) => v.push(digit),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<u64> {
+        match *self {
+            Inline(_, 0) => None,
+            Inline(ref inline, ref mut len) => {
+                *len -= 1;
+                Some(inline[*len])
+            }
+            Heap(ref mut v) => v.pop(),
+        }
+    }
+
+    pub fn resize(&mut self, new_len: usize, value: u64) {
+        if new_len > INLINE {
+            self.spill(new_len);
+        }
+        match *self {
+            Inline(ref mut inline, ref mut len) => {
+                if new_len > *len {
+                    for digit in inline[*len..new_len].iter_mut() {
+                        *digit = value;
+                    }
+                }
+                *len = new_len;
+            }
+            Heap(ref mut v) => v.resize(new_len, value),
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len() + additional;
+        if needed > INLINE {
+            self.spill(needed);
+        }
+        if let Heap(ref mut v) = *self {
+            v.reserve(additional);
+        }
+    }
+}
+
+impl ops::Deref for Digits {
+    type Target = [u64];
+    fn deref(&self) -> &[u64] {
+        match *self {
+            Inline(ref inline, len) => &inline[..len],
+            Heap(ref v) => v,
+        }
+    }
+}
+
+impl ops::DerefMut for Digits {
+    fn deref_mut(&mut self) -> &mut [u64] {
+        match *self {
+            Inline(ref mut inline, len) => &mut inline[..len],
+            Heap(ref mut v) => v,
+        }
+    }
+}
+
+// Small vectors are moved inline, so that their allocation can be freed.
+impl From<Vec<u64>> for Digits {
+    fn from(v: Vec<u64>) -> Self {
+        if v.len() <= INLINE { Digits::from_slice(&v) } else { Heap(v) }
+    }
+}
+
+impl FromIterator<u64> for Digits {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        Digits::from(iter.into_iter().collect::<Vec<u64>>())
+    }
+}
+
+// Two `Digits` are equal if they contain the same digits, no matter where they are stored.
+impl PartialEq for Digits {
+    fn eq(&self, other: &Digits) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Debug for Digits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_pop() {
+        let mut d = Digits::new();
+        assert!(d.is_empty());
+        d.push(1);
+        d.push(2);
+        assert!(d.is_inline());
+        assert_eq!(&d[..], &[1, 2]);
+        d.push(3);
+        assert!(!d.is_inline());
+        assert_eq!(&d[..], &[1, 2, 3]);
+        assert_eq!(d.pop(), Some(3));
+        assert_eq!(d.pop(), Some(2));
+        assert_eq!(d.pop(), Some(1));
+        assert_eq!(d.pop(), None);
+    }
+
+    #[test]
+    fn test_resize_reserve() {
+        let mut d = Digits::from_slice(&[7]);
+        d.resize(2, 5);
+        assert!(d.is_inline());
+        assert_eq!(&d[..], &[7, 5]);
+        d.resize(1, 0);
+        d.resize(2, 9);
+        assert_eq!(&d[..], &[7, 9]);
+        d.resize(4, 0);
+        assert_eq!(&d[..], &[7, 9, 0, 0]);
+
+        let mut d = Digits::from_slice(&[1]);
+        d.reserve(1);
+        assert!(d.is_inline());
+        d.reserve(10);
+        assert!(!d.is_inline());
+        assert_eq!(&d[..], &[1]);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert!(Digits::from(vec![1, 2]).is_inline());
+        assert!(!Digits::from(vec![1, 2, 3]).is_inline());
+        assert!(Digits::zeros(2).is_inline());
+        assert_eq!(&Digits::zeros(3)[..], &[0, 0, 0]);
+        // Equality does not care about the storage.
+        let mut heap = Digits::from(vec![1, 2, 3]);
+        heap.pop();
+        assert_eq!(heap, Digits::from_slice(&[1, 2]));
+        assert_eq!((0..5).collect::<Digits>()[..], [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,59 @@
+// Some classic functions whose results outgrow a `u64` quickly.
+use super::BigInt;
+
+/// Compute n! = 1 * 2 * ... * n.
+pub fn factorial(n: u64) -> BigInt {
+    let mut result = BigInt::one();
+    for i in 2..=n {
+        result = result * BigInt::new(i);
+    }
+    result
+}
+
+/// Compute the n-th Fibonacci number, starting with fib(0) = 0 and fib(1) = 1.
+pub fn fib(n: u64) -> BigInt {
+    let (mut a, mut b) = (BigInt::zero(), BigInt::one());
+    for _ in 0..n {
+        let next = &a + &b;
+        a = b;
+        b = next;
+    }
+    a
+}
+
+pub fn main() {
+    // 20! is the largest factorial that fits into a `u64`, and fib(93) is the largest Fibonacci
+    // number that does.
+    println!("20! = {}", factorial(20));
+    println!("21! = {}", factorial(21));
+    println!("100! = {}", factorial(100));
+    println!("fib(93) = {}", fib(93));
+    println!("fib(94) = {}", fib(94));
+    println!("fib(500) = {}", fib(500));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(factorial(0), 1u64);
+        assert_eq!(factorial(1), 1u64);
+        assert_eq!(factorial(20), 2432902008176640000u64);
+        assert_eq!(factorial(21), &factorial(20) * &BigInt::new(21));
+        assert_eq!(factorial(25).to_string(), "15511210043330985984000000");
+        assert_eq!(factorial(100).to_string().len(), 158);
+    }
+
+    #[test]
+    fn test_fib() {
+        assert_eq!(fib(0), 0u64);
+        assert_eq!(fib(1), 1u64);
+        assert_eq!(fib(10), 55u64);
+        assert_eq!(fib(93), 12200160415121876738u64);
+        assert_eq!(fib(100).to_string(), "354224848179261915075");
+        // fib(n + 2) = fib(n + 1) + fib(n)
+        assert_eq!(fib(302), &fib(301) + &fib(300));
+    }
+}
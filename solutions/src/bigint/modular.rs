@@ -0,0 +1,203 @@
+// Arithmetic modulo some number, as needed for cryptography: In RSA, for example, everything
+// happens modulo the product of two large primes. All functions panic if the modulus is 0.
+//
+// The operands can be any numbers, but the results are always reduced, i.e., smaller than the
+// modulus.
+use super::BigInt;
+
+/// Reduce `a` modulo `modulus`.
+pub fn reduce(a: &BigInt, modulus: &BigInt) -> BigInt {
+    a % modulus
+}
+
+// Reduce a number that is smaller than twice the modulus. This only needs a subtraction.
+fn reduce_once(a: BigInt, modulus: &BigInt) -> BigInt {
+    match a.checked_sub(modulus) {
+        Some(reduced) => reduced,
+        None => a,
+    }
+}
+
+/// Compute `(a + b) mod modulus`.
+pub fn add_mod(a: &BigInt, b: &BigInt, modulus: &BigInt) -> BigInt {
+    let (a, b) = (reduce(a, modulus), reduce(b, modulus));
+    // Both are smaller than the modulus, so the sum is smaller than twice the modulus.
+    reduce_once(a + &b, modulus)
+}
+
+/// Compute `(a - b) mod modulus`. The result is never negative: If `b` is larger than `a`, we add
+/// multiples of the modulus.
+pub fn sub_mod(a: &BigInt, b: &BigInt, modulus: &BigInt) -> BigInt {
+    let (a, b) = (reduce(a, modulus), reduce(b, modulus));
+    match a.checked_sub(&b) {
+        Some(difference) => difference,
+        // `a < b < modulus`, so this cannot underflow.
+        None => &(modulus - &b) + &a,
+    }
+}
+
+/// Compute `(a * b) mod modulus`.
+pub fn mul_mod(a: &BigInt, b: &BigInt, modulus: &BigInt) -> BigInt {
+    // Reducing first keeps the product small.
+    let (a, b) = (reduce(a, modulus), reduce(b, modulus));
+    reduce(&(&a * &b), modulus)
+}
+
+/// Compute `base^exponent mod modulus`.
+pub fn pow_mod(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    // Square-and-multiply: We go over the bits of the exponent, starting with the most significant
+    // one. For every bit, we square the result, and if the bit is set, we also multiply by `base`.
+    let base = reduce(base, modulus);
+    let mut result = reduce(&BigInt::one(), modulus);
+    for i in (0..exponent.bit_length()).rev() {
+        result = mul_mod(&result, &result, modulus);
+        if exponent.bit(i) {
+            result = mul_mod(&result, &base, modulus);
+        }
+    }
+    result
+}
+
+impl BigInt {
+    /// Find the inverse of the number modulo `modulus`, i.e., the `x < modulus` such that
+    /// `self * x mod modulus = 1`. It exists if and only if the number and the modulus have no
+    /// common divisor other than 1. Panics if `modulus` is 0.
+    pub fn mod_inverse(&self, modulus: &BigInt) -> Option<BigInt> {
+        // The extended Euclidean algorithm: Besides the remainders of Euclid's algorithm for the
+        // greatest common divisor, we keep track of coefficients `s` such that each remainder `r`
+        // satisfies `r = s * self mod modulus`. When we reach the gcd, its coefficient is the
+        // inverse. The coefficients may become negative, so we compute them modulo `modulus`.
+        let (mut old_r, mut r) = (reduce(self, modulus), modulus.clone());
+        let (mut old_s, mut s) = (BigInt::one(), BigInt::zero());
+        while !r.is_zero() {
+            let (quotient, remainder) = old_r.div_rem(&r);
+            old_r = r;
+            r = remainder;
+            let new_s = sub_mod(&old_s, &mul_mod(&quotient, &s, modulus), modulus);
+            old_s = s;
+            s = new_s;
+        }
+        if old_r == 1 {
+            Some(reduce(&old_s, modulus))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::BigInt;
+
+    // Small numbers, where we can compute the expected results with `u128`.
+    const SMALL: [u64; 7] = [0, 1, 2, 6, 1 << 40, u64::MAX - 1, u64::MAX];
+    const MODULI: [u64; 6] = [1, 2, 7, 1 << 32, 4_294_967_291, u64::MAX];
+
+    fn big(x: u128) -> BigInt {
+        BigInt::from(x)
+    }
+
+    #[test]
+    fn test_small() {
+        for &m in MODULI.iter() {
+            let (m128, modulus) = (m as u128, BigInt::new(m));
+            for &a in SMALL.iter() {
+                for &b in SMALL.iter() {
+                    let (a128, b128) = (a as u128, b as u128);
+                    let (a, b) = (BigInt::new(a), BigInt::new(b));
+                    assert_eq!(reduce(&a, &modulus), big(a128 % m128));
+                    assert_eq!(add_mod(&a, &b, &modulus), big((a128 + b128) % m128));
+                    let expected = (a128 % m128 + m128 - b128 % m128) % m128;
+                    assert_eq!(sub_mod(&a, &b, &modulus), big(expected));
+                    assert_eq!(mul_mod(&a, &b, &modulus), big(a128 * b128 % m128));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pow_mod() {
+        let pow_mod_u128 = |base: u128, mut exponent: u64, modulus: u128| {
+            let mut result = 1 % modulus;
+            while exponent > 0 {
+                result = result * base % modulus;
+                exponent -= 1;
+            }
+            result
+        };
+        for &m in [1, 7, 1000, 4_294_967_291].iter() {
+            for &base in [0, 1, 2, 3, 123_456_789].iter() {
+                for &exponent in [0, 1, 2, 10, 100].iter() {
+                    assert_eq!(pow_mod(&BigInt::new(base), &BigInt::new(exponent), &BigInt::new(m)),
+                               big(pow_mod_u128(base as u128, exponent, m as u128)));
+                }
+            }
+        }
+        // Fermat's little theorem: a^(p-1) = 1 mod p for a prime p, here p = 2^127 - 1.
+        let p = BigInt::from(u128::MAX >> 1);
+        let exponent = p.checked_sub(&BigInt::one()).unwrap();
+        for &a in [2, 3, u64::MAX].iter() {
+            assert_eq!(pow_mod(&BigInt::new(a), &exponent, &p), BigInt::one());
+        }
+    }
+
+    #[test]
+    fn test_large() {
+        let m = BigInt::random_with_limbs(4, 1);
+        let a = BigInt::random_with_limbs(6, 2);
+        let b = BigInt::random_with_limbs(3, 3);
+        // The results are reduced, and differ from the exact result by a multiple of `m`.
+        let sum = add_mod(&a, &b, &m);
+        assert!(sum.checked_sub(&m).is_none());
+        assert!(((&a + &b).checked_sub(&sum).unwrap() % &m).is_zero());
+        let product = mul_mod(&a, &b, &m);
+        assert!(product.checked_sub(&m).is_none());
+        assert!(((&a * &b).checked_sub(&product).unwrap() % &m).is_zero());
+        // Subtraction undoes addition.
+        assert_eq!(sub_mod(&sum, &b, &m), reduce(&a, &m));
+        assert_eq!(add_mod(&sub_mod(&b, &a, &m), &a, &m), b);
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(BigInt::new(3).mod_inverse(&BigInt::new(7)), Some(BigInt::new(5)));
+        assert_eq!(BigInt::new(10).mod_inverse(&BigInt::new(7)), Some(BigInt::new(5)));
+        assert_eq!(BigInt::new(1).mod_inverse(&BigInt::new(7)), Some(BigInt::one()));
+        assert_eq!(BigInt::new(6).mod_inverse(&BigInt::new(7)), Some(BigInt::new(6)));
+        // Modulo 1, everything is 0.
+        assert_eq!(BigInt::new(5).mod_inverse(&BigInt::one()), Some(BigInt::zero()));
+        // No inverse if there is a common divisor.
+        assert_eq!(BigInt::zero().mod_inverse(&BigInt::new(7)), None);
+        assert_eq!(BigInt::new(14).mod_inverse(&BigInt::new(7)), None);
+        assert_eq!(BigInt::new(4).mod_inverse(&BigInt::new(6)), None);
+        assert_eq!(BigInt::new(1 << 40).mod_inverse(&BigInt::power_of_2(100)), None);
+        // Check the defining property on large numbers: Modulo a prime, everything but 0 has an
+        // inverse, and modulo a power of 2, every odd number has one.
+        let p = BigInt::from(u128::MAX >> 1);
+        let m = BigInt::power_of_2(256);
+        for seed in 1..10 {
+            let a = BigInt::random_with_limbs(3, seed);
+            let inverse = a.mod_inverse(&p).unwrap();
+            assert!(inverse.checked_sub(&p).is_none());
+            assert_eq!(mul_mod(&a, &inverse, &p), BigInt::one());
+            let mut odd = a.clone();
+            if odd.is_even() {
+                odd.inc1();
+            }
+            assert_eq!(mul_mod(&odd, &odd.mod_inverse(&m).unwrap(), &m), BigInt::one());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Division of BigInt by zero")]
+    fn test_mod_inverse_zero_modulus() {
+        let _ = BigInt::one().mod_inverse(&BigInt::zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "Division of BigInt by zero")]
+    fn test_zero_modulus() {
+        let _ = add_mod(&BigInt::one(), &BigInt::one(), &BigInt::zero());
+    }
+}
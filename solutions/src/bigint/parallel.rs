@@ -0,0 +1,119 @@
+// Adding numbers with millions of digits, using several threads. This connects the `BigInt` of
+// parts 05-10 with the threads of parts 15 and 40.
+//
+// The carries are what makes addition hard to parallelize: Every digit depends on the carry out of
+// all the digits below it. So we split the work in two phases. First, every thread adds one chunk
+// of the digits, as if there was no carry coming in, and reports the carry going out. Then we go
+// over the chunks sequentially and add the incoming carries. That second phase is usually very
+// cheap: A carry only travels beyond the first digit of a chunk if that digit is `u64::MAX`.
+use std::thread;
+use super::{overflowing_add, BigInt, Digits};
+
+impl BigInt {
+    /// Add `rhs`, splitting the work between `threads` threads. Panics if `threads` is 0.
+    ///
+    /// Starting threads is expensive, so this only pays off for very large numbers.
+    pub fn par_add(&self, rhs: &BigInt, threads: usize) -> BigInt {
+        assert!(threads > 0, "par_add: need at least one thread");
+        let max_len = self.data.len().max(rhs.data.len());
+        if max_len == 0 {
+            return BigInt::zero();
+        }
+        // One more digit for the final carry.
+        let mut result_vec = Digits::zeros(max_len + 1);
+        let chunk_len = max_len.div_ceil(threads);
+        let carries: Vec<bool> = thread::scope(|scope| {
+            let handles: Vec<_> = result_vec[..max_len].chunks_mut(chunk_len).enumerate()
+                .map(|(i, out)| {
+                    let start = i * chunk_len;
+                    let lhs = chunk(&self.data, start, out.len());
+                    let rhs = chunk(&rhs.data, start, out.len());
+                    scope.spawn(move || add_chunk(out, lhs, rhs))
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        // Now propagate the carries. The carry out of chunk `i` goes into chunk `i + 1`, and out of
+        // the last chunk into the extra digit. The last chunk may be shorter than the others, so
+        // the extra digit is not necessarily at `(i + 1) * chunk_len`.
+        for (i, &carry) in carries.iter().enumerate() {
+            if carry {
+                let mut idx = ((i + 1) * chunk_len).min(max_len);
+                loop {
+                    let (sum, new_carry) = overflowing_add(result_vec[idx], 1, false);
+                    result_vec[idx] = sum;
+                    if !new_carry {
+                        break;
+                    }
+                    idx += 1;
+                }
+            }
+        }
+        BigInt::from_digits(result_vec)
+    }
+}
+
+// The digits `start..start + len` of `digits`, as far as they exist.
+fn chunk(digits: &[u64], start: usize, len: usize) -> &[u64] {
+    let end = (start + len).min(digits.len());
+    if start < end { &digits[start..end] } else { &[] }
+}
+
+// Add `lhs` and `rhs` into `out`, which is at least as long as both. Returns the carry.
+fn add_chunk(out: &mut [u64], lhs: &[u64], rhs: &[u64]) -> bool {
+    let mut carry = false;
+    for (i, digit) in out.iter_mut().enumerate() {
+        let lhs_val = lhs.get(i).cloned().unwrap_or(0);
+        let rhs_val = rhs.get(i).cloned().unwrap_or(0);
+        let (sum, new_carry) = overflowing_add(lhs_val, rhs_val, carry);
+        *digit = sum;
+        carry = new_carry;
+    }
+    carry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::BigInt;
+
+    #[test]
+    fn test_par_add() {
+        let a = BigInt::random_with_limbs(1000, 1);
+        let b = BigInt::random_with_limbs(700, 2);
+        for &threads in [1, 2, 3, 7, 16, 2000].iter() {
+            assert_eq!(a.par_add(&b, threads), &a + &b);
+            assert_eq!(b.par_add(&a, threads), &a + &b);
+        }
+        let zero = BigInt::zero();
+        assert_eq!(zero.par_add(&zero, 4), zero);
+        assert_eq!(a.par_add(&zero, 4), a);
+    }
+
+    #[test]
+    fn test_par_add_carries() {
+        // A carry that has to travel through all the chunks: (2^(64*n) - 1) + 1 = 2^(64*n)
+        let max = BigInt::from_vec(vec![u64::MAX; 100]);
+        for &threads in [1, 3, 4, 10, 30, 100].iter() {
+            assert_eq!(max.par_add(&BigInt::one(), threads), BigInt::power_of_2(64 * 100));
+            assert_eq!(max.par_add(&max, threads), &max + &max);
+        }
+        // With 2 threads, the chunks are 3 and 2 digits long. The carry out of the second, shorter
+        // chunk has to end up in the extra digit.
+        let max = BigInt::from_vec(vec![u64::MAX; 5]);
+        let b = BigInt::from_vec(vec![0, 0, 0, 1]);
+        assert_eq!(max.par_add(&b, 2), &max + &b);
+        assert_eq!(b.par_add(&max, 2), &max + &b);
+        // Here, the carry out of the first chunk ripples across the boundary and through the
+        // second chunk.
+        let b = BigInt::from_vec(vec![0, 0, u64::MAX]);
+        let a = BigInt::from_vec(vec![0, 0, 1, u64::MAX, u64::MAX]);
+        assert_eq!(a.par_add(&b, 2), &a + &b);
+        assert_eq!(a.par_add(&b, 2), BigInt::from_vec(vec![0, 0, 0, 0, 0, 1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "need at least one thread")]
+    fn test_par_add_no_threads() {
+        let _ = BigInt::one().par_add(&BigInt::one(), 0);
+    }
+}
@@ -0,0 +1,7 @@
+// rgrep as a standalone tool: `cargo run --bin rgrep -- PATTERN FILE...`, or install it with
+// `cargo install --path solutions --bin rgrep`.
+extern crate solutions;
+
+fn main() {
+    solutions::rgrep::main();
+}
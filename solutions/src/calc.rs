@@ -0,0 +1,279 @@
+// This is the calculator of part 32, evaluating on `BigInt` instead of `i64` (exercise 32.3).
+// The tokenizer and parser are those of part 32, except for the numbers. The tutorial is a program
+// and not a library, so we cannot use its code from here, and like the other solutions, this file
+// shows the whole result of the exercise.
+use std::cmp;
+use std::io::prelude::*;
+use std::io;
+use std::iter::Peekable;
+use std::str::CharIndices;
+use bigint::BigInt;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Error {
+    pub message: String,
+    pub span: Span,
+}
+
+fn error<T>(message: &str, start: usize, end: usize) -> Result<T, Error> {
+    Err(Error { message: message.to_string(), span: Span { start, end } })
+}
+
+/// Show the input, and mark the span of the error below it. Assumes ASCII input.
+pub fn report(input: &str, error: &Error) -> String {
+    let marker = "^".repeat(cmp::max(1, error.span.end - error.span.start));
+    format!("{}\n{}{} {}", input, " ".repeat(error.span.start), marker, error.message)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum TokenKind {
+    Number(BigInt),
+    Plus,
+    Minus,
+    Star,
+    LParen,
+    RParen,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut chars = input.char_indices().peekable();
+    let mut tokens = Vec::new();
+    while let Some(&(start, c)) = chars.peek() {
+        let kind = match c {
+            ' ' | '\t' => { chars.next(); continue; }
+            '0'..='9' => tokenize_number(input, &mut chars)?,
+            '+' => { chars.next(); TokenKind::Plus }
+            '-' => { chars.next(); TokenKind::Minus }
+            '*' => { chars.next(); TokenKind::Star }
+            '(' => { chars.next(); TokenKind::LParen }
+            ')' => { chars.next(); TokenKind::RParen }
+            _ => return error("unexpected character", start, start + c.len_utf8()),
+        };
+        let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+        tokens.push(Token { kind, span: Span { start, end } });
+    }
+    Ok(tokens)
+}
+
+fn tokenize_number(input: &str,
+                   chars: &mut Peekable<CharIndices<'_>>) -> Result<TokenKind, Error> {
+    let start = chars.peek().unwrap().0;
+    let mut end = start;
+    while let Some(&(i, c)) = chars.peek() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        end = i + 1;
+        chars.next();
+    }
+    // Unlike an `i64`, a `BigInt` is never too large, so this only fails for the empty string.
+    match input[start..end].parse() {
+        Ok(n) => Ok(TokenKind::Number(n)),
+        Err(_) => error("invalid number", start, end),
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Expr {
+    Number(BigInt),
+    Binary(Box<Expr>, Op, Span, Box<Expr>),
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn parse_expr(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_term()?;
+        while let Some(token) = self.peek() {
+            let op = match token.kind {
+                TokenKind::Plus => Op::Add,
+                TokenKind::Minus => Op::Sub,
+                _ => break,
+            };
+            self.pos += 1;
+            let rhs = self.parse_term()?;
+            lhs = Expr::Binary(Box::new(lhs), op, token.span, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // term := factor ('*' factor)*
+    fn parse_term(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_factor()?;
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Star {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.parse_factor()?;
+            lhs = Expr::Binary(Box::new(lhs), Op::Mul, token.span, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // factor := number | '(' expr ')'
+    fn parse_factor(&mut self) -> Result<Expr, Error> {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return error("expected a number or `(`", self.end, self.end),
+        };
+        self.pos += 1;
+        match token.kind {
+            TokenKind::Number(n) => Ok(Expr::Number(n)),
+            TokenKind::LParen => {
+                let expr = self.parse_expr()?;
+                match self.peek() {
+                    Some(Token { kind: TokenKind::RParen, .. }) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    Some(token) => error("expected `)`", token.span.start, token.span.end),
+                    None => error("expected `)`", self.end, self.end),
+                }
+            }
+            _ => error("expected a number or `(`", token.span.start, token.span.end),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr, Error> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0, end: input.len() };
+    let expr = parser.parse_expr()?;
+    match parser.peek() {
+        Some(token) => error("unexpected token", token.span.start, token.span.end),
+        None => Ok(expr),
+    }
+}
+
+impl Expr {
+    pub fn eval(&self) -> Result<BigInt, Error> {
+        match *self {
+            Expr::Number(ref n) => Ok(n.clone()),
+            Expr::Binary(ref lhs, op, span, ref rhs) => {
+                let (lhs, rhs) = (lhs.eval()?, rhs.eval()?);
+                match op {
+                    Op::Add => Ok(&lhs + &rhs),
+                    // `BigInt` is unsigned, and its subtraction panics on underflow. We use
+                    // `checked_sub`, so that we can report an error instead.
+                    Op::Sub => match lhs.checked_sub(&rhs) {
+                        Some(result) => Ok(result),
+                        None => error("negative result", span.start, span.end),
+                    },
+                    Op::Mul => Ok(&lhs * &rhs),
+                }
+            }
+        }
+    }
+}
+
+pub fn calculate(input: &str) -> Result<BigInt, Error> {
+    parse(input)?.eval()
+}
+
+pub fn main() {
+    println!("Enter expressions, one per line. End with Ctrl-D (Linux) or Ctrl-Z (Windows).");
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+        match calculate(&line) {
+            Ok(n) => println!("{}", n),
+            Err(e) => println!("{}", report(&line, &e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigint::BigInt;
+    use super::*;
+
+    fn span(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let kinds: Vec<TokenKind> =
+            tokenize("12+(3 *4)").unwrap().into_iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, vec![TokenKind::Number(BigInt::new(12)), TokenKind::Plus,
+                               TokenKind::LParen, TokenKind::Number(BigInt::new(3)),
+                               TokenKind::Star, TokenKind::Number(BigInt::new(4)),
+                               TokenKind::RParen]);
+        // Numbers can be as large as we like.
+        assert_eq!(tokenize("18446744073709551616").unwrap(),
+                   vec![Token { kind: TokenKind::Number(BigInt::from_vec(vec![0, 1])),
+                                span: span(0, 20) }]);
+        assert_eq!(tokenize("1 # 2").unwrap_err().message, "unexpected character");
+    }
+
+    #[test]
+    fn test_parse() {
+        use super::Expr::*;
+        let number = |n| Number(BigInt::new(n));
+        assert_eq!(parse("(1 + 2) - 3").unwrap(),
+                   Binary(Box::new(Binary(Box::new(number(1)), Op::Add, span(3, 4),
+                                          Box::new(number(2)))),
+                          Op::Sub, span(8, 9), Box::new(number(3))));
+        assert_eq!(parse("1 + 2 * 3").unwrap(),
+                   Binary(Box::new(number(1)), Op::Add, span(2, 3),
+                          Box::new(Binary(Box::new(number(2)), Op::Mul, span(6, 7),
+                                          Box::new(number(3))))));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("").unwrap_err().span, span(0, 0));
+        assert_eq!(parse("(1 + 2").unwrap_err(),
+                   Error { message: "expected `)`".to_string(), span: span(6, 6) });
+        assert_eq!(parse("1 + )").unwrap_err().span, span(4, 5));
+        assert_eq!(parse("(1))").unwrap_err().message, "unexpected token");
+    }
+
+    #[test]
+    fn test_calculate() {
+        assert_eq!(calculate("18446744073709551615 + 1"), Ok(BigInt::from_vec(vec![0, 1])));
+        assert_eq!(calculate("(18446744073709551615 + 5) - 10"), Ok(BigInt::new(u64::MAX - 5)));
+        assert_eq!(calculate("3 - 3"), Ok(BigInt::zero()));
+        let err = calculate("1 - (2 + 3)").unwrap_err();
+        assert_eq!(report("1 - (2 + 3)", &err), "1 - (2 + 3)\n  ^ negative result");
+        assert_eq!(calculate("2 * 3"), Ok(BigInt::new(6)));
+        assert_eq!(calculate("340282366920938463463374607431768211456 - 1"),
+                   Ok(BigInt::from(u128::MAX)));
+        assert_eq!(calculate("99999999999999999999 * 3").unwrap().to_string(),
+                   "299999999999999999997");
+        assert_eq!(calculate("(18446744073709551615 + 1) * (18446744073709551615 + 1) - 1 * 2"),
+                   Ok(BigInt::from_vec(vec![u64::MAX - 1, u64::MAX, 0])));
+    }
+}
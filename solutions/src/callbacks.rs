@@ -41,6 +41,20 @@ impl Callbacks {
     }
 }
 
+pub fn main() {
+    let mut c = Callbacks::new();
+    c.register(|val| println!("Callback 1: {}", val));
+    c.call(0);
+
+    let mut count = 0;
+    c.register(move |val| {
+        count += 1;
+        println!("Callback 2: {} ({}. time)", val, count);
+    });
+    c.call(1);
+    c.call(2);
+}
+
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
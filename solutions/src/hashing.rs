@@ -0,0 +1,70 @@
+// This is the file hashing project of part 41, built on the `pipeline` module instead of wiring up
+// the threads and channels by hand.
+use std::{fs, io};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use pipeline::Pipeline;
+
+pub const MOD_ADLER: u32 = 65521;
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1, 0);
+    for &byte in data {
+        a = (a + byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+// A missing file should not stop the pipeline, so the per-file results travel along as data. The
+// stages themselves can only fail when the pipeline is shutting down.
+pub fn hash_all(paths: Vec<PathBuf>, readers: usize,
+                hashers: usize) -> BTreeMap<PathBuf, io::Result<u32>> {
+    Pipeline::source(readers, move |out| {
+            for path in paths {
+                out.send(path)?;
+            }
+            Ok(())
+        })
+        .parallel_stage(readers, |input, out| {
+            for path in input {
+                let contents = fs::read(&path);
+                out.send((path, contents))?;
+            }
+            Ok(())
+        })
+        .parallel_stage(hashers, |input, out| {
+            for (path, contents) in input {
+                let checksum = contents.map(|data| adler32(&data));
+                out.send((path, checksum))?;
+            }
+            Ok(())
+        })
+        .sink(|input| Ok(input.collect()))
+        .expect("hash_all: the pipeline does not fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+    use temp_dir::TempDir;
+    use super::*;
+
+    #[test]
+    fn test_hash_all() {
+        let tmp = TempDir::new("hash");
+        let dir = tmp.path();
+        let mut paths: Vec<PathBuf> = (0..10).map(|i| dir.join(format!("file{}", i))).collect();
+        for (i, path) in paths.iter().enumerate() {
+            fs::write(path, "x".repeat(i * 1000)).unwrap();
+        }
+        paths.push(dir.join("missing"));
+
+        let results = hash_all(paths.clone(), 2, 3);
+        assert_eq!(results.len(), 11);
+        assert_eq!(results[&paths[0]].as_ref().unwrap(), &1);
+        assert_eq!(results[&paths[9]].as_ref().unwrap(), &adler32("x".repeat(9000).as_bytes()));
+        assert_eq!(results[&paths[10]].as_ref().unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+}
@@ -0,0 +1,31 @@
+// This crate contains solutions to *some* of the exercises, and it bundles
+// the projects that span multiple parts together in one file per project.
+// It is not always up-to-date with the code in the actual course, and mainly
+// serves as draft board for new parts or exercises.
+
+extern crate clap;
+extern crate regex;
+extern crate signal_hook;
+extern crate memmap2;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(test)]
+extern crate serde_json;
+
+pub mod bigint;
+pub mod vec;
+pub mod rgrep;
+pub mod callbacks;
+pub mod counter;
+pub mod list;
+pub mod adapters;
+pub mod calc;
+pub mod observer;
+pub mod lifetimes;
+pub mod pipeline;
+pub mod hashing;
+pub mod rng;
+#[cfg(test)]
+mod temp_dir;
@@ -0,0 +1,69 @@
+// Solutions to the exercises of part 39 on advanced lifetimes.
+use std::fmt::Debug;
+
+pub struct Config {
+    pub name: String,
+}
+
+impl Config {
+    // Exercise 39.1: By elision, the result borrows from `self`. A `&'static str` can be used for
+    // any shorter lifetime, so returning the literal is fine.
+    pub fn name_or_default(&self) -> &str {
+        if self.name.is_empty() { "anonymous" } else { &self.name }
+    }
+}
+
+// Exercise 39.2: With `Split<'a>`, `first_field` stops compiling ("cannot return value referencing
+// local variable `sep`"), because the pieces are tied to the lifetime of the separator.
+
+// Exercise 39.3: Passing `v` to the generic `consume` moves it. We reborrow explicitly.
+fn consume<T: Debug>(t: T) -> String {
+    format!("{:?}", t)
+}
+
+pub fn push_all(v: &mut Vec<i32>, items: &[i32]) {
+    for &i in items {
+        consume(&mut *v);
+        v.push(i);
+    }
+}
+
+// Exercise 39.4: The returned closure owns `f` and `g`, and it is put into a `Box<dyn ...>` without
+// a lifetime, which means `'static`. So `f` and `g` have to be `'static`, too.
+pub type StrFn = Box<dyn for<'a> Fn(&'a str) -> &'a str>;
+
+pub fn compose<F, G>(f: F, g: G) -> StrFn
+    where F: for<'a> Fn(&'a str) -> &'a str + 'static,
+          G: for<'a> Fn(&'a str) -> &'a str + 'static
+{
+    Box::new(move |s| g(f(s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_or_default() {
+        assert_eq!(Config { name: String::new() }.name_or_default(), "anonymous");
+        assert_eq!(Config { name: "ferris".to_string() }.name_or_default(), "ferris");
+    }
+
+    #[test]
+    fn test_push_all() {
+        let mut v = vec![0];
+        push_all(&mut v, &[1, 2]);
+        assert_eq!(v, vec![0, 1, 2]);
+    }
+
+    fn first_word(s: &str) -> &str {
+        s.split(' ').next().unwrap()
+    }
+
+    #[test]
+    fn test_compose() {
+        let trim_and_first = compose(str::trim, first_word);
+        let s = String::from("  hello world ");
+        assert_eq!(trim_and_first(&s), "hello");
+    }
+}
@@ -140,6 +140,20 @@ impl<T> Drop for LinkedList<T> {
     }
 }
 
+pub fn main() {
+    let mut list = LinkedList::new();
+    for i in 0..5 {
+        list.push_back(i);
+    }
+    list.push_front(-1);
+    for x in list.iter_mut() {
+        *x *= 10;
+    }
+    list.for_each(|x| print!("{} ", x));
+    println!();
+    println!("front: {:?}, back: {:?}", list.pop_front(), list.pop_back());
+}
+
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
@@ -1,17 +1,90 @@
-// This crate contains solutions to *some* of the exercises, and it bundles
-// the projects that span multiple parts together in one file per project.
-// It is not always up-to-date with the code in the actual course, and mainly
-// serves as draft board for new parts or exercises.
-
-extern crate docopt;
-
-pub mod bigint;
-pub mod vec;
-pub mod rgrep;
-pub mod callbacks;
-pub mod counter;
-pub mod list;
-
-pub fn main() {
-    rgrep::main();
-}
\ No newline at end of file
+// Run one of the demos of the solutions crate, selected by the first argument:
+//
+//   cargo run -- bigint
+//   cargo run -- rgrep -c fn src/rgrep.rs
+//
+// All arguments after `rgrep` are passed on to rgrep. rgrep is also a binary of its own, which
+// shares the library code with this one:
+//
+//   cargo run --bin rgrep -- -c fn src/rgrep.rs
+extern crate solutions;
+
+use std::{env, process};
+use solutions::*;
+
+#[derive(PartialEq, Debug)]
+enum Command {
+    Bigint,
+    BigintExamples,
+    List,
+    Callbacks,
+    Counter,
+    Calc,
+    // The arguments for rgrep, starting with its program name.
+    Rgrep(Vec<String>),
+}
+
+static USAGE: &str = "Usage: solutions <demo>
+Demos: bigint, bigint-examples, list, callbacks, counter, calc, rgrep <args>...";
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command, rest),
+        None => return Err("missing demo name".to_string()),
+    };
+    let command = match command.as_str() {
+        "bigint" => Command::Bigint,
+        "bigint-examples" => Command::BigintExamples,
+        "list" => Command::List,
+        "callbacks" => Command::Callbacks,
+        "counter" => Command::Counter,
+        "calc" => Command::Calc,
+        "rgrep" => return Ok(Command::Rgrep(args.to_vec())),
+        other => return Err(format!("unknown demo `{}`", other)),
+    };
+    if !rest.is_empty() {
+        return Err(format!("`{}` does not take any arguments", args[0]));
+    }
+    Ok(command)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Like for rgrep, a bad command line is exit status 2, and the complaint goes to stderr.
+    let command = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    match command {
+        Command::Bigint => bigint::main(),
+        Command::BigintExamples => bigint::examples::main(),
+        Command::List => list::main(),
+        Command::Callbacks => callbacks::main(),
+        Command::Counter => counter::main(),
+        Command::Calc => calc::main(),
+        Command::Rgrep(argv) => rgrep::main_with_args(argv),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&["bigint"]), Ok(Command::Bigint));
+        assert_eq!(parse(&["bigint-examples"]), Ok(Command::BigintExamples));
+        assert_eq!(parse(&["counter"]), Ok(Command::Counter));
+        assert_eq!(parse(&["rgrep", "-c", "fn", "a.rs"]),
+                   Ok(Command::Rgrep(vec!["rgrep".to_string(), "-c".to_string(), "fn".to_string(),
+                                         "a.rs".to_string()])));
+        assert_eq!(parse(&[]), Err("missing demo name".to_string()));
+        assert_eq!(parse(&["frobnicate"]), Err("unknown demo `frobnicate`".to_string()));
+        assert_eq!(parse(&["list", "extra"]), Err("`list` does not take any arguments".to_string()));
+    }
+}
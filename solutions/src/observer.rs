@@ -0,0 +1,92 @@
+// The observer pattern of exercise 37.2: A subject that does not keep its subscribers alive.
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+
+pub trait Observer {
+    fn notify(&self, event: &str);
+}
+
+#[derive(Default)]
+pub struct Subject {
+    observers: RefCell<Vec<Weak<dyn Observer>>>,
+}
+
+impl Subject {
+    pub fn new() -> Self {
+        Subject { observers: RefCell::new(Vec::new()) }
+    }
+
+    pub fn subscribe(&self, observer: &Rc<dyn Observer>) {
+        self.observers.borrow_mut().push(Rc::downgrade(observer));
+    }
+
+    // Notify all subscribers that are still alive, and forget about the others.
+    pub fn emit(&self, event: &str) {
+        // We first collect the live subscribers, and release the borrow before calling them: A
+        // subscriber may want to subscribe someone else from inside `notify`.
+        let alive: Vec<Rc<dyn Observer>> = {
+            let mut observers = self.observers.borrow_mut();
+            observers.retain(|o| o.upgrade().is_some());
+            observers.iter().filter_map(|o| o.upgrade()).collect()
+        };
+        for observer in alive {
+            observer.notify(event);
+        }
+    }
+
+    // The number of subscribers that were alive at the last `emit`.
+    pub fn len(&self) -> usize {
+        self.observers.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.borrow().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use super::*;
+
+    struct Recorder {
+        events: RefCell<Vec<String>>,
+    }
+
+    impl Observer for Recorder {
+        fn notify(&self, event: &str) {
+            self.events.borrow_mut().push(event.to_string());
+        }
+    }
+
+    fn recorder() -> Rc<Recorder> {
+        Rc::new(Recorder { events: RefCell::new(Vec::new()) })
+    }
+
+    #[test]
+    fn test_subject() {
+        let subject = Subject::new();
+        let a = recorder();
+        let b = recorder();
+        let a_dyn: Rc<dyn Observer> = a.clone();
+        let b_dyn: Rc<dyn Observer> = b.clone();
+        subject.subscribe(&a_dyn);
+        subject.subscribe(&b_dyn);
+        subject.emit("one");
+
+        drop(b_dyn);
+        drop(b);
+        subject.emit("two");
+        assert_eq!(*a.events.borrow(), vec!["one", "two"]);
+        assert_eq!(subject.len(), 1);
+
+        // The subject does not keep `a` alive.
+        drop(a_dyn);
+        let weak_a = Rc::downgrade(&a);
+        drop(a);
+        assert!(weak_a.upgrade().is_none());
+        subject.emit("three");
+        assert!(subject.is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+// A chain of threads connected by channels, as used by rgrep (part 13) and the file hashing project
+// (part 41). Every stage runs in its own thread(s), and the last one, the sink, runs on the calling
+// thread:
+//
+//   Pipeline::source(16, read).stage(filter).sink(output)
+//
+// Stages report errors by returning them. If a stage stops early (because of an error, or because
+// it has seen enough), the stages before it notice on their next `send`, which fails with
+// `BrokenPipe` - just like a process writing to a closed pipe. They are expected to return that
+// error, and the pipeline winds down. `BrokenPipe` is not reported as an error of the pipeline.
+use std::{io, panic, thread};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+
+// The receiving end of a stage. It can be shared by several threads of a parallel stage, and it is
+// an iterator that ends once all threads of the previous stage are done.
+pub struct Input<T>(Arc<Mutex<Receiver<T>>>);
+
+impl<T> Clone for Input<T> {
+    fn clone(&self) -> Self {
+        Input(self.0.clone())
+    }
+}
+
+impl<T> Iterator for Input<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // The guard is a temporary, so the lock is released before the item is processed.
+        self.0.lock().unwrap().recv().ok()
+    }
+}
+
+// The sending end of a stage.
+pub struct Output<T>(SyncSender<T>);
+
+impl<T> Clone for Output<T> {
+    fn clone(&self) -> Self {
+        Output(self.0.clone())
+    }
+}
+
+impl<T> Output<T> {
+    // Send `t` to the next stage. Fails with `BrokenPipe` if that stage has stopped.
+    pub fn send(&self, t: T) -> io::Result<()> {
+        self.0.send(t).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "pipeline stopped"))
+    }
+}
+
+pub struct Pipeline<T> {
+    input: Input<T>,
+    bound: usize,
+    handles: Vec<thread::JoinHandle<io::Result<()>>>,
+}
+
+fn channel<T>(bound: usize) -> (Output<T>, Input<T>) {
+    let (sender, receiver) = sync_channel(bound);
+    (Output(sender), Input(Arc::new(Mutex::new(receiver))))
+}
+
+impl<T: Send + 'static> Pipeline<T> {
+    // Start a pipeline with a source that produces items on its own. `bound` is the number of items
+    // that may be buffered between two stages.
+    pub fn source<F>(bound: usize, f: F) -> Self
+        where F: FnOnce(Output<T>) -> io::Result<()> + Send + 'static
+    {
+        let (output, input) = channel(bound);
+        let handle = thread::spawn(move || f(output));
+        Pipeline { input, bound, handles: vec![handle] }
+    }
+
+    // Add a stage running in a single thread.
+    pub fn stage<U, F>(mut self, f: F) -> Pipeline<U>
+        where U: Send + 'static, F: FnOnce(Input<T>, Output<U>) -> io::Result<()> + Send + 'static
+    {
+        let (output, input) = channel(self.bound);
+        let prev = self.input;
+        self.handles.push(thread::spawn(move || f(prev, output)));
+        Pipeline { input, bound: self.bound, handles: self.handles }
+    }
+
+    // Add a stage running in `threads` threads, which all take their items from the same input.
+    // The order of the items is not preserved.
+    pub fn parallel_stage<U, F>(mut self, threads: usize, f: F) -> Pipeline<U>
+        where U: Send + 'static, F: Fn(Input<T>, Output<U>) -> io::Result<()> + Send + Sync + 'static
+    {
+        assert!(threads > 0, "parallel_stage: need at least one thread");
+        let (output, input) = channel(self.bound);
+        let f = Arc::new(f);
+        for _ in 0..threads {
+            let (f, prev, output) = (f.clone(), self.input.clone(), output.clone());
+            self.handles.push(thread::spawn(move || f(prev, output)));
+        }
+        Pipeline { input, bound: self.bound, handles: self.handles }
+    }
+
+    // Add a stage running in `threads` threads that keeps the order of the items. `f` turns every
+    // item into at most one new item. On the way in, a thread numbers the items; on the way out,
+    // another one puts the results back in order. A result that arrives before the ones in front of
+    // it waits there, so if one item takes very long, many results may pile up.
+    pub fn ordered_parallel_stage<U, F>(self, threads: usize, f: F) -> Pipeline<U>
+        where U: Send + 'static, F: Fn(T) -> io::Result<Option<U>> + Send + Sync + 'static
+    {
+        self
+            .stage(|input, out| {
+                for item in input.enumerate() {
+                    out.send(item)?;
+                }
+                Ok(())
+            })
+            .parallel_stage(threads, move |input, out| {
+                for (seq, item) in input {
+                    // Items that are dropped are still sent, so the next stage knows they are done.
+                    out.send((seq, f(item)?))?;
+                }
+                Ok(())
+            })
+            .stage(|input, out| {
+                let mut waiting = HashMap::new();
+                let mut next = 0;
+                for (seq, result) in input {
+                    waiting.insert(seq, result);
+                    while let Some(result) = waiting.remove(&next) {
+                        next += 1;
+                        if let Some(item) = result {
+                            out.send(item)?;
+                        }
+                    }
+                }
+                Ok(())
+            })
+    }
+
+    // Run `f` on the output of the last stage, and wait for all threads. Returns the first error
+    // of any stage, in the order of the pipeline, or else the result of `f`. If a stage panicked,
+    // the panic is propagated.
+    pub fn sink<R, F>(self, f: F) -> io::Result<R>
+        where F: FnOnce(Input<T>) -> io::Result<R>
+    {
+        let result = f(self.input);
+        let mut first_error = None;
+        for handle in self.handles {
+            match handle.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(ref e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+                Ok(Err(e)) => { first_error.get_or_insert(e); }
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use super::*;
+
+    fn numbers(n: u32) -> impl FnOnce(Output<u32>) -> io::Result<()> {
+        move |out| {
+            for i in 0..n {
+                out.send(i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stages() {
+        let result = Pipeline::source(4, numbers(100))
+            .stage(|input, out| {
+                for i in input.filter(|i| i % 2 == 0) {
+                    out.send(i * 10)?;
+                }
+                Ok(())
+            })
+            .sink(|input| Ok(input.collect::<Vec<_>>()));
+        let expected: Vec<u32> = (0..50).map(|i| i * 20).collect();
+        assert_eq!(result.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parallel_stage() {
+        let mut result = Pipeline::source(2, numbers(1000))
+            .parallel_stage(4, |input, out| {
+                for i in input {
+                    out.send(i + 1)?;
+                }
+                Ok(())
+            })
+            .sink(|input| Ok(input.collect::<Vec<_>>()))
+            .unwrap();
+        result.sort();
+        assert_eq!(result, (1..1001).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_ordered_parallel_stage() {
+        let result = Pipeline::source(2, numbers(1000))
+            .ordered_parallel_stage(4, |i| {
+                // Make some items take longer, so that the threads overtake each other.
+                if i % 7 == 0 {
+                    thread::yield_now();
+                }
+                Ok(if i % 3 == 0 { None } else { Some(i * 2) })
+            })
+            .sink(|input| Ok(input.collect::<Vec<_>>()));
+        let expected: Vec<u32> = (0..1000).filter(|i| i % 3 != 0).map(|i| i * 2).collect();
+        assert_eq!(result.unwrap(), expected);
+        // Errors of `f` are errors of the pipeline.
+        let result = Pipeline::source(2, numbers(100))
+            .ordered_parallel_stage(3, |i| {
+                if i == 50 {
+                    Err(io::Error::new(io::ErrorKind::InvalidData, "fifty"))
+                } else {
+                    Ok(Some(i))
+                }
+            })
+            .sink(|input| Ok(input.count()));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_error() {
+        let seen = Arc::new(AtomicUsize::new(0));
+        let seen2 = seen.clone();
+        let result = Pipeline::source(1, numbers(10))
+            .stage(|input, out| {
+                for i in input {
+                    if i == 3 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "three"));
+                    }
+                    out.send(i)?;
+                }
+                Ok(())
+            })
+            .sink(move |input| {
+                seen2.store(input.count(), Ordering::SeqCst);
+                Ok(())
+            });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        // The sink still saw the end of its input.
+        assert_eq!(seen.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_early_shutdown() {
+        // The source never ends by itself. It stops because the sink is done.
+        let result = Pipeline::source(1, |out| {
+                let mut i = 0u32;
+                loop {
+                    out.send(i)?;
+                    i += 1;
+                }
+            })
+            .parallel_stage(3, |input, out| {
+                for i in input {
+                    out.send(i)?;
+                }
+                Ok(())
+            })
+            .sink(|input| Ok(input.take(5).count()));
+        assert_eq!(result.unwrap(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "stage failed")]
+    fn test_panic() {
+        let _ = Pipeline::source(1, numbers(10))
+            .stage(|_input, _out: Output<u32>| -> io::Result<()> { panic!("stage failed") })
+            .sink(|input| Ok(input.count()));
+    }
+}
@@ -1,154 +1,2374 @@
 use std::io::prelude::*;
-use std::{io, fs, thread, process, cmp};
-use std::sync::mpsc::{sync_channel, SyncSender, Receiver};
+use std::{io, env, fmt, fs, process, cmp, iter};
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use pipeline::{Pipeline, Input, Output};
+use regex::{Captures, Regex, RegexBuilder};
+use clap::{CommandFactory, Parser};
+use clap::error::ErrorKind;
+
+mod ignore;
+mod external_sort;
+mod mapped;
+#[cfg(feature = "gzip")]
+mod gzip;
+use self::ignore::Ignore;
+use self::external_sort::ExternalSort;
+use self::mapped::{read_mapped, LineData};
 
 #[derive(Clone,Copy)]
 enum OutputMode {
     Print,
     SortAndPrint,
+    // Sort, and print every different matching line only once (`--unique`). With `counts`, every
+    // line is preceded by the number of times it occurred, like `sort | uniq -c` does it.
+    SortUnique { counts: bool },
     Count,
+    // Count the lines, and also how many different ones there are (`--count-distinct`).
+    CountDistinct,
+    // One JSON object per match, for consumption by other programs.
+    Json,
+    // Print nothing, only the exit status says whether there was a match (`-q`).
+    Quiet,
+    // Print the lines with the matches replaced by `Options::replacement` (`--replace`).
+    Replace,
 }
 use self::OutputMode::*;
 
-struct Options {
+// Which part of a line a match has to cover.
+#[derive(Clone,Copy,PartialEq)]
+enum MatchScope {
+    Anywhere,
+    // The characters around a match must not be word characters (`-w`).
+    Word,
+    // The match must be the entire line (`-x`).
+    Line,
+}
+
+// Everything that says what to search, and how. Outside of this module, options come from a command
+// line (`get_options` and `Options::from_args`), or from `Options::new` and the setters after it.
+// Cloning them is cheap enough, and the clones share the Ctrl-C flag and the counts.
+#[derive(Clone)]
+pub struct Options {
     files: Vec<String>,
-    pattern: String,
+    // A line matches if it matches any of the patterns.
+    pattern: Vec<String>,
     output_mode: OutputMode,
+    // For `Replace`: What to put in place of every match.
+    replacement: String,
+    // For sorting: How many bytes of lines to keep in memory. The rest goes to temporary
+    // files.
+    sort_memory: usize,
+    ignore_case: bool,
+    scope: MatchScope,
+    // Forward the lines that do *not* match.
+    invert_match: bool,
+    // The number of lines to print before and after every match.
+    before_context: usize,
+    after_context: usize,
+    // Print the byte offset of every line within its file.
+    byte_offset: bool,
+    // Only print the parts of the lines that match (`-o`).
+    only_matching: bool,
+    // Follow file names and records by NUL bytes rather than `:` and newlines (`-0`).
+    null: bool,
+    // The number of threads reading files.
+    readers: usize,
+    // The number of threads filtering lines (`--threads`). Several filters cannot keep track of
+    // context or `-m`, since every one of them only sees some of the lines of a file.
+    filters: usize,
+    // How many items can wait in a channel between two threads before the sender blocks.
+    buffer: usize,
+    // Map the files into memory instead of reading them.
+    mmap: bool,
+    // Stop after this many matches.
+    max_count: Option<usize>,
+    // Skip what the `.gitignore` and `.ignore` files in the directories we search say.
+    use_ignore: bool,
+    // Follow symbolic links in the directories we search.
+    follow: bool,
+    // Where to write the output, if not to stdout.
+    output: Option<String>,
+    // Set when the user presses Ctrl-C. All the threads check it, and stop.
+    interrupted: Arc<AtomicBool>,
+    // Print `counts` to stderr at the end (`--stats`).
+    stats: bool,
+    counts: Arc<Counts>,
+    // If set, the patterns are regular expressions (exercise 14.3), compiled already into one.
+    regex: Option<Regex>,
+}
+
+// How much work the pipeline did. Every thread counts on its own, and adds its counts here when it
+// is done - updating shared counters for every line would make the threads wait for each other.
+#[derive(Default)]
+struct Counts {
+    files: AtomicUsize,
+    lines: AtomicUsize,
+    matches: AtomicUsize,
 }
 
 struct Line {
-    data: String,
-    file: usize,
+    data: LineData,
+    // The name of the file. Files found in directories are not in `Options::files`, so every line
+    // carries the name along. Thanks to the `Arc`, all lines of a file share it.
+    file: Arc<str>,
     line: usize,
+    // The byte offset of the beginning of the line within the file.
+    offset: usize,
+    // Whether this line is only printed as context of a match.
+    context: bool,
+    // With `-o`, the filter fills in the parts of `data` that matched. Otherwise, this stays empty.
+    matches: Vec<Range<usize>>,
 }
 
+// Lines are sorted by their text, and equal texts by where they were found. So the order is total:
+// No matter in which order the readers sent the lines, sorting them gives the same result.
 impl PartialEq for Line {
     fn eq(&self, other: &Line) -> bool {
-        self.data.eq(&other.data)
+        self.cmp(other) == cmp::Ordering::Equal
     }
 }
+impl Eq for Line {}
 impl PartialOrd for Line {
     fn partial_cmp(&self, other: &Line) -> Option<cmp::Ordering> {
-        self.data.partial_cmp(&other.data)
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Line {
+    fn cmp(&self, other: &Line) -> cmp::Ordering {
+        (&self.data, &self.file, self.line).cmp(&(&other.data, &other.file, other.line))
+    }
+}
+
+// Something we can read lines from. That's usually a file, but tests can use strings, so they do
+// not need the file system.
+trait LineSource: Send {
+    // The name we print in front of the lines.
+    fn name(&self) -> String;
+    fn open(&self) -> io::Result<Box<dyn BufRead>>;
+    // The path, for sources that are files we could map into memory.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+impl LineSource for PathBuf {
+    fn name(&self) -> String {
+        self.to_string_lossy().into_owned()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(self)
+    }
+
+    fn open(&self) -> io::Result<Box<dyn BufRead>> {
+        Ok(Box::new(io::BufReader::new(fs::File::open(self)?)))
+    }
+}
+
+// The length of `line` without the line terminator.
+fn trimmed_len(line: &[u8]) -> usize {
+    line.iter().rposition(|&b| b != b'\n' && b != b'\r').map_or(0, |pos| pos + 1)
+}
+
+// Turn `bytes` into a string, replacing what is not valid UTF-8 by U+FFFD. Like grep, we search
+// files in other encodings or with some binary data anyway, and the ASCII part still matches.
+fn lossy_string(bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(data) => data,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }
+}
+
+// Call `f` on the lines of `source`. With `mmap`, files are mapped into memory rather than read.
+fn read_file(source: &dyn LineSource, mmap: bool, f: &mut dyn FnMut(Line) -> io::Result<()>)
+        -> io::Result<()> {
+    let name: Arc<str> = Arc::from(source.name().as_str());
+    if let (true, Some(path)) = (mmap, source.path()) {
+        return read_mapped(path, name, f);
+    }
+    let file = source.open()?;
+    #[cfg(feature = "gzip")]
+    let file = gzip::decompress(file)?;
+    read_lines(file, name, f)
+}
+
+// Call `f` on the lines that can be read from `file`.
+fn read_lines(mut file: Box<dyn BufRead + '_>, name: Arc<str>,
+              f: &mut dyn FnMut(Line) -> io::Result<()>) -> io::Result<()> {
+    let mut offset = 0;
+    // Unlike `lines`, `read_until` tells us how many bytes the line terminator took. It also does
+    // not insist on UTF-8, so we can search files with some invalid bytes in there.
+    for lineidx in 0.. {
+        let mut bytes = Vec::new();
+        let len = file.read_until(b'\n', &mut bytes)?;
+        if len == 0 {
+            break;
+        }
+        bytes.truncate(trimmed_len(&bytes));
+        let data = lossy_string(bytes);
+        f(Line { data: data.into(), file: name.clone(), line: lineidx, offset, context: false,
+                 matches: Vec::new() })?;
+        offset += len;
     }
+    Ok(())
+}
+
+// What identifies a directory, no matter through which path or link we got there: On Unix, the
+// device and the inode number. Elsewhere, we make do with the canonical path.
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(path: &Path) -> io::Result<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+#[cfg(not(unix))]
+fn dir_id(path: &Path) -> io::Result<DirId> {
+    fs::canonicalize(path)
 }
 
-fn read_files(options: Arc<Options>, out_channel: SyncSender<Line>) {
-    for (fileidx, file) in options.files.iter().enumerate() {
-        let file = fs::File::open(file).unwrap();
-        let file = io::BufReader::new(file);
-        for (lineidx, line) in file.lines().enumerate() {
-            let line = Line { data: line.unwrap(), file: fileidx, line: lineidx };
-            out_channel.send(line).unwrap();
+// Call `f` on every regular file in `dir` and its subdirectories, depth-first and sorted by name.
+// Entries that cannot be read are skipped. If there is an `ignore`, it decides which files and
+// directories to skip.
+//
+// Symbolic links are only followed if there is a `visited` set. A link to a parent directory would
+// then send us into an endless loop, so we remember every directory we entered, and never enter
+// one twice.
+fn walk(dir: &Path, mut ignore: Option<&mut Ignore>, mut visited: Option<&mut HashSet<DirId>>,
+        f: &mut dyn FnMut(&Path) -> io::Result<()>) -> io::Result<()> {
+    let mut entries: Vec<fs::DirEntry> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).collect(),
+        Err(_) => return Ok(()),
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+    if let Some(ref mut ignore) = ignore {
+        ignore.push_dir(dir);
+    }
+    for entry in entries {
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            // To follow a link, we look at what it points to.
+            Ok(file_type) if file_type.is_symlink() && visited.is_some() => {
+                fs::metadata(&path).map(|metadata| metadata.file_type())
+            }
+            file_type => file_type,
+        };
+        let is_dir = match file_type {
+            Ok(file_type) if file_type.is_dir() => true,
+            Ok(file_type) if file_type.is_file() => false,
+            _ => continue,
+        };
+        if ignore.as_ref().is_some_and(|ignore| ignore.is_ignored(&path, is_dir)) {
+            continue;
+        }
+        if is_dir {
+            if let Some(ref mut visited) = visited {
+                match dir_id(&path) {
+                    Ok(id) if visited.insert(id) => {}
+                    _ => continue,
+                }
+            }
+            walk(&path, ignore.as_deref_mut(), visited.as_deref_mut(), f)?;
+        } else {
+            f(&path)?;
+        }
+    }
+    if let Some(ignore) = ignore {
+        ignore.pop_dir();
+    }
+    Ok(())
+}
+
+// A file for the readers to read. `explicit` says whether the file was given on the command line;
+// files we found in directories ourselves may be unreadable, and then we skip them.
+struct FileToRead {
+    source: Box<dyn LineSource>,
+    explicit: bool,
+}
+
+// Call `f` on all the files we have to read, looking into directories.
+fn for_each_file(options: &Options, f: &mut dyn FnMut(FileToRead) -> io::Result<()>)
+        -> io::Result<()> {
+    for file in options.files.iter() {
+        let path = Path::new(file);
+        if path.is_dir() {
+            let mut ignore = Ignore::new();
+            let ignore = if options.use_ignore { Some(&mut ignore) } else { None };
+            let mut visited = HashSet::new();
+            let visited = if options.follow {
+                visited.extend(dir_id(path).ok());
+                Some(&mut visited)
+            } else {
+                None
+            };
+            walk(path, ignore, visited, &mut |path| {
+                f(FileToRead { source: Box::new(path.to_path_buf()), explicit: false })
+            })?;
+        } else {
+            f(FileToRead { source: Box::new(path.to_path_buf()), explicit: true })?;
+        }
+    }
+    Ok(())
+}
+
+// Send all the files we have to read.
+fn list_files(options: Arc<Options>, out_channel: Output<FileToRead>) -> io::Result<()> {
+    for_each_file(&options, &mut |file| out_channel.send(file))
+}
+
+// Read the files we get from `list_files`. There can be several threads running this, and then the
+// lines of different files arrive interleaved at the next stage.
+fn read_files(options: &Options, in_channel: Input<FileToRead>, out_channel: Output<Line>)
+        -> io::Result<()> {
+    let (mut files, mut lines) = (0, 0);
+    for file in in_channel {
+        let result = read_file(&*file.source, options.mmap, &mut |line| {
+            check_interrupted(options)?;
+            lines += 1;
+            out_channel.send(line)
+        });
+        match result {
+            Ok(()) => files += 1,
+            // We always stop once the pipeline is gone, or the user wants us to.
+            Err(e) if file.explicit || is_stop(&e) => return Err(e),
+            Err(_) => {}
+        }
+    }
+    options.counts.files.fetch_add(files, Ordering::Relaxed);
+    options.counts.lines.fetch_add(lines, Ordering::Relaxed);
+    Ok(())
+}
+
+// Fail with `Interrupted` if the user pressed Ctrl-C. The stages call this for every line, so
+// that they all stop soon. Every stage returns normally, so the output is flushed and temporary
+// files are removed.
+fn check_interrupted(options: &Options) -> io::Result<()> {
+    if options.interrupted.load(Ordering::Relaxed) {
+        Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"))
+    } else {
+        Ok(())
+    }
+}
+
+// Whether `e` means we should stop rather than go on with the next file.
+fn is_stop(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::BrokenPipe || e.kind() == io::ErrorKind::Interrupted
+}
+
+// Lower-case `line`, but only copy it if there actually is an upper-case character in there.
+fn fold_case(line: &str) -> Cow<'_, str> {
+    if line.chars().any(|c| c.is_uppercase()) {
+        Cow::Owned(line.to_lowercase())
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+// The name of the group that holds the actual match when the regex is built for `-w`.
+const WORD_GROUP: &str = "rgrep_word";
+
+// Compile the patterns into a single regular expression that matches if any of them matches.
+fn build_regex(patterns: &[String], ignore_case: bool, scope: MatchScope)
+        -> Result<Regex, regex::Error> {
+    // Every pattern goes into its own group, so that e.g. `a|b` stays one alternative.
+    let alternatives: Vec<String> = patterns.iter().map(|p| format!("(?:{})", p)).collect();
+    let regex = alternatives.join("|");
+    let regex = match scope {
+        MatchScope::Anywhere => regex,
+        // We cannot use `\b`: A pattern may start or end with a character that is not part of a
+        // word, and then there would be no word boundary where we need one. Instead, we match the
+        // characters around the word, and put the word itself into a group for `-o`.
+        MatchScope::Word => format!(r"(?:^|\W)(?P<{}>{})(?:\W|$)", WORD_GROUP, regex),
+        MatchScope::Line => format!("^(?:{})$", regex),
+    };
+    RegexBuilder::new(&regex).case_insensitive(ignore_case).build()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Check whether `pattern` occurs in `line` as a whole word.
+fn contains_word(line: &str, pattern: &str) -> bool {
+    word_occurrences(line, pattern).next().is_some()
+}
+
+// The occurrences of `pattern` in `line` that are whole words. We have to look at every occurrence,
+// including overlapping ones, since only some of them may be surrounded by non-word characters.
+fn word_occurrences<'a>(line: &'a str, pattern: &'a str)
+        -> impl Iterator<Item = Range<usize>> + 'a {
+    let mut start = Some(0);
+    iter::from_fn(move || {
+        while let Some(pos) = line[start?..].find(pattern) {
+            let (begin, end) = (start? + pos, start? + pos + pattern.len());
+            // Try again, starting with the next character.
+            start = line[begin..].chars().next().map(|c| begin + c.len_utf8());
+            let before = line[..begin].chars().next_back();
+            let after = line[end..].chars().next();
+            if !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char) {
+                return Some(begin..end);
+            }
+        }
+        None
+    })
+}
+
+// Check whether `line` matches. For substring search, `patterns` are the patterns with their case
+// already folded, if needed.
+fn is_match(options: &Options, patterns: &[Cow<'_, str>], line: &str) -> bool {
+    if let Some(ref regex) = options.regex {
+        // The regex takes care of ignoring case and of the scope itself.
+        return regex.is_match(line);
+    }
+    let line = if options.ignore_case { fold_case(line) } else { Cow::Borrowed(line) };
+    match options.scope {
+        MatchScope::Anywhere => patterns.iter().any(|pattern| line.contains(&pattern[..])),
+        MatchScope::Word => patterns.iter().any(|pattern| contains_word(&line, pattern)),
+        MatchScope::Line => patterns.contains(&line),
+    }
+}
+
+// Find the parts of `line` that match, for `-o`. Like grep, we take the leftmost match first, and
+// then continue after it, so the matches do not overlap. Empty matches are left out, there is
+// nothing to print for them.
+fn match_ranges(options: &Options, patterns: &[Cow<'_, str>], line: &str) -> Vec<Range<usize>> {
+    let mut candidates: Vec<Range<usize>> = Vec::new();
+    if let Some(ref regex) = options.regex {
+        candidates.extend(regex_matches(regex, line).map(|(range, _)| range));
+    } else {
+        // Folding the case can change the length of characters, so we remember for every byte of
+        // the folded line where its character started in `line`.
+        let (folded, starts) = if options.ignore_case {
+            let mut folded = String::with_capacity(line.len());
+            let mut starts = Vec::with_capacity(line.len() + 1);
+            for (pos, c) in line.char_indices() {
+                let len = folded.len();
+                folded.extend(c.to_lowercase());
+                starts.extend(iter::repeat_n(pos, folded.len() - len));
+            }
+            starts.push(line.len());
+            (Cow::Owned(folded), Some(starts))
+        } else {
+            (Cow::Borrowed(line), None)
+        };
+        for pattern in patterns.iter() {
+            match options.scope {
+                MatchScope::Anywhere => candidates.extend(
+                    folded.match_indices(&pattern[..]).map(|(pos, m)| pos..pos + m.len())),
+                MatchScope::Word => candidates.extend(word_occurrences(&folded, pattern)),
+                MatchScope::Line if folded == *pattern => candidates.push(0..folded.len()),
+                MatchScope::Line => {}
+            }
+        }
+        if let Some(starts) = starts {
+            for range in candidates.iter_mut() {
+                *range = starts[range.start]..starts[range.end];
+            }
+        }
+    }
+    // At the same position, the longest match wins. (A regex has decided that already.)
+    candidates.sort_by_key(|range| (range.start, cmp::Reverse(range.end)));
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for range in candidates {
+        if !range.is_empty() && ranges.last().is_none_or(|last| last.end <= range.start) {
+            ranges.push(range);
+        }
+    }
+    ranges
+}
+
+// The matches of `regex` in `line`, with their groups. The ranges are what the user is looking for:
+// For `-w`, the characters around the word are part of the match, but not of the range.
+fn regex_matches<'a>(regex: &'a Regex, line: &'a str)
+        -> impl Iterator<Item = (Range<usize>, Captures<'a>)> + 'a {
+    let mut start = Some(0);
+    iter::from_fn(move || {
+        let captures = regex.captures_at(line, start?)?;
+        let found = captures.name(WORD_GROUP).unwrap_or_else(|| captures.get(0).unwrap());
+        // Continue after the word, so that its end can be the beginning of the next match.
+        start = match line[found.end()..].chars().next() {
+            Some(c) if found.is_empty() => Some(found.end() + c.len_utf8()),
+            Some(_) => Some(found.end()),
+            None => None,
+        };
+        Some((found.range(), captures))
+    })
+}
+
+// Replace the matches in `line` by `options.replacement`, for `--replace`. With a regex, `$1` etc.
+// in the replacement refer to the groups of the pattern. Like with `-o`, empty matches are left
+// alone.
+fn replace_matches(options: &Options, patterns: &[Cow<'_, str>], line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut last = 0;
+    if let Some(ref regex) = options.regex {
+        for (range, captures) in regex_matches(regex, line).filter(|(range, _)| !range.is_empty()) {
+            result.push_str(&line[last..range.start]);
+            captures.expand(&options.replacement, &mut result);
+            last = range.end;
+        }
+    } else {
+        for range in match_ranges(options, patterns, line) {
+            result.push_str(&line[last..range.start]);
+            result.push_str(&options.replacement);
+            last = range.end;
         }
     }
+    result.push_str(&line[last..]);
+    result
 }
 
-fn filter_lines(options: Arc<Options>, in_channel: Receiver<Line>, out_channel: SyncSender<Line>) {
-    for line in in_channel.iter() {
-        if line.data.contains(&options.pattern) {
-            out_channel.send(line).unwrap();
+// For `-w`, the regex has a group around the word, which comes before all the groups of the
+// patterns. So we add one to the group numbers in the replacement: `$1` becomes `${2}`. Names,
+// `$$` and everything else stay as they are.
+fn shift_groups(replacement: &str) -> String {
+    let mut result = String::with_capacity(replacement.len());
+    let mut rest = replacement;
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos + 1]);
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+            continue;
+        }
+        // The group is either `{...}`, or as many letters, digits and `_` as there are.
+        let (name, len) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => continue,
+            },
+            None => {
+                let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            }
+        };
+        if let Ok(group) = name.parse::<usize>() {
+            result.push_str(&format!("{{{}}}", group + 1));
+            rest = &rest[len..];
         }
     }
+    result.push_str(rest);
+    result
 }
 
-fn sort<T: PartialOrd>(data: &mut [T]) {
-    if data.len() < 2 { return; }
+// The patterns as `is_match` needs them.
+fn folded_patterns(options: &Options) -> Vec<Cow<'_, str>> {
+    options.pattern.iter()
+        .map(|p| if options.ignore_case { fold_case(p) } else { Cow::Borrowed(&p[..]) })
+        .collect()
+}
 
-    let mut lpos = 1;
-    let mut rpos = data.len();
-    // Invariant: pivot is data[0]; (0,lpos) is <= pivot; [rpos,len) is >= pivot; lpos < rpos
-    loop {
-        while lpos < rpos && data[lpos] <= data[0] {
-            lpos += 1;
+fn filter_lines(options: Arc<Options>, in_channel: Input<Line>, out_channel: Output<Line>) -> io::Result<()> {
+    let patterns = folded_patterns(&options);
+    // For the context, we remember the last `before_context` lines that were not forwarded, and
+    // how many lines after the last match still have to be forwarded. Context never reaches across
+    // files.
+    let mut before: VecDeque<Line> = VecDeque::with_capacity(options.before_context);
+    let mut after = 0;
+    let mut current_file: Option<Arc<str>> = None;
+    let (mut matches, max_count) = (0, options.max_count.unwrap_or(usize::MAX));
+    if max_count == 0 {
+        return Ok(());
+    }
+    for mut line in in_channel {
+        check_interrupted(&options)?;
+        if current_file.as_ref().is_none_or(|file| !Arc::ptr_eq(file, &line.file)) {
+            current_file = Some(line.file.clone());
+            before.clear();
+            after = 0;
         }
-        while rpos > lpos && data[rpos-1] >= data[0] {
-            rpos -= 1;
+        // After the last match we still forward its context, but no further matches.
+        let selected = is_match(&options, &patterns, &line.data) != options.invert_match;
+        if matches < max_count && selected {
+            prepare_match(&options, &patterns, &mut line);
+            for mut context in before.drain(..) {
+                context.context = true;
+                out_channel.send(context)?;
+            }
+            out_channel.send(line)?;
+            matches += 1;
+            after = options.after_context;
+        } else if after > 0 {
+            after -= 1;
+            line.context = true;
+            out_channel.send(line)?;
+        } else if options.before_context > 0 {
+            if before.len() == options.before_context {
+                before.pop_front();
+            }
+            before.push_back(line);
         }
-        if rpos == lpos {
+        // Once we are done, we stop reading. This drops `in_channel`, so the readers notice on
+        // their next `send` and stop as well, rather than reading the rest of the files.
+        if matches == max_count && after == 0 {
             break;
         }
+    }
+    options.counts.matches.fetch_add(matches, Ordering::Relaxed);
+    Ok(())
+}
 
-        data.swap(lpos, rpos-1);
+// Get a selected line ready for the output: `-o` needs the ranges of the matches, and
+// `--replace` the replaced text.
+fn prepare_match(options: &Options, patterns: &[Cow<'_, str>], line: &mut Line) {
+    if options.only_matching && !options.invert_match {
+        line.matches = match_ranges(options, patterns, &line.data);
+    }
+    if let (Replace, false) = (options.output_mode, options.invert_match) {
+        line.data = replace_matches(options, patterns, &line.data).into();
     }
+}
 
-    data.swap(0, lpos-1); // put pivot in the right place
+// Filter a single line, for the threads of `--threads`. Without context, this is all that
+// `filter_lines` does.
+fn filter_line(options: &Options, patterns: &[Cow<'_, str>], mut line: Line)
+        -> io::Result<Option<Line>> {
+    check_interrupted(options)?;
+    if is_match(options, patterns, &line.data) == options.invert_match {
+        return Ok(None);
+    }
+    prepare_match(options, patterns, &mut line);
+    options.counts.matches.fetch_add(1, Ordering::Relaxed);
+    Ok(Some(line))
+}
 
-    let (part1, part2) = data.split_at_mut(lpos);
-    sort(&mut part1[..lpos-1]);
-    sort(part2);
+// Like grep, we mark context lines with `-` instead of `:`.
+fn print_line(out: &mut dyn Write, options: &Options, line: &Match) -> io::Result<()> {
+    if !options.only_matching {
+        return print_text(out, options, line, line.offset, &line.text);
+    }
+    // With `-o`, every match gets a line of its own, and the offset is that of the match.
+    for range in line.ranges.iter() {
+        print_text(out, options, line, line.offset + range.start, &line.text[range.clone()])?;
+    }
+    Ok(())
 }
 
-fn output_lines(options: Arc<Options>, in_channel: Receiver<Line>) {
-    match options.output_mode {
-        Print => {
-            for line in in_channel.iter() {
-                println!("{}:{}: {}", options.files[line.file], line.line, line.data);
+// Print `text`, which is part of `line` starting at byte `offset` of the file.
+fn print_text(out: &mut dyn Write, options: &Options, line: &Match, offset: usize, text: &str)
+        -> io::Result<()> {
+    let separator = if line.context { '-' } else { ':' };
+    write!(out, "{}", line.file)?;
+    // File names can contain `:`, but never a NUL byte. So with `-0`, the end of the name is
+    // unambiguous.
+    if options.null {
+        out.write_all(b"\0")?;
+    } else {
+        write!(out, "{}", separator)?;
+    }
+    if options.byte_offset {
+        write!(out, "{}{sep}{}{sep} {}", line.line_no, offset, text, sep = separator)?;
+    } else {
+        write!(out, "{}{sep} {}", line.line_no, text, sep = separator)?;
+    }
+    end_record(out, options)
+}
+
+// Print a line of `--unique` output. Equal lines come from different places, so we print only the
+// text, not where it was found.
+fn print_unique(out: &mut dyn Write, options: &Options, counts: bool, line: &Match, count: usize)
+        -> io::Result<()> {
+    if counts {
+        write!(out, "{:7} ", count)?;
+    }
+    write!(out, "{}", line.text)?;
+    end_record(out, options)
+}
+
+// Finish a line of output. With `-0`, that is a NUL byte instead of a newline, so that lines
+// containing `\r` or other odd characters survive e.g. `xargs -0`.
+fn end_record(out: &mut dyn Write, options: &Options) -> io::Result<()> {
+    out.write_all(if options.null { b"\0" } else { b"\n" })
+}
+
+// Quote `s` as a JSON string. JSON strings are much like Rust string literals, except that all
+// characters below U+0020 must be escaped, and that `\u` takes exactly four hex digits.
+fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// Describe a matching line as a JSON object. The offset is only included with `-b`.
+fn json_line(options: &Options, line: &Match) -> String {
+    let offset = if options.byte_offset {
+        format!(",\"offset\":{}", line.offset)
+    } else {
+        String::new()
+    };
+    format!("{{\"file\":{},\"line\":{}{},\"text\":{}}}",
+            json_string(&line.file), line.line_no, offset, json_string(&line.text))
+}
+
+// Where the lines found by `run` end up. Every output mode is a sink: `print_matches` hands it the
+// lines one by one, and tells it when there are no more. Like the callbacks of part 11, which sink
+// we get is only decided at run-time, so we use trait objects. A new output mode is then just
+// another implementation of this trait.
+trait OutputSink {
+    // Take the next line. This may be a context line, which most sinks ignore.
+    fn consume(&mut self, line: Match) -> io::Result<()>;
+    // Called after the last line.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+// Print the lines as they come. The filter already did the replacing for `--replace`.
+struct PrintSink<'a> {
+    options: &'a Options,
+    out: &'a mut dyn Write,
+    last: Option<Match>,
+}
+
+impl<'a> OutputSink for PrintSink<'a> {
+    fn consume(&mut self, line: Match) -> io::Result<()> {
+        let with_context = self.options.before_context > 0 || self.options.after_context > 0;
+        // If there is context, we separate the groups of lines that are not adjacent.
+        if let Some(ref last) = self.last {
+            if with_context && (last.file != line.file || last.line_no + 1 != line.line_no) {
+                write!(self.out, "--")?;
+                end_record(self.out, self.options)?;
             }
-        },
-        Count => {
-            let count = in_channel.iter().count();
-            println!("{} hits for {}.", count, options.pattern);
-        },
-        SortAndPrint => {
-            let mut data: Vec<Line> = in_channel.iter().collect();
-            sort(&mut data[..]);
-            for line in data.iter() {
-                println!("{}:{}: {}", options.files[line.file], line.line, line.data);
+        }
+        print_line(self.out, self.options, &line)?;
+        self.last = Some(line);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct CountSink<'a> {
+    options: &'a Options,
+    out: &'a mut dyn Write,
+    count: usize,
+}
+
+impl<'a> OutputSink for CountSink<'a> {
+    fn consume(&mut self, line: Match) -> io::Result<()> {
+        // Context lines are not counted.
+        if !line.context {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let pattern = self.options.pattern.join(" or ");
+        if self.options.invert_match {
+            write!(self.out, "{} lines without {}.", self.count, pattern)?;
+        } else {
+            write!(self.out, "{} hits for {}.", self.count, pattern)?;
+        }
+        end_record(self.out, self.options)
+    }
+}
+
+struct DistinctCountSink<'a> {
+    options: &'a Options,
+    out: &'a mut dyn Write,
+    count: usize,
+    // The texts we have seen so far.
+    distinct: HashSet<String>,
+}
+
+impl<'a> OutputSink for DistinctCountSink<'a> {
+    fn consume(&mut self, line: Match) -> io::Result<()> {
+        if !line.context {
+            self.count += 1;
+            self.distinct.insert(line.text);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let pattern = self.options.pattern.join(" or ");
+        if self.options.invert_match {
+            write!(self.out, "{} lines without {}", self.count, pattern)?;
+        } else {
+            write!(self.out, "{} hits for {}", self.count, pattern)?;
+        }
+        write!(self.out, ", {} of them different.", self.distinct.len())?;
+        end_record(self.out, self.options)
+    }
+}
+
+// `run` already sorted the lines. Unlike `PrintSink`, we do not separate groups of context, since
+// sorting mixed them up anyway.
+struct SortSink<'a> {
+    options: &'a Options,
+    out: &'a mut dyn Write,
+}
+
+impl<'a> OutputSink for SortSink<'a> {
+    fn consume(&mut self, line: Match) -> io::Result<()> {
+        print_line(self.out, self.options, &line)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct UniqueSink<'a> {
+    options: &'a Options,
+    out: &'a mut dyn Write,
+    counts: bool,
+    // The line we saw last, and how often in a row.
+    last: Option<(Match, usize)>,
+}
+
+// After sorting, equal lines are next to each other. So we only have to compare every line with
+// the one before, and count how often it repeats. `run` left out the context already.
+impl<'a> OutputSink for UniqueSink<'a> {
+    fn consume(&mut self, line: Match) -> io::Result<()> {
+        match self.last {
+            Some((ref previous, ref mut count)) if previous.text == line.text => {
+                *count += 1;
             }
+            _ => {
+                if let Some((previous, count)) = self.last.replace((line, 1)) {
+                    print_unique(self.out, self.options, self.counts, &previous, count)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some((previous, count)) = self.last.take() {
+            print_unique(self.out, self.options, self.counts, &previous, count)?;
+        }
+        Ok(())
+    }
+}
+
+// Prints nothing. `run` already stopped at the first match, and the exit status tells about it.
+struct QuietSink;
+
+impl OutputSink for QuietSink {
+    fn consume(&mut self, _line: Match) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct JsonSink<'a> {
+    options: &'a Options,
+    out: &'a mut dyn Write,
+}
+
+impl<'a> OutputSink for JsonSink<'a> {
+    fn consume(&mut self, line: Match) -> io::Result<()> {
+        // Only the matches themselves are objects, the context is left out.
+        if line.context {
+            return Ok(());
+        }
+        write!(self.out, "{}", json_line(self.options, &line))?;
+        end_record(self.out, self.options)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The sink for the output mode of `options`, writing to `out`.
+fn output_sink<'a>(options: &'a Options, out: &'a mut dyn Write) -> Box<dyn OutputSink + 'a> {
+    match options.output_mode {
+        Print | Replace => Box::new(PrintSink { options, out, last: None }),
+        Count => Box::new(CountSink { options, out, count: 0 }),
+        CountDistinct => {
+            Box::new(DistinctCountSink { options, out, count: 0, distinct: HashSet::new() })
+        }
+        SortAndPrint => Box::new(SortSink { options, out }),
+        SortUnique { counts } => Box::new(UniqueSink { options, out, counts, last: None }),
+        Quiet => Box::new(QuietSink),
+        Json => Box::new(JsonSink { options, out }),
+    }
+}
+
+// Print what `run` returned to `out`, the way the output mode of `options` says. Returns whether
+// there were any matches, i.e., lines that are not just context.
+fn print_matches(options: &Options, matches: Vec<Match>, out: &mut dyn Write) -> io::Result<bool> {
+    let found = matches.iter().any(|line| !line.context);
+    {
+        let mut sink = output_sink(options, out);
+        for line in matches {
+            sink.consume(line)?;
         }
+        sink.finish()?;
     }
+    out.flush()?;
+    Ok(found)
+}
+
+// The command-line arguments. clap derives the parser, as well as the `--help` text, from this
+// struct: Every field is an argument, and its doc comment is the help.
+#[derive(Parser)]
+#[command(name = "rgrep", version, about = "Search files for lines containing a pattern.",
+          after_help = "Directories are searched recursively.\n\n\
+                        Default options can be put into the RGREP_OPTS environment variable.",
+          args_override_self = true,
+          override_usage = "rgrep [OPTIONS] <PATTERN> <FILE>...\n       \
+                            rgrep [OPTIONS] (-e PATTERN)... <FILE>...")]
+struct Args {
+    /// Print nothing, and stop at the first match. The exit status tells whether there was one.
+    /// This takes precedence over the other output options.
+    #[arg(short, long)]
+    quiet: bool,
+    /// Print the matching lines with every match replaced by REPLACEMENT. With -r, $1 or ${name}
+    /// in there stand for what a group of the pattern matched, and $$ is a $.
+    #[arg(long, value_name = "REPLACEMENT",
+          conflicts_with_all = ["count", "count_distinct", "sort", "json", "only_matching"])]
+    replace: Option<String>,
+    /// Count number of matching lines (rather than printing them).
+    #[arg(short, long, conflicts_with_all = ["sort", "json"])]
+    count: bool,
+    /// Like -c, but also count how many of the matching lines are different.
+    #[arg(long, conflicts_with_all = ["sort", "json"])]
+    count_distinct: bool,
+    /// Sort the lines before printing.
+    #[arg(short, long, conflicts_with = "json")]
+    sort: bool,
+    /// With -s, print every different matching line only once.
+    #[arg(long, requires = "sort",
+          conflicts_with_all = ["only_matching", "after_context", "before_context", "context"])]
+    unique: bool,
+    /// With --unique, precede every line by the number of times it occurred.
+    #[arg(long, requires = "unique")]
+    unique_count: bool,
+    /// Sort at most MB MiB of lines in memory, and use temporary files for the rest.
+    #[arg(long, value_name = "MB", default_value_t = 64)]
+    sort_memory: usize,
+    /// Print every match as a JSON object on its own line.
+    #[arg(long)]
+    json: bool,
+    /// Search for PATTERN. If this is given several times, lines match if they match any of the
+    /// patterns.
+    #[arg(short = 'e', value_name = "PATTERN")]
+    patterns: Vec<String>,
+    /// Ignore case distinctions in the patterns and the input.
+    #[arg(short, long)]
+    ignore_case: bool,
+    /// Only match whole words.
+    #[arg(short, long)]
+    word_regexp: bool,
+    /// Only match whole lines. This takes precedence over -w.
+    #[arg(short = 'x', long)]
+    line_regexp: bool,
+    /// Treat the patterns as regular expressions.
+    #[arg(short, long)]
+    regex: bool,
+    /// Select the lines that do not match.
+    #[arg(short = 'v', long)]
+    invert_match: bool,
+    /// Print the byte offset of every line within its file.
+    #[arg(short, long)]
+    byte_offset: bool,
+    /// Only print the parts of the lines that match, each on a line of its own.
+    #[arg(short, long, conflicts_with_all = ["json", "after_context", "before_context", "context"])]
+    only_matching: bool,
+    /// Follow file names by a NUL byte instead of ':', and end every line of output with a NUL
+    /// byte instead of a newline. This is safe to pipe into `xargs -0`.
+    #[arg(short = '0', long)]
+    null: bool,
+    /// Print NUM lines of context after every match.
+    #[arg(short = 'A', value_name = "NUM")]
+    after_context: Option<usize>,
+    /// Print NUM lines of context before every match.
+    #[arg(short = 'B', value_name = "NUM")]
+    before_context: Option<usize>,
+    /// Print NUM lines of context before and after every match.
+    #[arg(short = 'C', value_name = "NUM")]
+    context: Option<usize>,
+    /// Stop after NUM matching lines.
+    #[arg(short, long, value_name = "NUM")]
+    max_count: Option<usize>,
+    /// Read NUM files in parallel.
+    #[arg(short = 'j', long, value_name = "NUM", default_value_t = 1)]
+    readers: usize,
+    /// Filter the lines in NUM threads. The output stays in order. This does not work with context
+    /// or -m.
+    #[arg(long, value_name = "NUM", default_value_t = 1)]
+    threads: usize,
+    /// Let up to NUM files or lines wait between two threads. A thread that is ahead of the next
+    /// one blocks when the buffer is full.
+    #[arg(long, value_name = "NUM", default_value_t = 16)]
+    buffer: usize,
+    /// Map the files into memory instead of reading them, so that the lines are not copied.
+    #[arg(long)]
+    mmap: bool,
+    /// Skip what .gitignore and .ignore files in the searched directories exclude, as well as .git
+    /// directories.
+    #[arg(long)]
+    gitignore: bool,
+    /// When done, print how many files and lines were searched, how many matched, and how long it
+    /// took to stderr.
+    #[arg(long)]
+    stats: bool,
+    /// Follow symbolic links in the searched directories. Every directory is still only searched
+    /// once, even if several links lead there.
+    #[arg(short = 'L', long)]
+    follow: bool,
+    // No short form, since -o is --only-matching.
+    /// Write the output to FILE instead of the standard output.
+    #[arg(long, value_name = "FILE")]
+    output: Option<String>,
+    /// The pattern to search for, unless -e is given.
+    #[arg(value_name = "PATTERN", required_unless_present = "patterns")]
+    pattern: Option<String>,
+    /// The files and directories to search.
+    #[arg(value_name = "FILE")]
+    files: Vec<String>,
 }
 
-static USAGE: &'static str = "
-Usage: rgrep [-c] [-s] <pattern> <file>...
+// The environment variable with default arguments.
+const DEFAULTS_VAR: &str = "RGREP_OPTS";
+
+// Put the arguments in `defaults` in front of the ones in `argv`, right after the program name.
+// They are separated by whitespace; there is no quoting. Since the command line comes later, it
+// can override options that take a value, like `-j`.
+fn add_defaults(mut argv: Vec<String>, defaults: &str) -> Vec<String> {
+    let at = cmp::min(1, argv.len());
+    argv.splice(at..at, defaults.split_whitespace().map(|arg| arg.to_string()));
+    argv
+}
+
+// The options of the rgrep command: `argv` includes the program name, which clap skips. The
+// default arguments in `RGREP_OPTS` are added, and like rgrep, this exits the program with an
+// error message if the arguments are not valid. It also handles `--help` and `--version`.
+pub fn get_options(argv: Vec<String>) -> Options {
+    let defaults = env::var(DEFAULTS_VAR).unwrap_or_default();
+    Options::from_args(add_defaults(argv, &defaults)).unwrap_or_else(|e| e.exit())
+}
+
+impl Options {
+    // Search for `pattern` in `files`, like `rgrep PATTERN FILE...` with no other arguments.
+    pub fn new(pattern: &str, files: Vec<String>) -> Options {
+        Options {
+            files,
+            pattern: vec![pattern.to_string()],
+            output_mode: Print,
+            replacement: String::new(),
+            sort_memory: 64 << 20,
+            ignore_case: false,
+            scope: MatchScope::Anywhere,
+            invert_match: false,
+            before_context: 0,
+            after_context: 0,
+            byte_offset: false,
+            only_matching: false,
+            null: false,
+            readers: 1,
+            filters: 1,
+            buffer: 16,
+            mmap: false,
+            max_count: None,
+            use_ignore: false,
+            follow: false,
+            output: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            stats: false,
+            counts: Arc::default(),
+            regex: None,
+        }
+    }
+
+    // The setters below change options made by `new`, like the flags in their comments do on the
+    // command line. They can be chained: `Options::new(p, files).ignore_case().invert_match()`.
+
+    // `-i`
+    pub fn ignore_case(mut self) -> Options {
+        self.ignore_case = true;
+        self
+    }
+
+    // `-w`. Like on the command line, `-x` takes precedence.
+    pub fn word_regexp(mut self) -> Options {
+        if self.scope != MatchScope::Line {
+            self.scope = MatchScope::Word;
+        }
+        self
+    }
+
+    // `-x`
+    pub fn line_regexp(mut self) -> Options {
+        self.scope = MatchScope::Line;
+        self
+    }
+
+    // `-v`
+    pub fn invert_match(mut self) -> Options {
+        self.invert_match = true;
+        self
+    }
+
+    // `-o`: The `ranges` of every `Match` say which parts of the line matched.
+    pub fn only_matching(mut self) -> Options {
+        self.only_matching = true;
+        self
+    }
 
-Options:
-    -c, --count  Count number of matching lines (rather than printing them).
-    -s, --sort   Sort the lines before printing.
-";
+    // `-B before -A after`
+    pub fn context(mut self, before: usize, after: usize) -> Options {
+        self.before_context = before;
+        self.after_context = after;
+        self
+    }
 
-fn get_options() -> Options {
-    use docopt::Docopt;
+    // `-m max_count`
+    pub fn max_count(mut self, max_count: usize) -> Options {
+        self.max_count = Some(max_count);
+        self
+    }
 
-    // Parse argv and exit the program with an error message if it fails.
-    let args = Docopt::new(USAGE).and_then(|d| d.parse()).unwrap_or_else(|e| e.exit());
-    let count = args.get_bool("-c");
-    let sort = args.get_bool("-s");
-    let pattern = args.get_str("<pattern>");
-    let files = args.get_vec("<file>");
-    if count && sort {
-        println!("Setting both '-c' and '-s' at the same time does not make any sense.");
-        process::exit(1);
+    // `-r`. The regular expression is compiled right away, and it depends on `-i`, `-w` and `-x`.
+    // So this has to come after their setters.
+    pub fn regex(mut self) -> Result<Options, regex::Error> {
+        self.regex = Some(build_regex(&self.pattern, self.ignore_case, self.scope)?);
+        Ok(self)
     }
 
-    // We need to make the strings owned to construct the `Options` instance.
-    Options {
-        files: files.iter().map(|file| file.to_string()).collect(),
-        pattern: pattern.to_string(),
-        output_mode: if count { Count } else if sort { SortAndPrint } else { Print },
+    // The options for the command line `argv`, for callers that are not the rgrep command, like
+    // users of `run`. Unlike `get_options`, this ignores `RGREP_OPTS`, and it returns errors
+    // instead of exiting. `--help` and `--version` are errors as well, which carry the text to
+    // print.
+    pub fn from_args(argv: Vec<String>) -> Result<Options, clap::Error> {
+        let mut args = Args::try_parse_from(argv)?;
+        let error = |kind, message: &str| Err(Args::command().error(kind, message));
+        // With `-e`, there is no pattern argument, so clap took the first file for the pattern.
+        let pattern = if args.patterns.is_empty() {
+            vec![args.pattern.take().unwrap()]
+        } else {
+            args.files.splice(0..0, args.pattern.take());
+            args.patterns
+        };
+        if args.files.is_empty() {
+            return error(ErrorKind::MissingRequiredArgument, "At least one <FILE> must be given.");
+        }
+        let scope = if args.line_regexp {
+            MatchScope::Line
+        } else if args.word_regexp {
+            MatchScope::Word
+        } else {
+            MatchScope::Anywhere
+        };
+        // `-A` and `-B` take precedence over `-C`.
+        let both_context = args.context.unwrap_or(0);
+        let before_context = args.before_context.unwrap_or(both_context);
+        let after_context = args.after_context.unwrap_or(both_context);
+        if args.readers == 0 || args.threads == 0 {
+            return error(ErrorKind::ValueValidation, "We need at least one reader and one filter.");
+        }
+        // With several readers, the lines of different files get mixed up, so we could not tell
+        // which lines are context of which match.
+        if args.readers > 1 && (before_context > 0 || after_context > 0) {
+            return error(ErrorKind::ArgumentConflict,
+                         "Context lines can only be printed with a single reader.");
+        }
+        let with_context = before_context > 0 || after_context > 0;
+        if args.threads > 1 && (with_context || args.max_count.is_some()) {
+            return error(ErrorKind::ArgumentConflict,
+                         "Context lines and -m need a single filter thread.");
+        }
+        let regex = if args.regex {
+            match build_regex(&pattern, args.ignore_case, scope) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    return error(ErrorKind::ValueValidation,
+                                 &format!("Invalid regular expression: {}", e));
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(Options {
+            files: args.files,
+            pattern,
+            output_mode: if args.quiet {
+                Quiet
+            } else if args.replace.is_some() {
+                Replace
+            } else if args.count_distinct {
+                CountDistinct
+            } else if args.count {
+                Count
+            } else if args.unique {
+                SortUnique { counts: args.unique_count }
+            } else if args.sort {
+                SortAndPrint
+            } else if args.json {
+                Json
+            } else {
+                Print
+            },
+            // The regex for `-w` has a group of its own.
+            replacement: match args.replace {
+                Some(ref replacement) if args.regex && scope == MatchScope::Word => {
+                    shift_groups(replacement)
+                }
+                Some(replacement) => replacement,
+                None => String::new(),
+            },
+            sort_memory: args.sort_memory << 20,
+            ignore_case: args.ignore_case,
+            scope,
+            invert_match: args.invert_match,
+            before_context,
+            after_context,
+            byte_offset: args.byte_offset,
+            only_matching: args.only_matching,
+            null: args.null,
+            readers: args.readers,
+            filters: args.threads,
+            buffer: args.buffer,
+            mmap: args.mmap,
+            max_count: args.max_count,
+            use_ignore: args.gitignore,
+            follow: args.follow,
+            output: args.output,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            stats: args.stats,
+            counts: Arc::default(),
+            regex,
+        })
     }
 }
 
-fn run(options: Options) {
+// Set up the chain of threads up to the filter. A small buffer avoids needlessly filling RAM when
+// the filter cannot keep up with the readers.
+fn matching_lines(options: Arc<Options>) -> Pipeline<Line> {
+    let (options1, options2) = (options.clone(), options.clone());
+    let lines = Pipeline::source(options.buffer, move |out| list_files(options1, out))
+        .parallel_stage(options.readers, move |input, out| read_files(&options2, input, out));
+    filter_stage(lines, options)
+}
+
+// Add the filter to `lines`. With `--threads`, several threads filter the lines, and we use the
+// sequence numbers of an ordered stage to get the lines out in the order they came in.
+fn filter_stage(lines: Pipeline<Line>, options: Arc<Options>) -> Pipeline<Line> {
+    if options.filters == 1 {
+        return lines.stage(move |input, out| filter_lines(options, input, out));
+    }
+    // The threads share the patterns, so they cannot borrow from `options`.
+    let patterns: Vec<Cow<'static, str>> = folded_patterns(&options).into_iter()
+        .map(|pattern| Cow::Owned(pattern.into_owned()))
+        .collect();
+    let filters = options.filters;
+    lines.ordered_parallel_stage(filters, move |line| filter_line(&options, &patterns, line))
+}
+
+// Run rgrep as a command, and return its exit status. Like grep, that is 0 if there were matches,
+// 1 if there were none, and 2 if something went wrong - so rgrep can be used in conditions of the
+// shell.
+fn run_command(options: Options) -> i32 {
+    let start = Instant::now();
+    let result = search(&options);
+    // If something went wrong, the counts are incomplete, so there are no statistics.
+    if options.stats && result.is_ok() {
+        eprintln!("{}", stats(&options.counts, start.elapsed()));
+    }
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        // Whoever reads our output stopped listening, so there is nobody to tell.
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => 2,
+        // Like a program killed by Ctrl-C, we report 128 + the number of `SIGINT`.
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => 130,
+        Err(e) => {
+            eprintln!("rgrep: {}", e);
+            2
+        }
+    }
+}
+
+// Search with `run`, and print the result to stdout or the output file. Returns whether there were
+// matches. The output file is created first, so that we do not search in vain if that fails.
+fn search(options: &Options) -> io::Result<bool> {
+    let mut out: Box<dyn Write> = match options.output {
+        Some(ref path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    let matches = run(options.clone())?;
+    print_matches(options, matches, &mut out)
+}
+
+// A line that `run` found. The context lines of `-A`, `-B` and `-C` are in there as well, so that
+// the rgrep command can print everything from the result of `run`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub file: String,
+    // Like in the output of rgrep, lines are counted from 0.
+    pub line_no: usize,
+    pub text: String,
+    // The byte offset of the beginning of the line within the file.
+    pub offset: usize,
+    // Whether this line is only printed as context of a match.
+    pub context: bool,
+    // With `-o`, the parts of `text` that matched. Otherwise, this stays empty.
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl From<Line> for Match {
+    fn from(line: Line) -> Match {
+        Match { file: line.file.to_string(), line_no: line.line, text: line.data.to_string(),
+                offset: line.offset, context: line.context, ranges: line.matches }
+    }
+}
+
+// The way rgrep prints a line by default.
+impl fmt::Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let separator = if self.context { '-' } else { ':' };
+        write!(f, "{}{sep}{}{sep} {}", self.file, self.line_no, self.text, sep = separator)
+    }
+}
+
+// Search like rgrep, but return the lines instead of printing them. This is what the rgrep command
+// does, too, before it prints the result; so all the options about what to search apply, including
+// `--replace`, `-o`, and the context. The lines come in the order they were found, except for `-s`,
+// which sorts them. With `-q`, we stop at the first match, and return only that one. The options
+// can come from `Options::new` and its setters, or from `Options::from_args`.
+pub fn run(options: Options) -> io::Result<Vec<Match>> {
     let options = Arc::new(options);
+    matching_lines(options.clone()).sink(|input| collect_matches(&options, input))
+}
 
-    // This sets up the chain of threads. Use `sync_channel` with buffer-size of 16 to avoid needlessly filling RAM.
-    let (line_sender, line_receiver) = sync_channel(16);
-    let (filtered_sender, filtered_receiver) = sync_channel(16);
+// Turn the lines coming out of the filter into the result of `run`.
+fn collect_matches(options: &Options, mut input: Input<Line>) -> io::Result<Vec<Match>> {
+    match options.output_mode {
+        // Returning drops `input`, so the other threads stop on their next `send`, without reading
+        // the rest of the files.
+        Quiet => Ok(input.find(|line| !line.context).map(Match::from).into_iter().collect()),
+        // The sorting itself stays within `--sort-memory`, using temporary files for the rest. The
+        // result is in memory in the end, of course, but by then the lines are just the matches.
+        SortAndPrint | SortUnique { .. } => {
+            // `--unique` prints only the text of the matches, so it has no use for the context.
+            let unique = matches!(options.output_mode, SortUnique { .. });
+            let mut sorter = ExternalSort::new(options.sort_memory, env::temp_dir());
+            for line in input.filter(|line| !(unique && line.context)) {
+                sorter.push(line)?;
+            }
+            check_interrupted(options)?;
+            sorter.finish()?.map(|line| line.map(Match::from)).collect()
+        }
+        _ => Ok(input.map(Match::from).collect()),
+    }
+}
+
+// Summarize `counts` for `--stats`.
+fn stats(counts: &Counts, time: Duration) -> String {
+    format!("{} files, {} lines, {} matches in {:.3}s", counts.files.load(Ordering::Relaxed),
+            counts.lines.load(Ordering::Relaxed), counts.matches.load(Ordering::Relaxed),
+            time.as_secs_f64())
+}
 
-    let options1 = options.clone();
-    let handle1 = thread::spawn(move || read_files(options1, line_sender));
-    let options2 = options.clone();
-    let handle2 = thread::spawn(move || filter_lines(options2, line_receiver, filtered_sender));
-    let options3 = options.clone();
-    let handle3 = thread::spawn(move || output_lines(options3, filtered_receiver));
-    handle1.join().unwrap();
-    handle2.join().unwrap();
-    handle3.join().unwrap();
+// How `count_matches_with` splits up the work.
+#[derive(Clone, Copy, Debug)]
+pub enum Threads {
+    // Read and filter everything in the calling thread, without any channels.
+    Single,
+    // The pipeline: A thread listing the files, `readers` threads reading them, and `filters`
+    // threads filtering the lines, like with `--threads`. The calling thread counts. The channels
+    // between the threads hold up to `buffer` items.
+    Pipeline { readers: usize, filters: usize, buffer: usize },
+}
+
+// Count the lines in `files` that contain `pattern`, using `readers` threads to read the files.
+// This is `rgrep -c` without the command line and the output, e.g. for benchmarks.
+pub fn count_matches(pattern: &str, files: Vec<String>, readers: usize) -> io::Result<usize> {
+    count_matches_with(pattern, files, Threads::Pipeline { readers, filters: 1, buffer: 16 })
+}
+
+// Like `count_matches`, but with any split of the work. The result is always the same, only the
+// time it takes differs.
+pub fn count_matches_with(pattern: &str, files: Vec<String>, threads: Threads)
+        -> io::Result<usize> {
+    count(pattern, files, threads, None)
+}
+
+// Like `count_matches_with`, but `pattern` is a regular expression, like with `rgrep -c -r`. Fails
+// with `InvalidInput` if it is not a valid one.
+pub fn count_regex_matches_with(pattern: &str, files: Vec<String>, threads: Threads)
+        -> io::Result<usize> {
+    let regex = build_regex(&[pattern.to_string()], false, MatchScope::Anywhere)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    count(pattern, files, threads, Some(regex))
+}
+
+fn count(pattern: &str, files: Vec<String>, threads: Threads, regex: Option<Regex>)
+        -> io::Result<usize> {
+    let (readers, filters, buffer) = match threads {
+        Threads::Single => (1, 1, 0),
+        Threads::Pipeline { readers, filters, buffer } => (readers, filters, buffer),
+    };
+    assert!(readers > 0 && filters > 0, "count_matches: need at least one reader and filter");
+    let mut options = Options::new(pattern, files);
+    options.output_mode = Count;
+    options.readers = readers;
+    options.filters = filters;
+    options.buffer = buffer;
+    options.regex = regex;
+    match threads {
+        Threads::Single => count_sequentially(&options),
+        Threads::Pipeline { .. } => {
+            matching_lines(Arc::new(options)).sink(|input| Ok(input.count()))
+        }
+    }
+}
+
+// `rgrep -c` without any threads. Like `read_files`, we skip files we found in directories but
+// cannot read.
+fn count_sequentially(options: &Options) -> io::Result<usize> {
+    let patterns = folded_patterns(options);
+    let mut count = 0;
+    for_each_file(options, &mut |file| {
+        let result = read_file(&*file.source, options.mmap, &mut |line| {
+            if is_match(options, &patterns, &line.data) != options.invert_match {
+                count += 1;
+            }
+            Ok(())
+        });
+        match result {
+            Err(e) if file.explicit => Err(e),
+            _ => Ok(()),
+        }
+    })?;
+    Ok(count)
 }
 
 pub fn main() {
-    run(get_options());
+    main_with_args(env::args().collect());
+}
+
+// Run rgrep on the given command line. This lets other programs forward their arguments.
+pub fn main_with_args(argv: Vec<String>) {
+    let options = get_options(argv);
+    // On Ctrl-C, the signal handler sets the flag, and the threads wind down.
+    if let Err(e) = signal_hook::flag::register(signal_hook::consts::SIGINT,
+                                                options.interrupted.clone()) {
+        eprintln!("rgrep: cannot handle Ctrl-C: {}", e);
+    }
+    process::exit(run_command(options));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+    use std::io::BufRead;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use pipeline::Pipeline;
+    use serde_json::{self, Value};
+    use super::{build_regex, contains_word, count_matches, count_matches_with, filter_lines};
+    use super::{folded_patterns, is_match, json_line, match_ranges, replace_matches};
+    use super::{count_regex_matches_with, filter_stage, shift_groups};
+    use super::{collect_matches, json_string, list_files, print_matches};
+    use super::{add_defaults, dir_id, read_file, read_files, run, stats, walk};
+    use super::{run_command, Match};
+    use super::{Args, FileToRead, Ignore, Line, LineSource, MatchScope, Options};
+    use super::OutputMode;
+    use temp_dir::TempDir;
+    use super::Threads;
+    use clap::{CommandFactory, Parser};
+    use clap::error::ErrorKind;
+
+    fn options(pattern: &str, ignore_case: bool, regex: bool) -> Options {
+        patterns_options(&[pattern], ignore_case, regex)
+    }
+
+    fn patterns_options(patterns: &[&str], ignore_case: bool, regex: bool) -> Options {
+        let pattern: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        let regex = if regex {
+            Some(build_regex(&pattern, ignore_case, MatchScope::Anywhere).unwrap())
+        } else {
+            None
+        };
+        Options { files: vec![], pattern, output_mode: OutputMode::Print,
+                  replacement: String::new(), sort_memory: 0,
+                  ignore_case, scope: MatchScope::Anywhere, invert_match: false, before_context: 0,
+                  after_context: 0, byte_offset: false, only_matching: false, null: false,
+                  readers: 1, filters: 1, buffer: 16, mmap: false, max_count: None,
+                  use_ignore: false, follow: false, output: None,
+                  interrupted: Arc::new(AtomicBool::new(false)),
+                  stats: false, counts: Arc::default(), regex }
+    }
+
+    // Read the files of `options`, like rgrep does before filtering.
+    fn read(options: Options) -> Pipeline<Line> {
+        let options = Arc::new(options);
+        let (readers, options2) = (options.readers, options.clone());
+        Pipeline::source(4, move |out| list_files(options, out))
+            .parallel_stage(readers, move |input, out| read_files(&options2, input, out))
+    }
+
+    // A file in memory.
+    struct Text {
+        name: String,
+        text: String,
+    }
+
+    impl LineSource for Text {
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn open(&self) -> io::Result<Box<dyn BufRead>> {
+            Ok(Box::new(io::Cursor::new(self.text.clone().into_bytes())))
+        }
+    }
+
+    // Run some files through `read_files` and `filter_lines`. The files are called `file0`,
+    // `file1` and so on, and `files` contains their lines.
+    fn filter_pipeline(options: Arc<Options>, files: &[&[&str]]) -> Pipeline<Line> {
+        let files: Vec<Text> = files.iter().enumerate()
+            .map(|(idx, lines)| Text {
+                name: format!("file{}", idx),
+                text: lines.iter().map(|line| format!("{}\n", line)).collect(),
+            })
+            .collect();
+        let (readers, options2) = (options.readers, options.clone());
+        let lines = Pipeline::source(4, move |out| {
+                for file in files {
+                    out.send(FileToRead { source: Box::new(file), explicit: true })?;
+                }
+                Ok(())
+            })
+            .parallel_stage(readers, move |input, out| read_files(&options2, input, out));
+        filter_stage(lines, options)
+    }
+
+    // Return the lines that came out of `filter_lines`.
+    fn filter_files(options: Options, files: &[&[&str]]) -> Vec<Line> {
+        filter_pipeline(Arc::new(options), files).sink(|input| Ok(input.collect())).unwrap()
+    }
+
+    // Return what rgrep prints if `run` gets the lines that come out of `filter_lines`, and whether
+    // there were matches.
+    fn output(options: Options, files: &[&[&str]]) -> (String, bool) {
+        output_arc(Arc::new(options), files)
+    }
+
+    // Like `output`, but we keep access to the options, e.g. for the counts.
+    fn output_arc(options: Arc<Options>, files: &[&[&str]]) -> (String, bool) {
+        let matches = filter_pipeline(options.clone(), files)
+            .sink(|input| collect_matches(&options, input))
+            .unwrap();
+        let mut out = Vec::new();
+        let found = print_matches(&options, matches, &mut out).unwrap();
+        (String::from_utf8(out).unwrap(), found)
+    }
+
+    // Run `lines` through `filter_lines`, and return the numbers of the lines that came out.
+    fn filter(options: Options, lines: &[&str]) -> Vec<usize> {
+        filter_files(options, &[lines]).into_iter().map(|line| line.line).collect()
+    }
+
+    #[test]
+    fn test_context() {
+        let lines = ["a", "b", "match", "c", "d", "e", "match", "f", "match", "g"];
+        let with_context = |before, after| {
+            let mut opts = options("match", false, false);
+            opts.before_context = before;
+            opts.after_context = after;
+            filter_files(opts, &[&lines]).into_iter()
+                .map(|line| (line.line, line.context)).collect::<Vec<_>>()
+        };
+        assert_eq!(with_context(0, 0), vec![(2, false), (6, false), (8, false)]);
+        assert_eq!(with_context(1, 0),
+                   vec![(1, true), (2, false), (5, true), (6, false), (7, true), (8, false)]);
+        assert_eq!(with_context(0, 1),
+                   vec![(2, false), (3, true), (6, false), (7, true), (8, false), (9, true)]);
+        // Overlapping context is only sent once.
+        assert_eq!(with_context(2, 2),
+                   vec![(0, true), (1, true), (2, false), (3, true), (4, true), (5, true),
+                        (6, false), (7, true), (8, false), (9, true)]);
+
+        // Context does not reach across files.
+        let mut opts = options("match", false, false);
+        opts.before_context = 5;
+        opts.after_context = 5;
+        let result: Vec<_> = filter_files(opts, &[&["a", "match"], &["b", "c"], &["match"]])
+            .into_iter().map(|line| (line.file.to_string(), line.line, line.context)).collect();
+        assert_eq!(result, vec![("file0".to_string(), 0, true), ("file0".to_string(), 1, false),
+                                ("file2".to_string(), 0, false)]);
+    }
+
+    #[test]
+    fn test_directories() {
+        let tmp = TempDir::new("rgrep-directories");
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("b/c")).unwrap();
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::write(dir.join("z.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.join("b/c/y.txt"), "three\n").unwrap();
+        fs::write(dir.join("b/x.txt"), "four\n").unwrap();
+        // Not valid UTF-8, the bytes are replaced when reading.
+        fs::write(dir.join("b/binary"), [0xff, 0xfe, b'\n']).unwrap();
+
+        let mut found = Vec::new();
+        walk(dir, None, None, &mut |path: &Path| {
+            found.push(path.strip_prefix(dir).unwrap().to_string_lossy().into_owned());
+            Ok(())
+        }).unwrap();
+        assert_eq!(found, vec!["b/binary", "b/c/y.txt", "b/x.txt", "z.txt"]);
+        // A directory that does not exist is skipped, just like one we cannot read.
+        let missing = dir.join("missing");
+        walk(&missing, None, None, &mut |_: &Path| -> io::Result<()> { panic!() }).unwrap();
+
+        // Directories and files can be mixed.
+        let data = |opts: Options| -> Vec<String> {
+            read(opts).sink(|input| Ok(input.map(|line| line.data.to_string()).collect())).unwrap()
+        };
+        let mut opts = options("", false, false);
+        opts.files = vec![dir.to_string_lossy().into_owned(),
+                             dir.join("z.txt").to_string_lossy().into_owned()];
+        let files = opts.files.clone();
+        let expected = vec!["\u{fffd}\u{fffd}", "three", "four", "one", "two", "one", "two"];
+        assert_eq!(data(opts), expected);
+        // Mapping the files into memory makes no difference.
+        let mut opts = options("", false, false);
+        opts.files = files.clone();
+        opts.mmap = true;
+        assert_eq!(data(opts), expected);
+
+        // With several readers, we get the same lines, but maybe in a different order.
+        let mut opts = options("", false, false);
+        opts.files = files.clone();
+        opts.readers = 3;
+        let mut lines = data(opts);
+        lines.sort();
+        assert_eq!(lines, vec!["four", "one", "one", "three", "two", "two", "\u{fffd}\u{fffd}"]);
+        for &readers in [1, 2, 8].iter() {
+            assert_eq!(count_matches("o", files.clone(), readers).unwrap(), 5);
+        }
+        // So does every other split of the work.
+        let threads = [Threads::Single, Threads::Pipeline { readers: 1, filters: 3, buffer: 16 },
+                       Threads::Pipeline { readers: 2, filters: 2, buffer: 0 }];
+        for &threads in threads.iter() {
+            assert_eq!(count_matches_with("o", files.clone(), threads).unwrap(), 5);
+            assert_eq!(count_regex_matches_with("^t", files.clone(), threads).unwrap(), 3);
+        }
+        let error = count_regex_matches_with("(", files.clone(), Threads::Single).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        // A file in Latin-1: The accented letters are not valid UTF-8.
+        let file = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/latin1.txt").to_string();
+        let expected = vec!["caf\u{fffd} au lait", "na\u{fffd}ve", "plain ascii"];
+        for &mmap in [false, true].iter() {
+            let mut lines = Vec::new();
+            read_file(&PathBuf::from(&file), mmap, &mut |line| {
+                lines.push(line.data.to_string());
+                Ok(())
+            }).unwrap();
+            assert_eq!(lines, expected);
+        }
+        // We can still search those lines, and no thread panics.
+        let threads = [Threads::Single, Threads::Pipeline { readers: 2, filters: 2, buffer: 16 }];
+        for &threads in threads.iter() {
+            assert_eq!(count_matches_with("a", vec![file.clone()], threads).unwrap(), 3);
+            assert_eq!(count_matches_with("ve", vec![file.clone()], threads).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn test_gitignore() {
+        let tmp = TempDir::new("rgrep-ignore");
+        let dir = tmp.path();
+        for sub in ["target/debug", "src/gen", ".git"].iter() {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::write(dir.join(".gitignore"), "/target/\n*.log\n").unwrap();
+        fs::write(dir.join("src/.ignore"), "gen/\n!keep.log\n").unwrap();
+        let files = ["a.rs", "a.log", "target/debug/out", "src/b.rs", "src/keep.log",
+                     "src/gen/c.rs", ".git/HEAD"];
+        for file in files.iter() {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let found = |ignore: Option<&mut Ignore>| {
+            let mut found = Vec::new();
+            walk(dir, ignore, None, &mut |path: &Path| {
+                found.push(path.strip_prefix(dir).unwrap().to_string_lossy().into_owned());
+                Ok(())
+            }).unwrap();
+            found
+        };
+        assert_eq!(found(Some(&mut Ignore::new())), vec![".gitignore", "a.rs", "src/.ignore",
+                                                         "src/b.rs", "src/keep.log"]);
+        assert_eq!(found(None).len(), 9);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow() {
+        use std::os::unix::fs::symlink;
+        let tmp = TempDir::new("rgrep-follow");
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::create_dir_all(dir.join("other")).unwrap();
+        fs::write(dir.join("a/b/x.txt"), "x\n").unwrap();
+        fs::write(dir.join("other/y.txt"), "y\n").unwrap();
+        // A cycle back to the top, a link to a directory outside, a second one to the same
+        // directory, a link to a file, and one that leads nowhere.
+        symlink(dir, dir.join("a/b/up")).unwrap();
+        symlink(dir.join("other"), dir.join("a/1other")).unwrap();
+        symlink(dir.join("other"), dir.join("a/2other")).unwrap();
+        symlink(dir.join("other/y.txt"), dir.join("a/z.txt")).unwrap();
+        symlink(dir.join("missing"), dir.join("a/dangling")).unwrap();
+
+        let files = |follow| {
+            let mut found = Vec::new();
+            let mut visited = HashSet::new();
+            visited.insert(dir_id(&dir.join("a")).unwrap());
+            let visited = if follow { Some(&mut visited) } else { None };
+            walk(&dir.join("a"), None, visited, &mut |path: &Path| {
+                found.push(path.strip_prefix(dir).unwrap().to_string_lossy().into_owned());
+                Ok(())
+            }).unwrap();
+            found
+        };
+        assert_eq!(files(false), vec!["a/b/x.txt"]);
+        // `2other` is the same directory as `1other`. The cycle leads back to the top, where we
+        // find `a` and `other` once more, but we searched both already.
+        assert_eq!(files(true), vec!["a/1other/y.txt", "a/b/x.txt", "a/z.txt"]);
+
+        // The same through the options. Every directory given there starts afresh.
+        let mut opts = options("", false, false);
+        opts.files = vec![dir.join("a").to_string_lossy().into_owned(),
+                          dir.join("other").to_string_lossy().into_owned()];
+        opts.follow = true;
+        assert_eq!(read(opts).sink(|input| Ok(input.count())).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_byte_offsets() {
+        // The line terminators are not part of the data, but they count for the offset.
+        let text = Text { name: "text".to_string(), text: "ab\r\n\nc\nlast".to_string() };
+        let mut lines = Vec::new();
+        read_file(&text, false, &mut |line| {
+            lines.push((line.line, line.offset, line.data.to_string()));
+            Ok(())
+        }).unwrap();
+        assert_eq!(lines, vec![(0, 0, "ab".to_string()), (1, 4, "".to_string()),
+                               (2, 5, "c".to_string()), (3, 7, "last".to_string())]);
+    }
+
+    #[test]
+    fn test_run() {
+        let tmp = TempDir::new("rgrep-run");
+        let dir = tmp.path().to_path_buf();
+        fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(dir.join("b.txt"), "four\n").unwrap();
+        let files = vec![dir.to_string_lossy().into_owned()];
+        let found = |file: &str, line_no, offset, text: &str| Match {
+            file: dir.join(file).to_string_lossy().into_owned(), line_no, text: text.to_string(),
+            offset, context: false, ranges: Vec::new()
+        };
+        let mut opts = options("o", false, false);
+        opts.files = files.clone();
+        // The context is there, but marked as such. Nothing is printed, whatever the output mode.
+        opts.after_context = 1;
+        opts.output_mode = OutputMode::Count;
+        let matches = run(opts).unwrap();
+        assert_eq!(matches,
+                   vec![found("a.txt", 0, 0, "one"), found("a.txt", 1, 4, "two"),
+                        Match { context: true, ..found("a.txt", 2, 8, "three") },
+                        found("b.txt", 0, 0, "four")]);
+        assert_eq!(matches[2].to_string(), format!("{}-2- three", dir.join("a.txt").display()));
+        let mut opts = options("(t)(h?)", false, true);
+        opts.files = files.clone();
+        opts.output_mode = OutputMode::Replace;
+        opts.replacement = "$2$1".to_string();
+        let matches = run(opts).unwrap();
+        assert_eq!(matches, vec![found("a.txt", 1, 4, "two"), found("a.txt", 2, 8, "htree")]);
+        assert_eq!(matches[1].to_string(), format!("{}:2: htree", dir.join("a.txt").display()));
+        let mut opts = options("o", false, false);
+        opts.files = files.clone();
+        opts.invert_match = true;
+        assert_eq!(run(opts).unwrap(), vec![found("a.txt", 2, 8, "three")]);
+        // With `-s`, the lines come sorted, and with `-q`, there is only the first match.
+        let texts = |output_mode| {
+            let mut opts = options("o", false, false);
+            opts.files = files.clone();
+            opts.output_mode = output_mode;
+            run(opts).unwrap().into_iter().map(|line| line.text).collect::<Vec<_>>()
+        };
+        assert_eq!(texts(OutputMode::SortAndPrint), vec!["four", "one", "two"]);
+        assert_eq!(texts(OutputMode::Quiet), vec!["one"]);
+        // With `-o`, we get the ranges of the matches.
+        let mut opts = options("o", false, false);
+        opts.files = files;
+        opts.only_matching = true;
+        let ranges: Vec<_> = run(opts).unwrap().into_iter().map(|line| line.ranges).collect();
+        assert_eq!(ranges, vec![vec![0..1], vec![2..3], vec![1..2]]);
+        drop(tmp);
+        // Errors are returned, not printed.
+        let mut opts = options("o", false, false);
+        opts.files = vec![dir.to_string_lossy().into_owned()];
+        assert_eq!(run(opts).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let tmp = TempDir::new("rgrep-status");
+        let file = tmp.path().join("file.txt");
+        fs::write(&file, "one\ntwo\n").unwrap();
+        let status = |pattern: &str, invert_match: bool, files: Vec<String>| {
+            let mut opts = options(pattern, false, false);
+            opts.output_mode = OutputMode::Count;
+            opts.invert_match = invert_match;
+            opts.files = files;
+            run_command(opts)
+        };
+        let files = vec![file.to_string_lossy().into_owned()];
+        assert_eq!(status("o", false, files.clone()), 0);
+        assert_eq!(status("three", false, files.clone()), 1);
+        // With `-v`, the selected lines are the ones that count.
+        assert_eq!(status("o", true, files.clone()), 1);
+        assert_eq!(status("three", true, files.clone()), 0);
+        fs::remove_file(&file).unwrap();
+        // A file we cannot read is an error.
+        assert_eq!(status("o", false, files), 2);
+    }
+
+    #[test]
+    fn test_max_count() {
+        let lines = ["match", "a", "match", "b", "match", "c"];
+        let with_max = |max_count, after| {
+            let mut opts = options("match", false, false);
+            opts.max_count = Some(max_count);
+            opts.after_context = after;
+            filter_files(opts, &[&lines]).into_iter()
+                .map(|line| (line.line, line.context)).collect::<Vec<_>>()
+        };
+        assert_eq!(with_max(0, 0), vec![]);
+        assert_eq!(with_max(2, 0), vec![(0, false), (2, false)]);
+        assert_eq!(with_max(5, 0), vec![(0, false), (2, false), (4, false)]);
+        // The context of the last match is still printed, but matches in there are context, too.
+        assert_eq!(with_max(1, 3), vec![(0, false), (1, true), (2, true), (3, true)]);
+
+        // The filter stops reading, and so does the source, even though it never ends by itself.
+        let mut opts = options("7", false, false);
+        opts.max_count = Some(3);
+        let opts = Arc::new(opts);
+        let result = Pipeline::source(4, |out| {
+                let file: Arc<str> = Arc::from("endless");
+                for idx in 0.. {
+                    let data = idx.to_string().into();
+                    out.send(Line { data, file: file.clone(), line: idx, offset: 0,
+                                    context: false, matches: Vec::new() })?;
+                }
+                Ok(())
+            })
+            .stage(move |input, out| filter_lines(opts, input, out))
+            .sink(|input| Ok(input.map(|line| line.line).collect::<Vec<_>>()));
+        assert_eq!(result.unwrap(), vec![7, 17, 27]);
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a \"quote\" \\ and\ta\u{1}b"),
+                   "\"a \\\"quote\\\" \\\\ and\\ta\\u0001b\"");
+        // Whatever we produce, a real JSON parser must read back the same string.
+        let strings = ["", "äöü ∀ 🦀", "line\r\nbreak", "\u{0}\u{1f}\u{7f}", "{\"key\": [1]}"];
+        for s in strings.iter() {
+            assert_eq!(serde_json::from_str::<String>(&json_string(s)).unwrap(), *s);
+        }
+
+        let line = Match { file: "src/a b.rs".to_string(), line_no: 3,
+                           text: "let x = \"a\";".to_string(), offset: 42, context: false,
+                           ranges: Vec::new() };
+        let mut opts = options("let", false, false);
+        assert_eq!(json_line(&opts, &line),
+                   r#"{"file":"src/a b.rs","line":3,"text":"let x = \"a\";"}"#);
+        opts.byte_offset = true;
+        let value: Value = serde_json::from_str(&json_line(&opts, &line)).unwrap();
+        assert_eq!(value["file"], "src/a b.rs");
+        assert_eq!(value["line"], 3);
+        assert_eq!(value["offset"], 42);
+        assert_eq!(value["text"], line.text);
+    }
+
+    #[test]
+    fn test_output() {
+        let files: &[&[&str]] = &[&["a", "x", "b", "c", "d", "x"], &["y", "x"]];
+        let mut opts = options("x", false, false);
+        opts.after_context = 1;
+        assert_eq!(output(opts, files),
+                   ("file0:1: x\nfile0-2- b\n--\nfile0:5: x\n--\nfile1:1: x\n".to_string(), true));
+        let mut opts = options("x", false, false);
+        opts.output_mode = OutputMode::Count;
+        assert_eq!(output(opts, files), ("3 hits for x.\n".to_string(), true));
+        let mut opts = options("z", false, false);
+        opts.output_mode = OutputMode::Count;
+        assert_eq!(output(opts, files), ("0 hits for z.\n".to_string(), false));
+        // `--count-distinct` also counts the different lines, leaving out the context.
+        let repeated: &[&[&str]] = &[&["x", "a", "x y", "x"], &["x y", "x"]];
+        let mut opts = options("x", false, false);
+        opts.output_mode = OutputMode::CountDistinct;
+        opts.after_context = 1;
+        assert_eq!(output(opts, repeated),
+                   ("5 hits for x, 2 of them different.\n".to_string(), true));
+        let mut opts = options("y", false, false);
+        opts.output_mode = OutputMode::CountDistinct;
+        opts.invert_match = true;
+        assert_eq!(output(opts, repeated).0, "4 lines without y, 2 of them different.\n");
+        let mut opts = options("", false, false);
+        opts.output_mode = OutputMode::SortAndPrint;
+        let (sorted, _) = output(opts, &[&["b", "c"], &["a"]]);
+        assert_eq!(sorted, "file1:0: a\nfile0:0: b\nfile0:1: c\n");
+        // Equal lines are sorted by file and line number, even if several readers mix them up.
+        let mut opts = options("", false, false);
+        opts.output_mode = OutputMode::SortAndPrint;
+        opts.readers = 3;
+        let (sorted, _) = output(opts, &[&["b", "a", "a"], &["a", "b"], &["a"]]);
+        assert_eq!(sorted, "file0:1: a\nfile0:2: a\nfile1:0: a\nfile2:0: a\nfile0:0: b\n\
+                            file1:1: b\n");
+        // With `--unique`, only the text of the different lines is left, and maybe their counts.
+        let repeated: &[&[&str]] = &[&["b x", "a x", "b x", "c"], &["a x", "b x"]];
+        let mut opts = options("x", false, false);
+        opts.output_mode = OutputMode::SortUnique { counts: false };
+        assert_eq!(output(opts, repeated), ("a x\nb x\n".to_string(), true));
+        let mut opts = options("x", false, false);
+        opts.output_mode = OutputMode::SortUnique { counts: true };
+        assert_eq!(output(opts, repeated).0, "      2 a x\n      3 b x\n");
+        let mut opts = options("z", false, false);
+        opts.output_mode = OutputMode::SortUnique { counts: true };
+        assert_eq!(output(opts, repeated), (String::new(), false));
+        let mut opts = options("y", false, false);
+        opts.output_mode = OutputMode::Json;
+        opts.byte_offset = true;
+        assert_eq!(output(opts, files),
+                   ("{\"file\":\"file1\",\"line\":0,\"offset\":0,\"text\":\"y\"}\n".to_string(),
+                    true));
+        // With `--replace`, the matches are replaced, but the context is left as it is.
+        let mut opts = options("x", false, false);
+        opts.output_mode = OutputMode::Replace;
+        opts.replacement = "[x]".to_string();
+        opts.before_context = 1;
+        assert_eq!(output(opts, &[&["x", "a", "bx"]]).0,
+                   "file0:0: [x]\nfile0-1- a\nfile0:2: b[x]\n");
+        // With `-q`, nothing is printed at all.
+        for &(pattern, found) in [("x", true), ("z", false)].iter() {
+            let mut opts = options(pattern, false, false);
+            opts.output_mode = OutputMode::Quiet;
+            opts.after_context = 1;
+            assert_eq!(output(opts, files), (String::new(), found));
+        }
+        // And we stop at the first match, even if the input never ends.
+        let mut opts = options("7", false, false);
+        opts.output_mode = OutputMode::Quiet;
+        let opts = Arc::new(opts);
+        let opts1 = opts.clone();
+        let found = Pipeline::source(4, |out| {
+                let file: Arc<str> = Arc::from("endless");
+                for idx in 0.. {
+                    out.send(Line { data: idx.to_string().into(), file: file.clone(), line: idx,
+                                    offset: 0, context: false, matches: Vec::new() })?;
+                }
+                Ok(())
+            })
+            .stage(move |input, out| filter_lines(opts1, input, out))
+            .sink(|input| collect_matches(&opts, input));
+        let found: Vec<usize> = found.unwrap().iter().map(|line| line.line_no).collect();
+        assert_eq!(found, vec![7]);
+
+        // With `-o`, every match gets a line, and the offsets are those of the matches.
+        let mut opts = options("x", false, false);
+        opts.only_matching = true;
+        opts.byte_offset = true;
+        assert_eq!(output(opts, &[&["axbx", "y", "x"]]).0,
+                   "file0:0:1: x\nfile0:0:3: x\nfile0:2:7: x\n");
+        let mut opts = options("[a-z]+", false, true);
+        opts.only_matching = true;
+        opts.output_mode = OutputMode::SortAndPrint;
+        assert_eq!(output(opts, &[&["c1d", "2"], &["a3b"]]).0,
+                   "file1:0: a\nfile1:0: b\nfile0:0: c\nfile0:0: d\n");
+        // With `-0`, NUL bytes follow the file names and the lines.
+        let mut opts = options("x", false, false);
+        opts.after_context = 1;
+        opts.null = true;
+        assert_eq!(output(opts, &[&["x", "b\rc"], &["a", "x"]]).0,
+                   "file0\x000: x\0file0\x001- b\rc\0--\0file1\x001: x\0");
+        let mut opts = options("x", false, false);
+        opts.output_mode = OutputMode::Count;
+        opts.null = true;
+        assert_eq!(output(opts, files).0, "3 hits for x.\0");
+
+        // With `--output`, the output goes to a file.
+        let tmp = TempDir::new("rgrep-output");
+        let (input, output) = (tmp.path().join("in.txt"), tmp.path().join("out.txt"));
+        fs::write(&input, "one\ntwo\n").unwrap();
+        let mut opts = options("tw", false, false);
+        opts.files = vec![input.to_string_lossy().into_owned()];
+        opts.output = Some(output.to_string_lossy().into_owned());
+        assert_eq!(run_command(opts), 0);
+        let written = fs::read_to_string(&output).unwrap();
+        assert_eq!(written, format!("{}:1: two\n", input.display()));
+    }
+
+    #[test]
+    fn test_filter_threads() {
+        let lines: Vec<Vec<String>> = (0..3)
+            .map(|i| (0..500).map(|j| format!("line {} of {}: {}", j, i, j * j % 97)).collect())
+            .collect();
+        let lines: Vec<Vec<&str>> = lines.iter()
+            .map(|lines| lines.iter().map(|line| line.as_str()).collect())
+            .collect();
+        let files: Vec<&[&str]> = lines.iter().map(|lines| &lines[..]).collect();
+        let modes = [OutputMode::Print, OutputMode::Replace, OutputMode::Json, OutputMode::Count,
+                     OutputMode::CountDistinct];
+        for &mode in modes.iter() {
+            let flags = [(false, false), (true, false), (false, true)];
+            for &(only_matching, invert_match) in flags.iter() {
+                let with_filters = |filters| {
+                    let mut opts = options(r": \d?3", false, true);
+                    opts.output_mode = mode;
+                    opts.only_matching = only_matching && matches!(mode, OutputMode::Print);
+                    opts.invert_match = invert_match;
+                    opts.replacement = "<$0>".to_string();
+                    opts.filters = filters;
+                    let opts = Arc::new(opts);
+                    let result = output_arc(opts.clone(), &files);
+                    (result, opts.counts.matches.load(Ordering::Relaxed))
+                };
+                // Several filters give the same output, in the same order.
+                let expected = with_filters(1);
+                assert!((expected.0).1 && expected.1 > 0);
+                for &filters in [2, 5].iter() {
+                    assert_eq!(with_filters(filters), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let files: &[&[&str]] = &[&["a", "b", "ab"], &[], &["c", "a"]];
+        for &readers in [1, 3].iter() {
+            let mut opts = options("a", false, false);
+            opts.readers = readers;
+            let options = Arc::new(opts);
+            let lines = filter_pipeline(options.clone(), files).sink(|input| Ok(input.count()));
+            assert_eq!(lines.unwrap(), 3);
+            assert_eq!(stats(&options.counts, Duration::from_millis(1500)),
+                       "3 files, 5 lines, 3 matches in 1.500s");
+        }
+        // Context lines are not matches.
+        let mut opts = options("b", false, false);
+        opts.before_context = 1;
+        let options = Arc::new(opts);
+        let lines = filter_pipeline(options.clone(), files).sink(|input| Ok(input.count()));
+        assert_eq!(lines.unwrap(), 3);
+        assert_eq!(options.counts.matches.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_interrupted() {
+        let files: &[&[&str]] = &[&["a", "b"], &["c"]];
+        let modes = [OutputMode::Print, OutputMode::Count, OutputMode::SortAndPrint,
+                     OutputMode::SortUnique { counts: true }];
+        for &mode in modes.iter() {
+            let mut opts = options("", false, false);
+            opts.output_mode = mode;
+            opts.interrupted.store(true, Ordering::Relaxed);
+            let options = Arc::new(opts);
+            // So there is nothing to print, in particular no incomplete count.
+            let result = filter_pipeline(options.clone(), files)
+                .sink(|input| collect_matches(&options, input));
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        }
+    }
+
+    #[test]
+    fn test_invert_match() {
+        let lines = ["apple", "Banana", "cherry", "banana split"];
+        assert_eq!(filter(options("an", false, false), &lines), vec![1, 3]);
+        let mut inverted = options("an", false, false);
+        inverted.invert_match = true;
+        assert_eq!(filter(inverted, &lines), vec![0, 2]);
+        // Inverting composes with the other ways of matching.
+        let mut inverted = options("^b", true, true);
+        inverted.invert_match = true;
+        assert_eq!(filter(inverted, &lines), vec![0, 2]);
+        let mut inverted = options("", false, false);
+        inverted.invert_match = true;
+        assert_eq!(filter(inverted, &lines), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_is_match() {
+        let substring = options("a.c", false, false);
+        assert!(is_match(&substring, &["a.c".into()], "xa.cx"));
+        assert!(!is_match(&substring, &["a.c".into()], "abc"));
+        let folded = options("A.c", true, false);
+        assert!(is_match(&folded, &["a.c".into()], "XA.CX"));
+
+        let regex = options("^a.c$", false, true);
+        assert!(is_match(&regex, &[], "abc"));
+        assert!(is_match(&regex, &[], "a.c"));
+        assert!(!is_match(&regex, &[], "xabc"));
+        assert!(!is_match(&regex, &[], "ABC"));
+        let regex = options("^a.c$", true, true);
+        assert!(is_match(&regex, &[], "ABC"));
+        let regex = options(r"\bfn \w+\(", false, true);
+        assert!(is_match(&regex, &[], "pub fn main() {"));
+        assert!(!is_match(&regex, &[], "let fn_ = 3;"));
+    }
+
+    fn scoped(pattern: &str, scope: MatchScope, ignore_case: bool, regex: bool) -> Options {
+        let mut opts = options(pattern, ignore_case, false);
+        opts.scope = scope;
+        if regex {
+            opts.regex = Some(build_regex(&opts.pattern, ignore_case, scope).unwrap());
+        }
+        opts
+    }
+
+    #[test]
+    fn test_whole_lines() {
+        let lines = ["fn", "fn main()", " fn", "FN", "", "fn fn"];
+        for &regex in [false, true].iter() {
+            assert_eq!(filter(scoped("fn", MatchScope::Line, false, regex), &lines), vec![0]);
+            assert_eq!(filter(scoped("fn", MatchScope::Line, true, regex), &lines), vec![0, 3]);
+            let mut inverted = scoped("fn", MatchScope::Line, true, regex);
+            inverted.invert_match = true;
+            assert_eq!(filter(inverted, &lines), vec![1, 2, 4, 5]);
+            assert_eq!(filter(scoped("", MatchScope::Line, false, regex), &lines), vec![4]);
+        }
+        // All alternatives of a regular expression have to match the whole line.
+        assert_eq!(filter(scoped("fn|main", MatchScope::Line, false, true), &lines), vec![0]);
+        assert_eq!(filter(scoped("fn.*", MatchScope::Line, false, true), &lines), vec![0, 1, 5]);
+    }
+
+    #[test]
+    fn test_whole_words() {
+        assert!(contains_word("fn main", "fn"));
+        assert!(contains_word("let x = y;", "x"));
+        assert!(contains_word("(x)", "x"));
+        assert!(!contains_word("fn_main", "fn"));
+        assert!(!contains_word("defn", "fn"));
+        assert!(!contains_word("", "fn"));
+        // Only the second occurrence is a word.
+        assert!(contains_word("xfn fn", "fn"));
+        assert!(contains_word("aaa aa", "aa"));
+        // Patterns may have non-word characters at their ends.
+        assert!(contains_word("a -v b", "-v"));
+        assert!(contains_word("x--v", "-v"));
+        assert!(!contains_word("a -vb", "-v"));
+        // Non-ASCII letters are word characters, too.
+        assert!(!contains_word("größer", "er"));
+        assert!(contains_word("größer er", "er"));
+
+        let lines = ["fn", "fn main()", "pub fn", "fn_x", "xfn", "(fn)", "FN.", "defn fn"];
+        let words = |ignore_case, regex| {
+            filter(scoped("fn", MatchScope::Word, ignore_case, regex), &lines)
+        };
+        // Both modes agree, at line boundaries and around punctuation.
+        assert_eq!(words(false, false), vec![0, 1, 2, 5, 7]);
+        assert_eq!(words(false, true), vec![0, 1, 2, 5, 7]);
+        assert_eq!(words(true, false), vec![0, 1, 2, 5, 6, 7]);
+        assert_eq!(words(true, true), vec![0, 1, 2, 5, 6, 7]);
+        // Regular expressions that start and end with non-word characters.
+        let patterns = [r"\(\w+\)".to_string(), "-".to_string()];
+        let regex = build_regex(&patterns, false, MatchScope::Word).unwrap();
+        assert!(regex.is_match("call (x) now"));
+        assert!(!regex.is_match("call(x)now"));
+        assert!(regex.is_match("a - b"));
+        assert!(!regex.is_match("a-b"));
+    }
+
+    #[test]
+    fn test_multiple_patterns() {
+        let lines = ["abc", "bcd", "cde", "xyz", "ABC"];
+        // Overlapping patterns: A line matching several of them is still only forwarded once.
+        assert_eq!(filter(patterns_options(&["ab", "bc"], false, false), &lines), vec![0, 1]);
+        assert_eq!(filter(patterns_options(&["c", "cd", "z"], false, false), &lines),
+                   vec![0, 1, 2, 3]);
+        assert_eq!(filter(patterns_options(&["ab", "ab"], true, false), &lines), vec![0, 4]);
+        let mut inverted = patterns_options(&["a", "d"], false, false);
+        inverted.invert_match = true;
+        assert_eq!(filter(inverted, &lines), vec![3, 4]);
+        // Regular expressions stay separate, even if they contain alternatives or anchors.
+        assert_eq!(filter(patterns_options(&["^b|^x", "e$"], false, true), &lines), vec![1, 2, 3]);
+        assert_eq!(filter(patterns_options(&["^a", "z$"], true, true), &lines), vec![0, 3, 4]);
+    }
+
+    #[test]
+    fn test_match_ranges() {
+        let ranges = |options: Options, line: &str| -> Vec<String> {
+            let patterns = folded_patterns(&options);
+            match_ranges(&options, &patterns, line).into_iter().map(|r| line[r].to_string())
+                .collect()
+        };
+        let both = |patterns: &[&str], ignore_case, line| {
+            let substring = ranges(patterns_options(patterns, ignore_case, false), line);
+            let regex = ranges(patterns_options(patterns, ignore_case, true), line);
+            assert_eq!(substring, regex);
+            substring
+        };
+        assert_eq!(both(&["ab"], false, "xabyabab"), vec!["ab", "ab", "ab"]);
+        assert_eq!(both(&["aa"], false, "aaa"), vec!["aa"]);
+        assert_eq!(both(&["x"], false, "abc"), Vec::<String>::new());
+        // Empty matches are not reported.
+        assert_eq!(both(&[""], false, "abc"), Vec::<String>::new());
+        // Among several patterns, the leftmost match wins. (At the same position, the regex takes
+        // the first alternative that matches, so we list the longer pattern first.)
+        assert_eq!(both(&["bc", "abc", "ab", "d"], false, "abcd"), vec!["abc", "d"]);
+        assert_eq!(ranges(patterns_options(&["ab", "abc"], false, false), "abcd"), vec!["abc"]);
+        // The ranges are in the original line, even if folding the case changes its length.
+        assert_eq!(both(&["x"], true, "ẞ İX x"), vec!["X", "x"]);
+        assert_eq!(ranges(options("ab", true, false), "İAB ab"), vec!["AB", "ab"]);
+        assert_eq!(ranges(options("[0-9]+", false, true), "a1 b22 c"), vec!["1", "22"]);
+
+        // With `-w`, only the word itself is the match, not the characters around it.
+        for &regex in [false, true].iter() {
+            let words = scoped("fn", MatchScope::Word, false, regex);
+            assert_eq!(ranges(words, "fn fn_x (fn) fn"), vec!["fn", "fn", "fn"]);
+            assert_eq!(ranges(scoped("a", MatchScope::Word, false, regex), "a a a"),
+                       vec!["a", "a", "a"]);
+            assert_eq!(ranges(scoped("fn", MatchScope::Line, true, regex), "FN"), vec!["FN"]);
+        }
+    }
+
+    #[test]
+    fn test_replace() {
+        assert_eq!(shift_groups("<$1> ${2}x $0 $$1 ${name} $name $1a ${3"),
+                   "<${2}> ${3}x ${1} $$1 ${name} $name $1a ${3");
+        let replace = |mut options: Options, replacement: &str, line: &str| {
+            options.replacement = replacement.to_string();
+            let patterns = folded_patterns(&options);
+            replace_matches(&options, &patterns, line)
+        };
+        assert_eq!(replace(options("ab", false, false), "$1", "xabyab"), "x$1y$1");
+        assert_eq!(replace(options("ab", true, false), "-", "AB İab"), "- İ-");
+        assert_eq!(replace(options("", false, false), "-", "abc"), "abc");
+        assert_eq!(replace(options(r"(\w+)=(\w+)", false, true), "$2=$1", "a=1, b=2"),
+                   "1=a, 2=b");
+        assert_eq!(replace(options(r"(?P<n>\d)", false, true), "<${n}>", "a1b2"), "a<1>b<2>");
+        // With `-w`, only the words are replaced, and the groups keep their numbers.
+        for &regex in [false, true].iter() {
+            let words = scoped("fn", MatchScope::Word, false, regex);
+            assert_eq!(replace(words, "func", "fn fn_x (fn)"), "func fn_x (func)");
+        }
+        let mut words = scoped(r"f(n)", MatchScope::Word, false, true);
+        words.replacement = shift_groups("<$1 $0>");
+        assert_eq!(replace_matches(&words, &[], "fn fn_x (fn)"), "<n fn> fn_x (<n fn>)");
+    }
+
+    #[test]
+    fn test_from_args() {
+        let argv = |args: &str| args.split(' ').map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let error = |args: &str| Options::from_args(argv(args)).err().map(|e| e.kind());
+        assert_eq!(error("rgrep -v fn src"), None);
+        assert_eq!(error("rgrep -e fn"), Some(ErrorKind::MissingRequiredArgument));
+        assert_eq!(error("rgrep -j 0 fn src"), Some(ErrorKind::ValueValidation));
+        assert_eq!(error("rgrep -r fn( src"), Some(ErrorKind::ValueValidation));
+        assert_eq!(error("rgrep -A 1 -j 2 fn src"), Some(ErrorKind::ArgumentConflict));
+        assert_eq!(error("rgrep --threads 2 -m 1 fn src"), Some(ErrorKind::ArgumentConflict));
+        // Even `--help` does not exit, the caller decides what to do with the text.
+        assert_eq!(error("rgrep --help"), Some(ErrorKind::DisplayHelp));
+        // `Options::new` takes the pattern as it is, even if it looks like an option.
+        let options = Options::new("-v", vec!["src".to_string()]);
+        assert_eq!(options.pattern, vec!["-v"]);
+        assert_eq!(options.files, vec!["src"]);
+        assert!(!options.invert_match && matches!(options.output_mode, OutputMode::Print));
+    }
+
+    #[test]
+    fn test_options() {
+        // Let clap check the consistency of the argument definitions.
+        Args::command().debug_assert();
+        let argv = |args: &str| args.split(' ').map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let parse = |argv| Options::from_args(argv).unwrap();
+        let options = parse(argv("rgrep -c -w -C 2 -B 1 fn src lib.rs"));
+        assert_eq!(options.pattern, vec!["fn"]);
+        assert_eq!(options.files, vec!["src", "lib.rs"]);
+        assert!(matches!(options.output_mode, OutputMode::Count));
+        assert!(options.scope == MatchScope::Word);
+        assert_eq!((options.before_context, options.after_context), (1, 2));
+        assert_eq!((options.sort_memory, options.readers, options.max_count), (64 << 20, 1, None));
+        assert_eq!((options.buffer, options.filters), (16, 1));
+        assert!(!options.null && !options.only_matching && options.output.is_none());
+        // With `-e`, all the positional arguments are files.
+        let options = parse(argv("rgrep -e fn -x --json -e struct -r -m 3 -j 4 --buffer 0 \
+                                         -0 src"));
+        assert_eq!(options.pattern, vec!["fn", "struct"]);
+        assert_eq!(options.files, vec!["src"]);
+        assert!(matches!(options.output_mode, OutputMode::Json));
+        assert!(options.scope == MatchScope::Line && options.regex.is_some());
+        assert_eq!((options.readers, options.max_count, options.buffer), (4, Some(3), 0));
+        assert!(options.null);
+        // `-o` is short for `--only-matching`, the output file has no short option.
+        let options = parse(argv("rgrep -o --output out.txt fn src"));
+        assert!(options.only_matching);
+        assert_eq!(options.output.as_deref(), Some("out.txt"));
+        let options = parse(argv("rgrep -s --unique --unique-count fn src"));
+        assert!(matches!(options.output_mode, OutputMode::SortUnique { counts: true }));
+        let options = parse(argv("rgrep -w -r --replace <$1> f(n) src"));
+        assert!(matches!(options.output_mode, OutputMode::Replace));
+        assert_eq!(options.replacement, "<${2}>");
+        let options = parse(argv("rgrep --replace <$1> f(n) src"));
+        assert_eq!(options.replacement, "<$1>");
+        let options = parse(argv("rgrep -q -c fn src"));
+        assert!(matches!(options.output_mode, OutputMode::Quiet));
+        let options = parse(argv("rgrep -c --count-distinct fn src"));
+        assert!(matches!(options.output_mode, OutputMode::CountDistinct));
+        let options = parse(argv("rgrep --threads 4 -j 2 fn src"));
+        assert_eq!((options.readers, options.filters), (2, 4));
+        // `--unique` only works together with sorting.
+        assert!(Args::try_parse_from(argv("rgrep --unique fn src")).is_err());
+
+        // Defaults go in front of the command line, which can override them.
+        assert_eq!(add_defaults(argv("rgrep -j 2 fn src"), "  --mmap\t-j 4 "),
+                   argv("rgrep --mmap -j 4 -j 2 fn src"));
+        assert_eq!(add_defaults(argv("rgrep fn src"), ""), argv("rgrep fn src"));
+        assert_eq!(add_defaults(vec![], "-i"), vec!["-i"]);
+        let defaults = "-i -j 4 -e struct -c";
+        let options = parse(add_defaults(argv("rgrep -j 2 -e fn src"), defaults));
+        assert!(options.ignore_case && matches!(options.output_mode, OutputMode::Count));
+        assert_eq!(options.readers, 2);
+        // Patterns add up.
+        assert_eq!(options.pattern, vec!["struct", "fn"]);
+        // Flags may be given twice.
+        let options = parse(argv("rgrep -i -i --stats --stats fn src"));
+        assert!(options.ignore_case && options.stats);
+    }
 }
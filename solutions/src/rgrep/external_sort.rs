@@ -0,0 +1,383 @@
+// Sorting more lines than fit into memory. We collect lines until they use up the memory budget,
+// sort them, and write them to a temporary file - a *run*. In the end, all the runs are merged:
+// Since every run is sorted, the smallest remaining line is always at the beginning of one of them.
+// A heap tells us which one, so we only need the first line of every run in memory.
+//
+// The runs are sorted with the standard library's `sort_unstable`, not with the quicksort of part
+// 14: That one always takes the first element as the pivot, so on input that is already sorted, it
+// recurses once per line and overflows the stack.
+//
+// Merging too many runs at once would need too many open files. So whenever there are `MERGE_WIDTH`
+// runs of the same size, we merge them into one bigger run right away. Every line then takes part
+// in only a logarithmic number of merges.
+use std::{fs, io, mem, process};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::prelude::*;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use super::Line;
+
+const MERGE_WIDTH: usize = 16;
+
+// Give every run a different file name, even if several sorts happen at once.
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+// The memory used by a line: Its text and matches, plus the `Line` itself. The file name is shared,
+// so we don't count it.
+fn line_size(line: &Line) -> usize {
+    line.data.len() + line.matches.len() * mem::size_of::<Range<usize>>() + mem::size_of::<Line>()
+}
+
+fn write_u64(w: &mut impl Write, n: u64) -> io::Result<()> {
+    w.write_all(&n.to_le_bytes())
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_u64(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u64(r)? as usize];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// A sorted sequence of lines in a temporary file, which is deleted when the run is dropped.
+struct Run {
+    path: PathBuf,
+    len: usize,
+}
+
+impl Run {
+    fn create(dir: &Path, lines: impl Iterator<Item = io::Result<Line>>) -> io::Result<Run> {
+        let id = NEXT_RUN.fetch_add(1, AtomicOrdering::SeqCst);
+        let path = dir.join(format!("rgrep-sort-{}-{}", process::id(), id));
+        // From here on, dropping the run cleans up, even if writing fails.
+        let mut run = Run { path, len: 0 };
+        let mut file = io::BufWriter::new(fs::File::create(&run.path)?);
+        for line in lines {
+            let line = line?;
+            write_str(&mut file, &line.file)?;
+            write_str(&mut file, &line.data)?;
+            write_u64(&mut file, line.line as u64)?;
+            write_u64(&mut file, line.offset as u64)?;
+            file.write_all(&[line.context as u8])?;
+            write_u64(&mut file, line.matches.len() as u64)?;
+            for range in line.matches.iter() {
+                write_u64(&mut file, range.start as u64)?;
+                write_u64(&mut file, range.end as u64)?;
+            }
+            run.len += 1;
+        }
+        file.flush()?;
+        Ok(run)
+    }
+
+    fn read(self) -> io::Result<RunReader> {
+        let file = io::BufReader::new(fs::File::open(&self.path)?);
+        Ok(RunReader { remaining: self.len, file, last_file: None, _run: self })
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+struct RunReader {
+    remaining: usize,
+    file: io::BufReader<fs::File>,
+    // Consecutive lines usually come from the same file, so we share the name like `read_file`.
+    last_file: Option<Arc<str>>,
+    // Keep the file around until we are done reading.
+    _run: Run,
+}
+
+impl RunReader {
+    fn read_line(&mut self) -> io::Result<Line> {
+        let name = read_str(&mut self.file)?;
+        let file = match self.last_file {
+            Some(ref file) if **file == *name => file.clone(),
+            _ => Arc::from(name.as_str()),
+        };
+        self.last_file = Some(file.clone());
+        let data = read_str(&mut self.file)?;
+        let line = read_u64(&mut self.file)? as usize;
+        let offset = read_u64(&mut self.file)? as usize;
+        let mut context = [0];
+        self.file.read_exact(&mut context)?;
+        let mut matches = Vec::new();
+        for _ in 0..read_u64(&mut self.file)? {
+            let start = read_u64(&mut self.file)? as usize;
+            matches.push(start..read_u64(&mut self.file)? as usize);
+        }
+        Ok(Line { data: data.into(), file, line, offset, context: context[0] != 0, matches })
+    }
+}
+
+impl Iterator for RunReader {
+    type Item = io::Result<Line>;
+
+    fn next(&mut self) -> Option<io::Result<Line>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.read_line())
+    }
+}
+
+// The next line of one of the runs we are merging. `BinaryHeap` is a max-heap, so we order the
+// entries backwards. Lines are only equal if they come from the same place, e.g. because a file was
+// searched twice; then the earlier run comes first.
+struct Head {
+    line: Line,
+    run: usize,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Head) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Head {}
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Head) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Head {
+    fn cmp(&self, other: &Head) -> Ordering {
+        (&other.line, other.run).cmp(&(&self.line, self.run))
+    }
+}
+
+// The lines of several runs, merged in sorted order.
+pub(super) struct Merge {
+    runs: Vec<RunReader>,
+    heads: BinaryHeap<Head>,
+}
+
+impl Merge {
+    fn new(runs: Vec<Run>) -> io::Result<Merge> {
+        let mut merge = Merge { runs: Vec::with_capacity(runs.len()), heads: BinaryHeap::new() };
+        for run in runs {
+            merge.runs.push(run.read()?);
+            merge.advance(merge.runs.len() - 1)?;
+        }
+        Ok(merge)
+    }
+
+    // Put the next line of run `run` on the heap.
+    fn advance(&mut self, run: usize) -> io::Result<()> {
+        if let Some(line) = self.runs[run].next() {
+            self.heads.push(Head { line: line?, run });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = io::Result<Line>;
+
+    fn next(&mut self) -> Option<io::Result<Line>> {
+        let head = self.heads.pop()?;
+        match self.advance(head.run) {
+            Ok(()) => Some(Ok(head.line)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+// The result of sorting: Either everything fit into memory, or we have to merge runs.
+pub(super) enum Sorted {
+    Memory(::std::vec::IntoIter<Line>),
+    Merge(Merge),
+}
+
+impl Iterator for Sorted {
+    type Item = io::Result<Line>;
+
+    fn next(&mut self) -> Option<io::Result<Line>> {
+        match *self {
+            Sorted::Memory(ref mut lines) => lines.next().map(Ok),
+            Sorted::Merge(ref mut merge) => merge.next(),
+        }
+    }
+}
+
+pub(super) struct ExternalSort {
+    // How many bytes of lines we keep in memory before writing a run.
+    budget: usize,
+    // Where the runs go.
+    dir: PathBuf,
+    lines: Vec<Line>,
+    size: usize,
+    // The runs, each with its level: How many times its lines have been merged. The levels never
+    // increase along the vector.
+    runs: Vec<(usize, Run)>,
+}
+
+impl ExternalSort {
+    pub(super) fn new(budget: usize, dir: PathBuf) -> ExternalSort {
+        ExternalSort { budget, dir, lines: Vec::new(), size: 0, runs: Vec::new() }
+    }
+
+    pub(super) fn push(&mut self, line: Line) -> io::Result<()> {
+        self.size += line_size(&line);
+        self.lines.push(line);
+        if self.size > self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    // Sort the lines in memory, and write them to a new run.
+    fn spill(&mut self) -> io::Result<()> {
+        let mut lines = mem::take(&mut self.lines);
+        self.size = 0;
+        lines.sort_unstable();
+        self.runs.push((0, Run::create(&self.dir, lines.into_iter().map(Ok))?));
+        // If the last `MERGE_WIDTH` runs have the same level, merge them. This can cascade.
+        loop {
+            let len = self.runs.len();
+            if len < MERGE_WIDTH || self.runs[len - MERGE_WIDTH].0 != self.runs[len - 1].0 {
+                return Ok(());
+            }
+            let level = self.runs[len - 1].0;
+            let runs = self.runs.drain(len - MERGE_WIDTH..).map(|(_, run)| run).collect();
+            self.runs.push((level + 1, Run::create(&self.dir, Merge::new(runs)?)?));
+        }
+    }
+
+    pub(super) fn finish(mut self) -> io::Result<Sorted> {
+        if self.runs.is_empty() {
+            self.lines.sort_unstable();
+            return Ok(Sorted::Memory(self.lines.into_iter()));
+        }
+        if !self.lines.is_empty() {
+            self.spill()?;
+        }
+        Ok(Sorted::Merge(Merge::new(self.runs.into_iter().map(|(_, run)| run).collect())?))
+    }
+
+    // The number of runs we currently have on disk.
+    #[cfg(test)]
+    fn runs(&self) -> usize {
+        self.runs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use rng::Rng;
+    use temp_dir::TempDir;
+    use super::super::Line;
+    use super::{ExternalSort, Sorted, MERGE_WIDTH};
+
+    // Pseudo-random lines, in several files.
+    fn lines(n: usize) -> Vec<Line> {
+        let mut rng = Rng::new(7);
+        let files: Vec<Arc<str>> = ["a.txt", "b.txt"].iter().map(|&f| Arc::from(f)).collect();
+        (0..n).map(|i| {
+            let state = rng.next_u64();
+            let data = format!("{:x} line\r{}", state % 5000, "x".repeat((state % 30) as usize));
+            let matches = (0..i % 3).map(|j| j..j + 1).collect();
+            Line { data: data.into(), file: files[i % 2].clone(), line: i, offset: i * 10,
+                   context: i % 3 == 0, matches }
+        }).collect()
+    }
+
+    // Sort `input` with the given budget, putting the runs into a directory of their own. Returns
+    // the sorted lines, the largest number of runs at any time, and whether it all fit into memory.
+    // Every test needs its own `name`, since the tests run in parallel.
+    fn sort_with_budget(name: &str, input: Vec<Line>, budget: usize)
+            -> (Vec<Line>, usize, bool) {
+        let tmp = TempDir::new(&format!("rgrep-sort-{}", name));
+        let mut sorter = ExternalSort::new(budget, tmp.path().to_path_buf());
+        let mut max_runs = 0;
+        for line in input {
+            sorter.push(line).unwrap();
+            max_runs = max_runs.max(sorter.runs());
+        }
+        let sorted = sorter.finish().unwrap();
+        let in_memory = matches!(sorted, Sorted::Memory(_));
+        let sorted = sorted.map(|line| line.unwrap()).collect();
+        // The runs are deleted once they are merged, so the directory is empty again.
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+        (sorted, max_runs, in_memory)
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let (sorted, runs, in_memory) = sort_with_budget("memory", lines(100), usize::MAX);
+        assert!(in_memory);
+        assert_eq!(runs, 0);
+        assert_eq!(sorted.len(), 100);
+        assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+        let (sorted, _, _) = sort_with_budget("memory-empty", vec![], 0);
+        assert!(sorted.is_empty());
+    }
+
+    #[test]
+    fn test_external() {
+        let input = lines(20_000);
+        let mut expected: Vec<(String, Arc<str>, usize)> = input.iter()
+            .map(|line| (line.data.to_string(), line.file.clone(), line.line))
+            .collect();
+        expected.sort();
+        // With a tiny budget, we get lots of runs, which we have to merge in several levels.
+        for &(budget, min_runs) in [(1_000, 2 * MERGE_WIDTH), (100_000, 2)].iter() {
+            let (sorted, max_runs, in_memory) =
+                sort_with_budget("external", lines(20_000), budget);
+            assert!(!in_memory);
+            assert!(max_runs >= min_runs && max_runs < 4 * MERGE_WIDTH, "{} runs", max_runs);
+            assert_eq!(sorted.len(), expected.len());
+            // Equal texts are ordered by file and line number, so there is only one right result.
+            let result: Vec<(String, Arc<str>, usize)> = sorted.iter()
+                .map(|line| (line.data.to_string(), line.file.clone(), line.line))
+                .collect();
+            assert_eq!(result, expected);
+            // All the other fields survive the trip through the file.
+            for line in sorted.iter() {
+                let original = &input[line.line];
+                assert_eq!(line.file, original.file);
+                assert_eq!((line.offset, line.context), (original.offset, original.context));
+                assert_eq!(line.matches, original.matches);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sorted_input() {
+        // Input that is already sorted, or sorted backwards, used to overflow the stack.
+        let sorted_lines = || {
+            let mut input = lines(100_000);
+            input.sort_unstable();
+            input
+        };
+        let numbers = |lines: Vec<Line>| lines.iter().map(|line| line.line).collect::<Vec<_>>();
+        let expected = numbers(sorted_lines());
+        for &budget in [usize::MAX, 1 << 20].iter() {
+            let (sorted, _, in_memory) = sort_with_budget("sorted", sorted_lines(), budget);
+            assert_eq!(in_memory, budget == usize::MAX);
+            assert_eq!(numbers(sorted), expected);
+            let reversed = sorted_lines().into_iter().rev().collect();
+            let (sorted, _, _) = sort_with_budget("reversed", reversed, budget);
+            assert_eq!(numbers(sorted), expected);
+        }
+    }
+}
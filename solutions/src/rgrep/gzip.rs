@@ -0,0 +1,85 @@
+// Searching compressed files, with the `gzip` feature. Files that start like gzip data are
+// decompressed on the fly, no matter what they are called - so compressed logs can be searched
+// without unpacking them first. Line numbers and byte offsets then refer to the decompressed text.
+use std::io::{self, BufRead, BufReader};
+use flate2::bufread::MultiGzDecoder;
+
+// Every gzip file starts with these two bytes.
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Check whether `data`, the beginning of a file, is compressed.
+pub(super) fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+// If `reader` has compressed data, return a reader for the decompressed data instead. A gzip file
+// can consist of several compressed parts, e.g. after `cat a.gz b.gz`, so we decode all of them.
+pub(super) fn decompress<'a>(mut reader: Box<dyn BufRead + 'a>)
+        -> io::Result<Box<dyn BufRead + 'a>> {
+    if is_compressed(reader.fill_buf()?) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use super::super::read_file;
+    use temp_dir::TempDir;
+
+    // The lines of `file` with their numbers and offsets, read both mapped and the usual way.
+    fn lines(file: &PathBuf) -> Vec<(usize, usize, String)> {
+        let mut results = Vec::new();
+        for &mmap in [false, true].iter() {
+            let mut lines = Vec::new();
+            read_file(file, mmap, &mut |line| {
+                lines.push((line.line, line.offset, line.data.to_string()));
+                Ok(())
+            }).unwrap();
+            results.push(lines);
+        }
+        assert_eq!(results[0], results[1]);
+        results.pop().unwrap()
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_fixture() {
+        let file = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/small.txt.gz"));
+        assert_eq!(lines(&file),
+                   vec![(0, 0, "first line".to_string()), (1, 11, "second line".to_string()),
+                        (2, 24, "".to_string()), (3, 25, "last".to_string())]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tmp = TempDir::new("rgrep-gzip");
+        let file = tmp.path().join("text.gz");
+        let text = "äöü\nsome text\n".repeat(1000);
+        let expected: Vec<_> = text.lines().collect();
+        // The name does not matter, only the content.
+        fs::write(&file, compress(text.as_bytes())).unwrap();
+        let read: Vec<_> = lines(&file).into_iter().map(|(_, _, line)| line).collect();
+        assert_eq!(read, expected);
+        // Several compressed parts are read one after the other.
+        let mut data = compress(b"one\ntw");
+        data.extend(compress(b"o\nthree\n"));
+        fs::write(&file, data).unwrap();
+        let read: Vec<_> = lines(&file).into_iter().map(|(_, _, line)| line).collect();
+        assert_eq!(read, vec!["one", "two", "three"]);
+        // Files that are not compressed are read as they are.
+        fs::write(&file, "plain\n").unwrap();
+        assert_eq!(lines(&file), vec![(0, 0, "plain".to_string())]);
+    }
+}
@@ -0,0 +1,225 @@
+// Ignore files, so that searching a repository skips build outputs and the like. We support a
+// subset of the `.gitignore` format, which is also used by `.ignore` files:
+//
+// * Blank lines and lines starting with `#` are skipped.
+// * A pattern starting with `!` re-includes what an earlier pattern excluded.
+// * A pattern ending in `/` only matches directories.
+// * A pattern containing a `/` elsewhere is relative to the directory of the ignore file.
+//   Otherwise, it matches the name of a file or directory at any depth below that directory.
+// * `*` matches anything but `/`, `?` matches one character other than `/`, and a `**` component
+//   matches any number of directories.
+//
+// Character classes like `[a-z]` and escaping with `\` are not supported.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The names of the ignore files we read, in order of increasing priority.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+struct Pattern {
+    // The components of the pattern, split at `/`.
+    components: Vec<String>,
+    negated: bool,
+    dir_only: bool,
+    // Whether the pattern is matched against the entire path, or just against the name.
+    anchored: bool,
+}
+
+impl Pattern {
+    fn parse(line: &str) -> Option<Pattern> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.trim_start_matches('/');
+        if line.is_empty() {
+            return None;
+        }
+        let components = line.split('/').map(|c| c.to_string()).collect();
+        Some(Pattern { components, negated, dir_only, anchored })
+    }
+
+    // `path` are the components of a path relative to the directory of the ignore file.
+    fn matches(&self, path: &[&str], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            match_components(&self.components, path)
+        } else {
+            path.last().is_some_and(|name| match_component(&self.components[0], name))
+        }
+    }
+}
+
+// Match a list of pattern components against a list of path components.
+fn match_components(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        // `**` may swallow any number of components, including none.
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_components(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path)) => match_component(first, name) && match_components(rest, path),
+            None => false,
+        },
+    }
+}
+
+// Match a single component with `*` and `?` against a name.
+fn match_component(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_chars(&pattern, &name)
+}
+
+fn match_chars(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&'*', rest)) => (0..=name.len()).any(|skip| match_chars(rest, &name[skip..])),
+        Some((&'?', rest)) => !name.is_empty() && match_chars(rest, &name[1..]),
+        Some((&c, rest)) => name.first() == Some(&c) && match_chars(rest, &name[1..]),
+    }
+}
+
+// The patterns of the ignore files in one directory.
+struct Rules {
+    dir: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+// The rules of all directories we are currently in, from the outermost to the innermost one.
+pub struct Ignore {
+    rules: Vec<Rules>,
+}
+
+impl Ignore {
+    pub fn new() -> Ignore {
+        Ignore { rules: Vec::new() }
+    }
+
+    // Enter `dir`, reading its ignore files. Files that do not exist or that we cannot read are
+    // treated like empty ones.
+    pub fn push_dir(&mut self, dir: &Path) {
+        let patterns = IGNORE_FILES.iter()
+            .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+            .flat_map(|text| text.lines().filter_map(Pattern::parse).collect::<Vec<_>>())
+            .collect();
+        self.rules.push(Rules { dir: dir.to_path_buf(), patterns });
+    }
+
+    // Leave the directory we entered last.
+    pub fn pop_dir(&mut self) {
+        self.rules.pop();
+    }
+
+    // Check whether `path`, which must be inside the directories we entered, is ignored. Like git,
+    // the last pattern that matches decides, and patterns in inner directories come later.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // Version control data is never interesting to search.
+        if path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        let mut ignored = false;
+        for rules in self.rules.iter() {
+            let relative = match path.strip_prefix(&rules.dir) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            let components: Vec<String> = relative.iter()
+                .map(|c| c.to_string_lossy().into_owned())
+                .collect();
+            let components: Vec<&str> = components.iter().map(|c| c.as_str()).collect();
+            for pattern in rules.patterns.iter() {
+                if pattern.matches(&components, is_dir) {
+                    ignored = !pattern.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{match_component, Ignore, Pattern, Rules};
+
+    fn ignore(dir: &str, text: &str) -> Ignore {
+        let patterns = text.lines().filter_map(Pattern::parse).collect();
+        Ignore { rules: vec![Rules { dir: Path::new(dir).to_path_buf(), patterns }] }
+    }
+
+    #[test]
+    fn test_match_component() {
+        assert!(match_component("*.rs", "main.rs"));
+        assert!(match_component("*.rs", ".rs"));
+        assert!(!match_component("*.rs", "main.rs.bak"));
+        assert!(match_component("part??.rs", "part05.rs"));
+        assert!(!match_component("part??.rs", "part5.rs"));
+        assert!(match_component("*a*b*", "xxaxxbxx"));
+        assert!(match_component("target", "target"));
+        assert!(!match_component("target", "targets"));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Pattern::parse("").is_none());
+        assert!(Pattern::parse("   ").is_none());
+        assert!(Pattern::parse("# comment").is_none());
+        assert!(Pattern::parse("/").is_none());
+        let pattern = Pattern::parse("!/docs/*.html").unwrap();
+        assert!(pattern.negated && pattern.anchored && !pattern.dir_only);
+        assert_eq!(pattern.components, vec!["docs", "*.html"]);
+        let pattern = Pattern::parse("target/").unwrap();
+        assert!(!pattern.negated && !pattern.anchored && pattern.dir_only);
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let rules = ignore("repo", "# outputs\n/target/\n*.o\n!keep.o\ndocs/**/*.html\ntmp\n");
+        let is_ignored = |path: &str, is_dir| rules.is_ignored(Path::new(path), is_dir);
+        // `/target/` only matches a directory right in `repo`.
+        assert!(is_ignored("repo/target", true));
+        assert!(!is_ignored("repo/target", false));
+        assert!(!is_ignored("repo/src/target", true));
+        // Names match at any depth.
+        assert!(is_ignored("repo/a.o", false));
+        assert!(is_ignored("repo/src/deep/b.o", false));
+        assert!(is_ignored("repo/src/tmp", true));
+        assert!(is_ignored("repo/src/tmp", false));
+        // Negation wins because it comes later.
+        assert!(!is_ignored("repo/src/keep.o", false));
+        // `**` matches any number of directories.
+        assert!(is_ignored("repo/docs/index.html", false));
+        assert!(is_ignored("repo/docs/a/b/index.html", false));
+        assert!(!is_ignored("repo/src/docs/index.html", false));
+        assert!(!is_ignored("repo/docs/index.md", false));
+        // `.git` is always ignored, and paths outside of `repo` are not affected.
+        assert!(is_ignored("repo/.git", true));
+        assert!(!is_ignored("other/a.o", false));
+    }
+
+    #[test]
+    fn test_inner_rules() {
+        let mut rules = ignore("repo", "*.log\n");
+        rules.rules.push(Rules { dir: Path::new("repo/logs").to_path_buf(),
+                                 patterns: vec![Pattern::parse("!important.log").unwrap()] });
+        assert!(rules.is_ignored(Path::new("repo/debug.log"), false));
+        assert!(rules.is_ignored(Path::new("repo/logs/debug.log"), false));
+        assert!(!rules.is_ignored(Path::new("repo/logs/important.log"), false));
+        assert!(rules.is_ignored(Path::new("repo/important.log"), false));
+        rules.pop_dir();
+        assert!(rules.is_ignored(Path::new("repo/logs/important.log"), false));
+    }
+}
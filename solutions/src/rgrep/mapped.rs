@@ -0,0 +1,175 @@
+// Reading files by mapping them into memory (`--mmap`). The operating system then loads the file
+// as we look at it, and we never copy a line: Instead of a `String`, a line is a range of the
+// mapped file, and an `Arc` keeps the mapping alive as long as there are lines pointing into it.
+//
+// This is only faster if the copying was what took the time. Mapping a file is not free either,
+// and for small files, it is usually slower than reading them.
+use std::{fmt, fs, io, str};
+use std::cmp::Ordering;
+use std::ops::{Deref, Range};
+use std::path::Path;
+use std::sync::Arc;
+use memmap2::Mmap;
+use super::{lossy_string, trimmed_len, Line};
+#[cfg(feature = "gzip")]
+use super::{gzip, read_lines};
+
+// A file mapped into memory.
+pub(super) struct MappedFile {
+    // Empty files cannot be mapped, so we have no mapping for them.
+    map: Option<Mmap>,
+}
+
+impl MappedFile {
+    pub(super) fn open(path: &Path) -> io::Result<MappedFile> {
+        let file = fs::File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(MappedFile { map: None });
+        }
+        // Mapping is unsafe because the file could change while we look at it: Then the data
+        // would change under our feet, and it might not even be UTF-8 anymore. We accept that
+        // risk, like most programs that map files. (Reading the file while it is being written
+        // would also produce garbage, but no undefined behavior.)
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MappedFile { map: Some(map) })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self.map {
+            Some(ref map) => map,
+            None => &[],
+        }
+    }
+}
+
+// The text of a line: Either a string of its own, or a part of a mapped file.
+pub(super) enum LineData {
+    Owned(String),
+    // We only use this for lines that are valid UTF-8.
+    Mapped(Arc<MappedFile>, Range<usize>),
+}
+
+impl Deref for LineData {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match *self {
+            LineData::Owned(ref data) => data,
+            // We checked that the line is UTF-8 when we created it.
+            LineData::Mapped(ref file, ref range) => unsafe {
+                str::from_utf8_unchecked(&file.bytes()[range.clone()])
+            },
+        }
+    }
+}
+
+impl From<String> for LineData {
+    fn from(data: String) -> LineData {
+        LineData::Owned(data)
+    }
+}
+
+impl fmt::Display for LineData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+// Lines are compared by their text, no matter where it is stored.
+impl PartialEq for LineData {
+    fn eq(&self, other: &LineData) -> bool {
+        **self == **other
+    }
+}
+impl Eq for LineData {}
+impl PartialOrd for LineData {
+    fn partial_cmp(&self, other: &LineData) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for LineData {
+    fn cmp(&self, other: &LineData) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+// Call `f` on the lines of the file at `path`, just like `read_file`, but without copying them.
+pub(super) fn read_mapped(path: &Path, name: Arc<str>, f: &mut dyn FnMut(Line) -> io::Result<()>)
+        -> io::Result<()> {
+    let file = Arc::new(MappedFile::open(path)?);
+    // Compressed files have to be decompressed anyway, so we just read the mapping.
+    #[cfg(feature = "gzip")]
+    {
+        if gzip::is_compressed(file.bytes()) {
+            return read_lines(gzip::decompress(Box::new(file.bytes()))?, name, f);
+        }
+    }
+    let len = file.bytes().len();
+    let mut offset = 0;
+    for lineidx in 0.. {
+        if offset == len {
+            break;
+        }
+        let bytes = &file.bytes()[offset..];
+        // The line ends after the next `\n`, or at the end of the file.
+        let end = bytes.iter().position(|&b| b == b'\n').map_or(bytes.len(), |pos| pos + 1);
+        let line = &bytes[..trimmed_len(&bytes[..end])];
+        // Only lines with invalid UTF-8 have to be copied, to replace what is invalid.
+        let data = if str::from_utf8(line).is_ok() {
+            LineData::Mapped(file.clone(), offset..offset + line.len())
+        } else {
+            LineData::Owned(lossy_string(line.to_vec()))
+        };
+        f(Line { data, file: name.clone(), line: lineidx, offset, context: false,
+                 matches: Vec::new() })?;
+        offset += end;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+    use std::sync::Arc;
+    use super::read_mapped;
+    use super::super::read_file;
+    use temp_dir::TempDir;
+
+    // Read `text` from a file, both mapped and the usual way, and return the lines with their
+    // numbers and offsets.
+    fn lines(text: &[u8]) -> io::Result<Vec<(usize, usize, String)>> {
+        let tmp = TempDir::new("rgrep-mmap");
+        let file = tmp.path().join("text");
+        fs::write(&file, text).unwrap();
+        let (mut mapped, mut read) = (Vec::new(), Vec::new());
+        let result = read_mapped(&file, Arc::from("text"), &mut |line| {
+            mapped.push((line.line, line.offset, line.data.to_string()));
+            Ok(())
+        });
+        let read_result = read_file(&file, false, &mut |line| {
+            read.push((line.line, line.offset, line.data.to_string()));
+            Ok(())
+        });
+        // Both ways agree, also on errors.
+        match (&result, read_result) {
+            (Ok(()), Ok(())) => assert_eq!(mapped, read),
+            (Err(e), Err(read_e)) => assert_eq!(e.kind(), read_e.kind()),
+            _ => panic!("only one way of reading failed"),
+        }
+        result.map(|()| mapped)
+    }
+
+    #[test]
+    fn test_read_mapped() {
+        assert_eq!(lines(b"").unwrap(), vec![]);
+        assert_eq!(lines(b"ab\r\n\nc\nlast").unwrap(),
+                   vec![(0, 0, "ab".to_string()), (1, 4, "".to_string()), (2, 5, "c".to_string()),
+                        (3, 7, "last".to_string())]);
+        assert_eq!(lines("äö\n\r\r\n∀\n".as_bytes()).unwrap(),
+                   vec![(0, 0, "äö".to_string()), (1, 5, "".to_string()),
+                        (2, 8, "∀".to_string())]);
+        // Invalid UTF-8 is replaced.
+        assert_eq!(lines(&[b'a', b'\n', 0xff, b'b', b'\r', b'\n']).unwrap(),
+                   vec![(0, 0, "a".to_string()), (1, 2, "\u{fffd}b".to_string())]);
+    }
+}
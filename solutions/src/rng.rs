@@ -0,0 +1,19 @@
+// The xorshift pseudo-random number generator of part 23, shared by the tests and benchmarks that
+// need lots of input. The same seed always gives the same numbers, so a failing test can be
+// reproduced. That is also why it is no good for anything but testing.
+
+pub struct Rng(u64);
+
+impl Rng {
+    /// The state of a xorshift generator must never be 0, so a seed of 0 is replaced by 1.
+    pub fn new(seed: u64) -> Rng {
+        Rng(if seed == 0 { 1 } else { seed })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
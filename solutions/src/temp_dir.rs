@@ -0,0 +1,28 @@
+// A temporary directory that is deleted again when it is dropped, like the one in the tests of
+// part 19. It is only meant for tests. The unit tests use it as `temp_dir::TempDir`, and the
+// integration tests in `tests/` include this file with `#[path]`, so it does not have to be part
+// of the public API.
+use std::{env, fs, process};
+use std::path::{Path, PathBuf};
+
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    // Every test needs its own `name`, since the tests run in parallel.
+    pub fn new(name: &str) -> TempDir {
+        let dir = env::temp_dir().join(format!("rust-101-solutions-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
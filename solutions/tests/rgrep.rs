@@ -0,0 +1,183 @@
+// Integration tests for rgrep. They write some files into a temporary directory, search them, and
+// check what comes out - both through the library and by running the actual program. Unlike the
+// unit tests in `src/rgrep.rs`, they only use what other crates can see, so they keep working no
+// matter how the inside of rgrep changes.
+extern crate solutions;
+
+use std::fs;
+use std::process::Command;
+use solutions::rgrep::{count_matches, run, Match, Options};
+use temp_dir::TempDir;
+
+// The helper of the unit tests, which is not part of the public API of the crate.
+#[path = "../src/temp_dir.rs"]
+mod temp_dir;
+
+// A temporary directory with some files in it. It is removed when the fixture is dropped.
+struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    // Every test needs its own `name`, since the tests run in parallel.
+    fn new(name: &str) -> Fixture {
+        let dir = TempDir::new(&format!("rgrep-it-{}", name));
+        let files = [
+            ("fruits.txt", "apple\nBanana\ncherry\napple pie\n"),
+            ("vegetables.txt", "carrot\npineapple\nbean\n"),
+            ("sub/notes.txt", "An apple a day\nkeeps the doctor away\n"),
+        ];
+        for &(name, text) in files.iter() {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        Fixture { dir }
+    }
+
+    // The full path of the file `name` in the fixture.
+    fn path(&self, name: &str) -> String {
+        self.dir.path().join(name).to_string_lossy().into_owned()
+    }
+
+    // Run the rgrep program in the fixture directory, and return its exit status and output.
+    // Default options from the environment would change the results, so we remove them.
+    fn rgrep(&self, args: &[&str]) -> (i32, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_rgrep"))
+            .args(args)
+            .current_dir(self.dir.path())
+            .env_remove("RGREP_OPTS")
+            .output()
+            .unwrap();
+        (output.status.code().unwrap(), String::from_utf8(output.stdout).unwrap())
+    }
+
+    // Search through the library, with the fixture files given by name. Like for the program,
+    // `RGREP_OPTS` does not matter: `Options::from_args` does not look at it.
+    fn run(&self, args: &[&str], files: &[&str]) -> Vec<Match> {
+        let argv = Some("rgrep").iter().chain(args.iter()).map(|arg| arg.to_string())
+            .chain(files.iter().map(|file| self.path(file)))
+            .collect();
+        run(Options::from_args(argv).unwrap()).unwrap()
+    }
+}
+
+#[test]
+fn test_print() {
+    let fixture = Fixture::new("print");
+    assert_eq!(fixture.rgrep(&["apple", "fruits.txt", "vegetables.txt"]),
+               (0, "fruits.txt:0: apple\nfruits.txt:3: apple pie\nvegetables.txt:1: pineapple\n"
+                       .to_string()));
+    // Directories are searched recursively, in the order of the file names.
+    assert_eq!(fixture.rgrep(&["-i", "an", "."]),
+               (0, "./fruits.txt:1: Banana\n./sub/notes.txt:0: An apple a day\n\
+                    ./vegetables.txt:2: bean\n".to_string()));
+    assert_eq!(fixture.rgrep(&["-A", "1", "cherry", "fruits.txt"]),
+               (0, "fruits.txt:2: cherry\nfruits.txt-3- apple pie\n".to_string()));
+}
+
+#[test]
+fn test_count() {
+    let fixture = Fixture::new("count");
+    assert_eq!(fixture.rgrep(&["-c", "apple", "."]), (0, "4 hits for apple.\n".to_string()));
+    assert_eq!(fixture.rgrep(&["-c", "-e", "bean", "-e", "carrot", "."]),
+               (0, "2 hits for bean or carrot.\n".to_string()));
+    // No matches means exit status 1, but the count is still printed.
+    assert_eq!(fixture.rgrep(&["-c", "kiwi", "."]), (1, "0 hits for kiwi.\n".to_string()));
+    // The library agrees, with any number of readers.
+    for &readers in [1, 3].iter() {
+        assert_eq!(count_matches("apple", vec![fixture.path(".")], readers).unwrap(), 4);
+    }
+    // `--count-distinct` also tells how many of the lines are different.
+    fs::write(fixture.path("more.txt"), "apple\nbean\napple\n").unwrap();
+    assert_eq!(fixture.rgrep(&["--count-distinct", "-w", "apple", "."]),
+               (0, "5 hits for apple, 3 of them different.\n".to_string()));
+}
+
+#[test]
+fn test_sort() {
+    let fixture = Fixture::new("sort");
+    assert_eq!(fixture.rgrep(&["-s", "a", "fruits.txt", "vegetables.txt"]),
+               (0, "fruits.txt:1: Banana\nfruits.txt:0: apple\nfruits.txt:3: apple pie\n\
+                    vegetables.txt:2: bean\nvegetables.txt:0: carrot\n\
+                    vegetables.txt:1: pineapple\n".to_string()));
+    // Several readers do not change the result.
+    assert_eq!(fixture.rgrep(&["-s", "-j", "3", "e", "."]),
+               fixture.rgrep(&["-s", "e", "."]));
+    assert_eq!(fixture.rgrep(&["-s", "--unique", "--unique-count", "-w", "-i", "an",
+                               "fruits.txt", "sub"]),
+               (0, "      1 An apple a day\n".to_string()));
+}
+
+#[test]
+fn test_invert() {
+    let fixture = Fixture::new("invert");
+    assert_eq!(fixture.rgrep(&["-v", "a", "fruits.txt", "sub/notes.txt"]),
+               (0, "fruits.txt:2: cherry\n".to_string()));
+    assert_eq!(fixture.rgrep(&["-v", "-c", "e", "vegetables.txt"]),
+               (0, "1 lines without e.\n".to_string()));
+    // If every line matches, there is nothing left.
+    assert_eq!(fixture.rgrep(&["-v", "", "fruits.txt"]), (1, String::new()));
+}
+
+#[test]
+fn test_regex() {
+    let fixture = Fixture::new("regex");
+    assert_eq!(fixture.rgrep(&["-r", "^[bc]", "fruits.txt", "vegetables.txt"]),
+               (0, "fruits.txt:2: cherry\nvegetables.txt:0: carrot\nvegetables.txt:2: bean\n"
+                       .to_string()));
+    assert_eq!(fixture.rgrep(&["-r", "-i", "-x", "b.*a", "."]),
+               (0, "./fruits.txt:1: Banana\n".to_string()));
+    assert_eq!(fixture.rgrep(&["-r", "-o", r"\bap\w+", "sub"]),
+               (0, "sub/notes.txt:0: apple\n".to_string()));
+    assert_eq!(fixture.rgrep(&["-r", "--replace", "<$1>", r"(\w+) pie", "fruits.txt"]),
+               (0, "fruits.txt:3: <apple>\n".to_string()));
+    // An invalid regex is an error of the command line.
+    assert_eq!(fixture.rgrep(&["-r", "(", "fruits.txt"]).0, 2);
+}
+
+#[test]
+fn test_library() {
+    let fixture = Fixture::new("library");
+    let found = |file: &str, line_no, offset, text: &str| Match {
+        file: fixture.path(file), line_no, text: text.to_string(), offset, context: false,
+        ranges: Vec::new()
+    };
+    assert_eq!(fixture.run(&["-w", "apple"], &["fruits.txt", "sub"]),
+               vec![found("fruits.txt", 0, 0, "apple"), found("fruits.txt", 3, 20, "apple pie"),
+                    found("sub/notes.txt", 0, 0, "An apple a day")]);
+    // The output options do not matter, the library always returns the lines.
+    assert_eq!(fixture.run(&["-c", "-v", "-r", "[aeiou]"], &["vegetables.txt"]), vec![]);
+    let matches = fixture.run(&["--json", "cherry"], &["fruits.txt"]);
+    assert_eq!(matches, vec![found("fruits.txt", 2, 13, "cherry")]);
+    assert_eq!(matches[0].to_string(), format!("{}:2: cherry", fixture.path("fruits.txt")));
+    // Options can also be built without a command line.
+    let matches = run(Options::new("-", vec![fixture.path("."), fixture.path("missing")]));
+    assert_eq!(matches.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(run(Options::new("carrot", vec![fixture.path("vegetables.txt")])).unwrap(),
+               vec![found("vegetables.txt", 0, 0, "carrot")]);
+    let options = Options::new("NA$", vec![fixture.path("fruits.txt")]).ignore_case().regex();
+    assert_eq!(run(options.unwrap()).unwrap(), vec![found("fruits.txt", 1, 6, "Banana")]);
+    let options = Options::new("apple", vec![fixture.path("fruits.txt")])
+        .word_regexp().invert_match().context(0, 1).max_count(1);
+    assert_eq!(run(options).unwrap(),
+               vec![found("fruits.txt", 1, 6, "Banana"),
+                    Match { context: true, ..found("fruits.txt", 2, 13, "cherry") }]);
+    let options = Options::new("p", vec![fixture.path("fruits.txt")]).line_regexp().regex();
+    assert_eq!(run(options.unwrap()).unwrap(), vec![]);
+    let options = Options::new("an", vec![fixture.path("fruits.txt")]).only_matching();
+    assert_eq!(run(options).unwrap()[0].ranges, vec![1..3, 3..5]);
+    assert!(Options::new("(", vec![]).regex().is_err());
+    // Invalid command lines are errors, not the end of the program.
+    assert!(Options::from_args(vec!["rgrep".to_string(), "--json".to_string()]).is_err());
+}
+
+#[test]
+fn test_errors() {
+    let fixture = Fixture::new("errors");
+    // A file that is not there is an error, even if there are matches in the others. rgrep prints
+    // what `run` returns, so then there is no output at all.
+    assert_eq!(fixture.rgrep(&["apple", "fruits.txt", "missing.txt"]), (2, String::new()));
+    // So is a command line without files.
+    assert_eq!(fixture.rgrep(&["apple"]), (2, String::new()));
+}
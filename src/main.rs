@@ -85,6 +85,35 @@
 // * [Part 15: Mutex, Interior Mutability (cont.), RwLock, Sync](part15.html)
 // * [Part 16: Unsafe Rust, Drop](part16.html)
 // 
+// ### Further Topics
+// 
+// * [Part 17: Cow, Borrow-friendly APIs](part17.html)
+// * [Part 18: Networking, a Chat Server](part18.html)
+// * [Part 19: Paths, Walking the File System](part19.html)
+// * [Part 20: Iterator Adapters](part20.html)
+// * [Part 21: Advanced Pattern Matching](part21.html)
+// * [Part 22: Crates, Modules, Publishing](part22.html)
+// * [Part 23: Testing](part23.html)
+// * [Part 24: Const Generics, Fixed-Size Arrays](part24.html)
+// * [Part 25: Trait Objects, Downcasting](part25.html)
+// * [Part 26: Drop Order, ManuallyDrop, Leaks](part26.html)
+// * [Part 27: PhantomData, Variance](part27.html)
+// * [Part 28: Building Cell and RefCell](part28.html)
+// * [Part 29: Arenas](part29.html)
+// * [Part 30: Binary Search Trees](part30.html)
+// * [Part 31: Graphs with Indices](part31.html)
+// * [Part 32: A Calculator](part32.html)
+// * [Part 33: A Lexer without Allocation](part33.html)
+// * [Part 34: Processes, a Mini Shell](part34.html)
+// * [Part 35: Time, a Scheduler](part35.html)
+// * [Part 36: Closures Desugared](part36.html)
+// * [Part 37: Weak References, Breaking Cycles](part37.html)
+// * [Part 38: Trees with Rc and RefCell](part38.html)
+// * [Part 39: Advanced Lifetimes](part39.html)
+// * [Part 40: Implementing Send and Sync](part40.html)
+// * [Part 41: Project: Parallel File Hashing](part41.html)
+// * [Part 42: Project: A Key-Value Store](part42.html)
+// 
 #![allow(dead_code, unused_imports, unused_variables, unused_mut, unreachable_code)]
 mod part00;
 mod part01;
@@ -103,6 +132,32 @@ mod part13;
 mod part14;
 mod part15;
 mod part16;
+mod part17;
+mod part18;
+mod part19;
+mod part20;
+mod part21;
+mod part22;
+mod part23;
+mod part24;
+mod part25;
+mod part26;
+mod part27;
+mod part28;
+mod part29;
+mod part30;
+mod part31;
+mod part32;
+mod part33;
+mod part34;
+mod part35;
+mod part36;
+mod part37;
+mod part38;
+mod part39;
+mod part40;
+mod part41;
+mod part42;
 
 // To actually run the code of some part (after filling in the blanks, if necessary), simply edit
 // the `main` function.
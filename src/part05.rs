@@ -18,12 +18,13 @@
 
 //@ To write this down in Rust, we use a `struct`, which is a lot like structs in C:
 //@ Just a bunch of named fields. Every field can be private to the current module (which is the
-//@ default), or public (which is indicated by a `pub` in front of the name). For the sake of the
-//@ tutorial, we make `data` public - otherwise, the next parts of this course could not work on
-//@ `BigInt`s. Of course, in a real program, one would make the field private to ensure that the
-//@ invariant (no trailing zeros) is maintained.
+//@ default), or public (which is indicated by a `pub` in front of the name). We keep `data`
+//@ private: If other modules could change it, they could break the invariant (no trailing zeros),
+//@ and all the functions relying on it would compute nonsense. Instead, we will provide functions
+//@ to look at the digits, and the only way to make a number out of a vector will be `from_vec`,
+//@ which establishes the invariant.
 pub struct BigInt {
-    pub data: Vec<u64>, // least significant digit first, no trailing zeros
+    data: Vec<u64>, // least significant digit first, no trailing zeros
 }
 
 // Now that we fixed the data representation, we can start implementing methods on it.
@@ -61,6 +62,19 @@ impl BigInt {
     pub fn from_vec(mut v: Vec<u64>) -> Self {
         unimplemented!()
     }
+
+    // These functions give other modules read access to the digits.
+    //@ The type `&[u64]` is a *slice*: A shared reference to a bunch of `u64` that are stored next
+    //@ to each other in memory - like the content of a vector. Slices can be indexed and iterated
+    //@ over like vectors, but there is no way to change their length. Rust automatically turns
+    //@ `&self.data` into a slice. We will learn more about slices in part 14.
+    pub fn digits(&self) -> &[u64] {
+        &self.data
+    }
+
+    pub fn num_digits(&self) -> usize {
+        self.data.len()                                             /*@*/
+    }
 }
 
 // ## Cloning
@@ -122,7 +136,7 @@ impl<T: Clone> Clone for SomethingOrNothing<T> {
 //@ Now that we know how to create references to contents of an `enum` (like `v` above), there's
 //@ another example we can look at for why we have to rule out mutation in the presence of
 //@ aliasing. First, we define an `enum` that can hold either a number, or a string.
-enum Variant {
+pub enum Variant {
     Number(i32),
     Text(String),
 }
@@ -15,9 +15,9 @@ impl BigInt {
         debug_assert!(self.test_invariant() && other.test_invariant());
         // Now our assumption of having no trailing zeros comes in handy:
         // If the lengths of the two numbers differ, we already know which is larger.
-        if self.data.len() < other.data.len() {
+        if self.num_digits() < other.num_digits() {
             self
-        } else if self.data.len() > other.data.len() {
+        } else if self.num_digits() > other.num_digits() {
             other
         } else {
             // **Exercise 06.1**: Fill in this code.
@@ -59,7 +59,7 @@ impl PartialEq for BigInt {
     #[inline]
     fn eq(&self, other: &BigInt) -> bool {
         debug_assert!(self.test_invariant() && other.test_invariant());
-        self.data == other.data                                     /*@*/
+        self.digits() == other.digits()                             /*@*/
     }
 }
 
@@ -128,11 +128,11 @@ fn test_min() {
 // explain all the details, and refer you to the documentation instead.
 use std::fmt;
 
-//@ In the case of `BigInt`, we'd like to just output our internal `data` array, so we
-//@ simply call the formating function of `Vec<u64>`.
+//@ In the case of `BigInt`, we'd like to just output our digits, so we simply call the
+//@ formating function of `[u64]`.
 impl fmt::Debug for BigInt {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.data.fmt(f)
+        self.digits().fmt(f)
     }
 }
 //@ `Debug` implementations can be automatically generated using the `derive(Debug)` attribute.
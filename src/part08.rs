@@ -83,12 +83,12 @@ impl ops::Add<BigInt> for BigInt {
     fn add(self, rhs: BigInt) -> Self::Output {
         // We know that the result will be *at least* as long as the longer of the two operands,
         // so we can create a vector with sufficient capacity to avoid expensive reallocations.
-        let max_len = cmp::max(self.data.len(), rhs.data.len());
+        let max_len = cmp::max(self.num_digits(), rhs.num_digits());
         let mut result_vec:Vec<u64> = Vec::with_capacity(max_len);
         let mut carry = false; /* the current carry bit */
         for i in 0..max_len {
-            let lhs_val = if i < self.data.len() { self.data[i] } else { 0 };
-            let rhs_val = if i < rhs.data.len() { rhs.data[i] } else { 0 };
+            let lhs_val = if i < self.num_digits() { self.digits()[i] } else { 0 };
+            let rhs_val = if i < rhs.num_digits() { rhs.digits()[i] } else { 0 };
             // Compute next digit and carry. Then, store the digit for the result, and the carry
             // for later.
             //@ Notice how we can obtain names for the two components of the pair that
@@ -101,7 +101,7 @@ impl ops::Add<BigInt> for BigInt {
         if carry {                                                              /*@@*/
             result_vec.push(1);                                                 /*@@*/
         }                                                                       /*@@*/
-        BigInt { data: result_vec }                                             /*@@*/
+        BigInt::from_vec(result_vec)                                            /*@@*/
     }
 }
 
@@ -45,11 +45,25 @@ impl<'a> Iterator for Iter<'a> {
         } else {
             // Otherwise: Decrement, and return next digit.
             self.idx = self.idx - 1;                                /*@*/
-            Some(self.num.data[self.idx])                           /*@*/
+            Some(self.num.digits()[self.idx])                       /*@*/
         }
     }
+
+    //@ `Iterator` has many more methods, but they all come with a default implementation that only
+    //@ uses `next`. One of them, `size_hint`, is worth overriding: It returns a lower and an
+    //@ optional upper bound on the number of items that are left. Functions like `collect` use the
+    //@ hint to allocate the right amount of memory up-front. By default, it returns `(0, None)`,
+    //@ which is always correct, but not very helpful. We know exactly how many digits are left.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.idx, Some(self.idx))                                  /*@*/
+    }
 }
 
+//@ When the hint is exact, we can say so by implementing
+//@ [`ExactSizeIterator`](https://doc.rust-lang.org/stable/std/iter/trait.ExactSizeIterator.html).
+//@ There is nothing to implement: The trait provides `len`, which is computed from `size_hint`.
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
 // All we need now is a function that creates such an iterator for a given `BigInt`.
 impl BigInt {
     //@ Notice that when we write the type of `iter`, we don't actually have to give the lifetime
@@ -57,7 +71,7 @@ impl BigInt {
     //@ elide the lifetime. The rules for adding the lifetimes are exactly the same. (See the last
     //@ section of [part 06](part06.html).)
     fn iter(&self) -> Iter {
-        Iter { num: self, idx: self.data.len() }                    /*@*/
+        Iter { num: self, idx: self.num_digits() }                  /*@*/
     }
 }
 
@@ -30,6 +30,15 @@ pub struct Options {
     pub files: Vec<String>,
     pub pattern: String,
     pub output_mode: OutputMode,
+    // Whether to print the byte offset of every line within its file.
+    pub byte_offset: bool,
+}
+
+// The threads do not just pass the text of the lines around: To print byte offsets, we also need
+// to know where in the file the line started.
+pub struct Line {
+    pub data: String,
+    pub offset: usize,
 }
 
 //@ Now we can write three functions to do the actual job of reading, matching, and printing,
@@ -42,21 +51,40 @@ pub struct Options {
 //@ channels, and the output not being fast enough to keep up with the speed of input.
 //@
 //@ We also need all the threads to have access to the options of the job they are supposed to do.
-//@ Since it would be rather unnecessary to actually copy these options around, we will use
-//@ reference-counting to share them between all threads. `Arc` is the thread-safe version of `Rc`,
-//@ using atomic operations to keep the reference count up-to-date.
+//@ Since it would be rather unnecessary to actually copy these options around, the threads just
+//@ borrow them: All three functions take a shared reference `&Options`. We will see below how such
+//@ a reference can be handed to another thread.
 
 // The first function reads the files, and sends every line over the `out_channel`.
-fn read_files(options: Arc<Options>, out_channel: SyncSender<String>) {
+fn read_files(options: &Options, out_channel: SyncSender<Line>) {
     for file in options.files.iter() {
         // First, we open the file, ignoring any errors.
         let file = fs::File::open(file).unwrap();
-        // Then we obtain a `BufReader` for it, which provides the `lines` function.
-        let file = io::BufReader::new(file);
-        for line in file.lines() {
-            let line = line.unwrap();
+        // Then we obtain a `BufReader` for it, which provides the `read_until` function.
+        let mut file = io::BufReader::new(file);
+        let mut offset = 0;
+        loop {
+            //@ `read_until` appends the bytes up to and including the next `\n` to the given
+            //@ vector, and returns how many bytes it read. Unlike `lines`, it keeps the line
+            //@ terminator, so we know the exact length of the line in the file - even if it ended
+            //@ in "\r\n".
+            let mut bytes = Vec::new();
+            let len = file.read_until(b'\n', &mut bytes).unwrap();
+            // At the end of the file, there is nothing left to read.
+            if len == 0 {
+                break;
+            }
+            //@ We read bytes rather than a `String` because files are not always valid UTF-8, and
+            //@ we don't want the thread to panic on the first file in another encoding.
+            //@ `from_utf8_lossy` replaces whatever is invalid by the character U+FFFD. It returns a
+            //@ `Cow<str>`, which borrows from `bytes` if nothing had to be replaced; `into_owned`
+            //@ gives us a `String` either way.
+            let mut data = String::from_utf8_lossy(&bytes).into_owned();
+            let trimmed_len = data.trim_end_matches(&['\n', '\r'][..]).len();
+            data.truncate(trimmed_len);
             // Now we send the line over the channel, ignoring the possibility of `send` failing.
-            out_channel.send(line).unwrap();
+            out_channel.send(Line { data, offset }).unwrap();
+            offset += len;
         }
     }
     // When we drop the `out_channel`, it will be closed, which the other end can notice.
@@ -64,15 +92,15 @@ fn read_files(options: Arc<Options>, out_channel: SyncSender<String>) {
 
 // The second function filters the lines it receives through `in_channel` with the pattern, and sends
 // matches via `out_channel`.
-fn filter_lines(options: Arc<Options>,
-                in_channel: Receiver<String>,
-                out_channel: SyncSender<String>) {
+fn filter_lines(options: &Options,
+                in_channel: Receiver<Line>,
+                out_channel: SyncSender<Line>) {
     // We can simply iterate over the channel, which will stop when the channel is closed.
     for line in in_channel.iter() {
         // `contains` works on lots of types of patterns, but in particular, we can use it to test
         // whether one string is contained in another. This is another example of Rust using traits
         // as substitute for overloading.
-        if line.contains(&options.pattern) {
+        if line.data.contains(&options.pattern) {
             out_channel.send(line).unwrap();                        /*@*/
         }
     }
@@ -80,12 +108,16 @@ fn filter_lines(options: Arc<Options>,
 
 // The third function performs the output operations, receiving the relevant lines on its
 // `in_channel`.
-fn output_lines(options: Arc<Options>, in_channel: Receiver<String>) {
+fn output_lines(options: &Options, in_channel: Receiver<Line>) {
     match options.output_mode {
         Print => {
-            // Here, we just print every line we see.
+            // Here, we just print every line we see, with its offset if requested.
             for line in in_channel.iter() {
-                println!("{}", line);                               /*@*/
+                if options.byte_offset {                            /*@*/
+                    println!("{}:{}", line.offset, line.data);      /*@*/
+                } else {                                            /*@*/
+                    println!("{}", line.data);                      /*@*/
+                }                                                   /*@*/
             }
         },
         Count => {
@@ -97,7 +129,7 @@ fn output_lines(options: Arc<Options>, in_channel: Receiver<String>) {
         SortAndPrint => {
             // We are asked to sort the matching lines before printing. So let's collect them all
             // in a local vector...
-            let mut data: Vec<String> = in_channel.iter().collect();
+            let mut data: Vec<Line> = in_channel.iter().collect();
             // ...and implement the actual sorting later.
             unimplemented!()
         }
@@ -107,30 +139,61 @@ fn output_lines(options: Arc<Options>, in_channel: Receiver<String>) {
 // With the operations of the three threads defined, we can now implement a function that performs
 // grepping according to some given options.
 pub fn run(options: Options) {
-    // We move the `options` into an `Arc`, as that's what the thread workers expect.
-    let options = Arc::new(options);
-
     // This sets up the channels. We use a `sync_channel` with buffer-size of 16 to avoid needlessly
     // filling RAM.
     let (line_sender, line_receiver) = sync_channel(16);
     let (filtered_sender, filtered_receiver) = sync_channel(16);
 
+    // `thread::scope` calls the closure with a scope `s`, and `s.spawn` takes a closure that is run
+    // in a new thread.
+    //@ The threads of a scope may borrow local variables like `options`: Before `thread::scope`
+    //@ returns, it waits for all the threads spawned in there to finish. So no thread can outlive
+    //@ `options`.
+    thread::scope(|s| {
+        //@ The `move` keyword again tells Rust that we want ownership of captured variables to be
+        //@ moved into the closure. We need that for the channels, every thread should own its end.
+        //@ But we don't want to lose our `options` to the first thread! So we move a *reference*
+        //@ into the closures instead. Shared references are `Copy`, so every thread gets its own.
+        let options = &options;
+        // Spawn the read thread.
+        s.spawn(move || read_files(options, line_sender));
+        // Same with the filter thread.
+        s.spawn(move || filter_lines(options, line_receiver, filtered_sender));
+        // And the output thread.
+        s.spawn(move || output_lines(options, filtered_receiver));
+        //@ At the end of the scope, all three threads are joined, i.e., we wait until they did
+        //@ their job. If one of them panicked, `thread::scope` panics as well.
+    });
+}
+
+//@ Scoped threads are fairly new to Rust. Before, the way to share data between threads was to use
+//@ *reference-counting*, and you will still come across that a lot. `Arc` is the thread-safe
+//@ version of `Rc`, using atomic operations to keep the reference count up-to-date. For comparison,
+//@ here is `run` once more, with threads that are not bound to a scope.
+pub fn run_with_arc(options: Options) {
+    // We move the `options` into an `Arc`, so that all the threads can own it.
+    let options = Arc::new(options);
+
+    let (line_sender, line_receiver) = sync_channel(16);
+    let (filtered_sender, filtered_receiver) = sync_channel(16);
+
     // Spawn the read thread: `thread::spawn` takes a closure that is run in a new thread.
-    //@ The `move` keyword again tells Rust that we want ownership of captured variables to be
-    //@ moved into the closure. This means we need to do the `clone` *first*, otherwise we would
-    //@ lose our `options` to the new thread!
+    //@ This time, the closure has to own everything it uses (we will see why below). So every
+    //@ thread gets a clone of the `Arc`, and we need to do the `clone` *first*, otherwise we would
+    //@ lose our `options` to the new thread! A `&Arc<Options>` can be used as a `&Options`, so the
+    //@ functions doing the work don't care how the options are shared.
     let options1 = options.clone();
-    let handle1 = thread::spawn(move || read_files(options1, line_sender));
+    let handle1 = thread::spawn(move || read_files(&options1, line_sender));
 
     // Same with the filter thread.
     let options2 = options.clone();
     let handle2 = thread::spawn(move || {
-        filter_lines(options2, line_receiver, filtered_sender)
+        filter_lines(&options2, line_receiver, filtered_sender)
     });
 
     // And the output thread.
     let options3 = options.clone();
-    let handle3 = thread::spawn(move || output_lines(options3, filtered_receiver));
+    let handle3 = thread::spawn(move || output_lines(&options3, filtered_receiver));
 
     // Finally, wait until all three threads did their job.
     //@ Joining a thread waits for its termination. This can fail if that thread panicked: In this
@@ -149,14 +212,15 @@ pub fn main() {
                     "src/part11.rs".to_string(),
                     "src/part12.rs".to_string()],
         pattern: "let".to_string(),
-        output_mode: Print
+        output_mode: Print,
+        byte_offset: false,
     };
     run(options);
 }
 
-// **Exercise 13.1**: Change rgrep such that it prints not only the matching lines, but also the
-// name of the file and the number of the line in the file. You will have to change the type of the
-// channels from `String` to something that records this extra information.
+// **Exercise 13.1**: Change rgrep such that it prints not only the matching lines (and their
+// offset), but also the name of the file and the number of the line in the file. You will have to
+// add fields to `Line` to record this extra information.
 
 //@ ## Ownership, Borrowing, and Concurrency
 //@ The little demo above showed that concurrency in Rust has a fairly simple API. Considering Rust
@@ -167,7 +231,7 @@ pub fn main() {
 //@ the presence of aliasing, which Rust reliably rules out! It turns out that the same mechanism
 //@ that makes our single-threaded programs memory safe, and that prevents us from invalidating
 //@ iterators, also helps secure our multi-threaded code against data races. For example, notice
-//@ how `read_files` sends a `String` to `filter_lines`.
+//@ how `read_files` sends a `Line`, and with it a `String`, to `filter_lines`.
 //@ At run-time, only the pointer to the character data will actually be moved around (just like
 //@ when a `String` is passed to a function with full ownership). However, `read_files` has to
 //@ *give up* ownership of the string to perform `send`, to it is impossible for the string to
@@ -183,7 +247,14 @@ pub fn main() {
 //@ [its documentation](https://doc.rust-lang.org/stable/std/thread/fn.spawn.html). This avoids
 //@ another kind of data race, where the thread's access races with the callee deallocating its
 //@ stack frame. It is only thanks to the concept of lifetimes that this can be expressed as part
-//@ of the type of `spawn`.
+//@ of the type of `spawn`. That's why `run_with_arc` needs the `Arc`.
+//@
+//@ `thread::scope` turns this around: The threads of a scope are guaranteed to be done when the
+//@ scope ends, so the closures only have to outlive the scope, not `'static`. And the type of
+//@ [`Scope::spawn`](https://doc.rust-lang.org/stable/std/thread/struct.Scope.html#method.spawn)
+//@ says exactly that, using a lifetime `'scope` instead of `'static`. Borrowing `options` is thus
+//@ fine, as long as `options` lives longer than the scope - and if we tried to spawn a thread that
+//@ borrows a variable declared *inside* the closure, Rust would complain.
 
 //@ ## Send
 //@ However, the story goes even further. I said above that `Arc` is a thread-safe version of `Rc`,
@@ -208,5 +279,46 @@ pub fn main() {
 //@ however every captured variable *is* `Send`, then so is the entire environment, and you are
 //@ good.
 
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread};
+    use std::sync::mpsc::sync_channel;
+    use part19::tests::TempDir;
+    use super::*;
+
+    // Run the reading and filtering threads of `run`, and collect what would be sent to the output
+    // thread, as pairs of offset and text.
+    fn matching_lines(options: &Options) -> Vec<(usize, String)> {
+        let (line_sender, line_receiver) = sync_channel(16);
+        let (filtered_sender, filtered_receiver) = sync_channel(16);
+        thread::scope(|s| {
+            s.spawn(move || read_files(options, line_sender));
+            s.spawn(move || filter_lines(options, line_receiver, filtered_sender));
+            filtered_receiver.iter().map(|line| (line.offset, line.data)).collect()
+        })
+    }
+
+    #[test]
+    fn test_matching_lines() {
+        let tmp = TempDir::new("rgrep-part13");
+        let fruits = tmp.0.join("fruits.txt");
+        let latin1 = tmp.0.join("latin1.txt");
+        // Line endings are not part of the lines, but they count for the offsets.
+        fs::write(&fruits, "apple\r\nbanana\napple pie").unwrap();
+        // Not valid UTF-8: The byte is replaced when reading.
+        fs::write(&latin1, b"caf\xe9 apple\n").unwrap();
+        let options = Options {
+            files: vec![fruits.to_string_lossy().into_owned(),
+                        latin1.to_string_lossy().into_owned()],
+            pattern: "apple".to_string(),
+            output_mode: Print,
+            byte_offset: true,
+        };
+        assert_eq!(matching_lines(&options),
+                   vec![(0, "apple".to_string()), (14, "apple pie".to_string()),
+                        (0, "caf\u{fffd} apple".to_string())]);
+    }
+}
+
 //@ [index](main.html) | [previous](part12.html) | [raw source](workspace/src/part13.rs) |
 //@ [next](part14.html)
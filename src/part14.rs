@@ -54,10 +54,10 @@ pub fn sort<T: PartialOrd>(data: &mut [T]) {
     sort(part2);                                                    /*@*/
 }
 
-// **Exercise 14.2**: Since `String` implements `PartialEq`, you can now change the function
-// `output_lines` in the previous part to call the sort function above. If you did exercise 13.1,
-// you will have slightly more work. Make sure you sort by the matched line only, not by filename
-// or line number!
+// **Exercise 14.2**: Since `String` implements `PartialOrd`, you can implement `PartialOrd` for
+// `Line` from the previous part, and then change the function `output_lines` to call the sort
+// function above. Make sure you sort by the matched line only, not by offset (or, if you did
+// exercise 13.1, by filename or line number)!
 
 // Now, we can sort, e.g., an vector of numbers.
 fn sort_nums(data: &mut Vec<i32>) {
@@ -92,9 +92,9 @@ fn sort_array() {
 //@ This tooling is provided by `cargo`, the tool we are already using to build this tutorial.
 //@ (`cargo` also has support for *publishing* your crate on crates.io, I refer you to [the
 //@ documentation](http://doc.crates.io/crates-io.html) for more details.)
-//@ In this case, we are going to use the [`docopt` crate](https://crates.io/crates/docopt), which
-//@ creates a parser for command-line arguments based on the usage string. External dependencies
-//@ are declared in the `Cargo.toml` file.
+//@ In this case, we are going to use the [`clap` crate](https://crates.io/crates/clap), which
+//@ creates a parser for command-line arguments based on a struct describing them. External
+//@ dependencies are declared in the `Cargo.toml` file.
 
 //@ I already prepared that file, but the declaration of the dependency is still commented out. So
 //@ please open `Cargo.toml` of your workspace now, and enable the two commented-out lines. Then do
@@ -105,69 +105,95 @@ fn sort_array() {
 //@ of a git repository or some local path. All of this is explained in the
 //@ [Cargo Guide](http://doc.crates.io/guide.html).
 
-// I disabled the following module (using a rather bad hack), because it only compiles if `docopt`
-// is linked. Remove the attribute of the `rgrep` module to enable compilation.
+// I disabled the following module (using a rather bad hack), because it only compiles if `clap` is
+// linked. Remove the attribute of the `rgrep` module to enable compilation.
 #[cfg(feature = "disabled")]
 pub mod rgrep {
-    // Now that `docopt` is linked, we can first add it to the namespace with `extern crate` and
-    // then import shorter names with `use`. We also import some other pieces that we will need.
-    extern crate docopt;
-    use self::docopt::Docopt;
+    // Now that `clap` is linked, we can first add it to the namespace with `extern crate` and then
+    // import shorter names with `use`. We also import some other pieces that we will need.
+    extern crate clap;
+    use self::clap::{CommandFactory, Parser};
+    use self::clap::error::ErrorKind;
     use part13::{run, Options, OutputMode};
-    use std::process;
-
-    // The `USAGE` string documents how the program is to be called. It's written in a format that
-    // `docopt` can parse.
-    static USAGE: &'static str = "
-Usage: rgrep [-c] [-s] <pattern> <file>...
-
-Options:
-    -c, --count  Count number of matching lines (rather than printing them).
-    -s, --sort   Sort the lines before printing.
-";
+    use part19::expand_dirs;
+
+    // The `Args` struct documents how the program is to be called: Every field is one argument.
+    //@ You have already seen `#[derive(...)]` generate implementations of traits like `Clone`.
+    //@ Crates can provide their own derives, and `clap` uses this to write the parser for us. The
+    //@ `#[arg(...)]` attributes tell it that the flags have a short form (`-c`) and a long form
+    //@ (`--count`), and the doc comments become the text printed by `--help`. The fields without
+    //@ such an attribute are positional arguments.
+    #[derive(Parser)]
+    #[command(version, about = "Search files for lines containing a pattern.")]
+    struct Args {
+        /// Count number of matching lines (rather than printing them).
+        #[arg(short, long)]
+        count: bool,
+        /// Sort the lines before printing.
+        #[arg(short, long)]
+        sort: bool,
+        /// Print the byte offset of every line within its file.
+        #[arg(short, long)]
+        byte_offset: bool,
+        /// Search the directories among the files recursively.
+        #[arg(short = 'R', long)]
+        recursive: bool,
+        /// The pattern to search for.
+        pattern: String,
+        /// The files to search.
+        #[arg(required = true)]
+        files: Vec<String>,
+    }
 
     // This function extracts the rgrep options from the command-line arguments.
     fn get_options() -> Options {
-        // This parses `argv` and exit the program with an error message if it fails. The code is
-        // taken from the [`docopt` documentation](http://burntsushi.net/rustdoc/docopt/). <br/>
-        //@ The function `and_then` takes a closure from `T` to `Result<U, E>`, and uses it to
-        //@ transform a `Result<T, E>` to a `Result<U, E>`. This way, we can chain computations
-        //@ that only happen if the previous one succeeded (and the error type has to stay the
-        //@ same). In case you know about monads, this style of programming will be familiar to
-        //@ you.
+        // This parses `argv`, checks that the flags make sense together, and exits the program
+        // with an error message if either fails. It also handles `--help` and `--version` for us.
+        // The code is based on the [`clap` documentation](https://docs.rs/clap/). <br/>
+        //@ `try_parse` returns a `Result<Args, clap::Error>`. The function `and_then` takes a
+        //@ closure from `T` to `Result<U, E>`, and uses it to transform a `Result<T, E>` to a
+        //@ `Result<U, E>`. This way, we can chain computations that only happen if the previous
+        //@ one succeeded (and the error type has to stay the same - which is why our own check
+        //@ creates a `clap::Error`, too). In case you know about monads, this style of
+        //@ programming will be familiar to you.
         //@ There's a similar function for `Option`. `unwrap_or_else` is a bit like `unwrap`, but
         //@ rather than panicking in case of an `Err`, it calls the closure.
-        let args = Docopt::new(USAGE).and_then(|d| d.parse()).unwrap_or_else(|e| e.exit());
-        // Now we can get all the values out.
-        let count = args.get_bool("-c");
-        let sort = args.get_bool("-s");
-        let pattern = args.get_str("<pattern>");
-        let files = args.get_vec("<file>");
-        if count && sort {
-            println!("Setting both '-c' and '-s' at the same time does not make any sense.");
-            process::exit(1);
-        }
-
-        // We need to make the strings owned to construct the `Options` instance.
-        //@ If you check all the types carefully, you will notice that `pattern` above is of type
-        //@ `&str`. `str` is the type of a UTF-8 encoded string, that is, a bunch of bytes in
-        //@ memory (`[u8]`) that are valid according of UTF-8. `str` is unsized. `&str` stores the
-        //@ address of the character data, and their length.
+        let args = Args::try_parse()
+            .and_then(|args| {
+                if args.count && args.sort {
+                    Err(Args::command().error(ErrorKind::ArgumentConflict,
+                        "Setting both '-c' and '-s' at the same time does not make any sense."))
+                } else {
+                    Ok(args)
+                }
+            })
+            .unwrap_or_else(|e| e.exit());
+        // Now we can get all the values out of the fields of `args`. The strings are already owned,
+        // so we can move them into the `Options` instance.
+        //@ If you check all the types carefully, you will notice that the fields of `Args` are of
+        //@ type `String`, which owns a buffer on the heap. In contrast, the help text we passed to
+        //@ `about` is of type `&str`. `str` is the type of a UTF-8 encoded string, that is, a
+        //@ bunch of bytes in memory (`[u8]`) that are valid according of UTF-8. `str` is unsized.
+        //@ `&str` stores the address of the character data, and their length.
         //@ String literals like "this one" are of type `&'static str`: They point right to the
-        //@ constant section of the binary, so  the reference is valid for the entire program. The
-        //@ bytes pointed to by `pattern`, on the other hand, are owned by someone else,  and we
-        //@ call `to_string` on it to copy the string data into a buffer on the heap that we own.
-        let mode = if count {
+        //@ constant section of the binary, so  the reference is valid for the entire program. If
+        //@ we get a `&str` that points to bytes owned by someone else, we can call `to_string` on
+        //@ it to copy the string data into a buffer on the heap that we own.
+        let mode = if args.count {
             OutputMode::Count
-        } else if sort {
+        } else if args.sort {
             OutputMode::SortAndPrint
         } else {
             OutputMode::Print
         };
+        // With `-R`, every directory is replaced by the files it contains. We are going to write
+        // `expand_dirs`, which does this, in part 19.
+        let files = if args.recursive { expand_dirs(args.files) } else { args.files };
         Options {
-            files: files.iter().map(|file| file.to_string()).collect(),
-            pattern: pattern.to_string(),
+            files,
+            pattern: args.pattern,
             output_mode: mode,
+            byte_offset: args.byte_offset,
         }
     }
 
@@ -217,14 +217,17 @@ impl<T> Drop for LinkedList<T> {
 }
 
 // ## The End
-//@ Congratulations! You completed Rust-101. This was the last part of the course. I hope you
-//@ enjoyed it. If you have feedback or want to contribute yourself, please head to the
-//@ [Rust-101](https://www.ralfj.de/projects/rust-101/) website fur further information. The entire
-//@ course is open-source (under [CC-BY-SA 4.0](https://creativecommons.org/licenses/by-sa/4.0/)).
+//@ Congratulations! You completed Rust-101. This was the last part of the main course. I hope you
+//@ enjoyed it. The [following parts](part17.html) take a closer look at some further topics, and
+//@ contain a few more projects to play with. If you have feedback or want to contribute yourself,
+//@ please head to the [Rust-101](https://www.ralfj.de/projects/rust-101/) website fur further
+//@ information. The entire course is open-source (under
+//@ [CC-BY-SA 4.0](https://creativecommons.org/licenses/by-sa/4.0/)).
 //@ 
 //@ If you want to do more, the examples you saw in this course provide lots of playground for
 //@ coming up with your own little extensions here and there. The [index](main.html) contains some
 //@ more links to additional resources you may find useful.
 //@ With that, there's only one thing left to say: Happy Rust Hacking!
 
-//@ [index](main.html) | [previous](part15.html) | [raw source](workspace/src/part16.rs) | next
+//@ [index](main.html) | [previous](part15.html) | [raw source](workspace/src/part16.rs) |
+//@ [next](part17.html)
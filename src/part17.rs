@@ -0,0 +1,184 @@
+// Rust-101, Part 17: Cow, Borrow-friendly APIs
+// ============================================
+
+use std::borrow::Cow;
+use std::io::prelude::*;
+use std::{io, fs};
+
+//@ In part 14, I showed you `&str`, the type of borrowed string slices, and we have been using
+//@ `String` for owned strings all along. Often, the choice between the two is obvious: If a
+//@ function just looks at a string, it takes `&str`. If it needs to keep the string around, it
+//@ takes `String`. In this part, we will look at a situation where the answer is "it depends" -
+//@ namely, "it depends on the data".
+
+// ## Case-insensitive matching
+//@ Let us extend our rgrep with an option to ignore case. The simplest way to do that is to convert
+//@ both the pattern and every line to lower case before we call `contains`. Here's a first attempt
+//@ at the helper function doing the conversion.
+fn fold_case_v1(line: &str) -> String {
+    line.to_lowercase()
+}
+//@ This works, but look at what happens at run-time: Every single line that passes through the
+//@ filter is copied into a fresh `String`, even though the vast majority of lines in a typical
+//@ source file don't contain a single upper-case letter. For those lines, `to_lowercase` makes an
+//@ exact copy of the input, which we then throw away right after calling `contains`. That's a lot
+//@ of allocations for nothing.
+//@
+//@ What we would like to say is: "Return a borrowed `&str` if nothing had to change, and an owned
+//@ `String` otherwise". We already know how to say "this, or that" in Rust: With an `enum`.
+
+// ## `Cow`
+//@ The standard library already provides such an enum, called
+//@ [`Cow`](https://doc.rust-lang.org/stable/std/borrow/enum.Cow.html), which is short for
+//@ "clone-on-write". For strings, it is essentially defined as follows:
+/*
+  enum Cow<'a, str> {
+      Borrowed(&'a str),
+      Owned(String),
+  }
+*/
+//@ (The real definition is generic over the borrowed type, and uses the `ToOwned` trait to figure out
+//@ that the owned version of `str` is `String`.) Notice the lifetime: A `Cow<'a, str>` may borrow
+//@ from something, so it must not outlive that something. Again, this is the same kind of reasoning
+//@ that we did for `head` in part 06.
+
+// With `Cow`, `fold_case` only copies the string when there is actually something to change.
+//@ The `'_` in the return type says that there is a lifetime, but we would like Rust to fill it in
+//@ for us. Just like for `head`, lifetime elision ties it to the lifetime of `line`.
+pub fn fold_case(line: &str) -> Cow<'_, str> {
+    if line.chars().any(|c| c.is_uppercase()) {
+        Cow::Owned(line.to_lowercase())                             /*@*/
+    } else {
+        Cow::Borrowed(line)                                         /*@*/
+    }
+}
+//@ We still traverse the string once to check for upper-case characters, but that's cheap compared
+//@ to asking the allocator for memory, copying the data, and freeing it again.
+
+// ## Using a `Cow`
+//@ So far, so good. But how does the caller work with the result? Do we have to `match` on it every
+//@ time? Luckily not: `Cow<str>` implements `Deref<Target=str>`, so it behaves like a smart pointer
+//@ to a `str`. In particular, we can call all the methods of `str` on it.
+pub fn matches_ignore_case(line: &str, pattern: &str) -> bool {
+    //@ The `&*` first dereferences the `Cow` (yielding a `str`), and then borrows the result. In
+    //@ many places, Rust will insert this automatically, but not for generic arguments like the one
+    //@ of `contains`.
+    fold_case(line).contains(&*fold_case(pattern))                  /*@*/
+}
+//@ If we need ownership after all, `into_owned` turns a `Cow` into a `String`. That is free for
+//@ the `Owned` case, and performs the copy for the `Borrowed` case. There is also `to_mut`, which
+//@ gives us a `&mut String`, making a copy only if the data is still borrowed. That's where the name
+//@ "clone-on-write" comes from: The clone is delayed until the first time we write.
+fn shout(line: &str) -> String {
+    let mut line = fold_case(line);
+    line.to_mut().push('!');
+    line.into_owned()
+}
+
+// ## Measuring the savings
+//@ Of course, we should not just trust my claim that most lines are lower-case already. Let's
+//@ measure! For every line, we check whether `fold_case` had to allocate. This is one of the rare
+//@ cases where we actually look at the variant of a `Cow`.
+// The function returns the number of lines, and the number of lines that had to be copied.
+pub fn count_allocations<'a, I: Iterator<Item=&'a str>>(lines: I) -> (usize, usize) {
+    let mut total = 0;
+    let mut copies = 0;
+    for line in lines {
+        total += 1;
+        if let Cow::Owned(_) = fold_case(line) {                    /*@*/
+            copies += 1;                                            /*@*/
+        }                                                           /*@*/
+    }
+    (total, copies)
+}
+
+// Let us run this on the source files of rgrep. Remember to edit `main.rs` to run this function.
+pub fn main() {
+    for name in ["src/part13.rs", "src/part14.rs"].iter() {
+        let file = io::BufReader::new(fs::File::open(name).unwrap());
+        let lines: Vec<String> = file.lines().map(|l| l.unwrap()).collect();
+        let (total, copies) = count_allocations(lines.iter().map(|l| &l[..]));
+        println!("{}: {} lines, {} of them had to be copied.", name, total, copies);
+    }
+    println!("{}", shout("Cows say moo"));
+}
+//@ For these two files, only about half of the lines needed a copy - and that's with all the
+//@ prose in there, which contains plenty of capital letters. With `fold_case_v1`, we would have
+//@ copied every single line.
+
+// **Exercise 17.1**: Write a function `expand_tabs(line: &str, width: usize) -> Cow<str>` that
+// replaces every tab character by `width` spaces, and only allocates if there actually is a tab.
+// Extend the tests below to check that you get a `Cow::Borrowed` for lines without tabs.
+
+// ## Choosing the argument type
+//@ With `Cow` added to our toolbox, we now have three options for passing strings around. Here's a
+//@ rule of thumb:
+//@
+//@ * Take `&str` if you only need to look at the string. A `&String` is never better than a `&str`,
+//@   since the former can be turned into the latter for free (that's what happens when you pass
+//@   `&line` where a `&str` is expected), but not vice versa.
+//@ * Take `String` if you are going to store the string anyway. Then the caller can decide whether
+//@   to hand over a string they no longer need, or to make a copy. If you took a `&str` instead,
+//@   you would *always* have to copy.
+//@ * Return `Cow` if you *sometimes* have to create a new string, and sometimes can just hand back
+//@   (part of) your input. It is rarely useful to *take* a `Cow` as argument.
+//@
+//@ One more trick: A function that takes `S: Into<String>` can be called with both `&str` and
+//@ `String`, and only copies in the first case.
+pub struct Pattern {
+    text: String,
+}
+impl Pattern {
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Pattern { text: text.into() }
+    }
+}
+
+// **Exercise 17.2**: Consider the following functions. For each of them, decide whether the
+// argument should be `&str`, `String` or `Cow<str>`, and whether the return type should be changed.
+// Then change the code accordingly.
+fn count_words(text: String) -> usize {
+    text.split_whitespace().count()
+}
+fn make_greeting(name: &str) -> String {
+    let mut greeting = "Hello, ".to_string();
+    greeting.push_str(name);
+    greeting
+}
+fn strip_comment(line: String) -> String {
+    match line.find("//") {
+        Some(idx) => line[..idx].to_string(),
+        None => line,
+    }
+}
+
+// **Exercise 17.3**: Error messages are often literals, but sometimes have to be computed. Write a
+// function `describe(n: i32) -> Cow<'static, str>` that returns "zero" and "one" as borrowed
+// literals, and formats all other numbers into an owned string.
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::{fold_case, matches_ignore_case, count_allocations};
+
+    #[test]
+    fn test_fold_case() {
+        // Lines that are already lower-case must not be copied.
+        match fold_case("fn main() {") {
+            Cow::Borrowed(s) => assert_eq!(s, "fn main() {"),
+            Cow::Owned(_) => panic!("fold_case copied a lower-case line"),
+        }
+        assert_eq!(fold_case("Hello World"), "hello world");
+        assert!(matches_ignore_case("let Options = ...", "OPTIONS"));
+        assert!(!matches_ignore_case("let options = ...", "sort"));
+    }
+
+    #[test]
+    fn test_count_allocations() {
+        let lines = vec!["use std::io;", "pub struct Options {", "    pub files: Vec<String>,"];
+        assert_eq!(count_allocations(lines.into_iter()), (3, 2));
+    }
+}
+
+//@ [index](main.html) | [previous](part16.html) | [raw source](workspace/src/part17.rs) |
+//@ [next](part18.html)
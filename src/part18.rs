@@ -0,0 +1,211 @@
+// Rust-101, Part 18: Networking, a Chat Server
+// ============================================
+
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::thread;
+
+//@ In parts 13 and 15, we saw how threads communicate through channels and through shared memory
+//@ protected by a `Mutex`. Let us now put both of these to work in a program that talks to the
+//@ outside world: A small chat server. Clients connect via TCP, and every line of text that one
+//@ client sends is forwarded to all connected clients (including the sender, so with just a single
+//@ client, this is an echo server). You can try it with `nc 127.0.0.1 7878` or `telnet`.
+//@
+//@ The standard library provides all we need in the module
+//@ [`std::net`](https://doc.rust-lang.org/stable/std/net/index.html). A `TcpListener` waits for
+//@ incoming connections, and every connection is represented by a `TcpStream`. A `TcpStream`
+//@ implements `Read` and `Write`, so all the I/O machinery we used for files works for network
+//@ connections as well.
+
+// ## Shared state
+//@ All the threads handling connections need access to the list of connected clients, to be able
+//@ to forward messages to them. Following the recipe of part 15, we put that list into a `Mutex`,
+//@ and the `Mutex` into an `Arc`. Every client gets a numeric `id`, which we use as a key into a
+//@ `HashMap`. This is a map from keys to values, and we will need it to remove a client once it
+//@ disconnects.
+struct Room {
+    next_id: usize,
+    clients: HashMap<usize, TcpStream>,
+}
+
+// Just like `ConcurrentCounter`, `ChatRoom` is a newtype around an `Arc<Mutex<...>>`, so all
+// clones talk about the same room.
+#[derive(Clone)]
+pub struct ChatRoom(Arc<Mutex<Room>>);
+
+impl ChatRoom {
+    pub fn new() -> Self {
+        ChatRoom(Arc::new(Mutex::new(Room { next_id: 0, clients: HashMap::new() })))
+    }
+
+    // Register a new client, and return its id.
+    //@ We want to read from the stream in the connection thread, and write to it whenever any client
+    //@ sends a message. So we need two handles to the same connection. `try_clone` creates such a
+    //@ second handle, and it can fail because this involves asking the operating system.
+    fn join(&self, stream: &TcpStream) -> io::Result<usize> {
+        let stream = stream.try_clone()?;
+        let mut room = self.0.lock().unwrap();
+        let id = room.next_id;                                      /*@*/
+        room.next_id += 1;                                          /*@*/
+        room.clients.insert(id, stream);                            /*@*/
+        Ok(id)                                                      /*@*/
+    }
+
+    // Remove a client from the room. Dropping the stream we stored closes our handle to it.
+    fn leave(&self, id: usize) {
+        self.0.lock().unwrap().clients.remove(&id);                 /*@*/
+    }
+
+    // Send a line to all clients.
+    //@ Writing to a client fails if that client went away in the mean time. We don't want that to
+    //@ affect anybody else, so we just remember the ids of such clients and remove them afterwards.
+    //@ (We can't remove them while iterating - that would be iterator invalidation, and Rust would
+    //@ not let us do it.)
+    fn broadcast(&self, msg: &str) {
+        let mut room = self.0.lock().unwrap();
+        let mut gone = Vec::new();
+        for (id, stream) in room.clients.iter_mut() {
+            if writeln!(stream, "{}", msg).is_err() {               /*@*/
+                gone.push(*id);                                     /*@*/
+            }                                                       /*@*/
+        }
+        for id in gone {
+            room.clients.remove(&id);
+        }
+    }
+}
+
+// ## Handling a connection
+//@ Every connection is handled by its own thread, running `handle_client`. The protocol is
+//@ line-based: We greet the new client, and then wait for it to send lines. Every line is forwarded
+//@ to everybody, prefixed with the id of the sender. The special line `/quit` ends the session.
+//@
+//@ This function returns an `io::Result`, and uses the `?` operator to propagate errors: `e?`
+//@ evaluates to the value inside `e` if it is `Ok`, and *returns* the error from the current
+//@ function otherwise. This is much less noisy than a `match` everywhere - and unlike `unwrap`, a
+//@ client that sends garbage or disconnects at a bad time does not bring down the thread with a
+//@ panic.
+fn handle_client(room: ChatRoom, mut stream: TcpStream) -> io::Result<()> {
+    let id = room.join(&stream)?;
+    writeln!(stream, "Welcome, you are client {}.", id)?;
+    //@ `BufReader` provides the `lines` function, just like it did for files in part 13.
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim() == "/quit" {                                 /*@*/
+            break;                                                  /*@*/
+        }                                                           /*@*/
+        room.broadcast(&format!("[{}] {}", id, line));              /*@*/
+    }
+    room.leave(id);
+    Ok(())
+}
+//@ Notice that if any of the `?` fire, we never call `leave`. That's not a big deal, since the next
+//@ `broadcast` will notice that the client is gone. But it shows that early returns make it easy to
+//@ forget about cleanup. A guard with a `Drop` implementation (like the ones we saw for `RefCell`
+//@ and `Mutex`) would be a more robust way of doing this.
+
+// ## Accepting connections
+//@ The main loop of the server is now straight-forward: `incoming` gives an iterator over all the
+//@ connections that are made to the listener (it never ends), and for each of them, we spawn a new
+//@ thread. If accepting a connection fails, we just ignore it and wait for the next one.
+pub fn serve(listener: TcpListener, room: ChatRoom) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        // As usual, we clone the `ChatRoom` *before* moving it into the closure.
+        let room = room.clone();                                    /*@*/
+        thread::spawn(move || {                                     /*@*/
+            //@ There is nobody we could report the error to, so we just drop it. The `let _ =`
+            //@ tells Rust that we are aware of the `Result`, and are ignoring it on purpose.
+            let _ = handle_client(room, stream);                    /*@*/
+        });                                                         /*@*/
+    }
+}
+
+// Finally, we bind the listener to a local port and start serving.
+pub fn main() {
+    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
+    println!("Listening on {}.", listener.local_addr().unwrap());
+    serve(listener, ChatRoom::new());
+}
+
+//@ ## Discussion
+//@ With one thread per connection, the code reads just like a sequential program. That's nice, but
+//@ it also means that a server with ten thousand clients has ten thousand threads, which is a lot
+//@ of memory for stacks. Larger servers typically use a *thread pool*, or even asynchronous I/O,
+//@ where a few threads juggle many connections. Also, notice that `broadcast` holds the lock while
+//@ writing to the network. A single client that does not read its data can thus block the entire
+//@ room!
+
+// **Exercise 18.1**: Add a command `/nick NAME` that lets clients choose a name, which is then used
+// instead of the numeric id when forwarding their messages. Where do you store the names?
+
+// **Exercise 18.2**: Add a command `/who` that sends the list of connected clients to the client
+// asking for it (and only that client).
+
+// **Exercise 18.3**: Rather than spawning a new thread for every connection, write a small thread
+// pool: A fixed number of worker threads that receive connections through a channel, and handle
+// them one after the other. What happens when more clients connect than there are workers?
+
+#[cfg(test)]
+mod tests {
+    use std::io::prelude::*;
+    use std::io::BufReader;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use super::{serve, ChatRoom};
+
+    // Start a server on a port picked by the operating system, and return its address.
+    fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve(listener, ChatRoom::new()));
+        addr
+    }
+
+    fn read_line(reader: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    }
+
+    #[test]
+    fn test_chat() {
+        let addr = start_server();
+        let mut alice = TcpStream::connect(&addr[..]).unwrap();
+        let mut alice_in = BufReader::new(alice.try_clone().unwrap());
+        assert_eq!(read_line(&mut alice_in), "Welcome, you are client 0.");
+        let bob = TcpStream::connect(&addr[..]).unwrap();
+        let mut bob_in = BufReader::new(bob.try_clone().unwrap());
+        // Once the welcome message arrived, bob is registered in the room.
+        assert_eq!(read_line(&mut bob_in), "Welcome, you are client 1.");
+
+        writeln!(alice, "Hello, Bob!").unwrap();
+        assert_eq!(read_line(&mut alice_in), "[0] Hello, Bob!");
+        assert_eq!(read_line(&mut bob_in), "[0] Hello, Bob!");
+    }
+
+    #[test]
+    fn test_quit() {
+        let addr = start_server();
+        let mut client = TcpStream::connect(&addr[..]).unwrap();
+        let mut client_in = BufReader::new(client.try_clone().unwrap());
+        assert_eq!(read_line(&mut client_in), "Welcome, you are client 0.");
+        writeln!(client, "echo").unwrap();
+        assert_eq!(read_line(&mut client_in), "[0] echo");
+        writeln!(client, "/quit").unwrap();
+        // The server closes the connection, so we read the end of the stream.
+        let mut rest = String::new();
+        client_in.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "");
+    }
+}
+
+//@ [index](main.html) | [previous](part17.html) | [raw source](workspace/src/part18.rs) |
+//@ [next](part19.html)
@@ -0,0 +1,276 @@
+// Rust-101, Part 19: Paths, Walking the File System
+// =================================================
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::collections::HashSet;
+use part13::{run, Options, OutputMode};
+
+//@ So far, our rgrep needs to be told about every single file it should search. Real grep can do
+//@ better: With `-R`, it searches entire directory trees. In this part, we are going to write the
+//@ code that finds all the files in such a tree - twice, in fact.
+
+// ## Paths
+//@ Before we start, we need to talk about how Rust represents file names. You may have expected
+//@ `String`, which we have been using so far. However, file names on most operating systems are not
+//@ guaranteed to be valid UTF-8, so they cannot be stored in a `String`. Rust uses the types
+//@ [`PathBuf`](https://doc.rust-lang.org/stable/std/path/struct.PathBuf.html) and
+//@ [`Path`](https://doc.rust-lang.org/stable/std/path/struct.Path.html) instead. The relationship
+//@ between the two is the same as the one between `String` and `str`: `PathBuf` owns its data,
+//@ while `Path` is unsized, and we always work with references `&Path`. Both of them come with
+//@ handy methods, e.g., `join` appends a component to a path, and `extension` gives us the
+//@ extension of a file name.
+//@
+//@ Many functions, like `fs::File::open`, are generic over `P: AsRef<Path>`. This is another
+//@ conversion trait, implemented for all types that can be cheaply viewed as a `&Path` - including
+//@ `&str` and `String`. That's why we were able to pass our `String` file names to `open`.
+
+// ## Walking recursively
+//@ The function [`fs::read_dir`](https://doc.rust-lang.org/stable/std/fs/fn.read_dir.html) lists
+//@ the contents of a directory. It returns an iterator over `io::Result<DirEntry>`, because reading
+//@ each entry can go wrong. For every entry, we can get its path, and find out what kind of thing it
+//@ is.
+//@
+//@ The obvious way to walk a directory tree is recursion: For every directory we find, we call
+//@ ourselves. We want to be able to select only certain files (like, only Rust source files), so we
+//@ take a closure `filter` that decides whether a file should be included. We take it by reference,
+//@ so that we can hand it on to the recursive calls.
+//@
+//@ Finally, there is one more problem: *Symbolic links* can make the directory "tree" contain
+//@ cycles. A link pointing to its parent directory would send us into an infinite recursion. To
+//@ prevent that, we remember the directories we already visited. `fs::canonicalize` resolves all
+//@ links in a path, so every directory has exactly one canonical name. We store these names in a
+//@ `HashSet`, which remembers whether we have seen some value before.
+pub fn walk_recursive<F>(dir: &Path, filter: &F, visited: &mut HashSet<PathBuf>,
+                         files: &mut Vec<PathBuf>) -> io::Result<()>
+    where F: Fn(&Path) -> bool
+{
+    // `insert` returns `false` if the element was already in the set.
+    if !visited.insert(fs::canonicalize(dir)?) {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        //@ `Path::is_dir` follows symbolic links, so a link to a directory counts as a directory.
+        //@ That's exactly what we need the `visited` set for.
+        if path.is_dir() {
+            walk_recursive(&path, filter, visited, files)?;         /*@*/
+        } else if filter(&path) {
+            files.push(path);                                       /*@*/
+        }
+    }
+    Ok(())
+}
+//@ We used `where` to write the trait bound on `F`. This is just an alternative syntax to putting the
+//@ bound right next to `F` in the list of type parameters, which is handy when bounds get long.
+//@
+//@ Notice how `?` makes sure that errors are passed up all the way to the original caller: If any
+//@ directory cannot be read, the entire walk fails.
+
+// ## Walking with an explicit stack
+//@ Recursion is elegant, but it has two downsides here. First, a very deep directory tree leads to
+//@ a very deep call stack. Second, and more importantly, we have to produce *all* the file names
+//@ before the caller can start working on the first one. It would be much nicer to provide the
+//@ files one-by-one, as an iterator - then rgrep can start reading the first file while we are still
+//@ looking for the others.
+//@
+//@ To get there, we replace the call stack by a stack that we manage ourselves: A `Vec` of paths that
+//@ we still have to look at. Where the recursive version makes a call, we push onto this stack
+//@ instead.
+pub struct Walk<F> {
+    stack: Vec<PathBuf>,
+    visited: HashSet<PathBuf>,
+    filter: F,
+}
+
+impl<F: Fn(&Path) -> bool> Walk<F> {
+    pub fn new<P: AsRef<Path>>(root: P, filter: F) -> Self {
+        Walk { stack: vec![root.as_ref().to_path_buf()], visited: HashSet::new(), filter }
+    }
+}
+
+//@ In `next`, we keep taking paths off the stack until we find a file that should be returned. For
+//@ directories, we push all their entries onto the stack. Unlike the recursive version, we cannot
+//@ easily report errors here, so we simply skip over everything we cannot read.
+impl<F: Fn(&Path) -> bool> Iterator for Walk<F> {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        while let Some(path) = self.stack.pop() {
+            if path.is_dir() {
+                //@ We only descend into directories we did not visit yet. If we cannot canonicalize
+                //@ the path, we skip the directory.
+                let canonical = match fs::canonicalize(&path) {
+                    Ok(canonical) => canonical,
+                    Err(_) => continue,
+                };
+                if !self.visited.insert(canonical) {                /*@*/
+                    continue;                                       /*@*/
+                }                                                   /*@*/
+                if let Ok(entries) = fs::read_dir(&path) {          /*@*/
+                    //@ `flatten` skips over the entries that are an `Err`, and unwraps the others.
+                    for entry in entries.flatten() {                /*@*/
+                        self.stack.push(entry.path());              /*@*/
+                    }                                               /*@*/
+                }                                                   /*@*/
+            } else if (self.filter)(&path) {
+                //@ Notice the parentheses around `self.filter`: Without them, Rust would look for a
+                //@ *method* called `filter`.
+                return Some(path);                                  /*@*/
+            }
+        }
+        None
+    }
+}
+//@ Because directories pop off the stack in the reverse order of how they were pushed, the files
+//@ are not produced in the same order as by `walk_recursive`. But then, `read_dir` does not promise
+//@ any particular order either.
+
+// ## Feeding rgrep
+//@ Now we can make rgrep search entire directories. All we have to do is to replace every directory
+//@ in the list of files by the files it contains. Here, we don't care about errors; any file we
+//@ cannot read will be skipped. This is the function that the `-R` flag of rgrep in part 14 calls,
+//@ so once you filled it in, `cargo run -- -R fn src` searches the entire tutorial.
+pub fn expand_dirs(files: Vec<String>) -> Vec<String> {
+    let mut result = Vec::new();
+    for file in files {
+        if Path::new(&file).is_dir() {
+            //@ `to_string_lossy` converts a `Path` to a string, replacing everything that is not
+            //@ valid UTF-8.
+            for path in Walk::new(&file, |_| true) {
+                result.push(path.to_string_lossy().into_owned());   /*@*/
+            }
+        } else {
+            result.push(file);                                      /*@*/
+        }
+    }
+    result
+}
+
+// Let's count how often we write `fn` in the entire tutorial. Remember to edit `main.rs` to run
+// this.
+pub fn main() {
+    let is_rust = |path: &Path| path.extension().is_some_and(|ext| ext == "rs");
+    let mut files = Vec::new();
+    walk_recursive(Path::new("src"), &is_rust, &mut HashSet::new(), &mut files).unwrap();
+    println!("There are {} Rust files.", files.len());
+
+    let options = Options {
+        files: expand_dirs(vec!["src".to_string()]),
+        pattern: "fn ".to_string(),
+        output_mode: OutputMode::Count,
+        byte_offset: false,
+    };
+    run(options);
+}
+
+// **Exercise 19.1**: Change `Walk` to be an iterator over `io::Result<PathBuf>`, such that errors
+// are reported to the caller rather than silently ignored.
+
+// **Exercise 19.2**: Add a maximal depth to `Walk`. You will have to store the depth of every path
+// on the stack.
+
+// The tests of parts 13, 41 and 42 work on files as well, so they use the `TempDir` from here.
+#[cfg(test)]
+pub mod tests {
+    use std::{env, fs, process};
+    use std::path::{Path, PathBuf};
+    use std::collections::HashSet;
+    use super::{walk_recursive, Walk, expand_dirs};
+
+    // A temporary directory that is deleted again when it is dropped. Every test needs its own
+    // `name`, since the tests run in parallel.
+    pub struct TempDir(pub PathBuf);
+    impl TempDir {
+        pub fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("rust-101-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Create this tree: a.rs, b.txt, sub/c.rs, sub/deeper/d.rs, empty/
+    fn make_tree(name: &str) -> TempDir {
+        let tmp = TempDir::new(name);
+        fs::create_dir_all(tmp.0.join("sub").join("deeper")).unwrap();
+        fs::create_dir_all(tmp.0.join("empty")).unwrap();
+        for file in ["a.rs", "b.txt", "sub/c.rs", "sub/deeper/d.rs"].iter() {
+            fs::write(tmp.0.join(file), "fn main() {}\n").unwrap();
+        }
+        tmp
+    }
+
+    fn is_rust(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "rs")
+    }
+
+    // Make the paths relative to `root`, and sort them.
+    fn relative(root: &Path, files: Vec<PathBuf>) -> Vec<String> {
+        let mut files: Vec<String> = files.iter()
+            .map(|f| f.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_walk_recursive() {
+        let tmp = make_tree("walk-recursive");
+        let mut files = Vec::new();
+        walk_recursive(&tmp.0, &is_rust, &mut HashSet::new(), &mut files).unwrap();
+        assert_eq!(relative(&tmp.0, files), vec!["a.rs", "sub/c.rs", "sub/deeper/d.rs"]);
+    }
+
+    #[test]
+    fn test_walk_iterative() {
+        let tmp = make_tree("walk-iterative");
+        let files = Walk::new(&tmp.0, is_rust).collect();
+        assert_eq!(relative(&tmp.0, files), vec!["a.rs", "sub/c.rs", "sub/deeper/d.rs"]);
+        let all = Walk::new(&tmp.0, |_: &Path| true).count();
+        assert_eq!(all, 4);
+    }
+
+    #[test]
+    fn test_walk_missing() {
+        let tmp = TempDir::new("walk-missing");
+        let missing = tmp.0.join("missing");
+        assert!(walk_recursive(&missing, &is_rust, &mut HashSet::new(), &mut Vec::new()).is_err());
+        assert_eq!(Walk::new(&missing, is_rust).count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_cycle() {
+        use std::os::unix::fs::symlink;
+        let tmp = make_tree("walk-cycle");
+        // This link points back up to the root, creating a cycle.
+        symlink(&tmp.0, tmp.0.join("sub").join("loop")).unwrap();
+        let mut files = Vec::new();
+        walk_recursive(&tmp.0, &is_rust, &mut HashSet::new(), &mut files).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(Walk::new(&tmp.0, is_rust).count(), 3);
+    }
+
+    #[test]
+    fn test_expand_dirs() {
+        let tmp = make_tree("expand-dirs");
+        let dir = tmp.0.join("sub").to_string_lossy().into_owned();
+        // Files are passed on as they are, even if they do not exist.
+        let files = expand_dirs(vec![dir, "missing.rs".to_string()]);
+        let (missing, found): (Vec<String>, Vec<String>) =
+            files.into_iter().partition(|f| f == "missing.rs");
+        assert_eq!(missing.len(), 1);
+        let found = found.into_iter().map(PathBuf::from).collect();
+        assert_eq!(relative(&tmp.0, found), vec!["sub/c.rs", "sub/deeper/d.rs"]);
+    }
+}
+
+//@ [index](main.html) | [previous](part18.html) | [raw source](workspace/src/part19.rs) |
+//@ [next](part20.html)
@@ -0,0 +1,205 @@
+// Rust-101, Part 20: Iterator Adapters
+// ====================================
+
+//@ In part 09, we wrote an iterator over the digits of a `BigInt`. Since then, we have been happily
+//@ using `map`, `filter`, `zip` and their friends. These are *iterator adapters*: They take an
+//@ iterator, and return another iterator that does something with the elements of the first one.
+//@ There is no magic involved in writing them, and in this part, we will implement a few of them
+//@ ourselves.
+
+// ## Zip
+//@ Let us start with `zip`, which walks two iterators in lock-step and returns pairs of their
+//@ elements. Our adapter is a struct storing the two iterators. We don't know their types, so we
+//@ make it generic.
+pub struct Zip<A, B> {
+    a: A,
+    b: B,
+}
+
+//@ The interesting part is the type of the items. We yield pairs of an item of `A`, and an item of
+//@ `B`. `A::Item` refers to the associated type `Item` of the `Iterator` implementation of `A`.
+impl<A: Iterator, B: Iterator> Iterator for Zip<A, B> {
+    type Item = (A::Item, B::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // We stop as soon as either of the iterators is done.
+        match (self.a.next(), self.b.next()) {                      /*@*/
+            (Some(a), Some(b)) => Some((a, b)),                     /*@*/
+            _ => None,                                              /*@*/
+        }                                                           /*@*/
+    }
+}
+
+//@ To create a `Zip`, we accept anything that can be turned into an iterator - just like a `for`
+//@ loop does. That's the `IntoIterator` trait from part 09 again.
+pub fn zip<A: IntoIterator, B: IntoIterator>(a: A, b: B) -> Zip<A::IntoIter, B::IntoIter> {
+    Zip { a: a.into_iter(), b: b.into_iter() }
+}
+
+// ## Chunks
+//@ Slices have a method `chunks`, which splits them into pieces of a given size. We are going to
+//@ write an iterator adapter that does the same for arbitrary iterators. Since the elements are not
+//@ stored in memory one after the other, we cannot hand out slices. Instead, every chunk is a
+//@ `Vec`.
+pub struct Chunks<I> {
+    iter: I,
+    size: usize,
+}
+
+impl<I: Iterator> Chunks<I> {
+    pub fn new(iter: I, size: usize) -> Self {
+        //@ A chunk size of zero makes no sense, we would never make any progress. Just like the
+        //@ standard library, we panic in that case.
+        assert!(size > 0, "Chunk size must be non-zero");
+        Chunks { iter, size }
+    }
+}
+
+impl<I: Iterator> Iterator for Chunks<I> {
+    type Item = Vec<I::Item>;
+
+    fn next(&mut self) -> Option<Vec<I::Item>> {
+        let mut chunk: Vec<I::Item> = Vec::with_capacity(self.size);
+        // Fill up the chunk, until it is full or the iterator runs out of elements.
+        while chunk.len() < self.size {
+            match self.iter.next() {                                /*@*/
+                Some(e) => chunk.push(e),                           /*@*/
+                None => break,                                      /*@*/
+            }                                                       /*@*/
+        }
+        // The last chunk may be shorter than the others, but it must not be empty.
+        if chunk.is_empty() { None } else { Some(chunk) }           /*@*/
+    }
+}
+
+// ## StepBy
+//@ Our last adapter returns the first element, and then every `step`-th element after that. For
+//@ skipping elements, we use `nth`: `iter.nth(n)` throws away `n` elements and returns the one
+//@ after them. Like `map`, it has a default implementation, so every iterator provides it. We need
+//@ to remember whether we already returned the first element, because that one is not skipped.
+pub struct StepBy<I> {
+    iter: I,
+    step: usize,
+    first: bool,
+}
+
+impl<I: Iterator> StepBy<I> {
+    pub fn new(iter: I, step: usize) -> Self {
+        assert!(step > 0, "Step must be non-zero");
+        StepBy { iter, step, first: true }
+    }
+}
+
+impl<I: Iterator> Iterator for StepBy<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.first {
+            self.first = false;                                     /*@*/
+            self.iter.next()                                        /*@*/
+        } else {
+            self.iter.nth(self.step - 1)                            /*@*/
+        }
+    }
+}
+
+// ## Building pipelines
+//@ Our adapters work, but using them is a bit awkward: `StepBy::new(Chunks::new(v.iter(), 2), 3)`
+//@ has to be read inside-out. With the adapters of the standard library, we can just chain method
+//@ calls. How can we get the same for our adapters? We cannot add methods to `Iterator`, since that
+//@ trait is defined in another crate. But we can define our own trait, and implement it for all
+//@ iterators!
+//@
+//@ `IterExt` is an *extension trait*. Its methods all have default implementations, so the `impl`
+//@ below is empty. The bound `Sized` is needed because our methods take `self` by value, which is
+//@ not possible for unsized types (remember `str` from part 14).
+pub trait IterExt: Iterator + Sized {
+    fn my_zip<B: IntoIterator>(self, other: B) -> Zip<Self, B::IntoIter> {
+        zip(self, other)
+    }
+    fn my_chunks(self, size: usize) -> Chunks<Self> {
+        Chunks::new(self, size)
+    }
+    fn my_step_by(self, step: usize) -> StepBy<Self> {
+        StepBy::new(self, step)
+    }
+}
+//@ This is a *blanket implementation*: It provides `IterExt` for every type `I` that is an iterator.
+//@ As a consequence, whenever `IterExt` is in scope, all iterators get our methods.
+impl<I: Iterator> IterExt for I {}
+
+// Now we can chain our adapters, and mix them freely with those of the standard library. Remember to
+// edit `main.rs` to run this function.
+pub fn main() {
+    //@ `1..` is an endless iterator, counting up from 1. That's no problem, since `my_zip` stops when
+    //@ the shorter iterator is exhausted.
+    let pipeline = (1..).my_step_by(3).my_zip("abcdefg".chars()).map(|(n, c)| format!("{}{}", c, n))
+        .my_chunks(3);
+    for chunk in pipeline {
+        println!("{}", chunk.join(" "));
+    }
+}
+//@ Nothing happens until the `for` loop starts asking for elements: Iterators are *lazy*. Every
+//@ call to `next` on the pipeline calls `next` on the adapter below it, and so on. There are no
+//@ intermediate vectors. And since all our adapters are generic, the compiler produces specialized
+//@ code for this particular pipeline, which typically ends up as efficient as a hand-written loop.
+
+// **Exercise 20.1**: Iterators have a method `size_hint`, which returns a lower and (optionally) an
+// upper bound on the number of remaining elements. `collect` uses it to allocate the right amount of
+// memory up front. Implement `size_hint` for our three adapters. Make sure the tests still pass.
+
+// **Exercise 20.2**: Write an adapter `Windows` that yields overlapping windows of `size` elements:
+// For `1, 2, 3, 4` and a size of 2, it yields `[1, 2]`, `[2, 3]`, `[3, 4]`. You will need the items
+// to implement `Clone`. Compare your results with `chunks` and `windows` on slices.
+
+// **Exercise 20.3**: Write `my_interleave`, which alternates between the elements of two iterators,
+// and continues with the longer one when the shorter one runs out.
+
+#[cfg(test)]
+mod tests {
+    use super::{zip, IterExt};
+
+    #[test]
+    fn test_zip() {
+        let v = vec![1, 2, 3];
+        let w = vec!["a", "b"];
+        let ours: Vec<_> = zip(&v, &w).collect();
+        let std: Vec<_> = v.iter().zip(w.iter()).collect();
+        assert_eq!(ours, std);
+        assert_eq!(ours, vec![(&1, &"a"), (&2, &"b")]);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let v: Vec<i32> = (0..7).collect();
+        let ours: Vec<Vec<i32>> = v.iter().cloned().my_chunks(3).collect();
+        let std: Vec<Vec<i32>> = v.chunks(3).map(|c| c.to_vec()).collect();
+        assert_eq!(ours, std);
+        assert_eq!((0..0).my_chunks(3).count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chunks_zero() {
+        (0..5).my_chunks(0);
+    }
+
+    #[test]
+    fn test_step_by() {
+        for step in 1..5 {
+            let ours: Vec<i32> = (0..10).my_step_by(step).collect();
+            let std: Vec<i32> = (0..10).step_by(step).collect();
+            assert_eq!(ours, std);
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let ours: Vec<Vec<(i32, char)>> = (1..).my_step_by(2).my_zip("abcde".chars()).my_chunks(2)
+            .collect();
+        assert_eq!(ours, vec![vec![(1, 'a'), (3, 'b')], vec![(5, 'c'), (7, 'd')], vec![(9, 'e')]]);
+    }
+}
+
+//@ [index](main.html) | [previous](part19.html) | [raw source](workspace/src/part20.rs) |
+//@ [next](part21.html)
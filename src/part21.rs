@@ -0,0 +1,235 @@
+// Rust-101, Part 21: Advanced Pattern Matching
+// ============================================
+
+use std::io;
+use part05::Variant;
+use part13::{Options, OutputMode};
+use part13::OutputMode::*;
+
+//@ We have been using `match` since part 02, but so far, our patterns were pretty simple: A variant
+//@ of an enum, with some names for its fields. Patterns can do a lot more than that, and in this
+//@ part, we are going to see the most useful tricks. Our running examples are the `Variant` type
+//@ from part 05, which holds either a number or a text, and the `OutputMode` of rgrep.
+
+// ## Guards, ranges and bindings
+//@ Let's write a function that describes a `Variant` in words. Look at the patterns below:
+//@
+//@ * A pattern can be followed by `if` and a condition. This is a *match guard*. The arm is only
+//@   taken if the pattern matches, *and* the condition holds. Otherwise, Rust goes on trying the
+//@   next arm.
+//@ * Literals like `0` are patterns, and so are ranges like `1..=9`. (The `=` means that 9 is
+//@   included in the range.)
+//@ * `n @ 1..=9` matches the same values as `1..=9`, but also binds the value to `n`. That's
+//@   handy if you want to test the shape of some data, and then use it.
+//@
+//@ We match on a reference here, so `n` and `s` are references as well. That's why we have to
+//@ write `*n` in the guard.
+pub fn describe(var: &Variant) -> String {
+    match var {
+        Variant::Number(n) if *n < 0 => "a negative number".to_string(),
+        Variant::Number(0) => "zero".to_string(),
+        Variant::Number(n @ 1..=9) => format!("the digit {}", n),
+        Variant::Number(n) => format!("the number {}", n),
+        Variant::Text(s) if s.is_empty() => "an empty text".to_string(),
+        Variant::Text(s) => format!("the text \"{}\"", s),
+    }
+}
+//@ The order of the arms matters: The first arm that matches is taken. If we put the arm for `n`
+//@ before the arm for `0`, we'd never get to see "zero". Also notice that the compiler does not look
+//@ into guards when checking that we covered all cases. If you delete the last arm, it will
+//@ complain that `Text(_)` is not covered - even though the guarded arm above looks like it would
+//@ catch some of these.
+
+// ## Slice patterns
+//@ Patterns also work on slices. `[a, b]` matches a slice with exactly two elements. `..` stands for
+//@ "any number of elements" (including none), and can appear at most once. With `rest @ ..`, we
+//@ can even get our hands on the slice of elements that `..` matched.
+//@
+//@ This is exactly what we need to parse rgrep's command-line. The function below looks at the
+//@ first argument: If it is a flag, we determine the output mode, and return it together with the
+//@ remaining arguments. Since we match on the slice itself (and not a reference to it), we have to
+//@ write `*args`.
+pub fn parse_mode<'a>(args: &'a [&'a str]) -> Result<(OutputMode, &'a [&'a str]), String> {
+    match *args {                                                   /*@*/
+        [] => Err("Missing pattern".to_string()),                   /*@*/
+        ["-c", ref rest @ ..] => Ok((Count, rest)),                 /*@*/
+        ["-s", ref rest @ ..] => Ok((SortAndPrint, rest)),          /*@*/
+        [flag, ..] if flag.starts_with('-') => Err(format!("Unknown flag {}", flag)), /*@*/
+        _ => Ok((Print, args)),                                     /*@*/
+    }                                                               /*@*/
+}
+//@ The `ref` in `ref rest` is needed because the slice is not ours: It says that we want to bind a
+//@ reference to the remaining elements, rather than taking them by value (which is not even possible
+//@ for a slice). Again, this is the same reasoning as in part 05.
+
+// ## Nested patterns
+//@ Patterns can be nested arbitrarily deep. Here, we look into the `Options` struct, and further
+//@ into its `output_mode` and `files` fields. The `..` inside the braces means "we don't care about
+//@ the other fields". This lets us handle exactly the cases we are interested in, without writing a
+//@ cascade of `if`.
+pub fn summarize(options: &Options) -> String {
+    match *options {
+        Options { output_mode: Count, ref files, .. } if files.len() == 1 =>
+            format!("count the matches in {}", files[0]),
+        Options { output_mode: Count, .. } => "count the matches".to_string(),
+        Options { ref pattern, ref files, .. } => match files[..] {
+            [] => format!("search for {} in nothing at all", pattern),
+            [ref file] => format!("search for {} in {}", pattern, file),
+            [ref first, .., ref last] => format!("search for {} in {} to {}", pattern, first, last),
+        },
+    }
+}
+
+// ## `matches!`
+//@ Sometimes, we do not need to do anything with the data; we just want to know whether it has a
+//@ certain shape. `matches!` is a macro that turns a pattern into a boolean test. The pattern can
+//@ have a guard, too.
+pub fn needs_sorting(mode: OutputMode) -> bool {
+    matches!(mode, SortAndPrint)
+}
+pub fn is_positive(var: &Variant) -> bool {
+    matches!(var, Variant::Number(n) if *n > 0)                     /*@*/
+}
+
+// ## Exhaustiveness and `#[non_exhaustive]`
+//@ A `match` must cover all cases, and we have seen how that helps us when we add variants to an
+//@ enum: The compiler tells us about all the places we have to update. But this can also be a
+//@ problem. If you publish a library with an enum, adding a variant would break the code of all
+//@ your users!
+//@
+//@ Authors of libraries can prevent that by marking the enum as `#[non_exhaustive]`. Then, code
+//@ *outside the crate defining the enum* is forced to have a wildcard arm, and new variants can be
+//@ added later without breaking anything. Let's say rgrep should print its matches in several
+//@ formats, and more may come later. The module below plays the library that defines them.
+pub mod format {
+    #[non_exhaustive]
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub enum Format {
+        Text,
+        Json,
+    }
+
+    //@ Within the crate that defines the enum, nothing changes: This `match` is exhaustive without
+    //@ a wildcard. The compiler knows all the variants, and if we add one, it tells us to come
+    //@ back here.
+    pub fn extension(format: Format) -> &'static str {
+        match format {
+            Format::Text => "txt",
+            Format::Json => "json",
+        }
+    }
+}
+
+//@ The attribute only has an effect on *other* crates - and `format` is just a module in our crate.
+//@ Here, a wildcard arm after `Text` and `Json` would even be flagged as unreachable. To see the
+//@ effect, we need an enum from another crate. The standard library marks lots of its enums as
+//@ `#[non_exhaustive]`, for example
+//@ [`io::ErrorKind`](https://doc.rust-lang.org/stable/std/io/enum.ErrorKind.html). Our match on it
+//@ is *downstream*, so it has to have the last arm. Try removing it, and see what the compiler
+//@ says.
+pub fn explain(kind: io::ErrorKind) -> &'static str {
+    match kind {
+        io::ErrorKind::NotFound => "The file does not exist.",
+        io::ErrorKind::PermissionDenied => "You are not allowed to open the file.",
+        _ => "Something else went wrong.",
+    }
+}
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    for var in &[Variant::Number(-3), Variant::Number(7), Variant::Text("Rust".to_string())] {
+        println!("This is {}.", describe(var));
+    }
+    let args = ["-c", "fn", "src/part21.rs"];
+    match parse_mode(&args) {
+        Ok((mode, &[pattern, file])) => {
+            let options = Options { files: vec![file.to_string()], pattern: pattern.to_string(),
+                                    output_mode: mode, byte_offset: false };
+            println!("I am going to {}.", summarize(&options));
+        }
+        Ok(_) => println!("Please give exactly one pattern and one file."),
+        Err(msg) => println!("{}", msg),
+    }
+}
+//@ Here, the slice pattern is nested in a tuple, which is nested in a `Result`. Notice that
+//@ `&[pattern, file]` matches a *reference to* a slice, so that `pattern` and `file` are just
+//@ copies of the `&str` in there.
+
+// **Exercise 21.1**: Extend `parse_mode` such that several flags may be given, like `-c -s`, where
+// the last one wins. Hint: Make the function recursive.
+
+// **Exercise 21.2**: Write a function `combine(a: Variant, b: Variant) -> Variant` that adds two
+// numbers, concatenates two texts, and appends a number to a text (in either order). Match on the
+// pair `(a, b)` to handle all cases in a single `match`. Write a test.
+
+// **Exercise 21.3**: Move the module `format` into a crate of its own, like we are going to do with
+// the `BigInt` in part 22, and use it from this crate. Which of the two `match` need a wildcard arm
+// now?
+
+// **Exercise 21.4**: Write a function `first_word(line: &str) -> Option<&str>` that splits the line
+// at whitespace, collects the words into a `Vec`, and then uses a slice pattern to return the first
+// word - but only if the line has at least two words.
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use part05::Variant;
+    use part13::Options;
+    use part13::OutputMode::*;
+    use super::{describe, parse_mode, summarize, needs_sorting, is_positive, explain};
+    use super::format::{extension, Format};
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(&Variant::Number(-1)), "a negative number");
+        assert_eq!(describe(&Variant::Number(0)), "zero");
+        assert_eq!(describe(&Variant::Number(9)), "the digit 9");
+        assert_eq!(describe(&Variant::Number(10)), "the number 10");
+        assert_eq!(describe(&Variant::Text(String::new())), "an empty text");
+        assert_eq!(describe(&Variant::Text("hi".to_string())), "the text \"hi\"");
+        assert!(is_positive(&Variant::Number(1)));
+        assert!(!is_positive(&Variant::Number(0)));
+        assert!(!is_positive(&Variant::Text("1".to_string())));
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        let args = ["-c", "pat", "file"];
+        assert!(matches!(parse_mode(&args), Ok((Count, ["pat", "file"]))));
+        let args = ["-s", "pat"];
+        assert!(matches!(parse_mode(&args), Ok((SortAndPrint, ["pat"]))));
+        let args = ["pat", "-c"];
+        assert!(matches!(parse_mode(&args), Ok((Print, ["pat", "-c"]))));
+        let args = ["-x", "pat"];
+        assert_eq!(parse_mode(&args).err(), Some("Unknown flag -x".to_string()));
+        assert!(parse_mode(&[]).is_err());
+        assert!(needs_sorting(SortAndPrint) && !needs_sorting(Count));
+    }
+
+    #[test]
+    fn test_summarize() {
+        let options = |files: &[&str], output_mode| Options {
+            files: files.iter().map(|f| f.to_string()).collect(),
+            pattern: "fn".to_string(), output_mode, byte_offset: false,
+        };
+        assert_eq!(summarize(&options(&["a"], Count)), "count the matches in a");
+        assert_eq!(summarize(&options(&["a", "b"], Count)), "count the matches");
+        assert_eq!(summarize(&options(&[], Print)), "search for fn in nothing at all");
+        assert_eq!(summarize(&options(&["a"], Print)), "search for fn in a");
+        assert_eq!(summarize(&options(&["a", "b", "c"], SortAndPrint)), "search for fn in a to c");
+    }
+
+    #[test]
+    fn test_non_exhaustive() {
+        assert_eq!(extension(Format::Text), "txt");
+        assert_eq!(extension(Format::Json), "json");
+        assert_eq!(explain(io::ErrorKind::NotFound), "The file does not exist.");
+        assert_eq!(explain(io::ErrorKind::PermissionDenied),
+                   "You are not allowed to open the file.");
+        // Kinds we did not think of end up in the wildcard arm.
+        assert_eq!(explain(io::ErrorKind::Interrupted), "Something else went wrong.");
+    }
+}
+
+//@ [index](main.html) | [previous](part20.html) | [raw source](workspace/src/part21.rs) |
+//@ [next](part22.html)
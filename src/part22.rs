@@ -0,0 +1,157 @@
+// Rust-101, Part 22: Crates, Modules, Publishing
+// ==============================================
+
+//@ In part 08, I explained modules and `pub` rather abstractly, and in part 14 we used a crate that
+//@ somebody else wrote. In this part, we are going to get our hands dirty and turn our own `BigInt`
+//@ into a crate that others could use. Most of the work happens outside of this file, in your
+//@ terminal and editor, so read on with both of them open.
+
+// ## Creating a library crate
+//@ Go to your `workspace` directory and run `cargo new --lib bigint`. This creates a directory
+//@ `bigint` with a `Cargo.toml` and a `src/lib.rs`. A *library* crate starts compiling at `lib.rs`
+//@ rather than `main.rs`, and it does not have a `main` function: It is meant to be used by other
+//@ crates. Now copy the `BigInt` struct and the functions you wrote for it in parts 05 to 08 into
+//@ the new crate. Don't worry about putting them in the right place yet, we will get to that.
+//@
+//@ To use your crate from the workspace, add it as a *path dependency* to `workspace/Cargo.toml`:
+/*
+  [dependencies]
+  bigint = { path = "bigint" }
+*/
+//@ Then add `extern crate bigint;` to the top of `main.rs`, just like we did for `clap`. Cargo
+//@ will compile `bigint` whenever it compiles the workspace.
+
+// ## Organizing the modules
+//@ Let us split the crate into several modules. Here's the layout I suggest, written as inline
+//@ modules so that you can see all of it in one place. In your crate, every `mod name { ... }`
+//@ becomes a `mod name;` and a file `src/name.rs`.
+pub mod mini_bigint {
+    //@ The modules are all private. Users of our crate should not have to care about how we
+    //@ organized our files, and if we keep the modules private, we can freely move things around
+    //@ later.
+    mod bigint {
+        pub struct BigInt {
+            //@ `pub(crate)` is a visibility between private and `pub`: The field can be used
+            //@ anywhere *in this crate*, but not by our users. That's exactly what we need - the
+            //@ `ops` module has to access `data`, but nobody else should be able to break our
+            //@ invariant.
+            pub(crate) data: Vec<u64>,
+        }
+
+        impl BigInt {
+            pub fn new(x: u64) -> Self {
+                if x == 0 { BigInt { data: vec![] } } else { BigInt { data: vec![x] } }
+            }
+
+            pub fn num_digits(&self) -> usize {
+                self.data.len()
+            }
+        }
+    }
+
+    mod ops {
+        use std::ops;
+        //@ Since this module lives inside `mini_bigint`, we use `super::` to refer to its sibling.
+        //@ In your crate, `bigint` is at the root, so you can write `use bigint::BigInt;`.
+        use super::bigint::BigInt;
+
+        impl<'a> ops::Add<&'a BigInt> for &BigInt {
+            type Output = BigInt;
+            fn add(self, rhs: &'a BigInt) -> BigInt {
+                // A very simplified addition, just to demonstrate that `data` is accessible.
+                let lhs = self.data.first().cloned().unwrap_or(0);
+                let rhs = rhs.data.first().cloned().unwrap_or(0);
+                BigInt::new(lhs + rhs)
+            }
+        }
+    }
+
+    //@ Now we decide what our users get to see. `pub use` imports a name, and at the same time
+    //@ makes it available to everybody who can see this module. This is called a *re-export*: Users
+    //@ write `bigint::BigInt`, rather than `bigint::bigint::BigInt`.
+    pub use self::bigint::BigInt;
+}
+//@ Trait implementations, like the one of `Add` in `ops`, do not need to be re-exported. They are
+//@ always visible together with the trait and the type. That's why `ops` does not export anything.
+
+use self::mini_bigint::BigInt;
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let b = &BigInt::new(20) + &BigInt::new(22);
+    println!("The sum has {} digit(s).", b.num_digits());
+}
+//@ If you try to print `b.data` here, you will notice that this works just fine. That's because in
+//@ this file, `mini_bigint` is just a module, and we are still in the same crate. Move the code into
+//@ your `bigint` crate, and try accessing `data` from the workspace: That will fail.
+
+// **Exercise 22.1**: Move the code for `BigInt` into your crate, following this layout. Also add a
+// module `minimum` with the `Minimum` trait and `vec_min`. What do you have to re-export?
+
+// ## Feature flags
+//@ Not all users need all of our crate. Crates can declare *features*, which users can turn on or
+//@ off in their `Cargo.toml`. Let's make the arithmetic operators optional. In `bigint/Cargo.toml`,
+//@ add:
+/*
+  [features]
+  default = ["ops"]
+  ops = []
+*/
+//@ This declares a feature `ops` that does not depend on any other features (that's the `[]`), and
+//@ makes it enabled by default. In `lib.rs`, we then only compile the module when the feature is
+//@ enabled:
+/*
+  #[cfg(feature = "ops")]
+  mod ops;
+*/
+//@ A user who does not want our operators can write `bigint = { path = "bigint",
+//@ default-features = false }`. Features should always be *additive*: Turning on a feature must not
+//@ break code that worked without it, because Cargo enables a feature for everybody as soon as one
+//@ crate asks for it.
+
+// ## Documentation and doc examples
+//@ Comments starting with `///` are documentation comments. They document the item that comes next,
+//@ and `//!` documents the enclosing item - at the top of `lib.rs`, that's the entire crate. Run
+//@ `cargo doc --open` to see the result. Documentation is written in Markdown, and code blocks in
+//@ there are special: `cargo test` compiles and runs them as tests, called *doctests*. This way, the
+//@ examples in your documentation never get out of date. Here is how the documentation of `new`
+//@ could look:
+/*
+  /// Construct a `BigInt` from a "small" one.
+  ///
+  /// ```
+  /// use bigint::BigInt;
+  ///
+  /// assert_eq!(BigInt::new(42).num_digits(), 1);
+  /// ```
+  pub fn new(x: u64) -> Self {
+*/
+//@ A doctest is compiled as if it were a separate crate using yours, so it can only use what is
+//@ public. It is a good way to check that your re-exports work.
+
+// **Exercise 22.2**: Add the `ops` feature to your crate, and check that `cargo test` and
+// `cargo test --no-default-features` both pass. Your doc examples using `+` will fail in the latter
+// case. Find out how to fix that. Hint: Lines in doctests that start with `#` are hidden from the
+// documentation, but still compiled.
+
+// **Exercise 22.3**: Write documentation with examples for all public items of your crate.
+
+// ## Publishing
+//@ Crates are published on [crates.io](https://crates.io/). Before a crate can go there, it needs
+//@ some metadata in `Cargo.toml`: A `description` and a `license` are required, and a `repository`
+//@ or `documentation` link is strongly recommended. Cargo will warn you about missing fields.
+//@
+//@ You do not have to actually publish anything to check that your crate is ready. `cargo package
+//@ --list` shows the files that would be uploaded. `cargo package` assembles the `.crate` file in
+//@ `target/package`, unpacks it in a fresh place, and compiles it from there. That catches mistakes
+//@ like forgetting to include a file. Finally, `cargo publish --dry-run` performs all the checks of
+//@ a real upload, and then stops right before talking to crates.io. (Since your workspace is
+//@ probably not committed to git, you need to pass `--allow-dirty` to all of these.)
+
+// **Exercise 22.4**: Add the missing metadata to your crate, and make `cargo package` succeed
+// without warnings.
+
+// You can find a complete version of this crate in the `solutions` directory, under `mini-bigint`.
+
+//@ [index](main.html) | [previous](part21.html) | [raw source](workspace/src/part22.rs) |
+//@ [next](part23.html)
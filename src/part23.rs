@@ -0,0 +1,237 @@
+// Rust-101, Part 23: Testing
+// ==========================
+
+//@ We have been writing tests since part 08, mostly by putting some `assert_eq!` into a function
+//@ marked `#[test]`. That gets you surprisingly far, but there is more to testing in Rust. In this
+//@ part, we will look at the different kinds of tests that `cargo test` runs, and at a technique
+//@ that finds bugs you would never have thought of testing for: *property-based testing*.
+
+// ## The code under test
+//@ We need something to test, so here's an insertion sort. It is much simpler than the Quicksort of
+//@ part 14 (and much slower for large inputs), which makes it a good reference to compare other
+//@ sorting functions with.
+pub fn insertion_sort<T: PartialOrd>(data: &mut [T]) {
+    for i in 1..data.len() {
+        // Move `data[i]` to the left, until it is in the right place.
+        let mut j = i;
+        while j > 0 && data[j - 1] > data[j] {
+            data.swap(j - 1, j);                                    /*@*/
+            j -= 1;                                                 /*@*/
+        }
+    }
+}
+
+// ## Unit tests
+//@ Tests that live right next to the code, in a `tests` submodule, are called *unit tests*. Since
+//@ the submodule can access everything in its parent, unit tests can also test private functions.
+//@ You can find the unit tests of this part at the end of the file. Besides `assert!` and
+//@ `assert_eq!`, there are a few more things worth knowing:
+//@
+//@ * A test marked `#[should_panic]` only passes if it panics. With `expected = "..."`, the panic
+//@   message must contain the given text - otherwise, *any* panic would make the test pass, even
+//@   one you did not expect.
+//@ * A test can return a `Result`. If it returns an `Err`, the test fails. This lets you use `?`
+//@   in tests.
+//@ * A test marked `#[ignore]` only runs when you ask for it with `cargo test -- --ignored`. This
+//@   is useful for tests that take very long.
+//@ * `cargo test sort` only runs the tests whose name contains `sort`.
+
+// ## Integration tests
+//@ Unit tests see the crate from the inside. *Integration tests* see it from the outside, just like
+//@ your users do: They live in a directory `tests` next to `src`, and every file in there is
+//@ compiled as a separate crate that uses your library. Thus, integration tests only work for
+//@ library crates. Go ahead and add a file `tests/arithmetic.rs` to the `bigint` crate you created
+//@ in part 22:
+/*
+  extern crate bigint;
+  use bigint::BigInt;
+
+  #[test]
+  fn test_add_carry() {
+      let b = BigInt::new(1 << 63) + BigInt::new(1 << 63);
+      assert_eq!(b, BigInt::from_vec(vec![0, 1]));
+  }
+*/
+//@ `cargo test` now runs three groups of tests: Unit tests, integration tests, and doctests. If
+//@ several integration tests need some helper functions, put those in `tests/common/mod.rs`, and
+//@ write `mod common;` in the tests that use them. (A file `tests/common.rs` would be considered
+//@ an integration test on its own.)
+
+// ## Property-based testing
+//@ When we write tests by hand, we only test the cases we thought of. But the bugs are usually
+//@ hiding in the cases that we did *not* think of. Property-based testing turns this around: We
+//@ describe a *property* that must hold for all inputs, and then let the computer check it on
+//@ lots of random inputs. For sorting, the property is: After sorting, the data is sorted, and it
+//@ contains the same elements as before.
+//@
+//@ For random inputs, we need random numbers. There are crates for that (and for property-based
+//@ testing in general, like `quickcheck` and `proptest`), but we can easily write a simple
+//@ pseudo-random number generator ourselves. This is a *xorshift* generator: The details don't
+//@ matter, it just scrambles the bits of its state in a way that looks random.
+pub struct Rng(u64);
+
+impl Rng {
+    // The seed must not be 0.
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Generate a vector of random length, with elements between 0 and `max` (exclusive).
+    //@ We use small numbers on purpose, so that there are plenty of duplicates.
+    pub fn vec(&mut self, max_len: usize, max: i32) -> Vec<i32> {
+        let len = (self.next_u64() % (max_len as u64 + 1)) as usize;
+        (0..len).map(|_| (self.next_u64() % max as u64) as i32).collect()
+    }
+}
+//@ The sequence of numbers is completely determined by the seed. That's important: When a test
+//@ fails, we want to be able to run it again and get the same failure.
+
+// Now we can check the property. We take the sorting function as a closure, so that we can check
+// any function we like.
+//@ The standard library's `sort` serves as the *oracle*: We trust that it is correct, and compare
+//@ with its result. This checks both parts of our property at once.
+pub fn check_sort<F: Fn(&mut [i32])>(sort: F) {
+    let mut rng = Rng::new(42);
+    for _ in 0..1000 {
+        let original = rng.vec(40, 10);
+        let mut expected = original.clone();                        /*@*/
+        expected.sort();                                            /*@*/
+        let mut data = original.clone();                            /*@*/
+        sort(&mut data);                                            /*@*/
+        //@ If the property fails, we print the input. That's what you need to reproduce the bug.
+        assert_eq!(data, expected, "Sorting {:?} went wrong", original); /*@*/
+    }
+}
+//@ A proper property-testing library would additionally try to *shrink* a failing input: Remove
+//@ elements and make numbers smaller, as long as the test keeps failing. That way, you get the
+//@ smallest example that shows the bug.
+
+// ## Bug hunting
+//@ Now it's your turn. Below are some functions with bugs in them. For each of them, write a test
+//@ that fails because of the bug. Once you have such a test, you can fix the bug, and the test
+//@ makes sure it never comes back. The first one is done already, see `test_sort_v1_buggy` below.
+//@ Notice that the bugs do not show up on the examples in the comments!
+
+// Sort by repeatedly moving the largest element to the end. Try `[3, 1, 2]`.
+pub fn sort_v1<T: PartialOrd>(data: &mut [T]) {
+    if data.is_empty() { return; }
+    for end in (1..data.len()).rev() {
+        let mut max = 0;
+        for i in 1..end {
+            if data[i] > data[max] {
+                max = i;
+            }
+        }
+        data.swap(max, end);
+    }
+}
+
+// An "optimized" sort: Sort both halves separately, then merge them. Try `[4, 3, 2, 1]`.
+pub fn sort_v2(data: &mut [i32]) {
+    if data.len() <= 8 {
+        return insertion_sort(data);
+    }
+    let mid = data.len() / 2;
+    sort_v2(&mut data[..mid]);
+    sort_v2(&mut data[mid..]);
+    let mut merged = Vec::with_capacity(data.len());
+    let (mut i, mut j) = (0, mid);
+    while i < mid && j < data.len() {
+        if data[i] <= data[j] {
+            merged.push(data[i]);
+            i += 1;
+        } else {
+            merged.push(data[j]);
+            j += 1;
+        }
+    }
+    merged.extend_from_slice(&data[i..mid]);
+    data.copy_from_slice(&merged[..data.len()]);
+}
+
+// Add two numbers given as digits (least significant digit first), like our `BigInt`. Try adding
+// `[1, 2]` and `[3]`.
+pub fn add_digits(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut result = Vec::new();
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).cloned().unwrap_or(0);
+        let y = b.get(i).cloned().unwrap_or(0);
+        let (sum, overflow1) = x.overflowing_add(y);
+        let (sum, overflow2) = sum.overflowing_add(carry);
+        result.push(sum);
+        carry = if overflow1 || overflow2 { 1 } else { 0 };
+    }
+    result
+}
+
+// **Exercise 23.1**: Write a test that catches the bug in `sort_v2`. Then fix the bug.
+
+// **Exercise 23.2**: Write a property-based test for `add_digits`. Use numbers with up to two
+// digits, so that you can compare with addition on `u128` (which is big enough to hold the sum).
+// Then fix the bug. Hint: `as u64` cuts off the upper 64 bits of a `u128`.
+
+// **Exercise 23.3**: If you did exercise 14.1, use `check_sort` to test your Quicksort.
+
+// **Exercise 23.4**: Add integration tests to your `bigint` crate from part 22. Move the `Rng` to
+// `tests/common/mod.rs`, and use it to check that `a + b - b == a` for random numbers.
+
+// A complete set of integration tests for the crate of part 22 can be found in the `solutions`
+// directory, under `mini-bigint/tests`.
+
+#[cfg(test)]
+mod tests {
+    use super::{insertion_sort, check_sort, sort_v1, Rng};
+
+    #[test]
+    fn test_insertion_sort() {
+        let mut v = vec![5, 3, 8, 1, 3];
+        insertion_sort(&mut v);
+        assert_eq!(v, vec![1, 3, 3, 5, 8]);
+        check_sort(insertion_sort);
+    }
+
+    #[test]
+    fn test_sort_v1() {
+        // This looks fine...
+        let mut v = vec![3, 1, 2];
+        sort_v1(&mut v);
+        assert_eq!(v, vec![1, 2, 3]);
+    }
+
+    // ...but the property-based test finds the bug: `sort_v1` never looks at `data[end]`.
+    #[test]
+    #[should_panic(expected = "went wrong")]
+    fn test_sort_v1_buggy() {
+        check_sort(sort_v1);
+    }
+
+    // A test returning a `Result` can use `?`.
+    #[test]
+    fn test_parse_and_sort() -> Result<(), ::std::num::ParseIntError> {
+        let mut v = Vec::new();
+        for s in "12 7 -3".split(' ') {
+            v.push(s.parse::<i32>()?);
+        }
+        insertion_sort(&mut v);
+        assert_eq!(v, vec![-3, 7, 12]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rng() {
+        // The same seed always yields the same numbers.
+        assert_eq!(Rng::new(1).vec(10, 100), Rng::new(1).vec(10, 100));
+        assert!(Rng::new(7).vec(10, 5).iter().all(|x| (0..5).contains(x)));
+    }
+}
+
+//@ [index](main.html) | [previous](part22.html) | [raw source](workspace/src/part23.rs) |
+//@ [next](part24.html)
@@ -0,0 +1,144 @@
+// Rust-101, Part 24: Const Generics, Fixed-Size Arrays
+// ====================================================
+
+use std::ops;
+use part14::sort;
+
+//@ Back in part 14, I briefly introduced arrays: `[T; n]` is the type of exactly `n` elements of
+//@ type `T`, stored right next to each other. Back then, we quickly turned our arrays into slices,
+//@ and forgot about their length. In this part, we will see how to write code that works for arrays
+//@ of *any* length, without giving up on the fact that the length is known at compile-time.
+
+// ## Generic over the length
+//@ Here's the `sort_array` function from part 14 again. It only works for arrays of exactly 5
+//@ floating-point numbers. We can make it generic over the element type as usual, but what about
+//@ the length? It turns out that we can be generic over that, too! `const N: usize` declares a
+//@ *const generic* parameter: Rather than a type, it stands for a value of type `usize`, which is
+//@ fixed at compile-time. Inside the function, we can use `N` like a constant.
+pub fn sort_array<T: PartialOrd, const N: usize>(mut array: [T; N]) -> [T; N] {
+    sort(&mut array);
+    array
+}
+//@ Taking the array by value and returning it makes sense here: An array is not behind a pointer,
+//@ so this just moves the elements. The caller does not have to write the length; Rust infers it
+//@ from the argument, just like it infers `T`.
+fn sort_array_demo() {
+    let sorted = sort_array([1.0, 3.4, 12.7, -9.12, 0.1]);
+    let words = sort_array(["cow", "bull"]);
+    println!("{:?} {:?}", sorted, words);
+}
+
+// Since `N` is a constant, we can use it to create new arrays of the same length.
+//@ `[x; N]` creates an array with `N` copies of `x`. That only works if `x` is `Copy` (or a
+//@ constant), because Rust has to duplicate it.
+pub fn squares<const N: usize>(array: [i64; N]) -> [i64; N] {
+    let mut result = [0; N];
+    for i in 0..N {
+        result[i] = array[i] * array[i];                            /*@*/
+    }
+    result
+}
+
+// ## Matrices
+//@ Const generics really shine for types whose *shape* is part of their type. A matrix with `R`
+//@ rows and `C` columns is an array of `R` rows, each of which is an array of `C` numbers. There
+//@ are no pointers and no heap allocations: A `Matrix<2, 3>` is just six `f64` in a row.
+//@ All the standard traits we derive work for arrays of any length, so we can derive them here.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Matrix<const R: usize, const C: usize> {
+    data: [[f64; C]; R],
+}
+
+impl<const R: usize, const C: usize> Matrix<R, C> {
+    pub fn new(data: [[f64; C]; R]) -> Self {
+        Matrix { data }
+    }
+
+    pub fn zero() -> Self {
+        Matrix { data: [[0.0; C]; R] }
+    }
+
+    // Transposing swaps rows and columns. Notice the return type: The dimensions are swapped as
+    // well.
+    pub fn transpose(&self) -> Matrix<C, R> {
+        let mut result = Matrix::zero();
+        for i in 0..R {
+            for j in 0..C {
+                result.data[j][i] = self.data[i][j];                /*@*/
+            }
+        }
+        result
+    }
+}
+
+// Adding two matrices only makes sense if they have the same dimensions. With const generics, the
+// compiler checks that for us.
+impl<const R: usize, const C: usize> ops::Add for Matrix<R, C> {
+    type Output = Self;
+    //@ `Matrix` is `Copy`, so we can take the operands by value without worrying about ownership.
+    fn add(self, rhs: Self) -> Self {
+        let mut result = self;
+        for i in 0..R {
+            for j in 0..C {
+                result.data[i][j] += rhs.data[i][j];                /*@*/
+            }
+        }
+        result
+    }
+}
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let a = Matrix::new([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+    let b = Matrix::new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+    println!("{:?}", a + b);
+    println!("{:?}", a.transpose());
+    /* println!("{:?}", a + a.transpose()); */                      /* BAD! */
+    println!("{:?}", squares([1, 2, 3]));
+    sort_array_demo();
+}
+//@ If you enable the bad line, Rust complains that it expected `Matrix<2, 3>`, but found
+//@ `Matrix<3, 2>`. In a language without const generics, you would store the dimensions in fields,
+//@ and this mistake would only show up at run-time (hopefully as a panic, and not as garbage).
+
+//@ ## Limitations
+//@ Const generics do have limits. On stable Rust, the value of a const parameter can only be
+//@ an integer, `bool` or `char`, and you cannot compute with const parameters in types: A function
+//@ `fn push<const N: usize>(a: [T; N], x: T) -> [T; N + 1]` is not (yet) accepted. Slices remain
+//@ the right tool when the length is only known at run-time.
+
+// **Exercise 24.1**: Implement `ops::Mul` for matrices. The product of a `Matrix<R, K>` and a
+// `Matrix<K, C>` is a `Matrix<R, C>`. Which generic parameters does the `impl` need?
+
+// **Exercise 24.2**: Write a function `identity` that returns the identity matrix. It only makes
+// sense for square matrices, so put it in an `impl<const N: usize> Matrix<N, N>` block.
+
+// **Exercise 24.3**: Write a function `first_n<const N: usize>(v: &[i64]) -> Option<[i64; N]>`
+// that returns the first `N` elements of the slice as an array, or `None` if there are not enough
+// of them. Hint: There is an implementation of `TryFrom<&[T]>` for `[T; N]`. You will need
+// `use std::convert::TryInto;` to call `try_into`.
+
+#[cfg(test)]
+mod tests {
+    use super::{Matrix, squares};
+
+    #[test]
+    fn test_squares() {
+        assert_eq!(squares([1, -2, 3]), [1, 4, 9]);
+        assert_eq!(squares([]), []);
+    }
+
+    #[test]
+    fn test_matrix() {
+        let a = Matrix::new([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+        let t = Matrix::new([[1.0, 4.0], [2.0, 5.0], [3.0, 6.0]]);
+        assert_eq!(a.transpose(), t);
+        assert_eq!(t.transpose(), a);
+        assert_eq!(a + Matrix::zero(), a);
+        assert_eq!(a + a, Matrix::new([[2.0, 4.0, 6.0], [8.0, 10.0, 12.0]]));
+        assert_eq!((a + a).transpose(), t + t);
+    }
+}
+
+//@ [index](main.html) | [previous](part23.html) | [raw source](workspace/src/part24.rs) |
+//@ [next](part25.html)
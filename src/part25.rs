@@ -0,0 +1,236 @@
+// Rust-101, Part 25: Trait Objects, Downcasting
+// =============================================
+
+use std::any::Any;
+use std::fmt;
+use part17::fold_case;
+
+//@ In part 11, we met trait objects: `Box<FnMut(i32)>` is a pointer to *some* closure, and we only
+//@ find out at run-time which code gets executed when we call it. At the end of that part, I
+//@ mentioned that not every trait can be turned into a trait object. In this part, we will look at
+//@ these restrictions more closely, and at some more things we can do with trait objects.
+//@
+//@ Our running example is rgrep once more. So far, what it searches for and what it does with the
+//@ results is hard-coded in `filter_lines` and `output_lines`. Let us make both of these pluggable:
+//@ A `Matcher` decides which lines we are interested in, and a `Sink` receives the matching lines.
+
+// ## Supertraits
+//@ We would like to be able to print matchers for debugging. By writing `Matcher: fmt::Debug`, we
+//@ declare `Debug` to be a *supertrait* of `Matcher`: Every type implementing `Matcher` must also
+//@ implement `Debug`. In return, we can use `Debug` on everything that is a `Matcher` - including
+//@ trait objects `dyn Matcher`. (`dyn` is the keyword that marks a trait object. In part 11, we
+//@ left it away, which is allowed in old code but considered bad style nowadays.)
+pub trait Matcher: fmt::Debug {
+    fn is_match(&self, line: &str) -> bool;
+}
+
+// We provide some matchers: Searching for a substring, the same ignoring case, and negation of
+// another matcher.
+#[derive(Debug)]
+pub struct Contains(pub String);
+impl Matcher for Contains {
+    fn is_match(&self, line: &str) -> bool {
+        line.contains(&self.0[..])                                  /*@*/
+    }
+}
+
+#[derive(Debug)]
+pub struct ContainsIgnoreCase(pub String);
+impl Matcher for ContainsIgnoreCase {
+    fn is_match(&self, line: &str) -> bool {
+        fold_case(line).contains(&*fold_case(&self.0))              /*@*/
+    }
+}
+
+//@ `Not` contains another matcher, which could be of any type. That's a job for a trait object.
+#[derive(Debug)]
+pub struct Not(pub Box<dyn Matcher>);
+impl Matcher for Not {
+    fn is_match(&self, line: &str) -> bool {
+        !self.0.is_match(line)                                      /*@*/
+    }
+}
+
+// ## Object safety
+//@ Why can we not make *every* trait into a trait object? Remember that a trait object consists of
+//@ a pointer to the data, and a pointer to the vtable, which contains one function pointer for
+//@ every method. So, the compiler must be able to put every method into such a table. Here are
+//@ some methods that do not fit:
+//@
+//@ * Generic methods, like `fn match_all<I: Iterator<Item=String>>(&self, lines: I)`. There would
+//@   have to be a separate function for every `I`, and we cannot know all of them when creating
+//@   the vtable.
+//@ * Methods returning `Self`, like `fn duplicate(&self) -> Self`. The caller of this method does not
+//@   know the type `Self` (that's the point of a trait object), so it cannot know how much space it
+//@   has to reserve for the return value.
+//@ * Functions without a `self` argument, like `fn new() -> Self`. There is no object to take the
+//@   vtable from!
+//@
+//@ A trait with any of these is not *object safe* (these days, the official term is *dyn
+//@ compatible*), and `dyn Trait` becomes a compile error. Here's an example:
+pub trait Duplicate {
+    fn duplicate(&self) -> Self;
+}
+/* fn duplicate_all(v: &Vec<Box<dyn Duplicate>>) { } */             /* BAD! */
+//@ There's an escape hatch: A method with the bound `where Self: Sized` can only be called on types
+//@ whose size is known, which excludes trait objects. Thus, such a method does not go into the
+//@ vtable, and the rest of the trait can still be used as a trait object. We use this below to
+//@ provide a convenience method that boxes a matcher and negates it.
+pub trait MatcherExt: Matcher {
+    fn negate(self) -> Not where Self: Sized + 'static {
+        Not(Box::new(self))
+    }
+}
+impl<M: Matcher> MatcherExt for M {}
+
+// ## Sinks, and `Any`
+//@ A `Sink` receives all the matching lines. Some sinks print them, some count them, some collect
+//@ them. `finish` is called once at the end.
+pub trait Sink: Any {
+    fn accept(&mut self, line: &str);
+    fn finish(&mut self) {}
+}
+
+pub struct Print;
+impl Sink for Print {
+    fn accept(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+pub struct Count(pub usize);
+impl Sink for Count {
+    fn accept(&mut self, _line: &str) {
+        self.0 += 1;                                                /*@*/
+    }
+    fn finish(&mut self) {
+        println!("{} hits for this pattern.", self.0);
+    }
+}
+
+pub struct Collect(pub Vec<String>);
+impl Sink for Collect {
+    fn accept(&mut self, line: &str) {
+        self.0.push(line.to_string());                              /*@*/
+    }
+}
+
+// Now we can write the core of rgrep, without knowing anything about what we match, or where the
+// results go.
+//@ We take both arguments as trait objects behind references. The function is compiled only once,
+//@ and calls the methods through the vtables.
+pub fn grep<'a, I: Iterator<Item=&'a str>>(lines: I, matcher: &dyn Matcher, sink: &mut dyn Sink) {
+    for line in lines {
+        if matcher.is_match(line) {                                 /*@*/
+            sink.accept(line);                                      /*@*/
+        }                                                           /*@*/
+    }
+    sink.finish();
+}
+
+//@ Sometimes, after handing a trait object around, we would like to have the concrete type back.
+//@ For example, after the search is done, we would like to get our hands on the lines that the
+//@ `Collect` sink collected. That's what
+//@ [`Any`](https://doc.rust-lang.org/stable/std/any/trait.Any.html) is for: It is implemented by
+//@ all types that do not contain (non-`'static`) references, and it provides `downcast_ref` and
+//@ `downcast_mut`, which check at run-time whether the object is of the given type. (They compare a
+//@ unique identifier of the type, the `TypeId`, that is stored in the vtable.)
+//@
+//@ We declared `Any` as a supertrait of `Sink`, so we can turn a `&dyn Sink` into a `&dyn Any`. This
+//@ is called *upcasting*, and is available since Rust 1.86. (Before that, people would add a method
+//@ `fn as_any(&self) -> &dyn Any` to their trait, implemented as `self` in every `impl`.)
+pub fn collected(sink: &dyn Sink) -> Option<&Vec<String>> {
+    let sink: &dyn Any = sink;
+    sink.downcast_ref::<Collect>().map(|c| &c.0)                    /*@*/
+}
+
+// ## `Box<dyn Trait>` and `&dyn Trait`
+//@ Finally, let's talk about the different pointers we can put trait objects behind. A `Box<dyn
+//@ Matcher>` owns the matcher, while a `&dyn Matcher` borrows it. Just like for `String` and
+//@ `&str`, we can go from the former to the latter for free: `&*b` dereferences the box, and
+//@ borrows the trait object inside. The other direction is not possible without making a copy.
+//@
+//@ Also, a `Box<dyn Matcher>` is not itself a `Matcher` - it's a pointer to one. Functions that
+//@ are generic over `M: Matcher` would not accept it. We can fix that by *forwarding* the trait
+//@ through the box. Note the `?Sized`: It says that `M` may also be unsized, like `dyn Matcher`.
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn is_match(&self, line: &str) -> bool {
+        (**self).is_match(line)                                     /*@*/
+    }
+}
+
+// This function is generic, but thanks to the implementation above, it also accepts boxes.
+pub fn count_matches<M: Matcher>(lines: &[&str], matcher: M) -> usize {
+    lines.iter().filter(|line| matcher.is_match(line)).count()
+}
+
+// Let us try this on some source code. Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let text = "fn main() {\n    println!(\"Hello\");\n}\n// Say HELLO to Rust";
+    let matchers: Vec<Box<dyn Matcher>> = vec![
+        Box::new(Contains("Hello".to_string())),
+        Box::new(ContainsIgnoreCase("hello".to_string())),
+        Box::new(Contains("Hello".to_string()).negate()),
+    ];
+    for matcher in matchers.iter() {
+        println!("Matcher {:?}:", matcher);
+        //@ `&**matcher` first dereferences the reference into the vector, then the box, and then
+        //@ borrows the trait object.
+        grep(text.lines(), &**matcher, &mut Count(0));
+    }
+
+    let mut sink = Collect(Vec::new());
+    grep(text.lines(), &ContainsIgnoreCase("HELLO".to_string()), &mut sink);
+    println!("{:?}", collected(&sink));
+    println!("{}", count_matches(&["a", "b"], Box::new(Contains("a".to_string()))));
+}
+
+// **Exercise 25.1**: Add a matcher `All(Vec<Box<dyn Matcher>>)` that matches if all of the given
+// matchers match. Then add `Any` along the same lines. Can you name it `Any`?
+
+// **Exercise 25.2**: We cannot clone a `Box<dyn Matcher>`, because `Clone` is not object safe (why?).
+// Add a method `fn box_clone(&self) -> Box<dyn Matcher>` to `Matcher`, and use it to implement
+// `Clone` for `Box<dyn Matcher>`.
+
+// **Exercise 25.3**: Change rgrep from part 13 to use a `Box<dyn Matcher>` and `Box<dyn Sink>`.
+// What do you have to change to send them across threads? Hint: Look at part 15.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "First line\nsecond LINE\nthird";
+
+    #[test]
+    fn test_matchers() {
+        let mut sink = Collect(Vec::new());
+        grep(TEXT.lines(), &Contains("line".to_string()), &mut sink);
+        assert_eq!(sink.0, vec!["First line"]);
+
+        let mut sink = Collect(Vec::new());
+        grep(TEXT.lines(), &ContainsIgnoreCase("line".to_string()), &mut sink);
+        assert_eq!(sink.0, vec!["First line", "second LINE"]);
+
+        let mut sink = Count(0);
+        grep(TEXT.lines(), &Contains("line".to_string()).negate(), &mut sink);
+        assert_eq!(sink.0, 2);
+    }
+
+    #[test]
+    fn test_downcast() {
+        let mut sink = Collect(Vec::new());
+        grep(TEXT.lines(), &Contains("ir".to_string()), &mut sink);
+        assert_eq!(collected(&sink), Some(&vec!["First line".to_string(), "third".to_string()]));
+        assert_eq!(collected(&Count(0)), None);
+    }
+
+    #[test]
+    fn test_box_forwarding() {
+        let boxed: Box<dyn Matcher> = Box::new(Contains("i".to_string()));
+        assert_eq!(count_matches(&["hi", "ho", "i"], boxed), 2);
+        assert_eq!(format!("{:?}", Contains("x".to_string()).negate()), "Not(Contains(\"x\"))");
+    }
+}
+
+//@ [index](main.html) | [previous](part24.html) | [raw source](workspace/src/part25.rs) |
+//@ [next](part26.html)
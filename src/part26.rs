@@ -0,0 +1,291 @@
+// Rust-101, Part 26: Drop Order, ManuallyDrop, Leaks
+// ==================================================
+
+use std::mem::{self, ManuallyDrop};
+use std::ptr;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+//@ In part 16, we wrote our first destructor, for the linked list. In this part, we look closer at
+//@ *when* destructors run, and at how we can stop them from running - on purpose, or by accident.
+
+// ## Drop order
+//@ To observe the order in which things are dropped, we need a type that tells us when it is
+//@ dropped. `Noisy` has a name, and a shared log (remember `Rc<RefCell<...>>` from part 12). When
+//@ it is dropped, it writes its name to the log.
+pub struct Noisy {
+    name: &'static str,
+    log: Rc<RefCell<Vec<&'static str>>>,
+}
+
+impl Noisy {
+    pub fn new(name: &'static str, log: &Rc<RefCell<Vec<&'static str>>>) -> Self {
+        Noisy { name, log: log.clone() }
+    }
+}
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        self.log.borrow_mut().push(self.name);
+    }
+}
+
+// A pair of two noisy values, to see in which order fields are dropped.
+pub struct Pair {
+    pub first: Noisy,
+    pub second: Noisy,
+}
+
+//@ Now, let's create some noisy values. Try to predict the contents of the log before you read on.
+pub fn drop_order_demo(log: &Rc<RefCell<Vec<&'static str>>>) {
+    let _a = Noisy::new("a", log);
+    let _b = Noisy::new("b", log);
+    let _pair = Pair { first: Noisy::new("first", log), second: Noisy::new("second", log) };
+    let c = Noisy::new("c", log);
+    //@ `drop` is not magic: It is a completely normal function that takes ownership of its argument,
+    //@ and then does nothing with it. At the end of `drop`, the argument goes out of scope, and
+    //@ that's when it is dropped.
+    drop(c);
+    //@ Writing `let _ = ...` does *not* bind the value to anything, so it is dropped right away. In
+    //@ contrast, `_a` above is a normal variable that lives until the end of the function.
+    let _ = Noisy::new("temporary", log);
+    let d = Noisy::new("d", log);
+    let _moved = d;
+}
+//@ The log reads: `c`, `temporary`, `d`, `first`, `second`, `b`, `a`. Local variables are dropped
+//@ at the end of their scope, in *reverse* order of their declaration. That makes sense: Later
+//@ variables may borrow earlier ones, so they have to go first. `d` was moved to `_moved`, and it
+//@ is dropped when `_moved` goes out of scope - exactly once. Fields of a struct, on the other hand,
+//@ are dropped in the order they are declared, and so are the elements of tuples, arrays and
+//@ vectors.
+
+// ## Leaking
+//@ What if we never want a value to be dropped? `mem::forget` takes ownership of a value, and then
+//@ just forgets about it: The destructor never runs. If the value owned some memory, that memory is
+//@ never freed - it is *leaked*.
+//@
+//@ Surprisingly, `mem::forget` is not `unsafe`. Wouldn't it be nice to guarantee that destructors
+//@ always run? It would, but Rust cannot make this guarantee anyway. In part 12 we saw `Rc`, and
+//@ with `Rc` and `RefCell`, you can build a cycle of pointers: Two objects that keep each other
+//@ alive forever, even after everybody else forgot about them. So safe code can leak. As a
+//@ consequence, unsafe code must never rely on a destructor running for safety. A leak is a bug
+//@ (your program consumes more and more memory), but it does not lead to undefined behavior.
+pub fn forget_demo(log: &Rc<RefCell<Vec<&'static str>>>) {
+    let forgotten = Noisy::new("forgotten", log);
+    mem::forget(forgotten);
+}
+
+// ## `ManuallyDrop`
+//@ `mem::forget` is all-or-nothing.
+//@ [`ManuallyDrop<T>`](https://doc.rust-lang.org/stable/std/mem/struct.ManuallyDrop.html) is a
+//@ wrapper that stores a `T`, but never drops it. It dereferences to `T`, so we can still use
+//@ the value inside. If we *do* want to drop the value after all, we have to call the unsafe
+//@ function `ManuallyDrop::drop`. It is unsafe because we have to make sure not to use the value
+//@ anymore afterwards, and not to drop it twice.
+//@
+//@ One use of `ManuallyDrop` is to control the drop order of fields. The destructor of a struct
+//@ runs *before* its fields are dropped, and the fields then go in declaration order. If we wrap
+//@ the fields in `ManuallyDrop`, the automatic part does nothing, and our destructor can drop them
+//@ in whatever order it likes. The following struct drops `second` before `first`.
+pub struct ReversePair {
+    first: ManuallyDrop<Noisy>,
+    second: ManuallyDrop<Noisy>,
+}
+
+impl ReversePair {
+    pub fn new(first: Noisy, second: Noisy) -> Self {
+        ReversePair { first: ManuallyDrop::new(first), second: ManuallyDrop::new(second) }
+    }
+}
+
+impl Drop for ReversePair {
+    fn drop(&mut self) {
+        //@ This is safe because we drop each field exactly once, and never touch them again: After
+        //@ this function returns, the struct is gone.
+        unsafe {
+            ManuallyDrop::drop(&mut self.second);                   /*@*/
+            ManuallyDrop::drop(&mut self.first);                    /*@*/
+        }
+    }
+}
+
+// ## An iterator that leaks
+//@ Now we come to the main example. Let us write an iterator that consumes a list, and returns its
+//@ elements by value - the `IntoIter` of our list. In part 16, the functions taking elements out of
+//@ the list were an exercise. To keep things simple, here's a singly-linked stack that works the
+//@ same way. Instead of `transmute`, we use `Box::into_raw` and `Box::from_raw`, which have since
+//@ been added to the standard library.
+struct Node<T> {
+    next: *mut Node<T>,
+    data: T,
+}
+
+pub struct Stack<T> {
+    head: *mut Node<T>,
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Stack { head: ptr::null_mut() }
+    }
+
+    pub fn push(&mut self, data: T) {
+        let node = Box::new(Node { next: self.head, data });
+        self.head = Box::into_raw(node);
+    }
+}
+
+// This helper takes the first node off a chain of nodes, and returns its data.
+//@ It is `unsafe`, because `head` has to point to a valid chain of nodes that nobody else uses.
+unsafe fn pop_node<T>(head: &mut *mut Node<T>) -> Option<T> {
+    if head.is_null() {
+        return None;
+    }
+    let node = Box::from_raw(*head);
+    *head = node.next;
+    Some(node.data)
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        while let Some(data) = unsafe { pop_node(&mut self.head) } {
+            drop(data);
+        }
+    }
+}
+
+// The iterator owns the remaining nodes.
+pub struct IntoIter<T> {
+    next: *mut Node<T>,
+}
+
+impl<T> IntoIterator for Stack<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        //@ Here's a problem: When `into_iter` returns, `self` goes out of scope, and the destructor
+        //@ of `Stack` frees all the nodes - but the iterator still points to them! We have to make
+        //@ sure that `self` is *not* dropped. That's what `ManuallyDrop` does.
+        let this = ManuallyDrop::new(self);
+        IntoIter { next: this.head }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        unsafe { pop_node(&mut self.next) }
+    }
+}
+//@ This works fine if the iterator is consumed completely. But what if the caller stops early,
+//@ like in `for x in stack { if ... { break; } }`? Then the iterator is dropped while it still owns
+//@ some nodes. `IntoIter` has no destructor, so these nodes are never freed, and the elements in
+//@ there are never dropped. We have a leak! Rust did not complain, because leaking is safe. The
+//@ test `test_into_iter_leak` below demonstrates the problem.
+
+// **Exercise 26.1**: Fix the leak by implementing `Drop` for `IntoIter`. Then change
+// `test_into_iter_leak` to check that all elements are dropped.
+
+// **Exercise 26.2**: Write a method `into_parts(self) -> (Noisy, Noisy)` for `ReversePair`. Why
+// can you not just move the fields out of `self`? Hint: Look at `ManuallyDrop::take` and
+// `mem::forget`.
+
+// **Exercise 26.3**: Implement `IntoIterator` for the `LinkedList` of part 16, once you did the
+// `pop` functions of exercise 16.1. Make sure it does not leak.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    drop_order_demo(&log);
+    forget_demo(&log);
+    println!("Drop order: {:?}", log.borrow());
+
+    let mut stack = Stack::new();
+    for i in 0..5 {
+        stack.push(i);
+    }
+    for i in stack {
+        println!("{}", i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::cell::{Cell, RefCell};
+    use super::*;
+
+    // Counts how many clones of it have been dropped, like in the tests of the part 16
+    // solution (`solutions/src/list.rs`).
+    #[derive(Clone)]
+    struct DropChecker {
+        count: Rc<Cell<usize>>,
+    }
+    impl Drop for DropChecker {
+        fn drop(&mut self) {
+            self.count.set(self.count.get() + 1);
+        }
+    }
+
+    fn stack_of(checker: &DropChecker, n: usize) -> Stack<DropChecker> {
+        let mut stack = Stack::new();
+        for _ in 0..n {
+            stack.push(checker.clone());
+        }
+        stack
+    }
+
+    #[test]
+    fn test_drop_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        drop_order_demo(&log);
+        assert_eq!(*log.borrow(), vec!["c", "temporary", "d", "first", "second", "b", "a"]);
+        forget_demo(&log);
+        assert_eq!(log.borrow().len(), 7);
+    }
+
+    #[test]
+    fn test_manually_drop() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        {
+            let _pair = Pair { first: Noisy::new("first", &log), second: Noisy::new("second", &log) };
+            let _reverse = ReversePair::new(Noisy::new("r-first", &log), Noisy::new("r-second", &log));
+        }
+        assert_eq!(*log.borrow(), vec!["r-second", "r-first", "first", "second"]);
+    }
+
+    #[test]
+    fn test_stack_drop() {
+        let checker = DropChecker { count: Rc::new(Cell::new(0)) };
+        drop(stack_of(&checker, 10));
+        assert_eq!(checker.count.get(), 10);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let checker = DropChecker { count: Rc::new(Cell::new(0)) };
+        assert_eq!(stack_of(&checker, 5).into_iter().count(), 5);
+        assert_eq!(checker.count.get(), 5);
+
+        let mut stack = Stack::new();
+        for i in 0..3 {
+            stack.push(i);
+        }
+        assert_eq!(stack.into_iter().collect::<Vec<_>>(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_into_iter_leak() {
+        let checker = DropChecker { count: Rc::new(Cell::new(0)) };
+        {
+            let mut iter = stack_of(&checker, 5).into_iter();
+            iter.next();
+            iter.next();
+        }
+        // Only the two elements we took out were dropped, the other three leaked.
+        assert_eq!(checker.count.get(), 2);
+    }
+}
+
+//@ [index](main.html) | [previous](part25.html) | [raw source](workspace/src/part26.rs) |
+//@ [next](part27.html)
@@ -0,0 +1,243 @@
+// Rust-101, Part 27: PhantomData, Variance
+// ========================================
+
+use std::marker::PhantomData;
+use std::cell::Cell;
+
+//@ In part 16, we added a field of type `PhantomData<&'a mut LinkedList<T>>` to `IterMut`, and I
+//@ said that it tells Rust about our intent. In this part, we will see what exactly that intent is,
+//@ and what would go wrong if we picked the wrong marker. The key concept here is *variance*.
+
+// ## Subtyping and variance
+//@ Rust does not have subtyping between structs, like object-oriented languages do. However, there
+//@ is subtyping between *lifetimes*: A reference that lives longer can be used where a reference
+//@ that lives shorter is expected. For example, `&'static str` is a subtype of `&'a str`, for any
+//@ `'a`. That's why the following function is accepted.
+pub fn shorten<'a>(s: &'static str) -> &'a str {
+    s
+}
+
+//@ What about types that *contain* references? If `&'static str` is a subtype of `&'a str`, is
+//@ `Vec<&'static str>` a subtype of `Vec<&'a str>`? It is! We say that `Vec<T>` is *covariant* in
+//@ `T`: Subtyping of `T` carries over to `Vec<T>`. The same goes for `Box<T>`, `Option<T>`, `&'b
+//@ T`, and structs and tuples made from such types.
+pub fn shorten_vec<'a>(v: Vec<&'static str>) -> Vec<&'a str> {
+    v
+}
+
+//@ Not every type is covariant, though. Consider the following function, which adds a string to a
+//@ vector of strings.
+pub fn push_str<'a>(v: &mut Vec<&'a str>, s: &'a str) {
+    v.push(s);
+}
+//@ Now imagine `&'b mut T` were covariant in `T`. Then we could turn a `&mut Vec<&'static str>`
+//@ into a `&mut Vec<&'a str>`, and push a short-lived string into a vector that everybody else
+//@ believes to only contain strings that live forever:
+/*
+  fn dangle(v: &mut Vec<&'static str>) {
+      let s = String::from("short-lived");
+      push_str(v, &s);
+  }
+*/
+//@ After `dangle` returns, `s` is deallocated, but the vector still points to it. Rust rejects
+//@ this, because `&'b mut T` is *invariant* in `T`: A `&mut Vec<&'static str>` can only be used as
+//@ exactly that, never as `&mut Vec<&'a str>`. The lifetime of the mutable reference itself, `'b`,
+//@ can still be shortened - that's always safe. We can see the difference in the following two
+//@ functions. The first one is fine, the second one (in the comment) is rejected.
+pub fn shorten_mut_ref<'a, 'b>(v: &'a mut Vec<&'b str>) -> &'a mut Vec<&'b str> {
+    v
+}
+/*
+  fn shorten_content<'a, 'b>(v: &'a mut Vec<&'static str>) -> &'a mut Vec<&'b str> {
+      v
+  }
+*/
+//@ The general rule is: If you can *write* to something, it has to be invariant. `Cell<T>` is
+//@ invariant in `T` for the same reason - remember that you can `set` a cell through a shared
+//@ reference.
+/* fn shorten_cell<'a, 'b>(c: &'a Cell<&'static str>) -> &'a Cell<&'b str> { c } */ /* BAD! */
+pub fn shorten_cell_ref<'a, 'b>(c: &'a Cell<&'b str>) -> &'a Cell<&'b str> {
+    c
+}
+
+// ## Raw pointers and `PhantomData`
+//@ For references and the standard library types, Rust knows the variance already. But how does
+//@ Rust figure out the variance of *our* types? It looks at the fields. A struct is covariant in a
+//@ parameter if all the fields using it are covariant, and invariant if any of them is invariant.
+//@ Raw pointers play by the same rules as references: `*const T` is covariant in `T`, `*mut T` is
+//@ invariant.
+//@
+//@ Here's the problem: A parameter that is not used in any field at all gives Rust no information.
+//@ Rust rejects such types with "parameter `'a` is never used". `PhantomData<X>` is a field of
+//@ size zero that says "act as if this type contained an `X`". This determines the variance, and
+//@ also tells Rust that we may be holding on to an `X` (which matters for `Send`, `Sync`, and for
+//@ when data may be dropped).
+
+//@ To see this in action, let's write our own iterators over slices - the way the standard library
+//@ does it, with raw pointers. `Iter` hands out shared references, so we mark it as containing a
+//@ `&'a T`. This makes `Iter` covariant in both `'a` and `T`, just like `&'a [T]`.
+pub struct Iter<'a, T: 'a> {
+    ptr: *const T,
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    pub fn new(slice: &'a [T]) -> Self {
+        Iter { ptr: slice.as_ptr(), len: slice.len(), _marker: PhantomData }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            None
+        } else {
+            //@ This is safe because `ptr` points to one of the `len` remaining elements of a slice
+            //@ that we borrowed for `'a`.
+            let elt = unsafe { &*self.ptr };                        /*@*/
+            self.ptr = unsafe { self.ptr.add(1) };                  /*@*/
+            self.len -= 1;                                          /*@*/
+            Some(elt)                                               /*@*/
+        }
+    }
+}
+
+//@ `IterMut` hands out mutable references. Just like for the linked list, we use a `*mut T` and a
+//@ marker saying that we borrowed the slice mutably.
+pub struct IterMut<'a, T: 'a> {
+    ptr: *mut T,
+    len: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> IterMut<'a, T> {
+    pub fn new(slice: &'a mut [T]) -> Self {
+        IterMut { ptr: slice.as_mut_ptr(), len: slice.len(), _marker: PhantomData }
+    }
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.len == 0 {
+            None
+        } else {
+            //@ Every element is handed out only once, so the mutable references do not overlap.
+            let elt = unsafe { &mut *self.ptr };                    /*@*/
+            self.ptr = unsafe { self.ptr.add(1) };                  /*@*/
+            self.len -= 1;                                          /*@*/
+            Some(elt)                                               /*@*/
+        }
+    }
+}
+
+// ## Why `IterMut` needs `&'a mut`
+//@ Now, what if we had been sloppy and written `ptr: *const T` and `PhantomData<&'a T>` in
+//@ `IterMut`? We can still cast the pointer to `*mut T` when creating a reference in `next`, so the
+//@ code would compile just fine. But `IterMut` would now be *covariant* in `T`. That means a
+//@ `IterMut<'_, &'static str>` could be used as an `IterMut<'_, &'b str>` for a short-lived `'b`,
+//@ and we would have the same problem as with `dangle` above:
+/*
+  fn dangle_iter(v: &mut Vec<&'static str>) {
+      let s = String::from("short-lived");
+      let mut iter: IterMut<&str> = IterMut::new(v);
+      *iter.next().unwrap() = &s;
+  }
+*/
+//@ With our current definition, this is rejected, because `IterMut` is invariant in `T`. With the
+//@ sloppy definition, this compiles, and `v[0]` is a dangling pointer afterwards. The
+//@ compiler has no way to catch this: It trusts the markers to describe what our unsafe code does.
+//@ So, when writing a type with raw pointers, the `PhantomData` should always mention the
+//@ reference (or `Box`, or ...) that the raw pointers stand for. For `IterMut`, and for the
+//@ `IterMut` of our linked list, that's `&'a mut`.
+//@
+//@ The following function documents the variance of our iterators: `Iter` can be shortened, but
+//@ `IterMut` cannot - the commented-out function is rejected. If you change a marker and Rust starts
+//@ accepting it, you have made the iterator unsound.
+pub fn shorten_iter<'a, 'b>(iter: Iter<'a, &'static str>) -> Iter<'a, &'b str> {
+    iter
+}
+/*
+  fn shorten_iter_mut<'a, 'b>(iter: IterMut<'a, &'static str>) -> IterMut<'a, &'b str> {
+      iter
+  }
+*/
+
+// ## Typed identifiers
+//@ `PhantomData` is not only useful for unsafe code. Sometimes, we want a type parameter just to
+//@ keep different kinds of values apart. Say we have a database of users and of files, and both
+//@ are identified by a number. It is easy to accidentally pass a file's number to a function
+//@ expecting a user's number - the compiler cannot help us if both are just a `u32`.
+
+// **Exercise 27.1**: Write a type `Id<T>` that wraps a `u32`, and uses `PhantomData` to remember
+// *what* it identifies. `Id<User>` and `Id<File>` should be different types, so that mixing them
+// up is a type error. Add a constructor `Id::new(u32)` and a method `raw(&self) -> u32`. Try using
+// `Id<User>` where an `Id<File>` is expected to check that it is rejected.
+//
+// Which marker should you pick? `PhantomData<T>` claims that the `Id` owns a `T`, which is not
+// true, and it makes `Id<T>` `Send` only if `T` is. Try `PhantomData<fn() -> T>` instead. Why is
+// that a better fit?
+
+// **Exercise 27.2**: Make `Id<T>` `Copy`, `Eq` and `Hash`, so that you can use it as a key in a
+// `HashMap`. First try `#[derive(Clone, Copy, ...)]`, and check whether `Id<User>` is `Copy` if
+// `User` is not. Then write the `impl`s by hand. What's the difference?
+
+// **Exercise 27.3**: Our `Iter` and `IterMut` do not work well for zero-sized types: `add(1)` does
+// not move the pointer at all. Does that cause a problem? Write a test with a slice of `()`.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let mut v = vec!["a", "b", "c"];
+    for s in IterMut::new(&mut v) {
+        *s = shorten("x");
+    }
+    for s in shorten_iter(Iter::new(&v)) {
+        println!("{}", s);
+    }
+    let s = String::from("short-lived");
+    let mut short = shorten_vec(v);
+    push_str(&mut short, &s);
+    println!("{:?}", short);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iter() {
+        let v = vec![1, 2, 3];
+        assert_eq!(Iter::new(&v).cloned().collect::<Vec<_>>(), v);
+        assert_eq!(Iter::new(&[] as &[i32]).next(), None);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut v = vec![1, 2, 3];
+        for x in IterMut::new(&mut v) {
+            *x *= 10;
+        }
+        assert_eq!(v, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_variance() {
+        let s = String::from("short-lived");
+        let mut v = shorten_vec(vec!["static"]);
+        push_str(shorten_mut_ref(&mut v), &s);
+        assert_eq!(v, vec!["static", "short-lived"]);
+        let statics = vec!["a", "b"];
+        assert_eq!(shorten_iter(Iter::new(&statics)).count(), 2);
+
+        let cell = Cell::new(shorten("static"));
+        shorten_cell_ref(&cell).set(&s);
+        assert_eq!(cell.get(), "short-lived");
+    }
+}
+
+//@ [index](main.html) | [previous](part26.html) | [raw source](workspace/src/part27.rs) |
+//@ [next](part28.html)
@@ -0,0 +1,327 @@
+// Rust-101, Part 28: Building Cell and RefCell
+// ============================================
+
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+//@ In part 12, we used `Cell` and `RefCell` to mutate data through shared references. Back then, I
+//@ asked you to take it on trust that this is safe. Now that we know about unsafe code, we can open
+//@ the box and build both of them ourselves.
+
+// ## `UnsafeCell`
+//@ Usually, Rust assumes that data behind a shared reference does not change. The compiler relies
+//@ on this for optimizations, so even unsafe code may not mutate through a `&T` - with one
+//@ exception: [`UnsafeCell<T>`](https://doc.rust-lang.org/stable/std/cell/struct.UnsafeCell.html).
+//@ Its method `get` takes `&self` and returns a `*mut T`, and the compiler knows that the data
+//@ inside may change even while there are shared references to the cell. All the types with
+//@ interior mutability are built on top of `UnsafeCell`. Of course, `UnsafeCell` does not tell us
+//@ *when* it is safe to use this pointer - that's up to us.
+
+// ## `MyCell`
+//@ `Cell` makes sure nobody can ever hold a reference to the data inside: `get` returns a copy, and
+//@ `set` overwrites the data. Since nobody is looking at the data while we change it, that's safe.
+pub struct MyCell<T> {
+    value: UnsafeCell<T>,
+}
+
+impl<T> MyCell<T> {
+    pub fn new(value: T) -> Self {
+        MyCell { value: UnsafeCell::new(value) }
+    }
+
+    // Replace the value in the cell, and return the old one.
+    pub fn replace(&self, value: T) -> T {
+        unsafe { ::std::mem::replace(&mut *self.value.get(), value) } /*@*/
+    }
+
+    pub fn set(&self, value: T) {
+        //@ Why not just write `*self.value.get() = value`? That would drop the old value in place.
+        //@ Dropping runs a destructor, which is arbitrary code - what if it has a reference to our
+        //@ cell, and calls `set` again? It would overwrite the value that is in the middle of being
+        //@ dropped. Instead, we take the old value out first, and drop it once the cell is in a
+        //@ consistent state again.
+        let old = self.replace(value);                              /*@*/
+        drop(old);                                                  /*@*/
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Copy> MyCell<T> {
+    pub fn get(&self) -> T {
+        unsafe { *self.value.get() }                                /*@*/
+    }
+}
+//@ `get` makes a copy of the contents, so the caller does not get a reference into the cell. That's
+//@ why `T` has to be `Copy`.
+//@
+//@ There's one more thing that makes `MyCell` safe: It must never be shared across threads. If two
+//@ threads called `set` at the same time, there would be a data race. We don't have to do anything
+//@ for this, though: `UnsafeCell` is not `Sync`, and hence neither is `MyCell`.
+
+// ## `MyRefCell`
+//@ `RefCell` hands out references to its contents, so it has to keep track of them. We use a
+//@ counter (in a `MyCell`, of course): If it is positive, that's the number of shared borrows. `-1`
+//@ means that there is a mutable borrow, and `0` means that the cell is not borrowed at all.
+//@ Notice the `?Sized`: The contents can be a trait object, which will be important for the
+//@ callbacks. The unsized field has to come last, which is why `value` comes after `borrow`.
+pub struct MyRefCell<T: ?Sized> {
+    borrow: MyCell<isize>,
+    value: UnsafeCell<T>,
+}
+
+//@ When borrowing, we do not hand out plain references: We have to know when the borrow ends, so
+//@ that we can update the counter. So we return *guards*, which update the counter when they are
+//@ dropped. `Ref` is the guard for a shared borrow, and `RefMut` for a mutable one.
+pub struct Ref<'a, T: ?Sized + 'a> {
+    cell: &'a MyRefCell<T>,
+}
+
+pub struct RefMut<'a, T: ?Sized + 'a> {
+    cell: &'a MyRefCell<T>,
+}
+
+impl<T> MyRefCell<T> {
+    pub fn new(value: T) -> Self {
+        MyRefCell { borrow: MyCell::new(0), value: UnsafeCell::new(value) }
+    }
+}
+
+impl<T: ?Sized> MyRefCell<T> {
+    // Try to borrow the contents. This fails if the cell is mutably borrowed.
+    pub fn try_borrow(&self) -> Option<Ref<'_, T>> {
+        let borrow = self.borrow.get();
+        if borrow < 0 {                                             /*@*/
+            None                                                    /*@*/
+        } else {                                                    /*@*/
+            self.borrow.set(borrow + 1);                            /*@*/
+            Some(Ref { cell: self })                                /*@*/
+        }                                                           /*@*/
+    }
+
+    // Try to mutably borrow the contents. This fails if the cell is borrowed in any way.
+    pub fn try_borrow_mut(&self) -> Option<RefMut<'_, T>> {
+        if self.borrow.get() != 0 {                                 /*@*/
+            None                                                    /*@*/
+        } else {                                                    /*@*/
+            self.borrow.set(-1);                                    /*@*/
+            Some(RefMut { cell: self })                             /*@*/
+        }                                                           /*@*/
+    }
+
+    // The versions that panic are easy now.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.try_borrow().expect("already mutably borrowed")
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.try_borrow_mut().expect("already borrowed")
+    }
+}
+
+//@ The guards dereference to the contents. This is where we rely on the counter: As long as a
+//@ `Ref` exists, the counter is positive, so nobody can get a `RefMut`, and the shared reference we
+//@ create here does not alias with a mutable one. Notice that the lifetime of the reference we
+//@ return is tied to the guard, not to the cell. That makes sure it cannot outlive the guard.
+impl<'a, T: ?Sized> Deref for Ref<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }                          /*@*/
+    }
+}
+
+impl<'a, T: ?Sized> Drop for Ref<'a, T> {
+    fn drop(&mut self) {
+        self.cell.borrow.set(self.cell.borrow.get() - 1);           /*@*/
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RefMut<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }                          /*@*/
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.value.get() }                      /*@*/
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RefMut<'a, T> {
+    fn drop(&mut self) {
+        self.cell.borrow.set(0);                                    /*@*/
+    }
+}
+//@ Remember from part 26 that destructors may not run: Someone could `mem::forget` a guard. Then
+//@ the counter is never decremented, and the cell stays borrowed forever. Every later attempt to
+//@ borrow it panics - annoying, but safe. It would be a serious bug if forgetting a guard allowed
+//@ *more* borrows, rather than fewer.
+//@
+//@ The real `RefCell` is a bit more careful than we are: It makes sure that the counter does not
+//@ overflow, and it provides some additional methods. But the core is exactly what we did here.
+
+// ## Back to the callbacks
+// Here's `CallbacksMut` from part 12 again, with our own `MyRefCell` instead of `RefCell`.
+//@ (We also gave the type of the callbacks a name, to keep things readable.) Nothing else had to
+//@ change. In particular, we can still put a closure of type `F` into an `Rc<MyRefCell<F>>`, and
+//@ turn it into an `Rc<MyRefCell<dyn FnMut(i32)>>`. This works because the closure is the last
+//@ field of our cell, so Rust knows how to "unsize" the entire cell.
+type Callback = Rc<MyRefCell<dyn FnMut(i32)>>;
+
+#[derive(Clone)]
+pub struct CallbacksMut {
+    callbacks: Vec<Callback>,
+}
+
+impl CallbacksMut {
+    pub fn new() -> Self {
+        CallbacksMut { callbacks: Vec::new() }
+    }
+
+    pub fn register<F: FnMut(i32) + 'static>(&mut self, callback: F) {
+        let cell = Rc::new(MyRefCell::new(callback));
+        self.callbacks.push(cell);
+    }
+
+    pub fn call(&mut self, val: i32) {
+        for callback in self.callbacks.iter() {
+            let mut closure = callback.borrow_mut();
+            (*closure)(val);
+        }
+    }
+}
+
+// The demo from part 12 works just like before.
+fn demo_mut(c: &mut CallbacksMut) {
+    c.register(|val| println!("Callback 1: {}", val));
+    c.call(0);
+
+    {
+        let mut count: usize = 0;
+        c.register(move |val| {
+            count += 1;
+            println!("Callback 2: {} ({}. time)", val, count);
+        } );
+    }
+    c.call(1); c.clone().call(2);
+}
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let mut c = CallbacksMut::new();
+    demo_mut(&mut c);
+
+    let cell = MyRefCell::new(vec![1, 2, 3]);
+    cell.borrow_mut().push(4);
+    println!("{:?}, still borrowed: {}", *cell.borrow(), cell.try_borrow_mut().is_none());
+}
+//@ What about reentrancy? Exercise 12.1 asked you to make `RefCell` panic by calling a callback
+//@ from within itself. With `MyRefCell`, that panics just the same - see `test_reentrant` below.
+
+// **Exercise 28.1**: Add a method `take` to `MyCell<T>` that returns the contents and leaves
+// `T::default()` in their place. For which `T` does this make sense?
+
+// **Exercise 28.2**: Write an associated function `Ref::map` that turns a `Ref<'a, T>` into a guard
+// for some part of the data, e.g., a `Ref` of a struct into a guard of one of its fields. What
+// would you have to change in the definition of `Ref`? Hint: Instead of the cell, store a
+// `&'a MyCell<isize>` and a `*const U`.
+
+// **Exercise 28.3**: Why would it be a bad idea to implement `Sync` for `MyRefCell`? Think of two
+// threads calling `borrow_mut` at the same time. What would you have to use instead of `MyCell`
+// for the counter to make this safe? (Look ahead at part 15 if you need a hint.)
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use super::*;
+
+    #[test]
+    fn test_cell() {
+        let c = MyCell::new(5);
+        let r1 = &c;
+        let r2 = &c;
+        r1.set(r2.get() + 1);
+        assert_eq!(r2.replace(10), 6);
+        assert_eq!(c.into_inner(), 10);
+    }
+
+    #[test]
+    fn test_refcell() {
+        let c = MyRefCell::new(String::from("hello"));
+        {
+            let b1 = c.borrow();
+            let b2 = c.borrow();
+            assert_eq!(*b1, *b2);
+            assert!(c.try_borrow_mut().is_none());
+        }
+        c.borrow_mut().push_str(" world");
+        {
+            let _m = c.borrow_mut();
+            assert!(c.try_borrow().is_none());
+            assert!(c.try_borrow_mut().is_none());
+        }
+        assert_eq!(*c.borrow(), "hello world");
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_borrow_mut_while_borrowed() {
+        let c = MyRefCell::new(0);
+        let _b = c.borrow();
+        let _m = c.borrow_mut();
+    }
+
+    #[test]
+    #[should_panic(expected = "already mutably borrowed")]
+    fn test_borrow_while_borrowed_mut() {
+        let c = MyRefCell::new(0);
+        let _m = c.borrow_mut();
+        let _b = c.borrow();
+    }
+
+    #[test]
+    fn test_forget_guard() {
+        let c = MyRefCell::new(0);
+        ::std::mem::forget(c.borrow_mut());
+        assert!(c.try_borrow().is_none());
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let log = Rc::new(MyRefCell::new(Vec::new()));
+        let mut c = CallbacksMut::new();
+        let log2 = log.clone();
+        let mut count = 0;
+        c.register(move |val| {
+            count += 1;
+            log2.borrow_mut().push((val, count));
+        });
+        c.call(1);
+        c.clone().call(2);
+        assert_eq!(*log.borrow(), vec![(1, 1), (2, 2)]);
+    }
+
+    // This is the same reentrant call as in the solution of exercise 12.1, but this time, our own
+    // `MyRefCell` is the one that panics.
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_reentrant() {
+        let c = Rc::new(MyRefCell::new(CallbacksMut::new()));
+        let c2 = c.clone();
+        c.borrow_mut().register(move |val| {
+            // This borrow succeeds, because we clone the `CallbacksMut` below before calling it.
+            let mut guard = c2.borrow_mut();
+            guard.call(val + val)
+        });
+        let mut c3 = c.borrow().clone();
+        c3.call(42);
+    }
+}
+
+//@ [index](main.html) | [previous](part27.html) | [raw source](workspace/src/part28.rs) |
+//@ [next](part29.html)
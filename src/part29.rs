@@ -0,0 +1,230 @@
+// Rust-101, Part 29: Arenas
+// =========================
+
+use std::cell::RefCell;
+
+//@ If you tried to write a tree or a graph in Rust, you probably ended up with `Rc` everywhere, or
+//@ with `unsafe` raw pointers like our linked list in part 16. There's a third option, which is
+//@ popular in compilers: Allocate all the nodes in an *arena*, and let them refer to each other
+//@ with plain references. All the nodes live exactly as long as the arena, so that's the lifetime
+//@ of all these references. Nodes are never freed individually - they all go away together when the
+//@ arena is dropped.
+
+// ## The arena
+//@ Our `Arena<T>` has a single important method: `alloc(&self, value: T) -> &T`. It moves the value
+//@ into the arena, and returns a reference to it that lives as long as the arena. Notice that
+//@ `alloc` takes `&self`, not `&mut self`. That's crucial: If it took `&mut self`, we could not
+//@ call it again as long as any of the references it handed out is still around - and the entire
+//@ point is to keep all of them around! So we need interior mutability, and we use `RefCell`.
+//@
+//@ Now, where do we store the values? A single `Vec<T>` does not work: When it runs out of space,
+//@ it moves all its elements to a larger buffer, and all the references we handed out would dangle.
+//@ Instead, we store the values in *chunks*. Each chunk is a `Vec` that we create with a fixed
+//@ capacity, and that we never push beyond that capacity. So, it never reallocates. Once a chunk is
+//@ full, we start a new one, twice as large as the previous one.
+pub struct Arena<T> {
+    chunks: RefCell<Vec<Vec<T>>>,
+}
+
+const INITIAL_CAPACITY: usize = 8;
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Arena { chunks: RefCell::new(vec![Vec::with_capacity(INITIAL_CAPACITY)]) }
+    }
+
+    pub fn alloc(&self, value: T) -> &T {
+        let mut chunks = self.chunks.borrow_mut();
+        // If the current chunk is full, start a new one.
+        let full_capacity = {
+            let last = chunks.last().unwrap();
+            if last.len() == last.capacity() { Some(last.capacity()) } else { None }
+        };
+        if let Some(capacity) = full_capacity {
+            chunks.push(Vec::with_capacity(2 * capacity));          /*@*/
+        }
+        let chunk = chunks.last_mut().unwrap();
+        chunk.push(value);
+        //@ Now comes the unsafe part. `chunk.last()` gives us a reference to the value, but it is
+        //@ tied to the `RefMut` guard `chunks`. We cast it to a raw pointer, and back to a
+        //@ reference with the lifetime of `self`. Why is that safe? The value is stored in the heap
+        //@ buffer of the chunk. That buffer is never reallocated, as we just discussed. The buffer
+        //@ is also not moved when the *outer* vector `chunks` grows: That only moves the `Vec`
+        //@ structs themselves, i.e., pointer, length and capacity of every chunk. The buffer is
+        //@ only freed when the arena is dropped, and that cannot happen while we are borrowing
+        //@ `self`. Finally, we never hand out mutable references to the values, so the shared
+        //@ reference does not alias with a mutable one.
+        let ptr: *const T = chunk.last().unwrap();
+        unsafe { &*ptr }
+    }
+
+    // The number of values allocated so far.
+    pub fn len(&self) -> usize {
+        self.chunks.borrow().iter().map(|chunk| chunk.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+//@ (Real arena crates like `typed-arena` use a very similar design.)
+
+// ## A tree without `Rc`
+//@ Let's use the arena for a tree. Every node has a value, and a list of children. The children are
+//@ plain shared references, with the lifetime `'a` of the arena.
+pub struct Tree<'a> {
+    pub value: i32,
+    pub children: Vec<&'a Tree<'a>>,
+}
+
+impl<'a> Tree<'a> {
+    // Compute the sum of all values in the tree.
+    pub fn sum(&self) -> i32 {
+        let children: i32 = self.children.iter().map(|child| child.sum()).sum(); /*@*/
+        self.value + children                                       /*@*/
+    }
+}
+
+// We build the tree bottom-up: First the leaves, then their parent.
+pub fn build_tree<'a>(arena: &'a Arena<Tree<'a>>) -> &'a Tree<'a> {
+    let leaf1 = arena.alloc(Tree { value: 1, children: vec![] });
+    let leaf2 = arena.alloc(Tree { value: 2, children: vec![] });
+    let inner = arena.alloc(Tree { value: 3, children: vec![leaf1, leaf2] });
+    //@ Since the leaves are just shared references, a node can appear in the tree several times.
+    //@ Strictly speaking, this makes our "tree" a directed acyclic graph.
+    arena.alloc(Tree { value: 4, children: vec![inner, leaf2] })
+}
+//@ Look at the type of `arena`: `&'a Arena<Tree<'a>>`. The lifetime `'a` appears twice: The arena
+//@ is borrowed for `'a`, and it contains values that reference each other for `'a`.
+
+// ## A graph with cycles
+//@ A tree is built bottom-up, so every node can get its children when it is created. In a graph
+//@ with cycles, that is not possible: One of the nodes in the cycle has to be created first, and
+//@ later be updated to point to the others. Since the arena only gives us shared references, that
+//@ requires interior mutability - once again, it's `RefCell` to the rescue.
+pub struct Node<'a> {
+    pub name: &'static str,
+    edges: RefCell<Vec<&'a Node<'a>>>,
+}
+
+impl<'a> Node<'a> {
+    pub fn new(name: &'static str) -> Self {
+        Node { name, edges: RefCell::new(Vec::new()) }
+    }
+
+    pub fn add_edge(&self, target: &'a Node<'a>) {
+        self.edges.borrow_mut().push(target);                       /*@*/
+    }
+
+    // Compute the names of all nodes reachable from this one, in depth-first order.
+    //@ A node is reachable from itself, so it is always included. We have to remember which nodes
+    //@ we already visited, or we would loop forever. Two nodes may have the same name, so we
+    //@ compare their *addresses*, with `std::ptr::eq`.
+    pub fn reachable(&'a self) -> Vec<&'static str> {
+        let mut visited: Vec<&'a Node<'a>> = Vec::new();
+        let mut todo = vec![self];
+        while let Some(node) = todo.pop() {
+            if visited.iter().any(|&v| ::std::ptr::eq(v, node)) {  /*@*/
+                continue;                                           /*@*/
+            }                                                       /*@*/
+            visited.push(node);                                     /*@*/
+            //@ Push the successors in reverse, so that the first edge is visited first.
+            todo.extend(node.edges.borrow().iter().rev());          /*@*/
+        }
+        visited.iter().map(|node| node.name).collect()
+    }
+}
+
+pub fn build_graph<'a>(arena: &'a Arena<Node<'a>>) -> &'a Node<'a> {
+    let a = arena.alloc(Node::new("a"));
+    let b = arena.alloc(Node::new("b"));
+    let c = arena.alloc(Node::new("c"));
+    a.add_edge(b);
+    b.add_edge(c);
+    c.add_edge(a);
+    c.add_edge(c);
+    a
+}
+//@ Try doing the same with `Rc`: The cycle between `a`, `b` and `c` would keep all of them alive
+//@ forever, and we would leak the memory. With the arena, there's no such problem - the nodes are
+//@ freed together with the arena, no matter how they point to each other.
+//@
+//@ Of course, arenas also have a downside: Memory is only freed when the entire arena goes away. If
+//@ your program constantly creates and forgets nodes, the arena grows and grows.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let arena = Arena::new();
+    let tree = build_tree(&arena);
+    println!("The tree sums up to {}, using {} nodes.", tree.sum(), arena.len());
+
+    let arena = Arena::new();
+    let graph = build_graph(&arena);
+    println!("Reachable from a: {:?}", graph.reachable());
+}
+
+// **Exercise 29.1**: Add a method `alloc_mut(&self, value: T) -> &mut T`. Why is it still safe to
+// hand out mutable references from a shared reference to the arena? What would go wrong if you
+// added a method `get(&self, index: usize) -> &T` as well?
+
+// **Exercise 29.2**: Add a method `into_vec(self) -> Vec<T>` that returns all values, in the order
+// they were allocated. Why is this safe, even though `alloc` handed out references to them?
+
+// **Exercise 29.3**: Implement `Drop` for `Node`, printing the names of the nodes it has edges to.
+// Rust rejects this - why? Think about the order in which the nodes in the arena are dropped.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_references() {
+        let arena = Arena::new();
+        let first = arena.alloc(String::from("first"));
+        let addr = first as *const String;
+        let mut refs = Vec::new();
+        // This needs many new chunks. None of them may move the values we allocated earlier.
+        for i in 0..1000 {
+            refs.push(arena.alloc(i.to_string()));
+        }
+        assert_eq!(first, "first");
+        assert_eq!(first as *const String, addr);
+        for (i, s) in refs.iter().enumerate() {
+            assert_eq!(**s, i.to_string());
+        }
+        assert_eq!(arena.len(), 1001);
+        assert!(arena.chunks.borrow().len() < 10);
+    }
+
+    #[test]
+    fn test_zero_sized() {
+        let arena = Arena::new();
+        for _ in 0..100 {
+            arena.alloc(());
+        }
+        assert_eq!(arena.len(), 100);
+    }
+
+    #[test]
+    fn test_tree() {
+        let arena = Arena::new();
+        let tree = build_tree(&arena);
+        assert_eq!(tree.sum(), 12);
+        assert_eq!(arena.len(), 4);
+    }
+
+    #[test]
+    fn test_graph() {
+        let arena = Arena::new();
+        let a = build_graph(&arena);
+        assert_eq!(a.reachable(), vec!["a", "b", "c"]);
+        let d = arena.alloc(Node::new("d"));
+        assert_eq!(d.reachable(), vec!["d"]);
+        d.add_edge(a);
+        a.add_edge(d);
+        assert_eq!(a.reachable(), vec!["a", "b", "c", "d"]);
+    }
+}
+
+//@ [index](main.html) | [previous](part28.html) | [raw source](workspace/src/part29.rs) |
+//@ [next](part30.html)
@@ -0,0 +1,262 @@
+// Rust-101, Part 30: Binary Search Trees
+// ======================================
+
+use std::cmp::Ordering;
+use std::mem;
+
+//@ In part 16, we needed unsafe code for a doubly-linked list: Every node is pointed to by two
+//@ other nodes, so no single one of them can own it. Many data structures do not have that
+//@ problem, though. In a *tree*, every node has exactly one parent, which can own it. That's a
+//@ perfect fit for `Box`, and we can write the entire data structure in safe Rust. In this part,
+//@ we write a *binary search tree*: A set of values, where every node has up to two children. All
+//@ values in the left subtree of a node are smaller than the node's value, and all values in the
+//@ right subtree are larger.
+
+// ## The data structure
+//@ A tree is either empty (a `Leaf`), or it is a `Node` with a value and two subtrees. We cannot
+//@ put the subtrees directly into the enum: Then a `Tree` would have to contain two `Tree`s, and
+//@ it would be infinitely large. Instead, we put the node into a `Box`. A leaf needs no
+//@ allocation, and a tree with `n` values consists of exactly `n` boxes.
+pub struct Node<T> {
+    value: T,
+    left: Tree<T>,
+    right: Tree<T>,
+}
+
+pub enum Tree<T> {
+    Leaf,
+    Node(Box<Node<T>>),
+}
+
+impl<T: Ord> Tree<T> {
+    pub fn new() -> Self {
+        Tree::Leaf
+    }
+
+    // Insert a value into the tree. Returns whether the value was new.
+    //@ We walk down the tree recursively. When we reach a leaf, that's where the value goes: We
+    //@ overwrite the leaf (`*self`) with a new node. Recall from part 05 that `ref mut` borrows
+    //@ the box inside the node, rather than moving it out of `self`.
+    pub fn insert(&mut self, value: T) -> bool {
+        match *self {
+            Tree::Leaf => {
+                *self = Tree::Node(Box::new(Node { value, left: Tree::Leaf, right: Tree::Leaf }));
+                true
+            }
+            Tree::Node(ref mut node) => {
+                match value.cmp(&node.value) {                      /*@*/
+                    Ordering::Less => node.left.insert(value),      /*@*/
+                    Ordering::Greater => node.right.insert(value),  /*@*/
+                    Ordering::Equal => false,                       /*@*/
+                }                                                   /*@*/
+            }
+        }
+    }
+
+    // Checking whether a value is in the tree does not need recursion, a loop is enough.
+    //@ `tree` is a shared reference that we move down the tree, until we find the value or reach a
+    //@ leaf.
+    pub fn contains(&self, value: &T) -> bool {
+        let mut tree = self;
+        while let Tree::Node(ref node) = *tree {
+            match value.cmp(&node.value) {                          /*@*/
+                Ordering::Less => tree = &node.left,                /*@*/
+                Ordering::Greater => tree = &node.right,            /*@*/
+                Ordering::Equal => return true,                     /*@*/
+            }                                                       /*@*/
+        }
+        false
+    }
+
+    // ## Removal
+    // Remove the smallest value from the tree, and return it.
+    //@ The smallest value is in the leftmost node. That node has no left child, so we can replace
+    //@ it by its right subtree.
+    fn remove_min(&mut self) -> Option<T> {
+        match *self {
+            Tree::Leaf => return None,
+            Tree::Node(ref mut node) => {
+                if let Tree::Node(_) = node.left {
+                    return node.left.remove_min();
+                }
+            }
+        }
+        //@ Now we know that `self` is a node without a left child. We take it out of the tree with
+        //@ `mem::replace`, leaving a leaf behind for a moment. This gives us ownership of the node,
+        //@ so we can move its parts wherever we want.
+        match mem::replace(self, Tree::Leaf) {
+            Tree::Leaf => unreachable!(),
+            Tree::Node(node) => {
+                let node = *node;
+                *self = node.right;
+                Some(node.value)
+            }
+        }
+    }
+
+    // Remove a value from the tree. Returns whether the value was found.
+    //@ Removing a node with at most one child is easy: We replace it by that child. If the node has
+    //@ two children, we need a new value for this node, which is larger than everything in the left
+    //@ subtree and smaller than everything in the right subtree. The smallest value of the right
+    //@ subtree does the job! As a bonus, if the right subtree is empty, `remove_min` tells us
+    //@ so, and then we can just replace the node by its left subtree.
+    pub fn remove(&mut self, value: &T) -> bool {
+        match *self {
+            Tree::Leaf => return false,
+            Tree::Node(ref mut node) => match value.cmp(&node.value) {
+                Ordering::Less => return node.left.remove(value),
+                Ordering::Greater => return node.right.remove(value),
+                Ordering::Equal => {}
+            },
+        }
+        match mem::replace(self, Tree::Leaf) {
+            Tree::Leaf => unreachable!(),
+            Tree::Node(mut node) => {
+                *self = match node.right.remove_min() {             /*@*/
+                    None => node.left,                              /*@*/
+                    Some(successor) => {                            /*@*/
+                        node.value = successor;                     /*@*/
+                        Tree::Node(node)                            /*@*/
+                    }                                               /*@*/
+                };                                                  /*@*/
+                true                                                /*@*/
+            }
+        }
+    }
+}
+//@ Notice how `remove` first only *looks* at `self`, and takes ownership of the node in a second
+//@ step. Doing it all at once does not work: As long as `node` borrows from `self`, we cannot
+//@ assign to `self`. That's the borrow checker making sure we do not accidentally drop the node
+//@ we are still looking at.
+
+// ## Iteration
+//@ Iterating over the values in order is a bit more tricky. In a recursive function, we would
+//@ visit the left subtree, then the node, then the right subtree. But an iterator has to return
+//@ after every value, so it has to remember where it was. We do what the recursive function would
+//@ do implicitly, and maintain a stack of the nodes whose value we still have to return. On top of
+//@ the stack is the next node, and below it are its ancestors that are larger.
+pub struct Iter<'a, T: 'a> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    // Push `tree` and all its left descendants onto the stack.
+    fn push_left(&mut self, mut tree: &'a Tree<T>) {
+        while let Tree::Node(ref node) = *tree {
+            self.stack.push(node);                                  /*@*/
+            tree = &node.left;                                      /*@*/
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        //@ Once we are done with a node, we have to visit its right subtree, starting with the
+        //@ leftmost node there.
+        let node = self.stack.pop()?;                               /*@*/
+        self.push_left(&node.right);                                /*@*/
+        Some(&node.value)                                           /*@*/
+    }
+}
+
+impl<T> Tree<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(self);
+        iter
+    }
+}
+//@ Since `Iter` only holds shared references into the tree, the borrow checker makes sure the tree
+//@ is not modified while we iterate. Compare that to part 16, where we had to convince the borrow
+//@ checker with `PhantomData` and raw pointers.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let mut tree = Tree::new();
+    for x in [5, 3, 8, 1, 4, 9, 3].iter() {
+        if !tree.insert(*x) {
+            println!("{} was already in the tree.", x);
+        }
+    }
+    tree.remove(&5);
+    for x in tree.iter() {
+        println!("{}", x);
+    }
+}
+
+// **Exercise 30.1**: Implement `IntoIterator` for `&'a Tree<T>`, so that you can write `for x in
+// &tree`. Then write a method `len`.
+
+// **Exercise 30.2**: Write `insert` with a loop instead of recursion, like `contains`. You will need
+// a variable `tree: &mut Tree<T>` that you move down the tree. The borrow checker will not make it
+// easy for you!
+
+// **Exercise 30.3**: Insert the numbers from 0 to 100 000 in order, and then drop the tree. What
+// happens, and why? Compute the height of the tree to find out. How could you fix the drop? (Making
+// the tree *balanced* is a lot more work. The `BTreeSet` of the standard library is balanced.)
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use part23::Rng;
+    use super::*;
+
+    fn tree_of(values: &[i32]) -> Tree<i32> {
+        let mut tree = Tree::new();
+        for x in values {
+            tree.insert(*x);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_insert_contains() {
+        let mut tree = tree_of(&[5, 3, 8]);
+        assert!(!tree.insert(3));
+        assert!(tree.insert(4));
+        assert!(tree.contains(&4) && tree.contains(&5) && tree.contains(&8));
+        assert!(!tree.contains(&6));
+        assert!(!Tree::new().contains(&0));
+    }
+
+    #[test]
+    fn test_iter() {
+        let tree = tree_of(&[5, 3, 8, 1, 4, 9, 7]);
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), vec![1, 3, 4, 5, 7, 8, 9]);
+        assert_eq!(Tree::<i32>::new().iter().next(), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = tree_of(&[5, 3, 8, 1, 4, 9, 7]);
+        // A node with no children, one child, and two children.
+        assert!(tree.remove(&1));
+        assert!(tree.remove(&8));
+        assert!(tree.remove(&5));
+        assert!(!tree.remove(&5));
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), vec![3, 4, 7, 9]);
+    }
+
+    // Compare with the standard library's `BTreeSet`, on random operations.
+    #[test]
+    fn test_random() {
+        let mut rng = Rng::new(30);
+        for _ in 0..100 {
+            let mut tree = Tree::new();
+            let mut set = BTreeSet::new();
+            for x in rng.vec(50, 20) {
+                if x % 3 == 0 {
+                    assert_eq!(tree.remove(&(x / 3)), set.remove(&(x / 3)));
+                } else {
+                    assert_eq!(tree.insert(x), set.insert(x));
+                }
+            }
+            assert!(tree.iter().eq(set.iter()));
+        }
+    }
+}
+
+//@ [index](main.html) | [previous](part29.html) | [raw source](workspace/src/part30.rs) |
+//@ [next](part31.html)
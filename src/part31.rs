@@ -0,0 +1,280 @@
+// Rust-101, Part 31: Graphs with Indices
+// ======================================
+
+use std::collections::VecDeque;
+
+//@ We have seen three ways to build data structures where nodes point to each other: Raw pointers
+//@ (the list in part 16), `Rc` (the callbacks in part 12), and references into an arena (part 29).
+//@ In this part, we use a fourth way, which is the most popular one in Rust: We put all the nodes
+//@ into a vector, and instead of pointers, we use *indices* into that vector.
+
+// ## The data structure
+//@ Our graph consists of two vectors: One for the nodes, and one for the edges. The edges leaving a
+//@ node form a linked list - but the links are indices into the vector of edges. Every node knows
+//@ the first of its outgoing edges, and every edge knows the next edge leaving the same node.
+pub type NodeIndex = usize;
+pub type EdgeIndex = usize;
+
+pub struct NodeData<N> {
+    pub data: N,
+    first_outgoing_edge: Option<EdgeIndex>,
+}
+
+pub struct Edge {
+    target: NodeIndex,
+    next_outgoing_edge: Option<EdgeIndex>,
+}
+
+pub struct Graph<N> {
+    nodes: Vec<NodeData<N>>,
+    edges: Vec<Edge>,
+}
+
+impl<N> Graph<N> {
+    pub fn new() -> Self {
+        Graph { nodes: Vec::new(), edges: Vec::new() }
+    }
+
+    // Add a node, and return its index.
+    pub fn add_node(&mut self, data: N) -> NodeIndex {
+        self.nodes.push(NodeData { data, first_outgoing_edge: None });
+        self.nodes.len() - 1
+    }
+
+    // Add an edge from `source` to `target`.
+    //@ The new edge becomes the first in the list of edges leaving `source`. Notice that we can
+    //@ freely look at and modify nodes and edges here: It's all just numbers, and `self.nodes` and
+    //@ `self.edges` are owned by the graph.
+    pub fn add_edge(&mut self, source: NodeIndex, target: NodeIndex) {
+        assert!(target < self.nodes.len(), "add_edge: target is not a node");
+        let edge_index = self.edges.len();                          /*@*/
+        let node = &mut self.nodes[source];                         /*@*/
+        let next_outgoing_edge = node.first_outgoing_edge;          /*@*/
+        self.edges.push(Edge { target, next_outgoing_edge });       /*@*/
+        node.first_outgoing_edge = Some(edge_index);                /*@*/
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn data(&self, node: NodeIndex) -> &N {
+        &self.nodes[node].data
+    }
+
+    // Iterate over the successors of a node, i.e., the targets of its outgoing edges.
+    //@ The most recently added edge comes first.
+    pub fn successors(&self, node: NodeIndex) -> Successors<'_, N> {
+        Successors { graph: self, current_edge: self.nodes[node].first_outgoing_edge }
+    }
+}
+
+pub struct Successors<'a, N: 'a> {
+    graph: &'a Graph<N>,
+    current_edge: Option<EdgeIndex>,
+}
+
+impl<'a, N> Iterator for Successors<'a, N> {
+    type Item = NodeIndex;
+
+    fn next(&mut self) -> Option<NodeIndex> {
+        let edge = &self.graph.edges[self.current_edge?];           /*@*/
+        self.current_edge = edge.next_outgoing_edge;                /*@*/
+        Some(edge.target)                                           /*@*/
+    }
+}
+//@ This is the point where things would be painful with references: `Successors` borrows the
+//@ graph, and so do all the nodes it returns. With indices, only the iterator borrows the graph,
+//@ and only for as long as it runs. The indices it hands out are just `usize`, and they don't
+//@ borrow anything. After iterating, we are free to modify the graph again.
+
+// ## Search
+//@ Let's do something with our graph. A *breadth-first search* (BFS) visits all the nodes that are
+//@ reachable from a start node, ordered by their distance from the start. We keep a queue of nodes
+//@ we still have to visit - that's what the
+//@ [`VecDeque`](https://doc.rust-lang.org/stable/std/collections/struct.VecDeque.html) is for. And
+//@ we have to remember which nodes we already saw, or we would loop forever in a cycle. Indices
+//@ make that easy, too: A `Vec<bool>` with one entry per node does the job.
+impl<N> Graph<N> {
+    pub fn bfs(&self, start: NodeIndex) -> Vec<NodeIndex> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        let mut order = Vec::new();
+        seen[start] = true;
+        queue.push_back(start);
+        while let Some(node) = queue.pop_front() {
+            order.push(node);                                       /*@*/
+            for succ in self.successors(node) {                     /*@*/
+                if !seen[succ] {                                    /*@*/
+                    seen[succ] = true;                              /*@*/
+                    queue.push_back(succ);                          /*@*/
+                }                                                   /*@*/
+            }                                                       /*@*/
+        }
+        order
+    }
+
+    // A *depth-first search* (DFS) follows one path as far as possible, before backtracking.
+    //@ We use a stack instead of the queue. This time, we mark a node as seen when we take it off
+    //@ the stack, because the same node may be pushed several times before we get to it.
+    pub fn dfs(&self, start: NodeIndex) -> Vec<NodeIndex> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![start];
+        let mut order = Vec::new();
+        while let Some(node) = stack.pop() {
+            if seen[node] {                                         /*@*/
+                continue;                                           /*@*/
+            }                                                       /*@*/
+            seen[node] = true;                                      /*@*/
+            order.push(node);                                       /*@*/
+            stack.extend(self.successors(node));                    /*@*/
+        }
+        order
+    }
+}
+
+// ## Topological sorting
+//@ If the edges of a graph describe dependencies ("to put on your shoes, you first have to put on
+//@ your socks"), we'd like to find an order in which everything can be done. That's called a
+//@ *topological sort*: An order of all nodes, such that for every edge, the source comes before the
+//@ target. It exists exactly if the graph has no cycles.
+//@
+//@ We count, for every node, how many edges lead to it (its *in-degree*). The nodes with in-degree
+//@ 0 can go first. Once a node is done, we remove its outgoing edges (by decrementing the
+//@ in-degree of their targets), which may make further nodes ready. If we get stuck before all
+//@ nodes are done, there is a cycle.
+impl<N> Graph<N> {
+    pub fn topological_sort(&self) -> Option<Vec<NodeIndex>> {
+        let mut in_degree = vec![0; self.nodes.len()];
+        for edge in self.edges.iter() {
+            in_degree[edge.target] += 1;
+        }
+        let mut ready: Vec<NodeIndex> =
+            (0..self.nodes.len()).filter(|&n| in_degree[n] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(node) = ready.pop() {
+            order.push(node);                                       /*@*/
+            for succ in self.successors(node) {                     /*@*/
+                in_degree[succ] -= 1;                               /*@*/
+                if in_degree[succ] == 0 {                           /*@*/
+                    ready.push(succ);                               /*@*/
+                }                                                   /*@*/
+            }                                                       /*@*/
+        }
+        if order.len() == self.nodes.len() { Some(order) } else { None }
+    }
+}
+
+// ## Why indices?
+//@ Remember why the linked list of part 16 needed unsafe code: Every node is referenced by two
+//@ others, but the borrow checker wants every piece of data to have a single owner, and mutable
+//@ references to be unique. With indices, the vectors are the single owner of all nodes and edges.
+//@ A node "pointing" to another node is just a number, which does not borrow anything. So the
+//@ borrow checker is perfectly happy, and we can mutate the graph through a `&mut Graph` whenever
+//@ we like.
+//@
+//@ Have we cheated the borrow checker? In a way, yes: An index can become invalid, just like a
+//@ pointer can dangle. If we removed a node from the vector, all indices after it would suddenly
+//@ refer to the wrong node. However, the worst thing that can happen is a logic error, or a panic
+//@ because an index is out of bounds. Unlike with dangling pointers, there is no undefined
+//@ behavior, and no security hole. That's a good trade-off for many programs.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let mut g = Graph::new();
+    let socks = g.add_node("socks");
+    let shoes = g.add_node("shoes");
+    let pants = g.add_node("pants");
+    let belt = g.add_node("belt");
+    let shirt = g.add_node("shirt");
+    g.add_edge(socks, shoes);
+    g.add_edge(pants, shoes);
+    g.add_edge(pants, belt);
+    g.add_edge(shirt, belt);
+
+    match g.topological_sort() {
+        Some(order) => {
+            for node in order {
+                println!("Put on your {}.", g.data(node));
+            }
+        }
+        None => println!("You cannot get dressed."),
+    }
+    println!("BFS from pants: {:?}", g.bfs(pants));
+}
+
+// **Exercise 31.1**: Write a method `predecessors` that returns the sources of all edges leading
+// to a node. Can you make it as efficient as `successors`? What would you have to change in
+// `NodeData` and `Edge`?
+
+// **Exercise 31.2**: Write a method `remove_node`. What happens to the indices of other nodes, and
+// to the edges? One way out is to never actually remove anything, and instead mark the node as
+// deleted. Another one is to store a *generation* with every index, and check it when using it.
+
+// **Exercise 31.3**: `NodeIndex` and `EdgeIndex` are both `usize`, so it is easy to mix them up.
+// Turn them into separate types, like the `Id<T>` of exercise 27.1.
+
+// **Exercise 31.4**: Write the doubly-linked list of part 16 with indices into a `Vec`, without any
+// unsafe code.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A diamond: 0 -> 1 -> 3, 0 -> 2 -> 3, and a separate node 4.
+    fn diamond() -> Graph<char> {
+        let mut g = Graph::new();
+        for c in "abcde".chars() {
+            g.add_node(c);
+        }
+        g.add_edge(0, 1);
+        g.add_edge(0, 2);
+        g.add_edge(1, 3);
+        g.add_edge(2, 3);
+        g
+    }
+
+    #[test]
+    fn test_successors() {
+        let g = diamond();
+        assert_eq!(g.successors(0).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(g.successors(3).count(), 0);
+        assert_eq!(*g.data(4), 'e');
+    }
+
+    #[test]
+    fn test_search() {
+        let mut g = diamond();
+        assert_eq!(g.bfs(0), vec![0, 2, 1, 3]);
+        assert_eq!(g.dfs(0), vec![0, 1, 3, 2]);
+        assert_eq!(g.bfs(4), vec![4]);
+        // Add a cycle. The searches still terminate.
+        g.add_edge(3, 0);
+        assert_eq!(g.bfs(3), vec![3, 0, 2, 1]);
+        assert_eq!(g.dfs(3), vec![3, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_topological_sort() {
+        let mut g = diamond();
+        let order = g.topological_sort().unwrap();
+        assert_eq!(order.len(), g.len());
+        // Every edge must go forward in the order.
+        let position = |n| order.iter().position(|&m| m == n).unwrap();
+        for source in 0..g.len() {
+            for target in g.successors(source) {
+                assert!(position(source) < position(target));
+            }
+        }
+
+        g.add_edge(3, 0);
+        assert_eq!(g.topological_sort(), None);
+    }
+}
+
+//@ [index](main.html) | [previous](part30.html) | [raw source](workspace/src/part31.rs) |
+//@ [next](part32.html)
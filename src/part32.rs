@@ -0,0 +1,319 @@
+// Rust-101, Part 32: A Calculator
+// ===============================
+
+use std::io::prelude::*;
+use std::io;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+//@ Time for another project. We are going to write a calculator: The user types an expression like
+//@ `2 * (3 + 4)`, and we print the result. That's a task every compiler has to solve, too, and we
+//@ will do it the same way. First, a *tokenizer* splits the input into tokens: numbers, operators
+//@ and parentheses. Then, a *parser* turns the tokens into a tree that reflects the structure of
+//@ the expression. Finally, we *evaluate* the tree. If anything goes wrong along the way, we want
+//@ to tell the user exactly *where* the problem is.
+
+// ## Spans and errors
+//@ A `Span` describes a part of the input, given by the byte offsets of its start and end. Every
+//@ token remembers its span, and so does every error.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Error {
+    pub message: String,
+    pub span: Span,
+}
+
+fn error<T>(message: &str, start: usize, end: usize) -> Result<T, Error> {
+    Err(Error { message: message.to_string(), span: Span { start, end } })
+}
+
+// To show an error, we print the input, and mark the span below it.
+//@ This assumes that every character takes one byte, i.e., that the input is ASCII. Otherwise, the
+//@ markers would not line up.
+pub fn report(input: &str, error: &Error) -> String {
+    let marker = "^".repeat(::std::cmp::max(1, error.span.end - error.span.start));
+    format!("{}\n{}{} {}", input, " ".repeat(error.span.start), marker, error.message)
+}
+
+// ## Tokenizing
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TokenKind {
+    Number(i64),
+    Plus,
+    Minus,
+    Star,
+    LParen,
+    RParen,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+//@ We go over the characters of the input with `char_indices`, which also tells us the byte offset
+//@ of every character - exactly what we need for the spans. We wrap the iterator in a `Peekable`,
+//@ so that we can look at the next character without consuming it. That's needed for numbers: We
+//@ only know that a number ends when we see the first character that is not a digit.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut chars = input.char_indices().peekable();
+    let mut tokens = Vec::new();
+    while let Some(&(start, c)) = chars.peek() {
+        let kind = match c {
+            ' ' | '\t' => { chars.next(); continue; }
+            '0'..='9' => tokenize_number(input, &mut chars)?,
+            '+' => { chars.next(); TokenKind::Plus }
+            '-' => { chars.next(); TokenKind::Minus }
+            '*' => { chars.next(); TokenKind::Star }
+            '(' => { chars.next(); TokenKind::LParen }
+            ')' => { chars.next(); TokenKind::RParen }
+            _ => return error("unexpected character", start, start + c.len_utf8()),
+        };
+        //@ After the token, the iterator points to the next character (or to the end of the
+        //@ input). That's where the span of the token ends.
+        let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+        tokens.push(Token { kind, span: Span { start, end } });
+    }
+    Ok(tokens)
+}
+
+// Consume the digits of a number, and parse them.
+fn tokenize_number(input: &str,
+                   chars: &mut Peekable<CharIndices<'_>>) -> Result<TokenKind, Error> {
+    let start = chars.peek().unwrap().0;
+    let mut end = start;
+    while let Some(&(i, c)) = chars.peek() {
+        if !c.is_ascii_digit() {                                    /*@*/
+            break;                                                  /*@*/
+        }                                                           /*@*/
+        end = i + 1;                                                /*@*/
+        chars.next();                                               /*@*/
+    }
+    //@ The only way parsing can fail here is if the number is too large for an `i64`.
+    match input[start..end].parse() {
+        Ok(n) => Ok(TokenKind::Number(n)),
+        Err(_) => error("number too large", start, end),
+    }
+}
+
+// ## Parsing
+//@ The result of parsing is an *abstract syntax tree*. An expression is either a number, or an
+//@ operator applied to two expressions. For the operators, we remember their span: If evaluation
+//@ fails, that's what we point at.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Expr {
+    Number(i64),
+    Binary(Box<Expr>, Op, Span, Box<Expr>),
+}
+
+//@ Now, how do we get from the tokens to the tree? We have to take care of *precedence*: In
+//@ `1 + 2 * 3`, the multiplication happens first. We write down the structure of our expressions
+//@ as a *grammar*, with one rule for each level of precedence:
+//@
+//@ * An *expression* is a sequence of *terms*, separated by `+` or `-`.
+//@ * A *term* is a sequence of *factors*, separated by `*`.
+//@ * A *factor* is a number, or an expression in parentheses.
+//@
+//@ A *recursive-descent parser* has one function per rule, which calls the functions of the rules
+//@ it consists of. Because a factor can contain an expression, these functions are recursive.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    // The length of the input, to report errors at the end.
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_term()?;
+        //@ We keep going as long as the next token is a `+` or `-`. Notice that we build the tree
+        //@ from left to right: `1 - 2 - 3` is `(1 - 2) - 3`, as it should be.
+        while let Some(token) = self.peek() {
+            let op = match token.kind {
+                TokenKind::Plus => Op::Add,
+                TokenKind::Minus => Op::Sub,
+                _ => break,
+            };
+            self.pos += 1;
+            let rhs = self.parse_term()?;
+            lhs = Expr::Binary(Box::new(lhs), op, token.span, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_factor()?;
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Star {                      /*@*/
+                break;                                              /*@*/
+            }                                                       /*@*/
+            self.pos += 1;                                          /*@*/
+            let rhs = self.parse_factor()?;                         /*@*/
+            lhs = Expr::Binary(Box::new(lhs), Op::Mul, token.span, Box::new(rhs)); /*@*/
+        }
+        Ok(lhs)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expr, Error> {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return error("expected a number or `(`", self.end, self.end),
+        };
+        self.pos += 1;
+        match token.kind {
+            TokenKind::Number(n) => Ok(Expr::Number(n)),
+            TokenKind::LParen => {
+                let expr = self.parse_expr()?;                      /*@*/
+                match self.peek() {                                 /*@*/
+                    Some(Token { kind: TokenKind::RParen, .. }) => { /*@*/
+                        self.pos += 1;                              /*@*/
+                        Ok(expr)                                    /*@*/
+                    }                                               /*@*/
+                    Some(token) => error("expected `)`", token.span.start, token.span.end), /*@*/
+                    None => error("expected `)`", self.end, self.end), /*@*/
+                }                                                   /*@*/
+            }
+            _ => error("expected a number or `(`", token.span.start, token.span.end),
+        }
+    }
+}
+
+// To parse the entire input, we parse an expression, and make sure that nothing is left over.
+pub fn parse(input: &str) -> Result<Expr, Error> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0, end: input.len() };
+    let expr = parser.parse_expr()?;
+    match parser.peek() {
+        Some(token) => error("unexpected token", token.span.start, token.span.end),
+        None => Ok(expr),
+    }
+}
+
+// ## Evaluation
+//@ Evaluating the tree is a simple recursive function. We use `checked_add` and friends, which
+//@ return `None` on overflow, rather than silently wrapping around (or panicking, in debug builds).
+impl Expr {
+    pub fn eval(&self) -> Result<i64, Error> {
+        match *self {
+            Expr::Number(n) => Ok(n),
+            Expr::Binary(ref lhs, op, span, ref rhs) => {
+                let (lhs, rhs) = (lhs.eval()?, rhs.eval()?);        /*@*/
+                let result = match op {                             /*@*/
+                    Op::Add => lhs.checked_add(rhs),                /*@*/
+                    Op::Sub => lhs.checked_sub(rhs),                /*@*/
+                    Op::Mul => lhs.checked_mul(rhs),                /*@*/
+                };                                                  /*@*/
+                match result {                                      /*@*/
+                    Some(n) => Ok(n),                               /*@*/
+                    None => error("overflow", span.start, span.end), /*@*/
+                }                                                   /*@*/
+            }
+        }
+    }
+}
+
+pub fn calculate(input: &str) -> Result<i64, Error> {
+    parse(input)?.eval()
+}
+
+// ## The REPL
+// Finally, a *read-eval-print loop*. We read the input line-by-line, just like `read_vec` in
+// part 03.
+//@ Errors are reported, but they do not end the loop: The user can just try again.
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    println!("Enter expressions, one per line. End with Ctrl-D (Linux) or Ctrl-Z (Windows).");
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+        match calculate(&line) {
+            Ok(n) => println!("{}", n),
+            Err(e) => println!("{}", report(&line, &e)),
+        }
+    }
+}
+
+// **Exercise 32.1**: Add division and remainder, written `/` and `%`. They have the same precedence
+// as `*`. What should happen when dividing by zero?
+
+// **Exercise 32.2**: Add unary minus, so that `-(1 + 2)` and `2 * -3` work. Which rule of the
+// grammar has to change?
+
+// **Exercise 32.3**: Our numbers are limited to `i64`. Change the calculator to use the `BigInt`
+// from part 05. Which operations do you need, and what should happen with `1 - 2`? You can find a
+// version working on the `BigInt` of the solutions in `solutions/src/calc.rs`.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let kinds: Vec<TokenKind> = tokenize("12+(3 *4)").unwrap().iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, vec![TokenKind::Number(12), TokenKind::Plus, TokenKind::LParen,
+                               TokenKind::Number(3), TokenKind::Star, TokenKind::Number(4),
+                               TokenKind::RParen]);
+        assert_eq!(tokenize(" 42 ").unwrap()[0].span, span(1, 3));
+        assert_eq!(tokenize("1 + x").unwrap_err().span, span(4, 5));
+        assert_eq!(tokenize("99999999999999999999").unwrap_err().message, "number too large");
+    }
+
+    #[test]
+    fn test_parse() {
+        use super::Expr::*;
+        assert_eq!(parse("1 - 2 * 3").unwrap(),
+                   Binary(Box::new(Number(1)), Op::Sub, span(2, 3),
+                          Box::new(Binary(Box::new(Number(2)), Op::Mul, span(6, 7),
+                                          Box::new(Number(3))))));
+        assert_eq!(parse("(1)").unwrap(), Number(1));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("").unwrap_err().span, span(0, 0));
+        assert_eq!(parse("1 +").unwrap_err().span, span(3, 3));
+        assert_eq!(parse("1 + * 2").unwrap_err(),
+                   Error { message: "expected a number or `(`".to_string(), span: span(4, 5) });
+        assert_eq!(parse("(1 + 2").unwrap_err().message, "expected `)`");
+        assert_eq!(parse("(1 2)").unwrap_err().span, span(3, 4));
+        assert_eq!(parse("1 2").unwrap_err().message, "unexpected token");
+    }
+
+    #[test]
+    fn test_calculate() {
+        assert_eq!(calculate("2 * (3 + 4)"), Ok(14));
+        assert_eq!(calculate("1 - 2 - 3"), Ok(-4));
+        assert_eq!(calculate("2 * 3 + 4 * 5"), Ok(26));
+        let err = calculate("4611686018427387904 * 2").unwrap_err();
+        assert_eq!(err.span, span(20, 21));
+        assert_eq!(report("4611686018427387904 * 2", &err),
+                   "4611686018427387904 * 2\n                    ^ overflow");
+    }
+}
+
+//@ [index](main.html) | [previous](part31.html) | [raw source](workspace/src/part32.rs) |
+//@ [next](part33.html)
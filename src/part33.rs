@@ -0,0 +1,190 @@
+// Rust-101, Part 33: A Lexer without Allocation
+// =============================================
+
+use std::collections::HashMap;
+
+//@ The tokenizer of part 32 collected all tokens into a `Vec`, and converted every number into an
+//@ `i64` right away. That's fine for a calculator, but a compiler reading a large file would rather
+//@ not copy anything it doesn't have to. In this part, we write a lexer (that's another word for a
+//@ tokenizer) that produces tokens *lazily*, as an iterator, and where every token is just a
+//@ *slice* of the input. No token ever allocates.
+
+// ## Borrowed tokens
+//@ A token borrows a part of the input string, so it needs a lifetime parameter, just like the
+//@ iterators of part 09. `Token<'a>` is a token pointing into a string that lives for `'a`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Token<'a> {
+    Ident(&'a str),
+    Number(&'a str),
+    Symbol(&'a str),
+}
+
+//@ The lexer itself only needs to remember the part of the input that it did not look at yet.
+//@ That's again a slice of the input.
+pub struct Tokens<'a> {
+    rest: &'a str,
+}
+
+pub fn tokens(input: &str) -> Tokens<'_> {
+    Tokens { rest: input }
+}
+
+// Symbols consisting of two characters. All other symbols are a single character.
+const TWO_CHAR_SYMBOLS: [&str; 8] = ["==", "!=", "<=", ">=", "->", "=>", "&&", "||"];
+
+// Return the length (in bytes) of the longest prefix of `s` whose characters satisfy `pred`.
+//@ `find` returns the byte index of the first character that does *not* satisfy `pred`. If there
+//@ is none, the entire string is the prefix.
+fn prefix_len<F: Fn(char) -> bool>(s: &str, pred: F) -> usize {
+    s.find(|c| !pred(c)).unwrap_or(s.len())
+}
+
+//@ Now for the interesting part. Look closely at the type of `next`: It takes `&mut self`, but it
+//@ returns a `Token<'a>`. The returned token does *not* borrow the lexer - it borrows the input,
+//@ which the lexer itself only borrows for `'a`. That's why we can call `next` again while still
+//@ holding on to earlier tokens, or drop the lexer altogether and keep the tokens.
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        self.rest = self.rest.trim_start();
+        let c = self.rest.chars().next()?;
+        //@ First, we figure out how long the token is, and what kind of token it is.
+        let (len, kind): (usize, fn(&'a str) -> Token<'a>) =
+            if c.is_alphabetic() || c == '_' {
+                (prefix_len(self.rest, |c| c.is_alphanumeric() || c == '_'), Token::Ident)
+            } else if c.is_ascii_digit() {
+                (prefix_len(self.rest, |c| c.is_ascii_digit()), Token::Number) /*@*/
+            } else if TWO_CHAR_SYMBOLS.iter().any(|s| self.rest.starts_with(s)) {
+                (2, Token::Symbol)                                  /*@*/
+            } else {
+                (c.len_utf8(), Token::Symbol)                       /*@*/
+            };
+        //@ Then we split the rest of the input into the token, and what remains after it.
+        //@ `split_at` does not copy anything, it just creates two slices pointing into the same
+        //@ string. Both of them live for `'a`.
+        let (token, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Some(kind(token))
+    }
+}
+//@ You may be wondering about `Token::Ident` being used like a function. It *is* a function:
+//@ Every tuple-like enum variant can be used as a function that takes the fields and returns the
+//@ enum. Here, we store it in a variable of type `fn(&'a str) -> Token<'a>`, which is the type
+//@ of *function pointers*. Unlike closures, function pointers have no environment.
+
+// ## Using the lexer
+// Count how often every identifier appears in the input.
+//@ The keys of our `HashMap` are `&str`, borrowed from the input. Again, nothing is copied. The
+//@ signature tells us that the result borrows from `input`, so the input has to outlive the map.
+pub fn count_idents(input: &str) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for token in tokens(input) {
+        if let Token::Ident(name) = token {                         /*@*/
+            *counts.entry(name).or_insert(0) += 1;                  /*@*/
+        }                                                           /*@*/
+    }
+    counts
+}
+
+//@ The compiler makes sure that we do not free the input while the tokens are still in use:
+/*
+  let tokens: Vec<Token> = {
+      let input = String::from("let x = 1;");
+      tokens(&input).collect()
+  };
+*/
+//@ Rust rejects this, because `input` is dropped at the end of the block, while `tokens` still
+//@ borrows it. Try it, and then move `input` out of the block.
+
+// ## Why not own the input?
+//@ You may be tempted to let the lexer own the input, so that you don't have to worry about
+//@ lifetimes:
+/*
+  struct OwnedTokens {
+      input: String,
+      pos: usize,
+  }
+  impl Iterator for OwnedTokens {
+      type Item = &str;
+      // ...
+  }
+*/
+//@ This does not work. What should be the lifetime of the `&str` in `Item`? The tokens would
+//@ borrow from the lexer itself, so they could only live as long as the `&mut self` passed to
+//@ `next`. But the type `Item` is fixed for the entire iterator, and cannot mention the lifetime
+//@ of the `&mut self` of one particular call to `next`. An iterator whose items borrow from the
+//@ iterator itself is sometimes called a *lending iterator*, and the `Iterator` trait cannot
+//@ express it. In contrast, with `Tokens<'a>`, the items borrow from something *outside* the
+//@ iterator, and everything works out. That's the common pattern: Have the data owned somewhere
+//@ else, and let the iterator borrow it.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let input = String::from("fn add(x: i32, y: i32) -> i32 { x + y }");
+    for token in tokens(&input) {
+        println!("{:?}", token);
+    }
+    let mut counts: Vec<_> = count_idents(&input).into_iter().collect();
+    counts.sort();
+    println!("{:?}", counts);
+}
+
+// **Exercise 33.1**: Make the lexer skip comments, which start with `//` and extend to the end of
+// the line. Make sure `a / b` is still lexed as three tokens.
+
+// **Exercise 33.2**: Add string literals, like `"hello"`, as a new kind of token `Token::Str`. The
+// slice should include the quotes. What should happen if the closing quote is missing? Consider
+// changing the `Item` type to `Result<Token<'a>, &'a str>`.
+
+// **Exercise 33.3**: Add a method `offset(&self, input: &str) -> usize` to `Tokens`, returning the
+// position of the lexer in the input. Hint: The length of `rest` tells you how much is left.
+// Then use this lexer in the calculator of part 32 to compute the spans.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::Token::*;
+
+    #[test]
+    fn test_tokens() {
+        let toks: Vec<Token> = tokens("let x1 = foo_bar(42)->y;").collect();
+        assert_eq!(toks, vec![Ident("let"), Ident("x1"), Symbol("="), Ident("foo_bar"),
+                              Symbol("("), Number("42"), Symbol(")"), Symbol("->"), Ident("y"),
+                              Symbol(";")]);
+        assert_eq!(tokens("  \n\t ").next(), None);
+        assert_eq!(tokens("a==b").collect::<Vec<_>>(), vec![Ident("a"), Symbol("=="), Ident("b")]);
+        assert_eq!(tokens("12ab").collect::<Vec<_>>(), vec![Number("12"), Ident("ab")]);
+        assert_eq!(tokens("größe → 1").collect::<Vec<_>>(),
+                   vec![Ident("größe"), Symbol("→"), Number("1")]);
+    }
+
+    #[test]
+    fn test_tokens_borrow_input() {
+        let input = String::from("a + b");
+        let first = {
+            // The lexer is dropped at the end of this block, but the token lives on.
+            let mut lexer = tokens(&input);
+            let first = lexer.next().unwrap();
+            let second = lexer.next().unwrap();
+            assert_eq!(second, Symbol("+"));
+            first
+        };
+        assert_eq!(first, Ident("a"));
+        // The token really points into the input.
+        if let Ident(s) = first {
+            assert_eq!(s.as_ptr(), input.as_ptr());
+        }
+    }
+
+    #[test]
+    fn test_count_idents() {
+        let counts = count_idents("x = x + y * x");
+        assert_eq!(counts["x"], 3);
+        assert_eq!(counts["y"], 1);
+        assert_eq!(counts.len(), 2);
+    }
+}
+
+//@ [index](main.html) | [previous](part32.html) | [raw source](workspace/src/part33.rs) |
+//@ [next](part34.html)
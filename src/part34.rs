@@ -0,0 +1,235 @@
+// Rust-101, Part 34: Processes, a Mini Shell
+// ==========================================
+
+use std::io::prelude::*;
+use std::io;
+use std::env;
+use std::thread;
+use std::process::{Command, Stdio, ExitStatus};
+
+//@ So far, all our programs did their work by themselves. But often, the easiest way to get
+//@ something done is to ask another program to do it. In this part, we will learn how to start
+//@ other programs, talk to them, and connect them to each other. At the end, we will have written
+//@ a tiny shell.
+
+// ## Running a command
+//@ The central type is
+//@ [`Command`](https://doc.rust-lang.org/stable/std/process/struct.Command.html). It is a
+//@ *builder*: We create it with the name of the program, and then call methods to configure it,
+//@ like `arg` to add arguments. Finally, `output` runs the program, waits for it to finish, and
+//@ returns everything it printed, together with its exit status. The output is just a bunch of
+//@ bytes (`Vec<u8>`); we convert it to a string, replacing invalid UTF-8 with `�`.
+pub fn version_of(program: &str) -> io::Result<String> {
+    let output = Command::new(program).arg("--version").output()?;
+    //@ The program may run, but fail. By convention, it then exits with a non-zero exit code. We
+    //@ turn that into an `io::Error`, so that the caller only has to handle one kind of error.
+    if !output.status.success() {
+        let message = format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr));
+        return Err(io::Error::other(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Run a program, and return its exit code.
+//@ The exit code is an `Option`: On Unix, a program can also be killed by a *signal*, and then
+//@ there is no exit code. We still use `output` rather than `status`, because `status` would let
+//@ the program print to our terminal, and we want to keep it quiet.
+pub fn exit_code(program: &str, args: &[&str]) -> io::Result<Option<i32>> {
+    let output = Command::new(program).args(args).output()?;        /*@*/
+    Ok(output.status.code())                                        /*@*/
+}
+
+// ## Talking to a child process
+//@ Every process has three standard streams: input, output and error. With `stdin`, `stdout` and
+//@ `stderr`, we decide what they are connected to. `Stdio::piped()` creates a *pipe*: We get one
+//@ end of it, and the child gets the other. Instead of `output`, we call `spawn`, which starts the
+//@ program and returns immediately. The `Child` we get back has fields for the ends of the pipes
+//@ we asked for.
+pub fn feed(program: &str, args: &[&str], input: &str) -> io::Result<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    //@ Now we have to write `input` to the child, and read its output. If we first wrote all the
+    //@ input and then read the output, we could *deadlock*: The pipes have a limited capacity. If
+    //@ the child writes a lot of output before it reads all its input, it waits for us to read
+    //@ the output, while we wait for it to read the input. Nobody makes any progress.
+    //@ So, we write from a separate thread. `take` moves the pipe out of `child`, so that the
+    //@ thread can own it. Once the thread is done, the pipe is dropped, which closes it: The child
+    //@ sees the end of its input.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    //@ `wait_with_output` reads all the output, and waits for the child to exit.
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// ## Pipelines
+//@ A shell lets you connect programs with `|`: In `ls | sort`, the output of `ls` becomes the input
+//@ of `sort`. Let's do that ourselves. First, we split a command line into the stages of the
+//@ pipeline, and every stage into words. We do not support any quoting, which keeps this simple.
+//@ All the words are slices of the command line, just like the tokens in part 33.
+pub fn parse_pipeline(line: &str) -> Result<Vec<Vec<&str>>, String> {
+    let mut stages = Vec::new();
+    for stage in line.split('|') {
+        let words: Vec<&str> = stage.split_whitespace().collect();  /*@*/
+        if words.is_empty() {                                       /*@*/
+            return Err("empty command".to_string());                /*@*/
+        }                                                           /*@*/
+        stages.push(words);                                         /*@*/
+    }
+    Ok(stages)
+}
+
+// Run a pipeline, and return the exit status of the last stage together with its output.
+//@ The standard output of every stage is a pipe. We take the reading end of it, and hand it to the
+//@ next stage as its standard input: `Stdio::from` turns a `ChildStdout` into something we can
+//@ pass to `stdin`. The output of the last stage is what we return.
+pub fn run_pipeline(stages: &[Vec<&str>]) -> io::Result<(ExitStatus, String)> {
+    let mut children = Vec::new();
+    let mut previous_stdout = None;
+    for stage in stages {
+        let mut command = Command::new(stage[0]);
+        command.args(&stage[1..]).stdout(Stdio::piped());
+        if let Some(stdout) = previous_stdout.take() {
+            command.stdin(Stdio::from(stdout));                     /*@*/
+        }
+        let mut child = command.spawn()?;
+        previous_stdout = child.stdout.take();
+        children.push(child);
+    }
+    let mut output = Vec::new();
+    if let Some(mut stdout) = previous_stdout {
+        stdout.read_to_end(&mut output)?;
+    }
+    //@ All stages run at the same time, so we wait for all of them. The shell convention is that
+    //@ the status of the entire pipeline is the status of its last stage.
+    let mut status = None;
+    for mut child in children {
+        status = Some(child.wait()?);                               /*@*/
+    }
+    let status = status.expect("run_pipeline: no stages");
+    Ok((status, String::from_utf8_lossy(&output).into_owned()))
+}
+
+// ## The shell
+//@ Now we have all the pieces for our shell. It reads a line, runs it, and prints the output. Some
+//@ commands cannot be run as separate programs: `cd` has to change the working directory of the
+//@ *shell*, and `exit` has to end it. These are *builtins*.
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let stdin = io::stdin();
+    loop {
+        //@ `print!` does not end the line, so we have to `flush` the output, or the prompt would
+        //@ only show up later.
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break; // End of input
+        }
+        let stages = match parse_pipeline(&line) {
+            Ok(stages) => stages,
+            Err(e) => { println!("{}", e); continue; }
+        };
+        match stages[0][0] {
+            "exit" => break,
+            "cd" => {
+                let dir = stages[0].get(1).cloned().unwrap_or("/");
+                if let Err(e) = env::set_current_dir(dir) {
+                    println!("cd: {}", e);
+                }
+            }
+            _ => match run_pipeline(&stages) {
+                Ok((status, output)) => {
+                    print!("{}", output);
+                    if !status.success() {
+                        println!("[{}]", status);
+                    }
+                }
+                Err(e) => println!("{}: {}", stages[0][0], e),
+            },
+        }
+    }
+}
+
+// **Exercise 34.1**: Our shell collects the entire output before printing it. That's bad for
+// programs that run a long time. Change `run_pipeline` so that the last stage writes directly to
+// the terminal. Hint: Look at `Stdio::inherit`.
+
+// **Exercise 34.2**: If spawning one stage of the pipeline fails, the stages before it have
+// already been started. What happens to them? Make sure `run_pipeline` waits for them (or kills
+// them) before returning the error.
+
+// **Exercise 34.3**: Add support for quoting, so that `echo "a | b"` prints `a | b`. You will no
+// longer be able to return slices of the input for every word. Why? Which type would you use
+// instead?
+
+// **Exercise 34.4**: Add redirection of the output to a file, like `ls > files.txt`. Hint:
+// `Stdio::from` also accepts a `File`.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The one program we know exists on every platform is `cargo`, which runs these tests.
+    const CARGO: &str = env!("CARGO");
+
+    #[test]
+    fn test_version_of() {
+        assert!(version_of(CARGO).unwrap().starts_with("cargo "));
+        let err = version_of("this-program-does-not-exist").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(CARGO, &["--version"]).unwrap(), Some(0));
+        assert_ne!(exit_code(CARGO, &["no-such-subcommand"]).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_parse_pipeline() {
+        assert_eq!(parse_pipeline("ls -l | sort\n").unwrap(), vec![vec!["ls", "-l"], vec!["sort"]]);
+        assert_eq!(parse_pipeline("  echo   hi  ").unwrap(), vec![vec!["echo", "hi"]]);
+        assert!(parse_pipeline("ls | | sort").is_err());
+        assert!(parse_pipeline("   ").is_err());
+    }
+
+    #[test]
+    fn test_run_pipeline_portable() {
+        let (status, output) = run_pipeline(&[vec![CARGO, "--version"]]).unwrap();
+        assert!(status.success());
+        assert!(output.starts_with("cargo "));
+    }
+
+    // The following tests need some standard Unix programs.
+    #[cfg(unix)]
+    #[test]
+    fn test_feed() {
+        assert_eq!(feed("sort", &[], "b\nc\na\n").unwrap(), "a\nb\nc\n");
+        // This is enough data to fill the pipes, so it would deadlock without the writer thread.
+        let input = "line\n".repeat(100_000);
+        assert_eq!(feed("cat", &[], &input).unwrap(), input);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_pipeline() {
+        // Our parser does not support quotes, so we build the first stage by hand.
+        let mut stages = vec![vec!["printf", "b\na\nd\nc\n"]];
+        stages.extend(parse_pipeline("sort -r | head -n 3").unwrap());
+        let (status, output) = run_pipeline(&stages).unwrap();
+        assert!(status.success());
+        assert_eq!(output, "d\nc\nb\n");
+
+        let (status, _) = run_pipeline(&parse_pipeline("echo | false").unwrap()).unwrap();
+        assert!(!status.success());
+    }
+}
+
+//@ [index](main.html) | [previous](part33.html) | [raw source](workspace/src/part34.rs) |
+//@ [next](part35.html)
@@ -0,0 +1,286 @@
+// Rust-101, Part 35: Time, a Scheduler
+// ====================================
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex, Condvar};
+use std::thread;
+use std::time::{Duration, Instant};
+
+//@ In part 15, our threads used `thread::sleep` to wait a little between their steps. In this
+//@ part, we will take a closer look at how Rust represents time, and then build a *scheduler*: A
+//@ thread that runs closures we register with it at regular intervals, like a tiny `cron`.
+
+// ## `Instant` and `Duration`
+//@ The standard library has two types for time.
+//@ [`Instant`](https://doc.rust-lang.org/stable/std/time/struct.Instant.html) is a point in time,
+//@ and [`Duration`](https://doc.rust-lang.org/stable/std/time/struct.Duration.html) is the
+//@ difference between two points in time. The arithmetic operators do what you would expect:
+//@ Subtracting two `Instant` gives a `Duration`, and adding a `Duration` to an `Instant` gives
+//@ another `Instant`. Adding two `Instant` does not make sense, and indeed, it does not compile.
+//@
+//@ An `Instant` is *monotonic*: Time never goes backwards. That's what you want for measuring how
+//@ long something takes, or for timeouts. If you want to know what time it is for the user, you
+//@ need `SystemTime` instead - but the user may change that clock at any time.
+
+// Measure how long it takes to run `f`.
+//@ `elapsed` is just a short-hand for `Instant::now() - start`.
+pub fn time_it<F: FnOnce()>(f: F) -> Duration {
+    let start = Instant::now();                                     /*@*/
+    f();                                                            /*@*/
+    start.elapsed()                                                 /*@*/
+}
+
+// ## Timers
+//@ Our scheduler has to remember a bunch of closures, and when they should be run next. Storing
+//@ the closures is something we did before: It's the callbacks of part 12. This time, the
+//@ closures will run on another thread, so they have to be `Send`. And since each of them only
+//@ ever runs on the scheduler's thread, we can simply put them into a `Box`.
+pub type JobId = usize;
+type Job = Box<dyn FnMut() + Send>;
+
+//@ To find the closure that has to run next, we use a
+//@ [`BinaryHeap`](https://doc.rust-lang.org/stable/std/collections/struct.BinaryHeap.html). It
+//@ always gives us its *largest* element, but we want the earliest deadline. `Reverse` is a
+//@ wrapper that turns the ordering around, so we get a min-heap. We cannot put the closures
+//@ themselves into the heap, since closures cannot be compared. Instead, we do what we did for the
+//@ graph in part 31: The jobs live in a vector, and the heap contains their indices.
+//@
+//@ `Timers` does not look at the clock itself. Whoever uses it passes in the current time. That
+//@ makes it easy to test: The tests can make up any time they like.
+pub struct Timers {
+    // For every job, its interval and the closure. Cancelled jobs become `None`.
+    jobs: Vec<Option<(Duration, Job)>>,
+    queue: BinaryHeap<Reverse<(Instant, JobId)>>,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Timers { jobs: Vec::new(), queue: BinaryHeap::new() }
+    }
+
+    // Run `job` every `interval`, starting at `now + interval`.
+    pub fn add<F: FnMut() + Send + 'static>(&mut self, now: Instant, interval: Duration,
+                                            job: F) -> JobId {
+        assert!(interval > Duration::from_secs(0), "Timers::add: interval must not be zero");
+        let id = self.jobs.len();                                   /*@*/
+        self.jobs.push(Some((interval, Box::new(job))));            /*@*/
+        self.queue.push(Reverse((now + interval, id)));             /*@*/
+        id                                                          /*@*/
+    }
+
+    // Stop running the given job.
+    //@ Removing an element from the middle of a heap is not possible. So we only forget the
+    //@ closure, and leave the entry in the heap. Once it comes up, we skip it.
+    pub fn cancel(&mut self, id: JobId) {
+        self.jobs[id] = None;
+    }
+
+    // Run all jobs that are due at `now`, and return when the next job is due.
+    //@ After running a job, we put it back into the heap with its next deadline. We compute that
+    //@ from the previous *deadline*, not from `now`: If we are a little late, we do not want that
+    //@ delay to add up over time.
+    pub fn run_due(&mut self, now: Instant) -> Option<Instant> {
+        while let Some(&Reverse((deadline, id))) = self.queue.peek() {
+            if deadline > now {                                     /*@*/
+                return Some(deadline);                              /*@*/
+            }                                                       /*@*/
+            self.queue.pop();                                       /*@*/
+            if let Some((interval, ref mut job)) = self.jobs[id] {  /*@*/
+                job();                                              /*@*/
+                self.queue.push(Reverse((deadline + interval, id))); /*@*/
+            }                                                       /*@*/
+        }
+        None
+    }
+}
+
+// ## The scheduler thread
+//@ Now we put `Timers` on a thread of its own. The thread and the `Scheduler` handle share the
+//@ timers, so they go into an `Arc<Mutex<_>>` like the counter of part 15. We also store a flag
+//@ that tells the thread to stop.
+//@
+//@ What should the thread do while waiting for the next deadline? It could `sleep`, but then it
+//@ would not notice if we add a job with an earlier deadline, or ask it to shut down. Instead, we
+//@ use a [`Condvar`](https://doc.rust-lang.org/stable/std/sync/struct.Condvar.html), a *condition
+//@ variable*. Waiting on a condition variable releases the lock, and goes to sleep until somebody
+//@ calls `notify_one` - or, with `wait_timeout`, until the time is up. Afterwards, the lock is
+//@ taken again.
+struct State {
+    timers: Timers,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wakeup: Condvar,
+}
+
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+// This is what the scheduler thread runs.
+//@ `wait` and `wait_timeout` may also return *spuriously*, without anybody having notified us.
+//@ That's not a problem, because we check everything again in the next round of the loop.
+fn run(shared: &Shared) {
+    let mut state = shared.state.lock().unwrap();
+    while !state.shutdown {
+        state = match state.timers.run_due(Instant::now()) {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now()); /*@*/
+                shared.wakeup.wait_timeout(state, timeout).unwrap().0 /*@*/
+            }
+            None => shared.wakeup.wait(state).unwrap(),
+        };
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        let state = State { timers: Timers::new(), shutdown: false };
+        let shared = Arc::new(Shared { state: Mutex::new(state), wakeup: Condvar::new() });
+        let thread_shared = shared.clone();
+        let thread = thread::spawn(move || run(&thread_shared));
+        Scheduler { shared, thread: Some(thread) }
+    }
+
+    // Run `job` every `interval`.
+    //@ After adding the job, we wake up the thread: The new job may have to run before the one it
+    //@ is currently waiting for.
+    pub fn every<F: FnMut() + Send + 'static>(&self, interval: Duration, job: F) -> JobId {
+        let mut state = self.shared.state.lock().unwrap();          /*@*/
+        let id = state.timers.add(Instant::now(), interval, job);   /*@*/
+        self.shared.wakeup.notify_one();                            /*@*/
+        id                                                          /*@*/
+    }
+
+    pub fn cancel(&self, id: JobId) {
+        self.shared.state.lock().unwrap().timers.cancel(id);
+    }
+
+    // Stop the scheduler, and wait for the thread to finish.
+    //@ This method takes `self` by value, so the scheduler cannot be used afterwards. All the
+    //@ work happens in `drop`, which runs at the end of this function.
+    pub fn shutdown(self) {}
+}
+
+// ## Graceful shutdown
+//@ When the `Scheduler` is dropped, we do not want to leave the thread behind. We set the flag,
+//@ wake up the thread, and then `join` it. The thread finishes whatever job it is running at the
+//@ moment, notices the flag, and returns. This is a common pattern: Tie the lifetime of a thread
+//@ to the lifetime of a value, and use `Drop` to clean up. The `Option` around the `JoinHandle`
+//@ is needed because `join` takes the handle by value, but `drop` only gets `&mut self`. `take`
+//@ moves the handle out of the `Option`, and leaves `None` behind.
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().shutdown = true;          /*@*/
+        self.shared.wakeup.notify_one();                            /*@*/
+        if let Some(thread) = self.thread.take() {                  /*@*/
+            thread.join().unwrap();                                 /*@*/
+        }                                                           /*@*/
+    }
+}
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let took = time_it(|| thread::sleep(Duration::from_millis(50)));
+    println!("Sleeping for 50ms took {:?}.", took);
+
+    let scheduler = Scheduler::new();
+    let start = Instant::now();
+    scheduler.every(Duration::from_millis(100), move || println!("tick at {:?}", start.elapsed()));
+    let tock = scheduler.every(Duration::from_millis(250), || println!("tock"));
+    thread::sleep(Duration::from_millis(600));
+    scheduler.cancel(tock);
+    thread::sleep(Duration::from_millis(400));
+    scheduler.shutdown();
+    println!("Done.");
+}
+
+// **Exercise 35.1**: Add a method `after` that runs a job only *once*, after the given duration.
+// Which type would you use for the jobs, so that they can be `FnOnce`?
+
+// **Exercise 35.2**: The jobs run while the scheduler thread holds the lock. What happens if a
+// job takes a long time? What happens if a job calls `every` on the scheduler (through an `Arc`)?
+// Change `run_due` so that the lock is not held while a job is running.
+
+// **Exercise 35.3**: If the scheduler falls behind (e.g., because a job took longer than its
+// interval), `run_due` runs the late jobs several times in a row to catch up. Change it so that
+// missed runs are skipped instead.
+
+// **Exercise 35.4**: `Scheduler` always uses `Instant::now()`. Define a trait `Clock` with a method
+// `now`, and make `Scheduler` generic over it. Can you write a fake clock for the tests? What about
+// the waiting - can you make that fake, too?
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_time_it() {
+        assert!(time_it(|| thread::sleep(ms(20))) >= ms(20));
+    }
+
+    // With `Timers`, we decide what time it is, so the test is exact.
+    #[test]
+    fn test_timers() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (log_a, log_b) = (log.clone(), log.clone());
+        let start = Instant::now();
+        let mut timers = Timers::new();
+        let a = timers.add(start, ms(10), move || log_a.lock().unwrap().push("a"));
+        let b = timers.add(start, ms(25), move || log_b.lock().unwrap().push("b"));
+
+        assert_eq!(timers.run_due(start), Some(start + ms(10)));
+        assert_eq!(timers.run_due(start + ms(10)), Some(start + ms(20)));
+        assert_eq!(*log.lock().unwrap(), vec!["a"]);
+        // Jobs run in the order of their deadlines, and catch up if we are late.
+        assert_eq!(timers.run_due(start + ms(30)), Some(start + ms(40)));
+        assert_eq!(*log.lock().unwrap(), vec!["a", "a", "b", "a"]);
+
+        timers.cancel(a);
+        assert_eq!(timers.run_due(start + ms(50)), Some(start + ms(75)));
+        assert_eq!(*log.lock().unwrap(), vec!["a", "a", "b", "a", "b"]);
+        timers.cancel(b);
+        assert_eq!(timers.run_due(start + ms(100)), None);
+        assert_eq!(log.lock().unwrap().len(), 5);
+    }
+
+    // With real threads, timing is never exact, so we are generous.
+    #[test]
+    fn test_scheduler() {
+        let count = Arc::new(Mutex::new(0));
+        let count2 = count.clone();
+        let scheduler = Scheduler::new();
+        scheduler.every(ms(10), move || *count2.lock().unwrap() += 1);
+        thread::sleep(ms(200));
+        scheduler.shutdown();
+        let n = *count.lock().unwrap();
+        assert!((5..=25).contains(&n), "job ran {} times", n);
+        // After the shutdown, nothing runs any more.
+        thread::sleep(ms(30));
+        assert_eq!(*count.lock().unwrap(), n);
+    }
+
+    #[test]
+    fn test_scheduler_cancel() {
+        let count = Arc::new(Mutex::new(0));
+        let count2 = count.clone();
+        let scheduler = Scheduler::new();
+        let id = scheduler.every(ms(10), move || *count2.lock().unwrap() += 1);
+        scheduler.cancel(id);
+        thread::sleep(ms(50));
+        assert_eq!(*count.lock().unwrap(), 0);
+        // A scheduler without any jobs also shuts down.
+        drop(scheduler);
+    }
+}
+
+//@ [index](main.html) | [previous](part34.html) | [raw source](workspace/src/part35.rs) |
+//@ [next](part36.html)
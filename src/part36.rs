@@ -0,0 +1,277 @@
+// Rust-101, Part 36: Closures Desugared
+// =====================================
+
+use std::mem;
+
+//@ Back in part 10, we claimed that a closure is really just a struct holding its environment,
+//@ together with an implementation of one of the closure traits. In part 11, we said that
+//@ `Box<dyn FnMut>` is a pointer to the code together with a pointer to the environment. In this
+//@ part, we are going to take these claims literally: We will write, by hand, what the compiler
+//@ generates for a bunch of closures, and then check that our version behaves just like the real
+//@ thing.
+
+// ## The closure traits
+//@ We cannot implement `Fn`, `FnMut` and `FnOnce` ourselves: The details of these traits are still
+//@ unstable. So we define lookalike traits. The real ones look almost exactly like this.
+//@ Notice that the arguments are a single type parameter `Args`. For a closure taking an `i32` and
+//@ a `bool`, `Args` is the tuple `(i32, bool)`. For a closure taking one `i32`, `Args` is the
+//@ one-element tuple `(i32,)` - the comma is needed to tell it apart from a parenthesized `i32`.
+//@ The syntax `Fn(i32, bool) -> u64` is just sugar for `Fn<(i32, bool), Output = u64>`.
+pub trait MyFnOnce<Args> {
+    type Output;
+    fn call_once(self, args: Args) -> Self::Output;
+}
+
+//@ `FnMut` is a *subtrait* of `FnOnce`: Everything that can be called many times can also be called
+//@ once. The same goes for `Fn` and `FnMut`. The return type is always the `Output` of `FnOnce`.
+pub trait MyFnMut<Args>: MyFnOnce<Args> {
+    fn call_mut(&mut self, args: Args) -> Self::Output;
+}
+
+pub trait MyFn<Args>: MyFnMut<Args> {
+    fn call(&self, args: Args) -> Self::Output;
+}
+
+// ## A closure borrowing its environment
+//@ Let's start with a closure that reads a local variable: `let add_offset = |x: i32| x + offset;`,
+//@ where `offset` is a local `i32`. The closure uses `offset`, but it does not need to own it, nor
+//@ does it need to change it. So the compiler captures it by shared reference. The environment is a
+//@ struct with one field per captured variable. Since it holds a reference, it has a lifetime
+//@ parameter: The closure cannot outlive `offset`.
+pub struct AddOffset<'a> {
+    offset: &'a i32,
+}
+
+//@ A closure that only reads its environment implements `Fn`. The body of the closure becomes
+//@ the body of `call`. Every use of `offset` turns into `*self.offset`.
+impl<'a> MyFn<(i32,)> for AddOffset<'a> {
+    fn call(&self, (x,): (i32,)) -> i32 {
+        x + *self.offset                                            /*@*/
+    }
+}
+//@ The other two traits simply forward to `call`. The compiler does the same, it just does not
+//@ need to write it down.
+impl<'a> MyFnMut<(i32,)> for AddOffset<'a> {
+    fn call_mut(&mut self, args: (i32,)) -> i32 {
+        self.call(args)
+    }
+}
+impl<'a> MyFnOnce<(i32,)> for AddOffset<'a> {
+    type Output = i32;
+    fn call_once(self, args: (i32,)) -> i32 {
+        self.call(args)
+    }
+}
+
+// ## A closure mutating its environment
+//@ Next, a closure that changes a local variable `count` of type `usize`: `let mut increment = |by:
+//@ usize| { count += by; count };`. Now the closure needs a *mutable* reference to `count`. And
+//@ since it mutates its environment, it can only implement `FnMut` - `call` only gets `&self`, and
+//@ cannot write through `self.count`. That's also why `increment` itself has to be declared `mut`:
+//@ Calling it needs a `&mut` to the environment.
+pub struct Increment<'a> {
+    count: &'a mut usize,
+}
+
+impl<'a> MyFnMut<(usize,)> for Increment<'a> {
+    fn call_mut(&mut self, (by,): (usize,)) -> usize {
+        *self.count += by;                                          /*@*/
+        *self.count                                                 /*@*/
+    }
+}
+impl<'a> MyFnOnce<(usize,)> for Increment<'a> {
+    type Output = usize;
+    fn call_once(mut self, args: (usize,)) -> usize {
+        self.call_mut(args)
+    }
+}
+
+// ## Moving into the environment
+//@ With `move`, the closure takes ownership of the variables it uses. The fields of the struct are
+//@ no longer references. There is no lifetime any more, so this closure can be returned from a
+//@ function, or sent to another thread. Here is the struct for `move |x: i32| x * factor`:
+pub struct Scale {
+    factor: i32,
+}
+
+impl MyFn<(i32,)> for Scale {
+    fn call(&self, (x,): (i32,)) -> i32 {
+        x * self.factor                                             /*@*/
+    }
+}
+impl MyFnMut<(i32,)> for Scale {
+    fn call_mut(&mut self, args: (i32,)) -> i32 {
+        self.call(args)
+    }
+}
+impl MyFnOnce<(i32,)> for Scale {
+    type Output = i32;
+    fn call_once(self, args: (i32,)) -> i32 {
+        self.call(args)
+    }
+}
+
+// ## A closure consuming its environment
+//@ Finally, a closure that gives away something it owns: `move || name + ", hello!"`, where `name`
+//@ is a `String`. `+` on a `String` consumes its left operand (remember part 07). Once the closure
+//@ did that, it does not have `name` any more, so it cannot be called a second time. This closure
+//@ only implements `FnOnce`, where `call_once` takes `self` by value. The `Args` are the empty
+//@ tuple.
+pub struct Greeting {
+    name: String,
+}
+
+impl MyFnOnce<()> for Greeting {
+    type Output = String;
+    fn call_once(self, (): ()) -> String {
+        self.name + ", hello!"                                      /*@*/
+    }
+}
+
+// ## Using our closures
+//@ Functions that take closures look just like they did in part 10, except for the traits. We have
+//@ to spell out the `Args` tuple and the `Output`, since the nice `Fn(i32) -> i32` syntax is only
+//@ available for the real traits. Calling `f(3)` becomes `f.call((3,))`.
+pub fn apply_to_3<F: MyFn<(i32,), Output = i32>>(f: &F) -> i32 {
+    f.call((3,))
+}
+
+pub fn call_twice<F: MyFnMut<(usize,), Output = usize>>(f: &mut F, by: usize) -> usize {
+    f.call_mut((by,));
+    f.call_mut((by,))
+}
+
+pub fn call_once_unit<F: MyFnOnce<()>>(f: F) -> F::Output {
+    f.call_once(())
+}
+
+// ## Real closures implement our traits
+//@ We can also go the other way: Every real closure implements our lookalike traits, by calling
+//@ itself. These are *blanket implementations*, like those in part 09. We only do this for
+//@ closures taking one argument, and closures taking none. The standard library has to do this for
+//@ all lengths of the `Args` tuple - which is one of the reasons its traits are still unstable.
+impl<A, R, F: FnOnce(A) -> R> MyFnOnce<(A,)> for F {
+    type Output = R;
+    fn call_once(self, (a,): (A,)) -> R {
+        self(a)
+    }
+}
+impl<A, R, F: FnMut(A) -> R> MyFnMut<(A,)> for F {
+    fn call_mut(&mut self, (a,): (A,)) -> R {
+        self(a)
+    }
+}
+impl<A, R, F: Fn(A) -> R> MyFn<(A,)> for F {
+    fn call(&self, (a,): (A,)) -> R {
+        self(a)
+    }
+}
+impl<R, F: FnOnce() -> R> MyFnOnce<()> for F {
+    type Output = R;
+    fn call_once(self, (): ()) -> R {
+        self()
+    }
+}
+//@ You may wonder why these do not overlap with the implementations for our structs. Rust knows
+//@ that `AddOffset` does not implement `FnOnce(i32) -> i32`, because the only crate that could add
+//@ such an implementation is ours - and we did not.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let offset = 5;
+    let add_offset = |x: i32| x + offset;
+    println!("real: {}, desugared: {}", apply_to_3(&add_offset),
+             apply_to_3(&AddOffset { offset: &offset }));
+
+    let mut count = 0;
+    {
+        let mut increment = Increment { count: &mut count };
+        call_twice(&mut increment, 2);
+    }
+    {
+        let mut increment = |by: usize| { count += by; count };
+        call_twice(&mut increment, 3);
+    }
+    println!("count: {}", count);
+
+    let greeting = Greeting { name: String::from("Ferris") };
+    println!("{}", call_once_unit(greeting));
+    //@ The environment really is all there is to a closure. A closure capturing a single reference
+    //@ is exactly as big as a reference.
+    println!("size of the closure: {}, size of the struct: {}", mem::size_of_val(&add_offset),
+             mem::size_of::<AddOffset>());
+}
+
+// **Exercise 36.1**: Write the struct and the trait implementations for the closure
+// `|a: &str, b: &str| a.len() + b.len() + extra`, where `extra` is a local `usize`. Which traits
+// does it implement? Also write the blanket implementations for real closures with two arguments.
+
+// **Exercise 36.2**: A closure that does not capture anything has an empty environment. What is the
+// size of its struct? Such closures can be converted to function pointers, like
+// `let f: fn(i32) -> i32 = |x| x + 1;`. Why does that not work for `add_offset`?
+
+// **Exercise 36.3**: Desugar `let print_all = |v: &Vec<i32>| for x in v { println!("{}", x) };`.
+// The argument is a reference, so your `impl` needs a lifetime for it. Where does it go? Hint:
+// Every call may pass a reference with a different lifetime.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_offset() {
+        let offset = 5;
+        let real = |x: i32| x + offset;
+        let desugared = AddOffset { offset: &offset };
+        for x in -3..3 {
+            assert_eq!(real.call((x,)), desugared.call((x,)));
+        }
+        assert_eq!(apply_to_3(&real), 8);
+        assert_eq!(apply_to_3(&desugared), 8);
+        assert_eq!(desugared.call_once((10,)), 15);
+    }
+
+    #[test]
+    fn test_increment() {
+        let mut real_count = 0;
+        let mut desugared_count = 0;
+        {
+            let mut real = |by: usize| { real_count += by; real_count };
+            let mut desugared = Increment { count: &mut desugared_count };
+            assert_eq!(call_twice(&mut real, 2), 4);
+            assert_eq!(call_twice(&mut desugared, 2), 4);
+            assert_eq!(real.call_once((1,)), desugared.call_once((1,)));
+        }
+        assert_eq!(real_count, 5);
+        assert_eq!(desugared_count, 5);
+    }
+
+    #[test]
+    fn test_move_and_once() {
+        let factor = 3;
+        let real = move |x: i32| x * factor;
+        let desugared = Scale { factor };
+        assert_eq!(apply_to_3(&real), apply_to_3(&desugared));
+
+        let name = String::from("Ferris");
+        let real = move || name + ", hello!";
+        let desugared = Greeting { name: String::from("Ferris") };
+        assert_eq!(call_once_unit(real), call_once_unit(desugared));
+    }
+
+    // The environment is all there is to a closure: The sizes match our structs.
+    #[test]
+    fn test_sizes() {
+        let offset = 5;
+        let add_offset = |x: i32| x + offset;
+        assert_eq!(mem::size_of_val(&add_offset), mem::size_of::<AddOffset>());
+        let factor = 3;
+        let scale = move |x: i32| x * factor;
+        assert_eq!(mem::size_of_val(&scale), mem::size_of::<Scale>());
+        let nothing = |x: i32| x + 1;
+        assert_eq!(mem::size_of_val(&nothing), 0);
+    }
+}
+
+//@ [index](main.html) | [previous](part35.html) | [raw source](workspace/src/part36.rs) |
+//@ [next](part37.html)
@@ -0,0 +1,205 @@
+// Rust-101, Part 37: Weak References, Breaking Cycles
+// ===================================================
+
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, RefCell};
+
+//@ In part 26, we mentioned that `Rc` can leak memory: If two objects hold an `Rc` to each other,
+//@ they keep each other alive forever. In this part, we will build such a cycle on purpose, watch
+//@ it leak, and then fix it with the other half of `Rc`: `Weak`.
+
+// ## Watching for drops
+//@ To see whether something is dropped, we use the `DropChecker` from the tests of part 26. All
+//@ clones of a checker share a counter, and every clone that is dropped increments it.
+#[derive(Clone)]
+pub struct DropChecker {
+    count: Rc<Cell<usize>>,
+}
+
+impl DropChecker {
+    pub fn new() -> Self {
+        DropChecker { count: Rc::new(Cell::new(0)) }
+    }
+
+    // How many clones of this checker have been dropped so far.
+    pub fn dropped(&self) -> usize {
+        self.count.get()
+    }
+}
+
+impl Drop for DropChecker {
+    fn drop(&mut self) {
+        self.count.set(self.count.get() + 1);
+    }
+}
+
+// ## A tree that leaks
+//@ Let's build a tree, where every node knows its children *and* its parent. The children are
+//@ shared with whoever else holds on to them, so we use `Rc`. To add children after a node has
+//@ been created, we need interior mutability, so the vector goes into a `RefCell`. The parent is
+//@ an `Rc`, too - after all, that's what we use for pointers to shared data.
+pub struct LeakyNode<T> {
+    pub data: T,
+    parent: RefCell<Option<Rc<LeakyNode<T>>>>,
+    children: RefCell<Vec<Rc<LeakyNode<T>>>>,
+}
+
+impl<T> LeakyNode<T> {
+    pub fn new(data: T) -> Rc<Self> {
+        Rc::new(LeakyNode { data, parent: RefCell::new(None), children: RefCell::new(Vec::new()) })
+    }
+}
+
+// Make `child` a child of `parent`.
+//@ This is a function rather than a method, because we need the `Rc` of the parent, not just a
+//@ reference to the node.
+pub fn add_leaky_child<T>(parent: &Rc<LeakyNode<T>>, child: Rc<LeakyNode<T>>) {
+    *child.parent.borrow_mut() = Some(parent.clone());              /*@*/
+    parent.children.borrow_mut().push(child);                       /*@*/
+}
+
+//@ Now consider a parent with a single child. The parent holds an `Rc` to the child, and the child
+//@ holds an `Rc` to the parent. When we drop our own `Rc` to the parent, its count goes down from
+//@ 2 to 1 - the child still points to it. So the parent is not dropped, and neither is the child.
+//@ Nobody can reach them any more, but they are still there: We have leaked them. The function
+//@ `Rc::strong_count` tells us how many `Rc` point to the same data. Take a look at
+//@ `test_leaky_tree` below to see this in action.
+
+// ## `Weak`
+//@ The fix is to make one of the two directions not count. A
+//@ [`Weak`](https://doc.rust-lang.org/stable/std/rc/struct.Weak.html) pointer points to the same
+//@ data as an `Rc`, but it does not keep it alive. We get one with `Rc::downgrade`. Once the last
+//@ `Rc` is gone, the data is dropped, even if there are still `Weak` pointers to it. That means we
+//@ cannot just use a `Weak` to access the data: It may already be gone. Instead, we call `upgrade`,
+//@ which returns an `Option<Rc<T>>` - `None` if the data has been dropped.
+//@
+//@ In a tree, the parent owns its children, so the children get the `Rc`, and the parent pointer
+//@ becomes `Weak`. Now the data flows in one direction: Dropping the root drops its children, which
+//@ drop their children, and so on. `Weak::new()` creates a `Weak` that does not point anywhere, and
+//@ can never be upgraded. That's the parent of the root.
+pub struct Node<T> {
+    pub data: T,
+    parent: RefCell<Weak<Node<T>>>,
+    children: RefCell<Vec<Rc<Node<T>>>>,
+}
+
+impl<T> Node<T> {
+    pub fn new(data: T) -> Rc<Self> {
+        Rc::new(Node { data, parent: RefCell::new(Weak::new()), children: RefCell::new(Vec::new()) })
+    }
+
+    // Return the parent of this node, if there is one and it is still alive.
+    pub fn parent(&self) -> Option<Rc<Node<T>>> {
+        self.parent.borrow().upgrade()                              /*@*/
+    }
+
+    pub fn children(&self) -> Vec<Rc<Node<T>>> {
+        self.children.borrow().clone()
+    }
+
+    // Count the steps from this node to the root.
+    //@ Every `upgrade` gives us an `Rc`, which keeps the parent alive while we look at it.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut current = self.parent();
+        while let Some(node) = current {
+            depth += 1;                                             /*@*/
+            current = node.parent();                                /*@*/
+        }
+        depth
+    }
+}
+
+pub fn add_child<T>(parent: &Rc<Node<T>>, child: Rc<Node<T>>) {
+    *child.parent.borrow_mut() = Rc::downgrade(parent);             /*@*/
+    parent.children.borrow_mut().push(child);                       /*@*/
+}
+
+//@ `Rc::weak_count` tells us how many `Weak` point to some data. A parent with two children has a
+//@ `strong_count` of 1 (our own `Rc`), and a `weak_count` of 2.
+//@
+//@ Which direction should be weak? Think about who *owns* whom. The owner holds an `Rc`, everybody
+//@ else gets a `Weak`. If the data structure has no clear owner, that's often a sign that an arena
+//@ (part 29) or indices (part 31) are the better choice.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let checker = DropChecker::new();
+    {
+        let root = LeakyNode::new(checker.clone());
+        add_leaky_child(&root, LeakyNode::new(checker.clone()));
+        println!("Leaky root: strong count {}", Rc::strong_count(&root));
+    }
+    println!("Leaky tree dropped, {} nodes actually dropped.", checker.dropped());
+
+    let checker = DropChecker::new();
+    let leaf;
+    {
+        let root = Node::new(checker.clone());
+        leaf = Node::new(checker.clone());
+        add_child(&root, leaf.clone());
+        println!("Root: strong count {}, weak count {}", Rc::strong_count(&root),
+                 Rc::weak_count(&root));
+        println!("Leaf depth: {}", leaf.depth());
+    }
+    println!("Tree dropped, {} nodes actually dropped.", checker.dropped());
+    println!("Leaf still has a parent: {}", leaf.parent().is_some());
+}
+
+// **Exercise 37.1**: Write a function `remove_child(parent: &Rc<Node<T>>, index: usize)` that
+// removes a child from its parent. Don't forget to reset the parent pointer of the child.
+
+// **Exercise 37.2**: Implement the *observer pattern*. Define a trait `Observer` with a method
+// `notify(&self, event: &str)`, and a type `Subject` that stores a list of subscribers. The subject
+// should not keep its subscribers alive, so store them as `Weak<dyn Observer>`. Write a method
+// `subscribe(&self, observer: &Rc<dyn Observer>)`, and a method `emit(&self, event: &str)` that
+// notifies all subscribers that are still alive - and removes the others from the list. Hint:
+// `Vec::retain`. Test it by dropping a subscriber, and checking that it is no longer notified.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leaky_tree() {
+        let checker = DropChecker::new();
+        let root = LeakyNode::new(checker.clone());
+        let child = LeakyNode::new(checker.clone());
+        add_leaky_child(&root, child.clone());
+        assert_eq!(Rc::strong_count(&root), 2);
+        drop(child);
+        drop(root);
+        // The cycle keeps both nodes alive, so nothing was dropped.
+        assert_eq!(checker.dropped(), 0);
+    }
+
+    #[test]
+    fn test_tree() {
+        let checker = DropChecker::new();
+        let root = Node::new(checker.clone());
+        let child = Node::new(checker.clone());
+        let grandchild = Node::new(checker.clone());
+        add_child(&root, child.clone());
+        add_child(&root, Node::new(checker.clone()));
+        add_child(&child, grandchild.clone());
+        assert_eq!(Rc::strong_count(&root), 1);
+        assert_eq!(Rc::weak_count(&root), 2);
+        assert_eq!(root.children().len(), 2);
+        assert_eq!(grandchild.depth(), 2);
+        assert!(Rc::ptr_eq(&grandchild.parent().unwrap(), &child));
+        assert!(root.parent().is_none());
+
+        drop(child);
+        drop(root);
+        // Dropping the root dropped all its children. `grandchild` only holds a `Weak` to `child`,
+        // so that did not keep it alive.
+        assert_eq!(checker.dropped(), 3);
+        assert!(grandchild.parent().is_none());
+        assert_eq!(grandchild.depth(), 0);
+        drop(grandchild);
+        assert_eq!(checker.dropped(), 4);
+    }
+}
+
+//@ [index](main.html) | [previous](part36.html) | [raw source](workspace/src/part37.rs) |
+//@ [next](part38.html)
@@ -0,0 +1,254 @@
+// Rust-101, Part 38: Trees with Rc and RefCell
+// ============================================
+
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+
+//@ In part 16, we wrote a doubly-linked list with raw pointers and unsafe code. We said that doing
+//@ the same with `Rc` and `RefCell` would be "quite annoying", and incur some overhead. Now that
+//@ we know about `Weak` (part 37), it is time to check that claim. We will build a tree where
+//@ every node can be changed after it was created: We can add children, move entire subtrees
+//@ around, and modify the data in all nodes. And we will not write a single line of unsafe code.
+
+// ## The data structure
+//@ Every node is shared (by its parent, and by anybody else who holds on to it), and mutable. So it
+//@ lives in an `Rc<RefCell<_>>`. The parent pointer is `Weak`, to avoid the leak we saw in part 37.
+//@ The type names get long quickly, so we introduce some abbreviations.
+pub struct Node<T> {
+    pub data: T,
+    parent: WeakNodeRef<T>,
+    children: Vec<NodeRef<T>>,
+}
+
+pub type NodeRef<T> = Rc<RefCell<Node<T>>>;
+type WeakNodeRef<T> = Weak<RefCell<Node<T>>>;
+
+pub fn new_node<T>(data: T) -> NodeRef<T> {
+    Rc::new(RefCell::new(Node { data, parent: Weak::new(), children: Vec::new() }))
+}
+
+// Return the parent of `node`, if it has one.
+//@ Every access to a node has to go through `borrow` or `borrow_mut`. This is the "annoying" part:
+//@ Compare this to `(*node).prev` in part 16.
+pub fn parent<T>(node: &NodeRef<T>) -> Option<NodeRef<T>> {
+    node.borrow().parent.upgrade()                                  /*@*/
+}
+
+pub fn children<T>(node: &NodeRef<T>) -> Vec<NodeRef<T>> {
+    node.borrow().children.clone()
+}
+
+// Remove `node` from its parent. The node, and its children, stay alive as long as somebody else
+// holds an `Rc` to it.
+//@ Notice how careful we have to be about the borrows. We first find the parent, and release the
+//@ borrow of `node` (at the end of the `let` statement). Then we borrow the parent to remove the
+//@ node from its children, and finally we borrow the node again to reset its parent pointer. If we
+//@ tried to hold on to a `borrow` of `node` while taking a `borrow_mut`, the program would panic.
+pub fn detach<T>(node: &NodeRef<T>) {
+    let parent = node.borrow().parent.upgrade();
+    if let Some(parent) = parent {
+        parent.borrow_mut().children.retain(|child| !Rc::ptr_eq(child, node)); /*@*/
+    }
+    node.borrow_mut().parent = Weak::new();
+}
+
+// Make `child` the last child of `parent`. If `child` already has a parent, it is moved.
+pub fn add_child<T>(parent: &NodeRef<T>, child: NodeRef<T>) {
+    detach(&child);                                                 /*@*/
+    child.borrow_mut().parent = Rc::downgrade(parent);              /*@*/
+    parent.borrow_mut().children.push(child);                       /*@*/
+}
+
+// ## Visitors
+//@ To do something with every node of the tree, we use a *visitor*: A type with a method that is
+//@ called when we enter a node, and one that is called when we leave it, after all its children
+//@ have been visited. `leave` has a default implementation that does nothing, like the provided
+//@ methods of part 08.
+pub trait Visitor<T> {
+    fn enter(&mut self, data: &mut T, depth: usize);
+    fn leave(&mut self, _data: &mut T, _depth: usize) {}
+}
+
+//@ `walk` hands the visitor a mutable reference to the data of every node. That reference comes
+//@ from a `borrow_mut`, which is active while the visitor runs. Afterwards, we copy the list of
+//@ children (that's just cloning a bunch of `Rc`), so that we do not have to hold a borrow of this
+//@ node while walking its children. This is another example of the hidden cost of `RefCell`: We
+//@ pay with a small allocation to keep the borrows short.
+pub fn walk<T, V: Visitor<T>>(node: &NodeRef<T>, visitor: &mut V) {
+    walk_depth(node, 0, visitor)
+}
+
+fn walk_depth<T, V: Visitor<T>>(node: &NodeRef<T>, depth: usize, visitor: &mut V) {
+    let children: Vec<NodeRef<T>> = {
+        let mut node = node.borrow_mut();
+        visitor.enter(&mut node.data, depth);                       /*@*/
+        node.children.clone()                                       /*@*/
+    };
+    for child in children.iter() {
+        walk_depth(child, depth + 1, visitor);                      /*@*/
+    }
+    visitor.leave(&mut node.borrow_mut().data, depth);
+}
+
+// A visitor that prints the tree, indenting every node according to its depth.
+pub struct Printer;
+
+impl<T: ::std::fmt::Display> Visitor<T> for Printer {
+    fn enter(&mut self, data: &mut T, depth: usize) {
+        println!("{}{}", "  ".repeat(depth), data);
+    }
+}
+
+// A visitor that adds a number to every node.
+pub struct AddTo(pub i32);
+
+impl Visitor<i32> for AddTo {
+    fn enter(&mut self, data: &mut i32, _depth: usize) {
+        *data += self.0;                                            /*@*/
+    }
+}
+
+// ## The run-time checks
+//@ `RefCell` enforces the borrowing rules at run-time. If we break them, the program panics, just
+//@ like in exercise 12.1. For example, the following looks harmless:
+/*
+  let root_data = root.borrow();
+  add_child(&root, new_node(1));
+*/
+//@ But `root_data` is a shared borrow of `root`, and `add_child` needs a mutable one. Rust cannot
+//@ detect this at compile-time, since both only use a shared reference to the `Rc`. See
+//@ `test_borrow_panic` below. Visitors can run into the same problem: If a visitor holds an `Rc`
+//@ to some node, and tries to borrow it while `walk` is visiting that very node, it panics.
+
+// ## Comparison with the unsafe list
+//@ So, how does this compare to the raw pointers of part 16?
+//@
+//@ * *Ergonomics*: We need `borrow` and `borrow_mut` everywhere, and have to think hard about how
+//@   long each borrow lasts - a mistake is not caught by the compiler, but by a panic at run-time.
+//@   We cannot hand out a plain `&T` pointing into the tree, because the borrow would end with the
+//@   guard. On the other hand, every mistake we make is a panic, never undefined behavior.
+//@ * *Overhead*: Every node stores two reference counts (strong and weak) and a borrow flag, and
+//@   every access checks and updates the flag. Every `clone` of an `Rc` updates a count. In the
+//@   unsafe list, a node is just its data and the pointers.
+//@ * *Safety*: With raw pointers, *we* have to make sure that no pointer dangles, and that nobody
+//@   mutates data while somebody else looks at it. With `Rc<RefCell<_>>`, the compiler and the
+//@   run-time checks do that for us.
+//@
+//@ For a library data structure that is used a lot, like the list, the unsafe version can be worth
+//@ it. For everything else, start with `Rc<RefCell<_>>`, or with indices (part 31).
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let root = new_node(1);
+    let a = new_node(2);
+    let b = new_node(3);
+    add_child(&root, a.clone());
+    add_child(&root, b.clone());
+    add_child(&a, new_node(4));
+    walk(&root, &mut Printer);
+
+    // Move `a` (including its child) below `b`, and add 10 to every number.
+    add_child(&b, a.clone());
+    walk(&root, &mut AddTo(10));
+    walk(&root, &mut Printer);
+}
+
+// **Exercise 38.1**: If `a` is a child of `root`, then `add_child(&a, root.clone())` creates a
+// cycle. What happens? Make `add_child` panic in this case, by walking up the parents of `parent`.
+
+// **Exercise 38.2**: Write a visitor that computes the height of the tree (the largest depth of any
+// node), and one that collects all the data into a `Vec<T>` (for `T: Clone`).
+
+// **Exercise 38.3**: Rewrite the doubly-linked list of part 16 with `Rc`, `Weak` and `RefCell`.
+// What does the type of the iterator look like? Can you still implement `IterMut`?
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A visitor recording every call, for checking the order.
+    struct Log(Vec<String>);
+
+    impl Visitor<i32> for Log {
+        fn enter(&mut self, data: &mut i32, depth: usize) {
+            self.0.push(format!("enter {}@{}", data, depth));
+        }
+        fn leave(&mut self, data: &mut i32, depth: usize) {
+            self.0.push(format!("leave {}@{}", data, depth));
+        }
+    }
+
+    fn tree() -> (NodeRef<i32>, NodeRef<i32>, NodeRef<i32>) {
+        let root = new_node(1);
+        let a = new_node(2);
+        let b = new_node(3);
+        add_child(&root, a.clone());
+        add_child(&root, b.clone());
+        add_child(&a, new_node(4));
+        (root, a, b)
+    }
+
+    fn data(nodes: Vec<NodeRef<i32>>) -> Vec<i32> {
+        nodes.iter().map(|n| n.borrow().data).collect()
+    }
+
+    #[test]
+    fn test_add_detach() {
+        let (root, a, b) = tree();
+        assert_eq!(data(children(&root)), vec![2, 3]);
+        assert!(Rc::ptr_eq(&parent(&a).unwrap(), &root));
+        assert!(parent(&root).is_none());
+
+        // Moving a subtree.
+        add_child(&b, a.clone());
+        assert_eq!(data(children(&root)), vec![3]);
+        assert_eq!(data(children(&b)), vec![2]);
+        assert!(Rc::ptr_eq(&parent(&a).unwrap(), &b));
+
+        // A detached subtree lives on.
+        detach(&b);
+        drop(root);
+        assert!(parent(&b).is_none());
+        assert_eq!(data(children(&a)), vec![4]);
+    }
+
+    #[test]
+    fn test_walk() {
+        let (root, _, _) = tree();
+        let mut log = Log(Vec::new());
+        walk(&root, &mut log);
+        assert_eq!(log.0, vec!["enter 1@0", "enter 2@1", "enter 4@2", "leave 4@2", "leave 2@1",
+                               "enter 3@1", "leave 3@1", "leave 1@0"]);
+        walk(&root, &mut AddTo(10));
+        let mut log = Log(Vec::new());
+        walk(&root, &mut log);
+        assert_eq!(log.0[..3], ["enter 11@0", "enter 12@1", "enter 14@2"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_borrow_panic() {
+        let root = new_node(1);
+        let _root_data = root.borrow();
+        add_child(&root, new_node(2));
+    }
+
+    // A visitor that looks at the root while it is being visited.
+    struct Peek(NodeRef<i32>);
+
+    impl Visitor<i32> for Peek {
+        fn enter(&mut self, _data: &mut i32, _depth: usize) {
+            let _ = self.0.borrow().data;
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "already mutably borrowed")]
+    fn test_visitor_borrow_panic() {
+        let (root, _, _) = tree();
+        walk(&root, &mut Peek(root.clone()));
+    }
+}
+
+//@ [index](main.html) | [previous](part37.html) | [raw source](workspace/src/part38.rs) |
+//@ [next](part39.html)
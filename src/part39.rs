@@ -0,0 +1,267 @@
+// Rust-101, Part 39: Advanced Lifetimes
+// =====================================
+
+use std::fmt::Debug;
+
+//@ Lifetimes have been with us since part 06, and we wrote plenty of functions and types that
+//@ borrow. Most of the time, Rust figured out the lifetimes on its own. In this part, we will look
+//@ at the cases where it does not, or where it figures out something other than what we meant.
+
+// ## Lifetime elision
+//@ When a function takes references and returns one, the returned reference has to have *some*
+//@ lifetime. If we do not write it down, Rust applies the rules of *lifetime elision*:
+//@
+//@ * Every reference in the arguments gets its own, fresh lifetime.
+//@ * If there is exactly one reference in the arguments, its lifetime is used for the result.
+//@ * If one of the arguments is `&self` or `&mut self`, the lifetime of `self` is used for the
+//@   result.
+//@
+//@ Otherwise, we have to annotate the lifetimes ourselves. The first rule covers `first_word`
+//@ below: It is short-hand for `fn first_word<'a>(s: &'a str) -> &'a str`. In `longest`, there
+//@ are two references in the arguments, so Rust cannot know which one we are returning.
+pub fn first_word(s: &str) -> &str {
+    s.split_whitespace().next().unwrap_or("")
+}
+
+pub fn longest<'a>(a: &'a str, b: &'a str) -> &'a str {
+    if a.len() >= b.len() { a } else { b }
+}
+
+//@ The rule for methods is convenient: Usually, a method returns something borrowed from `self`.
+//@ But not always. Consider the following method, which returns the name in the config, or a
+//@ fallback if the name is empty:
+pub struct Config {
+    pub name: String,
+}
+
+/*
+  impl Config {
+      pub fn name_or(&self, fallback: &str) -> &str {
+          if self.name.is_empty() { fallback } else { &self.name }
+      }
+  }
+*/
+//@ Rust rejects this. By the third elision rule, the result borrows from `self`, but we may
+//@ return `fallback`, which has a different lifetime. The fix is to say that the result may
+//@ borrow from either: We give both the same lifetime `'a`.
+impl Config {
+    pub fn name_or<'a>(&'a self, fallback: &'a str) -> &'a str {
+        if self.name.is_empty() { fallback } else { &self.name }    /*@*/
+    }
+}
+
+//@ There is one more elision that can be surprising: Lifetimes in *types* can be elided entirely.
+//@ `fn tokens(input: &str) -> Tokens` compiles, even though `Tokens` has a lifetime parameter.
+//@ That makes it easy to miss that the result borrows from `input`. That's why, in part 33, we
+//@ wrote `Tokens<'_>`: The `'_` says "there is a lifetime here, and it is elided".
+
+// ## Structs with several lifetimes
+//@ A struct can have more than one lifetime parameter. Here is an iterator that splits a text at
+//@ a separator. Both are borrowed, but the pieces we return only borrow from the *text*.
+pub struct Split<'text, 'sep> {
+    rest: Option<&'text str>,
+    sep: &'sep str,
+}
+
+pub fn split<'text, 'sep>(text: &'text str, sep: &'sep str) -> Split<'text, 'sep> {
+    Split { rest: Some(text), sep }
+}
+
+impl<'text, 'sep> Iterator for Split<'text, 'sep> {
+    type Item = &'text str;
+
+    fn next(&mut self) -> Option<&'text str> {
+        let rest = self.rest?;                                      /*@*/
+        match rest.find(self.sep) {                                 /*@*/
+            Some(i) => {                                            /*@*/
+                self.rest = Some(&rest[i + self.sep.len()..]);      /*@*/
+                Some(&rest[..i])                                    /*@*/
+            }                                                       /*@*/
+            None => {                                               /*@*/
+                self.rest = None;                                   /*@*/
+                Some(rest)                                          /*@*/
+            }                                                       /*@*/
+        }                                                           /*@*/
+    }
+}
+
+//@ Why bother with two lifetimes? With just one, say `Split<'a>`, both the text and the separator
+//@ would have to live for `'a`, and so would the pieces. Then the following function would not
+//@ compile: The separator is a local variable, so `'a` could be no longer than the body of the
+//@ function - but we return a piece of the text. With two lifetimes, the pieces are independent
+//@ of the separator.
+pub fn first_field(text: &str) -> &str {
+    let sep = String::from(",");
+    split(text, &sep).next().unwrap()
+}
+
+// ## Reborrowing
+//@ A mutable reference is not `Copy`: There must only be one of them. So how come we can pass
+//@ `v: &mut Vec<i32>` to a function twice, like in `add_one` below? Rust silently inserts a
+//@ *reborrow*: It passes `&mut *v`, a new mutable reference that borrows from `v` for the duration
+//@ of the call. While that reborrow is in use, `v` cannot be used. Once the call is done, `v` is
+//@ available again.
+fn add_one(v: &mut Vec<i32>) {
+    v.push(1);
+}
+
+pub fn add_two(v: &mut Vec<i32>) {
+    add_one(v);
+    add_one(v);
+}
+
+//@ However, the reborrow only happens if Rust knows that a mutable reference is expected. With a
+//@ generic function, `v` is simply moved:
+fn consume<T: Debug>(t: T) -> String {
+    format!("{:?}", t)
+}
+/*
+  pub fn show_and_push(v: &mut Vec<i32>) -> String {
+      let s = consume(v);
+      v.push(0);
+      s
+  }
+*/
+//@ This fails with "borrow of moved value". The fix is to reborrow explicitly, by passing `&mut *v`
+//@ - or, since we only need to look at the vector, `&*v`.
+pub fn show_and_push(v: &mut Vec<i32>) -> String {
+    let s = consume(&*v);                                           /*@*/
+    v.push(0);                                                      /*@*/
+    s                                                               /*@*/
+}
+
+// ## `'static` data and `'static` bounds
+//@ The lifetime `'static` shows up in two different roles, and they are easily confused.
+//@
+//@ A *reference* `&'static T` points to data that lives for the entire program. String literals
+//@ are like that: They are stored in the program binary. We can also create such data by leaking
+//@ a `Box` (remember part 26): `Box::leak` gives us a `&'static mut T`, and the memory is never
+//@ freed.
+pub fn greeting() -> &'static str {
+    "Hello"
+}
+
+pub fn leak_string(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+//@ A *bound* `T: 'static`, on the other hand, says that values of type `T` do not contain any
+//@ references that could become invalid, i.e., all the lifetimes *inside* `T` are `'static`. That
+//@ is true for every type without references, like `String` or `Vec<i32>`. Such a value does not
+//@ live forever: It can be dropped at any time. But *if* we own it, we may keep it as long as we
+//@ like. That's what the bound in `register_generic` in part 11 was about: The callbacks are kept
+//@ around for an unknown amount of time, so they must not borrow anything that could go away.
+//@ A `move` closure that owns a `usize` is `'static`, but a closure that borrows a local variable
+//@ is not.
+pub fn boxed<T: Debug + 'static>(t: T) -> Box<dyn Debug> {
+    Box::new(t)
+}
+
+// ## Higher-ranked trait bounds
+//@ Let's write a function that takes a closure mapping a string slice to a string slice, and
+//@ applies it to some strings, like `trim` or `first_word`. Our first attempt looks like this:
+/*
+  pub fn apply_to_local<'a, F: Fn(&'a str) -> &'a str>(f: F) -> usize {
+      let s = String::from("  hello world  ");
+      f(&s).len()
+  }
+*/
+//@ This does not compile. The *caller* chooses `'a`, and it may choose a lifetime that is much
+//@ longer than the local variable `s`. But we need `f` to work for a reference to `s`, and for any
+//@ other lifetime we might come up with inside the function. The way to say this is
+//@ `for<'a> Fn(&'a str) -> &'a str`: "For all lifetimes `'a`, `F` can be called with a `&'a str`,
+//@ and returns a `&'a str`". That's called a *higher-ranked trait bound*.
+pub fn apply_to_local<F>(f: F) -> usize
+    where F: for<'a> Fn(&'a str) -> &'a str
+{
+    let s = String::from("  hello world  ");
+    f(&s).len()
+}
+
+pub fn apply_to_all<'s, F>(words: &'s [String], f: F) -> Vec<&'s str>
+    where F: for<'a> Fn(&'a str) -> &'a str
+{
+    words.iter().map(|w| f(w)).collect()                            /*@*/
+}
+//@ Actually, we have been using higher-ranked bounds all the time without knowing it: When we
+//@ write `F: Fn(&str) -> &str`, the elision rules turn this into exactly the `for<'a>` version
+//@ above. The explicit syntax is only needed in more complicated cases - but now you know what is
+//@ going on behind the scenes.
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    println!("{}", longest(first_word("hello world"), "hi"));
+    let config = Config { name: String::new() };
+    println!("name: {}", config.name_or("anonymous"));
+    println!("first field: {}", first_field("a,b,c"));
+    println!("{}", apply_to_local(str::trim));
+    let words = vec!["  one ".to_string(), "two three".to_string()];
+    println!("{:?}", apply_to_all(&words, first_word));
+    println!("{:?}", boxed(leak_string(format!("{}, world", greeting()))));
+}
+
+// **Exercise 39.1**: Write a method `name_or_default(&self) -> &str` that returns the name, or
+// `"anonymous"` if the name is empty. Why does this compile without any annotation, even though the
+// string literal is not borrowed from `self`?
+
+// **Exercise 39.2**: Change `Split` to have just one lifetime parameter. Which functions stop
+// compiling? Read the error messages carefully, then change it back.
+
+// **Exercise 39.3**: Fix the following function without changing its signature:
+// `fn push_all(v: &mut Vec<i32>, items: &[i32]) { for &i in items { consume(v); v.push(i); } }`.
+
+// **Exercise 39.4**: Write a function `compose(f, g)` that takes two functions of type
+// `for<'a> Fn(&'a str) -> &'a str`, and returns their composition as a `Box` of the same type.
+// Which other bound do `f` and `g` need?
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elision() {
+        assert_eq!(first_word("  hello world"), "hello");
+        assert_eq!(longest("ab", "abc"), "abc");
+        let config = Config { name: String::new() };
+        assert_eq!(config.name_or("x"), "x");
+        let config = Config { name: "ferris".to_string() };
+        assert_eq!(config.name_or("x"), "ferris");
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("a, b,, c", ", ").collect::<Vec<_>>(), vec!["a", "b,", "c"]);
+        assert_eq!(split("", ",").collect::<Vec<_>>(), vec![""]);
+        assert_eq!(split("a,", ",").collect::<Vec<_>>(), vec!["a", ""]);
+        assert_eq!(first_field("x,y"), "x");
+        // The pieces outlive the separator.
+        let pieces: Vec<&str> = {
+            let sep = "-".to_string();
+            split("1-2", &sep).collect()
+        };
+        assert_eq!(pieces, vec!["1", "2"]);
+    }
+
+    #[test]
+    fn test_reborrow() {
+        let mut v = Vec::new();
+        add_two(&mut v);
+        assert_eq!(show_and_push(&mut v), "[1, 1]");
+        assert_eq!(v, vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn test_static_and_hrtb() {
+        let s: &'static str = leak_string("leaked".to_string());
+        assert_eq!(format!("{:?}", boxed(s)), "\"leaked\"");
+        assert_eq!(format!("{:?}", boxed(vec![1])), "[1]");
+        assert_eq!(apply_to_local(str::trim), 11);
+        assert_eq!(apply_to_local(|s| &s[..1]), 1);
+        let words = vec![" a b ".to_string(), "c".to_string()];
+        assert_eq!(apply_to_all(&words, first_word), vec!["a", "c"]);
+        assert_eq!(apply_to_all(&words, str::trim), vec!["a b", "c"]);
+    }
+}
+
+//@ [index](main.html) | [previous](part38.html) | [raw source](workspace/src/part39.rs) |
+//@ [next](part40.html)
@@ -0,0 +1,205 @@
+// Rust-101, Part 40: Implementing Send and Sync
+// =============================================
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+//@ In part 13, we learned that only types that are `Send` can be moved to another thread, and in
+//@ part 15, that `Sync` is needed to share a reference between threads. Rust implements both
+//@ traits automatically, as long as all the fields of a type implement them. In this part, we will
+//@ see a type where this automatic reasoning does not work, and implement `Send` and `Sync`
+//@ ourselves.
+
+// ## A foreign library
+//@ Types that need manual `Send` and `Sync` implementations typically wrap a raw pointer. Very
+//@ often, that pointer comes from a library written in C. Its interface could look as follows:
+//@ There is a function that creates a counter and returns a pointer to it, functions to work with
+//@ the counter, and a function that frees it again. For the purpose of this part, we write this
+//@ "foreign library" in Rust, in a module of its own. In real code, these functions would be
+//@ declared in an `extern "C"` block instead.
+//@
+//@ The documentation of such a library would tell us the rules: A counter may be used from any
+//@ thread, not just the one that created it. `counter_get` may be called by several threads at the
+//@ same time, but `counter_increment` and `counter_free` must not run at the same time as any other
+//@ call on the same counter.
+pub mod ffi {
+    pub struct RawCounter {
+        value: u64,
+    }
+
+    pub unsafe fn counter_new() -> *mut RawCounter {
+        Box::into_raw(Box::new(RawCounter { value: 0 }))
+    }
+
+    pub unsafe fn counter_increment(counter: *mut RawCounter) {
+        (*counter).value += 1;
+    }
+
+    pub unsafe fn counter_get(counter: *const RawCounter) -> u64 {
+        (*counter).value
+    }
+
+    pub unsafe fn counter_free(counter: *mut RawCounter) {
+        drop(Box::from_raw(counter));
+    }
+}
+
+// ## A safe wrapper
+//@ As in part 16, we hide the unsafe code behind a safe interface. `ForeignHandle` owns a counter:
+//@ It creates it in `new`, and frees it in `drop`. The raw pointer is private, so nobody else can
+//@ get hold of it.
+pub struct ForeignHandle {
+    raw: *mut ffi::RawCounter,
+}
+
+impl ForeignHandle {
+    pub fn new() -> Self {
+        ForeignHandle { raw: unsafe { ffi::counter_new() } }
+    }
+
+    //@ `increment` takes `&mut self`. That's the key to the whole story: The borrow checker makes
+    //@ sure that nobody else can use the handle while `increment` runs, so the rule that no other
+    //@ call may happen at the same time is upheld - no matter how many threads are involved.
+    pub fn increment(&mut self) {
+        unsafe { ffi::counter_increment(self.raw) }                 /*@*/
+    }
+
+    pub fn get(&self) -> u64 {
+        unsafe { ffi::counter_get(self.raw) }                       /*@*/
+    }
+}
+
+impl Drop for ForeignHandle {
+    fn drop(&mut self) {
+        unsafe { ffi::counter_free(self.raw) }                      /*@*/
+    }
+}
+
+// ## Why raw pointers are neither `Send` nor `Sync`
+//@ Raw pointers do not implement `Send` or `Sync`. The compiler has no idea what a raw pointer is
+//@ used for - maybe it points to data that is shared with another thread without any
+//@ synchronization, maybe it points to thread-local data. So it plays safe. As a consequence,
+//@ `ForeignHandle` is neither `Send` nor `Sync`, and the following is rejected:
+/*
+  let mut handle = ForeignHandle::new();
+  thread::spawn(move || handle.increment());
+*/
+//@ The error says that "`*mut RawCounter` cannot be sent between threads safely". That's too
+//@ strict: We know more than the compiler, namely the rules of our library. So we tell Rust about
+//@ it.
+
+// ## `unsafe impl`
+//@ Implementing `Send` or `Sync` is unsafe: If we get it wrong, safe code can cause data races.
+//@ So we write `unsafe impl`, and just like for `unsafe` blocks, we document why it is correct.
+//@
+//@ `ForeignHandle: Send` means we may move a handle to another thread, and use it there. The
+//@ library allows using a counter from any thread. And since the handle owns the counter (nobody
+//@ else has the pointer), moving the handle moves all access to the counter to the new thread.
+// SAFETY: The counter is not tied to the thread that created it, and the handle is the only way
+// to access it.
+unsafe impl Send for ForeignHandle {}
+
+//@ `ForeignHandle: Sync` means we may share a `&ForeignHandle` between threads. Through a shared
+//@ reference, one can only call `get`. The library permits several `counter_get` at the same time,
+//@ and `increment` (which needs `&mut`) can never run while a shared reference exists.
+// SAFETY: `&ForeignHandle` only allows calling `counter_get`, which may run concurrently with
+// itself. All other calls require `&mut ForeignHandle` or ownership.
+unsafe impl Sync for ForeignHandle {}
+
+//@ Notice how these arguments depend on *all* the methods of `ForeignHandle`. Imagine we added a
+//@ method `reset(&self)` that calls a (hypothetical) `counter_reset`. Then `Sync` would be wrong:
+//@ Two threads could call `reset` and `get` at the same time. An `unsafe impl` is a promise about
+//@ the entire module, not just about the code right next to it.
+
+// ## Sharing a handle
+//@ With these implementations in place, all the tools of parts 13 and 15 work for our handle. To
+//@ increment the same counter from several threads, we put it into an `Arc<Mutex<_>>`. `Mutex<T>`
+//@ is `Sync` if `T` is `Send`, so that's why we needed `Send` here: The mutex hands out `&mut` to
+//@ one thread at a time.
+pub fn count_in_parallel(threads: usize, per_thread: usize) -> u64 {
+    let handle = Arc::new(Mutex::new(ForeignHandle::new()));
+    let workers: Vec<thread::JoinHandle<()>> = (0..threads).map(|_| {
+        let handle = handle.clone();                                /*@*/
+        thread::spawn(move || {                                     /*@*/
+            for _ in 0..per_thread {                                /*@*/
+                handle.lock().unwrap().increment();                 /*@*/
+            }                                                       /*@*/
+        })                                                          /*@*/
+    }).collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    // The guard returned by `lock` has to be dropped before `handle`, so we store the result in a
+    // variable instead of returning it directly.
+    let count = handle.lock().unwrap().get();
+    count
+}
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let mut handle = ForeignHandle::new();
+    handle.increment();
+    // Move the handle to another thread, and get it back.
+    let handle = thread::spawn(move || { handle.increment(); handle }).join().unwrap();
+    println!("After two increments: {}", handle.get());
+    println!("Counted in parallel: {}", count_in_parallel(4, 1000));
+}
+
+// **Exercise 40.1**: Remove the two `unsafe impl`, and try to compile the code. Which functions
+// fail, and what do the errors say? Now put back only `Send`. Which functions still fail, and why?
+
+// **Exercise 40.2**: Suppose the library was not thread-safe at all: Every counter must only be
+// used by the thread that created it. Which of the two implementations would you have to remove?
+// Is `Arc<Mutex<ForeignHandle>>` still useful then?
+
+// **Exercise 40.3**: Add a type `Snapshot<'a>` that holds a `&'a ForeignHandle` and remembers the
+// value at the time it was created. Does it need any `unsafe impl`? Why not?
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These only compile if the type implements the trait.
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn test_traits() {
+        assert_send::<ForeignHandle>();
+        assert_sync::<ForeignHandle>();
+        assert_sync::<Arc<Mutex<ForeignHandle>>>();
+    }
+
+    #[test]
+    fn test_move_to_thread() {
+        let mut handle = ForeignHandle::new();
+        handle.increment();
+        let handle = thread::spawn(move || {
+            handle.increment();
+            handle
+        }).join().unwrap();
+        assert_eq!(handle.get(), 2);
+    }
+
+    #[test]
+    fn test_shared_get() {
+        let mut handle = ForeignHandle::new();
+        handle.increment();
+        let handle = Arc::new(handle);
+        let readers: Vec<_> = (0..4).map(|_| {
+            let handle = handle.clone();
+            thread::spawn(move || handle.get())
+        }).collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn test_count_in_parallel() {
+        assert_eq!(count_in_parallel(4, 250), 1000);
+    }
+}
+
+//@ [index](main.html) | [previous](part39.html) | [raw source](workspace/src/part40.rs) |
+//@ [next](part41.html)
@@ -0,0 +1,223 @@
+// Rust-101, Part 41: Project: Parallel File Hashing
+// =================================================
+
+use std::{fs, io, thread};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, sync_channel, Sender, SyncSender, Receiver};
+
+//@ In part 13, we built rgrep as a pipeline of threads connected by channels. In this part, we use
+//@ the same architecture for a different problem: Computing a checksum of many files. Reading a
+//@ file mostly waits for the disk, while computing the checksum keeps the CPU busy. So we will
+//@ have several threads for both jobs, and a final stage that collects the results.
+
+// ## Adler-32
+//@ Our checksum is [Adler-32](https://en.wikipedia.org/wiki/Adler-32). It is not a cryptographic
+//@ hash, but it is simple and fast, and good enough to notice that a file changed. It keeps two
+//@ sums: `a` is one plus the sum of all bytes, and `b` is the sum of all the values `a` had along
+//@ the way. Both are taken modulo 65521, the largest prime below 2^16.
+pub const MOD_ADLER: u32 = 65521;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    pub fn new() -> Self {
+        Adler32 { a: 1, b: 0 }
+    }
+
+    // Feed more data into the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.a = (self.a + byte as u32) % MOD_ADLER;            /*@*/
+            self.b = (self.b + self.a) % MOD_ADLER;                 /*@*/
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+
+    // Slide a window of `len` bytes one byte further: `old` leaves the window, `new` enters it.
+    //@ This is what makes Adler-32 a *rolling* checksum: If we know the checksum of a window, we
+    //@ can compute the checksum of the next window in constant time, without looking at all the
+    //@ bytes again. `old` was counted once in `a`, and `len` times in `b` (once for every step
+    //@ since it was added). Tools like `rsync` use this to find blocks that two files have in
+    //@ common. We add multiples of `MOD_ADLER` before subtracting, so that we never go below zero.
+    pub fn roll(&mut self, old: u8, new: u8, len: usize) {
+        let (old, new) = (old as u32, new as u32);
+        let len = (len as u32) % MOD_ADLER;
+        self.a = (self.a + MOD_ADLER - old + new) % MOD_ADLER;      /*@*/
+        self.b = (self.b + MOD_ADLER * MOD_ADLER - len * old - 1 + self.a) % MOD_ADLER; /*@*/
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut checksum = Adler32::new();
+    checksum.update(data);
+    checksum.finish()
+}
+
+// ## The pipeline
+//@ The pipeline has three stages:
+//@
+//@ * Several *reader* threads take paths from a shared list, read the files, and send the contents
+//@   on.
+//@ * Several *hasher* threads receive the contents, and compute the checksums.
+//@ * A single *aggregator* collects all checksums.
+//@
+//@ Reading a file may fail, and we do not want one missing file to stop everything. So we send the
+//@ `io::Result` along the pipeline, and let the aggregator decide what to do with the errors.
+type Contents = (PathBuf, io::Result<Vec<u8>>);
+type Checksum = (PathBuf, io::Result<u32>);
+
+//@ The readers share the list of paths in an `Arc<Mutex<_>>`, and every reader takes the next path
+//@ from it until none are left. Notice that the lock is only held while we `pop`: The temporary
+//@ guard is dropped at the end of the `let` statement, before we start reading the file.
+fn read_files(paths: Arc<Mutex<Vec<PathBuf>>>, out_channel: SyncSender<Contents>) {
+    loop {
+        let path = match paths.lock().unwrap().pop() {
+            Some(path) => path,
+            None => return,
+        };
+        let contents = fs::read(&path);                             /*@*/
+        out_channel.send((path, contents)).unwrap();                /*@*/
+    }
+}
+
+//@ The hashers all receive from the same channel. A `Sender` can be cloned, but a `Receiver`
+//@ cannot: The channel has a single consumer. So, the hashers share the receiver through an
+//@ `Arc<Mutex<_>>`. (`Receiver` is `Send`, so `Mutex<Receiver<_>>` is `Sync` - remember part 40.)
+//@ Once all readers are done and have dropped their senders, `recv` returns an error, and the
+//@ hashers finish.
+fn hash_files(in_channel: Arc<Mutex<Receiver<Contents>>>, out_channel: Sender<Checksum>) {
+    loop {
+        let message = in_channel.lock().unwrap().recv();
+        let (path, contents) = match message {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+        let checksum = contents.map(|data| adler32(&data));         /*@*/
+        out_channel.send((path, checksum)).unwrap();                /*@*/
+    }
+}
+
+// Compute the checksums of all `paths`, using `readers` threads for reading and `hashers` threads
+// for hashing.
+//@ The aggregator runs on the calling thread. It puts the results into a `BTreeMap`, so that they
+//@ end up sorted by path - the files finish in an unpredictable order.
+pub fn hash_all(paths: Vec<PathBuf>, readers: usize,
+                hashers: usize) -> BTreeMap<PathBuf, io::Result<u32>> {
+    assert!(readers > 0 && hashers > 0, "hash_all: need at least one thread per stage");
+    let paths = Arc::new(Mutex::new(paths));
+    let (contents_sender, contents_receiver) = sync_channel::<Contents>(readers);
+    let contents_receiver = Arc::new(Mutex::new(contents_receiver));
+    let (checksum_sender, checksum_receiver) = channel::<Checksum>();
+
+    let mut handles: Vec<thread::JoinHandle<()>> = Vec::new();
+    for _ in 0..readers {
+        let (paths, sender) = (paths.clone(), contents_sender.clone()); /*@*/
+        handles.push(thread::spawn(move || read_files(paths, sender))); /*@*/
+    }
+    for _ in 0..hashers {
+        let (receiver, sender) = (contents_receiver.clone(), checksum_sender.clone()); /*@*/
+        handles.push(thread::spawn(move || hash_files(receiver, sender))); /*@*/
+    }
+    //@ We still hold one sender for each channel. If we did not drop them, the channels would
+    //@ never be closed, and the loop below would wait forever.
+    drop(contents_sender);
+    drop(checksum_sender);
+
+    let results = checksum_receiver.iter().collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    results
+}
+
+// Remember to edit `main.rs` to run this function.
+pub fn main() {
+    let paths = (0..42).map(|i| PathBuf::from(format!("src/part{:02}.rs", i))).collect();
+    for (path, checksum) in hash_all(paths, 2, 4) {
+        match checksum {
+            Ok(checksum) => println!("{:08x}  {}", checksum, path.display()),
+            Err(e) => println!("error     {}: {}", path.display(), e),
+        }
+    }
+}
+
+// **Exercise 41.1**: Large files are read entirely into memory before they are hashed. Change the
+// readers to send chunks of at most 64 KiB instead. How does a hasher know which chunks belong
+// together, and in which order? Hint: Maybe every file should be hashed by a single thread after
+// all.
+
+// **Exercise 41.2**: Add a function that takes a directory, and hashes all the files in it,
+// including subdirectories. You can use the `Walk` iterator of part 19. Can you start hashing
+// before the walk is complete?
+
+// **Exercise 41.3**: Use `Adler32::roll` to write a function `find_block(haystack: &[u8], needle:
+// &[u8]) -> Option<usize>` that finds a block in a file by comparing checksums first, and only
+// comparing the bytes if the checksums match.
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use part19::tests::TempDir;
+    use super::*;
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // Long input, to check the modulus.
+        assert_eq!(adler32(&[255; 100_000]), 0x149A_302C);
+        let mut checksum = Adler32::new();
+        checksum.update(b"Wiki");
+        checksum.update(b"pedia");
+        assert_eq!(checksum.finish(), adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn test_roll() {
+        let data = b"the quick brown fox jumps over the lazy dog";
+        let len = 8;
+        let mut checksum = Adler32::new();
+        checksum.update(&data[..len]);
+        for start in 1..data.len() - len + 1 {
+            checksum.roll(data[start - 1], data[start + len - 1], len);
+            assert_eq!(checksum.finish(), adler32(&data[start..start + len]));
+        }
+    }
+
+    #[test]
+    fn test_hash_all() {
+        let tmp = TempDir::new("hash-all");
+        let mut paths = Vec::new();
+        for i in 0..20 {
+            let path = tmp.0.join(format!("file{:02}.txt", i));
+            fs::write(&path, "x".repeat(i * 1000)).unwrap();
+            paths.push(path);
+        }
+        let missing = tmp.0.join("missing.txt");
+        paths.push(missing.clone());
+
+        for &(readers, hashers) in [(1, 1), (3, 4)].iter() {
+            let results = hash_all(paths.clone(), readers, hashers);
+            assert_eq!(results.len(), 21);
+            for (i, path) in paths[..20].iter().enumerate() {
+                let expected = adler32("x".repeat(i * 1000).as_bytes());
+                assert_eq!(results[path].as_ref().unwrap(), &expected);
+            }
+            assert_eq!(results[&missing].as_ref().unwrap_err().kind(), io::ErrorKind::NotFound);
+            // The results are sorted by path.
+            assert_eq!(results.keys().next(), Some(&paths[0]));
+        }
+    }
+}
+
+//@ [index](main.html) | [previous](part40.html) | [raw source](workspace/src/part41.rs) |
+//@ [next](part42.html)
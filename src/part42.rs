@@ -0,0 +1,414 @@
+// Rust-101, Part 42: Project: A Key-Value Store
+// =============================================
+
+use std::io::prelude::*;
+use std::{env, fmt, fs, io, result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+//@ To finish off, let us put many of the things we learned together in one small project: A
+//@ *persistent key-value store*. It maps string keys to string values, like a `HashMap`, but it
+//@ remembers its contents when the program is restarted. We will need files, error handling,
+//@ a simple serialization format, and a careful look at who owns what.
+//@
+//@ The design is that of a *log-structured* store. All changes are appended to a file, the *log*.
+//@ We never modify what is already in the log. To find out the current contents, we read the log
+//@ from the beginning and *replay* all changes into a `HashMap`. That map is our in-memory index:
+//@ Reading a key never touches the disk. Since the log only grows, we will also need a way to throw
+//@ away old entries: *compaction*.
+
+// ## Errors
+//@ Many things can go wrong here: Files may be missing or unreadable, the log may be corrupt, and
+//@ the user may give us a command we do not understand. So far, we mostly used `io::Error` or a
+//@ `String` for errors. Now we define our own error type, an `enum` with one variant for each kind
+//@ of error. Again, we use the pattern of the calculator in part 32: The error knows *where* the
+//@ problem is.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Corrupt { line: usize, message: String },
+    Usage(String),
+}
+
+//@ With this `From` implementation, the `?` operator converts an `io::Error` into our `Error`
+//@ automatically. That's how `?` works in general: If the error types do not match, it calls
+//@ `From::from`.
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Corrupt { line, ref message } => {
+                write!(f, "corrupt log, line {}: {}", line, message)
+            }
+            Error::Usage(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+//@ Most functions in this module return a `Result` with our error type. It is common to define a
+//@ type alias for that, which we can then use as `Result<T>`.
+pub type Result<T> = result::Result<T, Error>;
+
+// ## Serialization
+//@ Every change becomes one line in the log. The fields are separated by tabs: `set`, key, value,
+//@ or `rm`, key. But what if a key contains a tab, or a newline? Then we could not tell the fields
+//@ or lines apart any more. We *escape* these characters, writing `\t` and `\n` instead - and
+//@ then, of course, the backslash itself has to be escaped as `\\`. A carriage return needs
+//@ escaping as well, as `\r`: When we read the log back, `lines` treats "\r\n" as a line ending,
+//@ so a value ending in '\r' would silently lose that character.
+#[derive(PartialEq, Debug)]
+pub enum Record {
+    Set(String, String),
+    Remove(String),
+}
+
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {                                                   /*@*/
+            '\\' => escaped.push_str("\\\\"),                       /*@*/
+            '\t' => escaped.push_str("\\t"),                        /*@*/
+            '\n' => escaped.push_str("\\n"),                        /*@*/
+            '\r' => escaped.push_str("\\r"),                        /*@*/
+            c => escaped.push(c),                                   /*@*/
+        }                                                           /*@*/
+    }
+    escaped
+}
+
+// Undo `escape`. Returns `None` if `s` contains an invalid escape sequence.
+pub fn unescape(s: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {                                              /*@*/
+            unescaped.push(c);                                      /*@*/
+            continue;                                               /*@*/
+        }                                                           /*@*/
+        match chars.next() {                                        /*@*/
+            Some('\\') => unescaped.push('\\'),                     /*@*/
+            Some('t') => unescaped.push('\t'),                      /*@*/
+            Some('n') => unescaped.push('\n'),                      /*@*/
+            Some('r') => unescaped.push('\r'),                      /*@*/
+            _ => return None,                                       /*@*/
+        }                                                           /*@*/
+    }
+    Some(unescaped)
+}
+
+impl Record {
+    // Turn the record into a line of the log, including the final newline.
+    pub fn serialize(&self) -> String {
+        match *self {
+            Record::Set(ref key, ref value) => format!("set\t{}\t{}\n", escape(key), escape(value)),
+            Record::Remove(ref key) => format!("rm\t{}\n", escape(key)),
+        }
+    }
+
+    // Parse a line of the log (without the newline).
+    //@ Every field is unescaped, and `?` bails out with `None` if that fails. The `match` is on a
+    //@ slice of the fields - slice patterns let us check the number of fields, and bind them, at the
+    //@ same time.
+    pub fn parse(line: &str) -> Option<Record> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields[..] {                                          /*@*/
+            ["set", key, value] => Some(Record::Set(unescape(key)?, unescape(value)?)), /*@*/
+            ["rm", key] => Some(Record::Remove(unescape(key)?)),    /*@*/
+            _ => None,                                              /*@*/
+        }                                                           /*@*/
+    }
+}
+
+// ## The store
+//@ The store owns the open log file, and the map. It also counts how many records in the log are
+//@ *stale*, i.e., have been overwritten or removed by later records. Those are what compaction
+//@ gets rid of.
+pub struct KvStore {
+    path: PathBuf,
+    log: fs::File,
+    map: HashMap<String, String>,
+    stale: usize,
+}
+
+// Open the log for appending, creating it if it does not exist.
+fn open_log(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().append(true).create(true).open(path)
+}
+
+impl KvStore {
+    // Open the store in the given file, replaying the log.
+    //@ We read the log line by line with a `BufReader`, like in part 13. If the file does not exist
+    //@ yet, that's fine: The store is just empty. Every other error is passed on, thanks to `?`.
+    pub fn open(path: &Path) -> Result<KvStore> {
+        let mut map = HashMap::new();
+        let mut stale = 0;
+        match fs::File::open(path) {
+            Ok(file) => {
+                for (i, line) in io::BufReader::new(file).lines().enumerate() {
+                    let line = line?;
+                    let record = match Record::parse(&line) {
+                        Some(record) => record,
+                        None => {
+                            let message = format!("cannot parse `{}`", line);
+                            return Err(Error::Corrupt { line: i + 1, message });
+                        }
+                    };
+                    //@ Both kinds of record make the previous value of the key stale, if there
+                    //@ was one. A removal is itself stale right away: After compaction, it is not
+                    //@ needed any more.
+                    let old = match record {                        /*@*/
+                        Record::Set(key, value) => map.insert(key, value), /*@*/
+                        Record::Remove(key) => { stale += 1; map.remove(&key) } /*@*/
+                    };                                              /*@*/
+                    if old.is_some() {                              /*@*/
+                        stale += 1;                                 /*@*/
+                    }                                               /*@*/
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let log = open_log(path)?;
+        Ok(KvStore { path: path.to_path_buf(), log, map, stale })
+    }
+
+    //@ `get` returns a reference into the map. The caller can look at the value, but as long as it
+    //@ does so, the store is borrowed and cannot be changed.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.map.get(key).map(|value| value.as_str())
+    }
+
+    //@ The order matters here: We first write to the log, and only if that worked, we change the
+    //@ map. Otherwise, the map could contain something that would be lost on restart. `set` takes
+    //@ ownership of the key and value, since the map needs to own them anyway.
+    pub fn set(&mut self, key: String, value: String) -> Result<()> {
+        let record = Record::Set(key, value);                       /*@*/
+        self.log.write_all(record.serialize().as_bytes())?;         /*@*/
+        if let Record::Set(key, value) = record {                   /*@*/
+            if self.map.insert(key, value).is_some() {              /*@*/
+                self.stale += 1;                                    /*@*/
+            }                                                       /*@*/
+        }                                                           /*@*/
+        Ok(())                                                      /*@*/
+    }
+
+    // Remove a key, and return whether it was present.
+    pub fn remove(&mut self, key: &str) -> Result<bool> {
+        if !self.map.contains_key(key) {                            /*@*/
+            return Ok(false);                                       /*@*/
+        }                                                           /*@*/
+        let record = Record::Remove(key.to_string());               /*@*/
+        self.log.write_all(record.serialize().as_bytes())?;         /*@*/
+        self.map.remove(key);                                       /*@*/
+        self.stale += 2;                                            /*@*/
+        Ok(true)                                                    /*@*/
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn stale(&self) -> usize {
+        self.stale
+    }
+
+    // ## Compaction
+    //@ To compact the log, we write a new one that contains exactly one `set` for every key in the
+    //@ map. We write it to a temporary file first, and then `rename` it over the old log. On most
+    //@ systems, renaming is *atomic*: If the program crashes in the middle of compaction, we are
+    //@ left with either the old log or the new one, but never with half a log. Finally, we open the
+    //@ new log for appending. Assigning to `self.log` drops the old file handle, which closes it.
+    pub fn compact(&mut self) -> Result<()> {
+        let tmp_path = self.path.with_extension("compact");
+        {
+            let mut tmp = io::BufWriter::new(fs::File::create(&tmp_path)?);
+            for (key, value) in self.map.iter() {
+                let record = Record::Set(key.clone(), value.clone()); /*@*/
+                tmp.write_all(record.serialize().as_bytes())?;      /*@*/
+            }
+            tmp.flush()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+        self.log = open_log(&self.path)?;
+        self.stale = 0;
+        Ok(())
+    }
+}
+//@ Notice the clones in `compact`: `Record::Set` wants to own its strings, but the map owns them.
+//@ If that bothers you, have a look at exercise 42.2.
+
+// ## The command line
+// Run a command given as a list of arguments, and return what should be printed.
+//@ The arguments are the log file, followed by the command. We match on a slice of `&str` again,
+//@ which gives us a compact overview of all the commands we understand.
+pub fn run(args: &[String]) -> Result<String> {
+    let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    let usage = "usage: <file> (get <key> | set <key> <value> | rm <key> | compact)";
+    let (path, command) = match args.split_first() {
+        Some((path, command)) => (Path::new(path), command),
+        None => return Err(Error::Usage(usage.to_string())),
+    };
+    let mut store = KvStore::open(path)?;
+    match *command {                                                /*@*/
+        ["get", key] => Ok(store.get(key).unwrap_or("(not found)").to_string()), /*@*/
+        ["set", key, value] => {                                    /*@*/
+            store.set(key.to_string(), value.to_string())?;         /*@*/
+            Ok("ok".to_string())                                    /*@*/
+        }                                                           /*@*/
+        ["rm", key] => {                                            /*@*/
+            let found = store.remove(key)?;                         /*@*/
+            Ok((if found { "ok" } else { "(not found)" }).to_string()) /*@*/
+        }                                                           /*@*/
+        ["compact"] => {                                            /*@*/
+            let stale = store.stale();                              /*@*/
+            store.compact()?;                                       /*@*/
+            Ok(format!("removed {} stale records", stale))          /*@*/
+        }                                                           /*@*/
+        _ => Err(Error::Usage(usage.to_string())),                  /*@*/
+    }                                                               /*@*/
+}
+
+// Remember to edit `main.rs` to run this function. Then try `cargo run -- store.log set a 1`.
+pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => println!("{}", output),
+        Err(e) => println!("{}", e),
+    }
+}
+
+// **Exercise 42.1**: Compact automatically: When more than half of the records in the log are
+// stale, `set` and `remove` should call `compact`.
+
+// **Exercise 42.2**: Write a function `serialize_set(key: &str, value: &str) -> String` that does
+// not need a `Record`, and use it to get rid of the clones in `compact` and of the dance with
+// `if let` in `set`.
+
+// **Exercise 42.3**: If the program crashes while writing a record, the last line of the log may be
+// incomplete. `lines` cannot tell us whether the last line ended in a newline. Use `read_line`
+// instead, and ignore an incomplete last line rather than reporting the log as corrupt.
+
+// **Exercise 42.4**: Keeping all the values in memory does not work for large stores. Change the
+// map to store, for every key, the position of its record in the log file. `get` then has to read
+// the value from the file. Hint: Look at `Seek`, and at `stream_position`.
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use part19::tests::TempDir;
+    use super::*;
+
+    #[test]
+    fn test_serialization() {
+        let tricky = "tab\there, newline\nthere, CR\r\n, and a \\ backslash\\t\r";
+        assert_eq!(unescape(&escape(tricky)).unwrap(), tricky);
+        assert_eq!(unescape("bad \\x escape"), None);
+        assert_eq!(unescape("trailing \\"), None);
+        let records = vec![Record::Set(tricky.to_string(), "".to_string()),
+                           Record::Remove("key".to_string())];
+        for record in records {
+            let line = record.serialize();
+            assert!(line.ends_with('\n'));
+            assert_eq!(line.matches('\n').count(), 1);
+            assert_eq!(Record::parse(line.trim_end_matches('\n')), Some(record));
+        }
+        assert_eq!(Record::parse("set\tonly key"), None);
+        assert_eq!(Record::parse("frobnicate\tkey"), None);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let tmp = TempDir::new("kv-persistence");
+        let path = tmp.0.join("store.log");
+        {
+            let mut store = KvStore::open(&path).unwrap();
+            assert!(store.is_empty());
+            store.set("a".to_string(), "1".to_string()).unwrap();
+            store.set("b".to_string(), "2".to_string()).unwrap();
+            store.set("a".to_string(), "3".to_string()).unwrap();
+            assert!(store.remove("b").unwrap());
+            assert!(!store.remove("b").unwrap());
+            assert_eq!(store.get("a"), Some("3"));
+            assert_eq!(store.stale(), 3);
+        }
+        let mut store = KvStore::open(&path).unwrap();
+        assert_eq!(store.get("a"), Some("3"));
+        assert_eq!(store.get("b"), None);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.stale(), 3);
+
+        store.compact().unwrap();
+        assert_eq!(store.stale(), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "set\ta\t3\n");
+        // The log is still usable after compaction.
+        store.set("c".to_string(), "4".to_string()).unwrap();
+        drop(store);
+        let store = KvStore::open(&path).unwrap();
+        assert_eq!(store.get("a"), Some("3"));
+        assert_eq!(store.get("c"), Some("4"));
+    }
+
+    #[test]
+    fn test_carriage_return() {
+        let tmp = TempDir::new("kv-carriage-return");
+        let path = tmp.0.join("store.log");
+        {
+            let mut store = KvStore::open(&path).unwrap();
+            store.set("line\r".to_string(), "ends in CR\r".to_string()).unwrap();
+            store.set("crlf".to_string(), "\r\n".to_string()).unwrap();
+        }
+        // The log has no carriage returns of its own, so reading it line by line keeps them.
+        assert!(!fs::read_to_string(&path).unwrap().contains('\r'));
+        let store = KvStore::open(&path).unwrap();
+        assert_eq!(store.get("line\r"), Some("ends in CR\r"));
+        assert_eq!(store.get("crlf"), Some("\r\n"));
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let tmp = TempDir::new("kv-corrupt");
+        let path = tmp.0.join("store.log");
+        fs::write(&path, "set\ta\t1\nset\tb\n").unwrap();
+        match KvStore::open(&path) {
+            Err(Error::Corrupt { line, .. }) => assert_eq!(line, 2),
+            Err(e) => panic!("wrong error: {}", e),
+            Ok(_) => panic!("corrupt log was accepted"),
+        }
+        // A directory cannot be opened as a log.
+        match KvStore::open(&tmp.0) {
+            Err(Error::Io(_)) => {}
+            _ => panic!("expected an I/O error"),
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let tmp = TempDir::new("kv-run");
+        let file = tmp.0.join("store.log").to_string_lossy().into_owned();
+        let run_str = |args: &[&str]| {
+            let mut all = vec![file.clone()];
+            all.extend(args.iter().map(|s| s.to_string()));
+            run(&all).map_err(|e| e.to_string())
+        };
+        assert_eq!(run_str(&["set", "k", "v"]).unwrap(), "ok");
+        assert_eq!(run_str(&["get", "k"]).unwrap(), "v");
+        assert_eq!(run_str(&["set", "k", "w"]).unwrap(), "ok");
+        assert_eq!(run_str(&["compact"]).unwrap(), "removed 1 stale records");
+        assert_eq!(run_str(&["rm", "k"]).unwrap(), "ok");
+        assert_eq!(run_str(&["get", "k"]).unwrap(), "(not found)");
+        assert!(run_str(&["frobnicate"]).unwrap_err().starts_with("usage"));
+        assert!(run(&[]).is_err());
+    }
+}
+
+//@ [index](main.html) | [previous](part41.html) | [raw source](workspace/src/part42.rs) | next
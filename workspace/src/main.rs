@@ -23,6 +23,32 @@ mod part13;
 mod part14;
 mod part15;
 mod part16;
+mod part17;
+mod part18;
+mod part19;
+mod part20;
+mod part21;
+mod part22;
+mod part23;
+mod part24;
+mod part25;
+mod part26;
+mod part27;
+mod part28;
+mod part29;
+mod part30;
+mod part31;
+mod part32;
+mod part33;
+mod part34;
+mod part35;
+mod part36;
+mod part37;
+mod part38;
+mod part39;
+mod part40;
+mod part41;
+mod part42;
 
 // This decides which part is actually run.
 fn main() {